@@ -9,6 +9,8 @@ use std::fmt::{Display, Formatter, Result};
 pub enum QueryContextError {
     /// Error that occurs when a date string cannot be parsed into a valid date
     InvalidDate(String),
+    /// Error that occurs when a query expression cannot be parsed
+    InvalidQuery(String),
 }
 
 impl std::error::Error for QueryContextError {}
@@ -20,6 +22,9 @@ impl Display for QueryContextError {
                 fmt,
                 "Invalid date provided: {date}! Must be in format YYYY-MM-DD."
             ),
+            QueryContextError::InvalidQuery(reason) => {
+                write!(fmt, "Invalid query provided: {reason}!")
+            }
         }
     }
 }
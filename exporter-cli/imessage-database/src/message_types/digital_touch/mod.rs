@@ -3,7 +3,8 @@
 */
 
 pub use crate::message_types::digital_touch::{
-    digital_touch_proto::TouchKind as DigitalTouch, models::from_payload,
+    digital_touch_proto::TouchKind as DigitalTouch,
+    models::{DigitalTouchMessage, Touch, from_payload},
 };
 
 pub(crate) mod digital_touch_proto;
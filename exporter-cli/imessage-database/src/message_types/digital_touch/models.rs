@@ -1,14 +1,33 @@
 /*!
 Parser for [Digital Touch](https://support.apple.com/guide/ipod-touch/send-a-digital-touch-effect-iph3fadba219/ios) iMessages.
 This message type is not documented by Apple, but represents messages displayed as `com.apple.DigitalTouchBalloonProvider`.
+
+The layouts of the payloads were reverse engineered from sample messages:
+- Coordinates are pairs of little-endian `u16`s, scaled so that `u16::MAX` is the far edge of the canvas
+- Colors are `RGBA` bytes
+- Delays are little-endian `u16`s, in milliseconds
 */
 
+use std::fmt::Write;
+
 use crate::message_types::digital_touch::digital_touch_proto::{
-    BaseMessage, TouchKind as DigitalTouch,
+    BaseMessage, FireballMessage, HeartbeatMessage, KissMessage, SketchMessage, TapMessage,
+    TouchKind as DigitalTouch,
 };
 
 use protobuf::Message;
 
+/// Width and height of the rendered `svg` canvas
+const CANVAS: f64 = 1000.;
+
+/// Color used when a payload does not include one
+const DEFAULT_COLOR: Color = Color {
+    red: 255,
+    green: 255,
+    blue: 255,
+    alpha: 255,
+};
+
 /// Converts a raw byte payload from the database into a [`DigitalTouch`].
 #[must_use]
 pub fn from_payload(payload: &[u8]) -> Option<DigitalTouch> {
@@ -17,9 +36,476 @@ pub fn from_payload(payload: &[u8]) -> Option<DigitalTouch> {
     Some(msg.TouchKind.enum_value_or_default())
 }
 
+/// Parser for [Digital Touch](https://support.apple.com/guide/ipod-touch/send-a-digital-touch-effect-iph3fadba219/ios) iMessages.
+#[derive(Debug, PartialEq)]
+pub struct DigitalTouchMessage {
+    /// Unique identifier for the Digital Touch message
+    pub id: String,
+    /// The decoded effect
+    pub touch: Touch,
+}
+
+/// The effects a Digital Touch message can contain
+#[derive(Debug, PartialEq)]
+pub enum Touch {
+    /// One or more taps on the canvas
+    Tap(Vec<Tap>),
+    /// A drawing made of one or more strokes
+    Sketch(Vec<Stroke>),
+    /// One or more kisses placed on the canvas
+    Kiss(Vec<Kiss>),
+    /// A beating, and optionally breaking, heart
+    Heartbeat(Heartbeat),
+    /// A fireball dragged across the canvas
+    Fireball(Fireball),
+    /// An effect that is not yet supported
+    Unknown,
+}
+
+/// A position on the canvas, where `0.0` is the top or left edge and `1.0` is the bottom or right edge
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Point {
+    /// Horizontal position
+    pub x: f64,
+    /// Vertical position
+    pub y: f64,
+}
+
+/// An `RGBA` color
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Color {
+    /// Red channel
+    pub red: u8,
+    /// Green channel
+    pub green: u8,
+    /// Blue channel
+    pub blue: u8,
+    /// Alpha channel
+    pub alpha: u8,
+}
+
+/// A single tap
+#[derive(Debug, PartialEq)]
+pub struct Tap {
+    /// Milliseconds after the previous tap
+    pub delay: u16,
+    /// Where the tap landed
+    pub point: Point,
+    /// Color of the ripple
+    pub color: Color,
+}
+
+/// A single line in a sketch
+#[derive(Debug, PartialEq)]
+pub struct Stroke {
+    /// Points along the line, in the order they were drawn
+    pub points: Vec<Point>,
+    /// Color of the line
+    pub color: Color,
+}
+
+/// A single kiss
+#[derive(Debug, PartialEq)]
+pub struct Kiss {
+    /// Milliseconds after the previous kiss
+    pub delay: u16,
+    /// Where the kiss landed
+    pub point: Point,
+    /// Clockwise rotation of the lips, in degrees
+    pub rotation: f64,
+}
+
+/// A heartbeat
+#[derive(Debug, PartialEq)]
+pub struct Heartbeat {
+    /// Beats per minute
+    pub bpm: f32,
+    /// Length of the animation, in seconds
+    pub duration: u64,
+    /// Seconds into the animation when the heart breaks, if it does
+    pub broken_at: Option<f32>,
+}
+
+/// A fireball
+#[derive(Debug, PartialEq)]
+pub struct Fireball {
+    /// Length of the animation, in seconds
+    pub duration: f32,
+    /// Where the fireball starts
+    pub start: Point,
+    /// Where the fireball was dragged, with the milliseconds taken to reach each point
+    pub path: Vec<(u16, Point)>,
+}
+
+impl DigitalTouchMessage {
+    /// Converts a raw byte payload from the database into a [`DigitalTouchMessage`].
+    #[must_use]
+    pub fn from_payload(payload: &[u8]) -> Option<Self> {
+        let msg = BaseMessage::parse_from_bytes(payload).ok()?;
+        let data = &msg.TouchPayload;
+
+        let touch = match msg.TouchKind.enum_value_or_default() {
+            DigitalTouch::Tap => Touch::Tap(parse_taps(&TapMessage::parse_from_bytes(data).ok()?)),
+            DigitalTouch::Sketch => {
+                Touch::Sketch(parse_strokes(&SketchMessage::parse_from_bytes(data).ok()?)?)
+            }
+            DigitalTouch::Kiss => {
+                Touch::Kiss(parse_kisses(&KissMessage::parse_from_bytes(data).ok()?))
+            }
+            DigitalTouch::Heartbeat => Touch::Heartbeat(parse_heartbeat(
+                &HeartbeatMessage::parse_from_bytes(data).ok()?,
+            )),
+            DigitalTouch::Fireball => Touch::Fireball(parse_fireball(
+                &FireballMessage::parse_from_bytes(data).ok()?,
+            )),
+            DigitalTouch::Unknown => Touch::Unknown,
+        };
+
+        Some(Self {
+            id: msg.ID.clone(),
+            touch,
+        })
+    }
+
+    /// The kind of effect in the message
+    #[must_use]
+    pub fn kind(&self) -> DigitalTouch {
+        match self.touch {
+            Touch::Tap(_) => DigitalTouch::Tap,
+            Touch::Sketch(_) => DigitalTouch::Sketch,
+            Touch::Kiss(_) => DigitalTouch::Kiss,
+            Touch::Heartbeat(_) => DigitalTouch::Heartbeat,
+            Touch::Fireball(_) => DigitalTouch::Fireball,
+            Touch::Unknown => DigitalTouch::Unknown,
+        }
+    }
+
+    /// Renders the Digital Touch message as an `svg` graphic.
+    ///
+    /// Effects with timing data are animated with `SMIL`; viewers that do not support it show the finished effect.
+    #[must_use]
+    pub fn render_svg(&self) -> String {
+        let mut svg = String::new();
+        svg.push('\n');
+        svg.push_str(format!(r#"<svg viewBox="0 0 {CANVAS} {CANVAS}" preserveAspectRatio="xMidYMid meet" width="100%" height="100%" xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink">"#).as_str());
+        svg.push('\n');
+        svg.push_str(&format!("<title>{}</title>\n", self.id));
+        svg.push_str("<metadata>\n");
+        svg.push_str(&format!("<id>{}</id>\n", self.id));
+        svg.push_str(&format!("<kind>{:?}</kind>\n", self.kind()));
+        svg.push_str("</metadata>\n");
+        svg.push_str("<style>\n");
+        svg.push_str(
+            r"    .background {
+        fill: black;
+    }
+    .line {
+        fill: none;
+        stroke-linecap: round;
+        stroke-linejoin: round;
+        stroke-width: 12;
+    }
+",
+        );
+        svg.push_str("</style>\n");
+        svg.push_str(r#"<rect class="background" width="100%" height="100%" />"#);
+        svg.push('\n');
+        match &self.touch {
+            Touch::Tap(taps) => generate_taps(&mut svg, taps),
+            Touch::Sketch(strokes) => generate_strokes(&mut svg, strokes),
+            Touch::Kiss(kisses) => generate_kisses(&mut svg, kisses),
+            Touch::Heartbeat(heartbeat) => generate_heartbeat(&mut svg, heartbeat),
+            Touch::Fireball(fireball) => generate_fireball(&mut svg, fireball),
+            Touch::Unknown => {}
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+impl Point {
+    /// Position of the point on the `svg` canvas
+    fn on_canvas(self) -> (f64, f64) {
+        ((self.x * CANVAS).round(), (self.y * CANVAS).round())
+    }
+}
+
+impl Color {
+    /// The color as an `svg` paint value
+    fn to_svg(self) -> String {
+        format!(
+            "rgba({},{},{},{:.2})",
+            self.red,
+            self.green,
+            self.blue,
+            f64::from(self.alpha) / 255.
+        )
+    }
+}
+
+// MARK: Parsers
+/// Parses taps from the protobuf message.
+fn parse_taps(msg: &TapMessage) -> Vec<Tap> {
+    let colors = parse_colors(&msg.Color);
+    parse_u16s(&msg.Delays)
+        .into_iter()
+        .zip(parse_points(&msg.Location))
+        .enumerate()
+        .map(|(idx, (delay, point))| Tap {
+            delay,
+            point,
+            color: pick_color(&colors, idx),
+        })
+        .collect()
+}
+
+/// Parses strokes from the protobuf message.
+///
+/// Each stroke starts with two `u16`s: one whose meaning is unknown, then the number of points in the stroke.
+fn parse_strokes(msg: &SketchMessage) -> Option<Vec<Stroke>> {
+    let data = &msg.Strokes;
+    let colors = parse_colors(&msg.Colors);
+
+    let mut strokes = vec![];
+    let mut idx = 0;
+    while idx < data.len() {
+        let header = data.get(idx..idx + 4)?;
+        let num_points = u16::from_le_bytes([header[2], header[3]]) as usize;
+        idx += 4;
+
+        let points = data.get(idx..idx + (num_points * 4))?;
+        idx += num_points * 4;
+
+        strokes.push(Stroke {
+            points: parse_points(points),
+            color: pick_color(&colors, strokes.len()),
+        });
+    }
+    Some(strokes)
+}
+
+/// Parses kisses from the protobuf message.
+fn parse_kisses(msg: &KissMessage) -> Vec<Kiss> {
+    let rotations = parse_u16s(&msg.Rotations);
+    parse_u16s(&msg.Delays)
+        .into_iter()
+        .zip(parse_points(&msg.Points))
+        .enumerate()
+        .map(|(idx, (delay, point))| Kiss {
+            delay,
+            point,
+            rotation: rotations.get(idx).map_or(0., |rotation| {
+                f64::from(*rotation) * 360. / f64::from(u16::MAX)
+            }),
+        })
+        .collect()
+}
+
+/// Parses a heartbeat from the protobuf message.
+fn parse_heartbeat(msg: &HeartbeatMessage) -> Heartbeat {
+    Heartbeat {
+        bpm: msg.BPM,
+        duration: msg.Duration,
+        broken_at: (msg.HeartBrokenAt > 0.).then_some(msg.HeartBrokenAt),
+    }
+}
+
+/// Parses a fireball from the protobuf message.
+///
+/// The start position is stored as an offset from the center of the canvas.
+fn parse_fireball(msg: &FireballMessage) -> Fireball {
+    Fireball {
+        duration: msg.Duration,
+        start: Point {
+            x: 0.5 + f64::from(msg.StartX),
+            y: 0.5 + f64::from(msg.StartY),
+        },
+        path: parse_u16s(&msg.Delays)
+            .into_iter()
+            .zip(parse_points(&msg.Points))
+            .collect(),
+    }
+}
+
+/// Converts bytes to little-endian `u16`s, ignoring a trailing odd byte.
+fn parse_u16s(data: &[u8]) -> Vec<u16> {
+    data.chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect()
+}
+
+/// Converts bytes to points on the canvas.
+fn parse_points(data: &[u8]) -> Vec<Point> {
+    parse_u16s(data)
+        .chunks_exact(2)
+        .map(|coordinates| Point {
+            x: f64::from(coordinates[0]) / f64::from(u16::MAX),
+            y: f64::from(coordinates[1]) / f64::from(u16::MAX),
+        })
+        .collect()
+}
+
+/// Converts bytes to `RGBA` colors.
+fn parse_colors(data: &[u8]) -> Vec<Color> {
+    data.chunks_exact(4)
+        .map(|rgba| Color {
+            red: rgba[0],
+            green: rgba[1],
+            blue: rgba[2],
+            alpha: rgba[3],
+        })
+        .collect()
+}
+
+/// Gets the color at `idx`, reusing the last color if there are fewer colors than shapes.
+fn pick_color(colors: &[Color], idx: usize) -> Color {
+    colors
+        .get(idx)
+        .or(colors.last())
+        .copied()
+        .unwrap_or(DEFAULT_COLOR)
+}
+
+// MARK: Renderers
+/// Converts milliseconds to an `svg` clock value.
+fn seconds(millis: u64) -> String {
+    format!("{}s", millis as f64 / 1000.)
+}
+
+/// Generates svg ripples for each tap.
+fn generate_taps(svg: &mut String, taps: &[Tap]) {
+    let mut begin = 0;
+    for tap in taps {
+        begin += u64::from(tap.delay);
+        let (x, y) = tap.point.on_canvas();
+        let _ = writeln!(
+            svg,
+            r#"<circle cx="{x}" cy="{y}" r="90" fill="{}" opacity="0.5"><animate attributeName="r" from="0" to="90" begin="{}" dur="0.6s" fill="freeze" /><animate attributeName="opacity" from="1" to="0.5" begin="{}" dur="0.6s" fill="freeze" /></circle>"#,
+            tap.color.to_svg(),
+            seconds(begin),
+            seconds(begin),
+        );
+    }
+}
+
+/// Generates svg lines for each stroke.
+fn generate_strokes(svg: &mut String, strokes: &[Stroke]) {
+    for stroke in strokes {
+        let mut points_svg = String::with_capacity(stroke.points.len() * 8);
+        for point in &stroke.points {
+            let (x, y) = point.on_canvas();
+            let _ = write!(points_svg, " {x},{y}");
+        }
+        let _ = writeln!(
+            svg,
+            r#"<polyline class="line" points="{}" stroke="{}" />"#,
+            points_svg.trim_start(),
+            stroke.color.to_svg()
+        );
+    }
+}
+
+/// Generates svg lips for each kiss.
+fn generate_kisses(svg: &mut String, kisses: &[Kiss]) {
+    let mut begin = 0;
+    for kiss in kisses {
+        begin += u64::from(kiss.delay);
+        let (x, y) = kiss.point.on_canvas();
+        let _ = writeln!(
+            svg,
+            r#"<g transform="translate({x} {y}) rotate({:.1})"><path d="M -90,0 C -60,-50 -15,-45 0,-27 C 15,-45 60,-50 90,0 C 60,50 -60,50 -90,0 Z" fill="rgb(224,36,94)"><animate attributeName="opacity" from="0" to="1" begin="{}" dur="0.3s" fill="freeze" /></path></g>"#,
+            kiss.rotation,
+            seconds(begin),
+        );
+    }
+}
+
+/// Generates a beating svg heart, split down the middle if it breaks.
+fn generate_heartbeat(svg: &mut String, heartbeat: &Heartbeat) {
+    /// Left half of the heart, ending in the crack it breaks along
+    const LEFT: &str = "M 0,135 C -90,60 -225,-15 -225,-120 C -225,-210 -105,-240 0,-150 L -30,-60 L 22,0 L -15,75 Z";
+    /// Right half of the heart, ending in the crack it breaks along
+    const RIGHT: &str =
+        "M 0,135 C 90,60 225,-15 225,-120 C 225,-210 105,-240 0,-150 L -30,-60 L 22,0 L -15,75 Z";
+
+    let beat = if heartbeat.bpm > 0. {
+        60. / f64::from(heartbeat.bpm)
+    } else {
+        1.
+    };
+    let end = heartbeat
+        .broken_at
+        .map_or(heartbeat.duration as f64, f64::from);
+
+    svg.push_str(r#"<g transform="translate(500 500)">"#);
+    svg.push('\n');
+    let _ = writeln!(
+        svg,
+        r#"<animateTransform attributeName="transform" type="scale" values="1;1.15;1" dur="{beat:.3}s" end="{end:.3}s" additive="sum" repeatCount="indefinite" />"#
+    );
+    for (half, direction) in [(LEFT, -1), (RIGHT, 1)] {
+        let _ = write!(svg, r#"<path d="{half}" fill="rgb(224,36,94)">"#);
+        if let Some(broken_at) = heartbeat.broken_at {
+            let _ = write!(
+                svg,
+                r#"<animateTransform attributeName="transform" type="translate" from="0 0" to="{} 30" begin="{broken_at:.3}s" dur="0.4s" fill="freeze" />"#,
+                direction * 40
+            );
+        }
+        svg.push_str("</path>\n");
+    }
+    svg.push_str("</g>\n");
+}
+
+/// Generates an svg fireball that travels along its path.
+fn generate_fireball(svg: &mut String, fireball: &Fireball) {
+    let mut points = vec![(0, fireball.start)];
+    let mut elapsed = 0;
+    for (delay, point) in &fireball.path {
+        elapsed += u64::from(*delay);
+        points.push((elapsed, *point));
+    }
+    let (end_x, end_y) = points
+        .last()
+        .map_or(fireball.start, |(_, point)| *point)
+        .on_canvas();
+
+    let mut trail = String::with_capacity(points.len() * 8);
+    let mut values = String::with_capacity(points.len() * 8);
+    let mut key_times = String::with_capacity(points.len() * 6);
+    let duration = (f64::from(fireball.duration) * 1000.)
+        .max(elapsed as f64)
+        .max(1.);
+    for (time, point) in &points {
+        let (x, y) = point.on_canvas();
+        let _ = write!(trail, " {x},{y}");
+        let _ = write!(values, "{},{};", x - end_x, y - end_y);
+        let _ = write!(key_times, "{:.3};", *time as f64 / duration);
+    }
+    // The fireball rests at its last point for the remainder of the animation
+    let _ = write!(values, "0,0");
+    key_times.push('1');
+
+    svg.push_str(r#"<defs><radialGradient id="fireball"><stop offset="0" stop-color="white" /><stop offset="0.4" stop-color="rgb(255,200,40)" /><stop offset="1" stop-color="rgb(255,60,0)" stop-opacity="0" /></radialGradient></defs>"#);
+    svg.push('\n');
+    let _ = writeln!(
+        svg,
+        r#"<polyline class="line" points="{}" stroke="rgb(255,120,0)" opacity="0.3" />"#,
+        trail.trim_start()
+    );
+    let _ = writeln!(
+        svg,
+        r#"<circle cx="{end_x}" cy="{end_y}" r="80" fill="url(#fireball)"><animateMotion values="{values}" keyTimes="{key_times}" dur="{}" fill="freeze" /></circle>"#,
+        seconds(duration as u64)
+    );
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::message_types::digital_touch::{DigitalTouch, from_payload};
+    use crate::message_types::digital_touch::{
+        DigitalTouch, from_payload,
+        models::{Color, DigitalTouchMessage, Touch},
+    };
 
     use std::env::current_dir;
     use std::fs::File;
@@ -108,4 +594,136 @@ mod tests {
         let actual = from_payload(&data);
         assert_eq!(actual, Some(DigitalTouch::Fireball));
     }
+
+    fn read_payload(name: &str) -> Vec<u8> {
+        let protobuf_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/digital_touch_message")
+            .join(name);
+        let mut proto_data = File::open(protobuf_path).unwrap();
+        let mut data = vec![];
+        proto_data.read_to_end(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn can_decode_tap() {
+        let actual = DigitalTouchMessage::from_payload(&read_payload("tap.bin")).unwrap();
+        assert_eq!(actual.id, "E3F4E72A-A863-43C3-8277-E17680251B06");
+        assert_eq!(actual.kind(), DigitalTouch::Tap);
+
+        let Touch::Tap(taps) = &actual.touch else {
+            panic!("expected a tap, got {:?}", actual.touch);
+        };
+        assert_eq!(taps.len(), 1);
+        assert_eq!(taps[0].delay, 0);
+        assert_eq!(taps[0].point.on_canvas(), (470., 571.));
+        assert_eq!(
+            taps[0].color,
+            Color {
+                red: 255,
+                green: 0,
+                blue: 252,
+                alpha: 255
+            }
+        );
+    }
+
+    #[test]
+    fn can_decode_sketch() {
+        let actual = DigitalTouchMessage::from_payload(&read_payload("sketch.bin")).unwrap();
+        assert_eq!(actual.kind(), DigitalTouch::Sketch);
+
+        let Touch::Sketch(strokes) = &actual.touch else {
+            panic!("expected a sketch, got {:?}", actual.touch);
+        };
+        assert_eq!(strokes.len(), 1);
+        assert_eq!(strokes[0].points.len(), 81);
+        assert_eq!(strokes[0].points[0].on_canvas(), (216., 658.));
+        assert_eq!(strokes[0].color.to_svg(), "rgba(255,0,252,1.00)");
+    }
+
+    #[test]
+    fn can_decode_kiss() {
+        let actual = DigitalTouchMessage::from_payload(&read_payload("kiss.bin")).unwrap();
+
+        let Touch::Kiss(kisses) = &actual.touch else {
+            panic!("expected a kiss, got {:?}", actual.touch);
+        };
+        assert_eq!(kisses.len(), 1);
+        assert_eq!(kisses[0].point.on_canvas(), (517., 521.));
+        assert!((kisses[0].rotation - 1.615).abs() < 0.001);
+    }
+
+    #[test]
+    fn can_decode_heartbeat() {
+        let actual = DigitalTouchMessage::from_payload(&read_payload("heartbeat.bin")).unwrap();
+
+        let Touch::Heartbeat(heartbeat) = &actual.touch else {
+            panic!("expected a heartbeat, got {:?}", actual.touch);
+        };
+        assert_eq!(heartbeat.bpm, 84.);
+        assert_eq!(heartbeat.duration, 2);
+        assert_eq!(heartbeat.broken_at, None);
+    }
+
+    #[test]
+    fn can_decode_heartbreak() {
+        let actual = DigitalTouchMessage::from_payload(&read_payload("heartbreak.bin")).unwrap();
+
+        let Touch::Heartbeat(heartbeat) = &actual.touch else {
+            panic!("expected a heartbeat, got {:?}", actual.touch);
+        };
+        assert!((heartbeat.broken_at.unwrap() - 1.714).abs() < 0.001);
+    }
+
+    #[test]
+    fn can_decode_fireball() {
+        let actual = DigitalTouchMessage::from_payload(&read_payload("fireball.bin")).unwrap();
+
+        let Touch::Fireball(fireball) = &actual.touch else {
+            panic!("expected a fireball, got {:?}", actual.touch);
+        };
+        assert!((fireball.duration - 2.08).abs() < 0.001);
+        assert_eq!(fireball.start.on_canvas(), (486., 453.));
+        assert_eq!(
+            fireball
+                .path
+                .iter()
+                .map(|(delay, _)| *delay)
+                .collect::<Vec<_>>(),
+            vec![859, 0, 83]
+        );
+    }
+
+    #[test]
+    fn cant_decode_invalid() {
+        assert!(DigitalTouchMessage::from_payload(&[0xff, 0xff]).is_none());
+    }
+
+    #[test]
+    fn can_render_sketch_svg() {
+        let actual = DigitalTouchMessage::from_payload(&read_payload("sketch.bin"))
+            .unwrap()
+            .render_svg();
+        assert!(actual.contains("<title>F7D92232-92B3-4C5A-8DC7-2704BE93890E</title>"));
+        assert!(actual.contains(r#"<polyline class="line" points="216,658 "#));
+        assert!(actual.ends_with("</svg>\n"));
+    }
+
+    #[test]
+    fn can_render_animated_svg() {
+        for name in ["tap.bin", "kiss.bin", "heartbreak.bin", "fireball.bin"] {
+            let actual = DigitalTouchMessage::from_payload(&read_payload(name))
+                .unwrap()
+                .render_svg();
+            assert!(actual.contains("<animate"), "{name} is not animated");
+        }
+
+        let fireball = DigitalTouchMessage::from_payload(&read_payload("fireball.bin"))
+            .unwrap()
+            .render_svg();
+        assert!(fireball.contains(r#"keyTimes="0.000;0.413;0.413;0.453;1""#));
+    }
 }
@@ -8,3 +8,4 @@ pub use models::HandwrittenMessage;
 
 pub(crate) mod handwriting_proto;
 pub mod models;
+pub(crate) mod raster;
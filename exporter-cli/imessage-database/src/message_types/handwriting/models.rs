@@ -7,11 +7,26 @@ use std::io::Cursor;
 
 use crate::{
     error::handwriting::HandwritingError,
-    message_types::handwriting::handwriting_proto::{BaseMessage, Compression},
+    message_types::handwriting::{
+        handwriting_proto::{BaseMessage, Compression},
+        raster::Canvas,
+    },
 };
 
 use protobuf::Message;
 
+/// Distance along a line, in canvas units, that animated strokes draw per second
+const DRAW_SPEED: f64 = 800.;
+
+/// Shortest time, in seconds, that an animated line takes to draw
+const MIN_SEGMENT_DURATION: f64 = 0.02;
+
+/// Seconds between the end of one animated stroke and the start of the next
+const STROKE_PAUSE: f64 = 0.15;
+
+/// Most pixels a `png` render can contain, about 4096 × 4096
+pub const MAX_PNG_PIXELS: u32 = 4096 * 4096;
+
 /// Parser for [handwritten](https://support.apple.com/en-us/HT206894) iMessages.
 ///
 /// This message type is not documented by Apple, but represents messages displayed as
@@ -61,6 +76,61 @@ impl HandwrittenMessage {
     /// Renders the handwriting message as an `svg` graphic.
     #[must_use]
     pub fn render_svg(&self) -> String {
+        let mut svg = self.svg_header();
+        generate_strokes(&mut svg, &self.strokes);
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// Renders the handwriting message as an `svg` graphic that replays each stroke in the order it was written.
+    ///
+    /// Strokes are drawn with `SMIL` animations; viewers that do not support it show the finished message.
+    #[must_use]
+    pub fn render_animated_svg(&self) -> String {
+        let mut svg = self.svg_header();
+        generate_animated_strokes(&mut svg, &self.strokes);
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// Renders the handwriting message as a `png` image with black ink on a transparent background.
+    ///
+    /// A `scale` of `1.0` renders one pixel per unit of the `svg` graphic's canvas. Images that
+    /// would be larger than [`MAX_PNG_PIXELS`] are scaled down to fit.
+    #[must_use]
+    pub fn render_png(&self, scale: f64) -> Vec<u8> {
+        let scale = if scale.is_finite() && scale > 0. {
+            scale
+        } else {
+            1.
+        };
+        let area = f64::from(self.width) * f64::from(self.height);
+        let scale = scale.min((f64::from(MAX_PNG_PIXELS) / area).sqrt());
+        let mut canvas = Canvas::new(
+            (f64::from(self.width) * scale).ceil() as usize,
+            (f64::from(self.height) * scale).ceil() as usize,
+        );
+        for stroke in &self.strokes {
+            let scaled = |point: &Point| {
+                (
+                    f64::from(point.x) * scale,
+                    f64::from(point.y) * scale,
+                    f64::from(point.width) * scale,
+                )
+            };
+            // Single points still leave a dot
+            if let [point] = stroke.as_slice() {
+                canvas.draw_segment(scaled(point), scaled(point));
+            }
+            for window in stroke.windows(2) {
+                canvas.draw_segment(scaled(&window[0]), scaled(&window[1]));
+            }
+        }
+        canvas.to_png()
+    }
+
+    /// Builds the opening of an `svg` graphic, up to where the strokes are drawn.
+    fn svg_header(&self) -> String {
         let mut svg = String::new();
         svg.push('\n');
         svg.push_str(format!(r#"<svg viewBox="0 0 {} {}" preserveAspectRatio="xMidYMid meet" width="100%" height="100%" xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink">"#, self.width, self.height).as_str());
@@ -81,8 +151,6 @@ impl HandwrittenMessage {
 ",
         );
         svg.push_str("</style>\n");
-        generate_strokes(&mut svg, &self.strokes);
-        svg.push_str("</svg>\n");
         svg
     }
 
@@ -183,6 +251,36 @@ fn generate_strokes(svg: &mut String, strokes: &[Vec<Point>]) {
     }
 }
 
+/// Generates svg lines from an array of strokes, each drawn after the one before it.
+///
+/// The payload does not record when points were written, so lines are drawn at a constant speed.
+fn generate_animated_strokes(svg: &mut String, strokes: &[Vec<Point>]) {
+    let mut begin = 0.;
+    for stroke in strokes {
+        for (width, points) in &group_points(stroke) {
+            let mut points_svg = String::with_capacity(points.len() * 3);
+            let mut length = 0.;
+            for (idx, point) in points.iter().enumerate() {
+                let _ = write!(points_svg, " {},{}", point.x, point.y);
+                if let Some(previous) = idx.checked_sub(1).map(|prev| points[prev]) {
+                    length += (f64::from(point.x) - f64::from(previous.x))
+                        .hypot(f64::from(point.y) - f64::from(previous.y));
+                }
+            }
+            let duration = (length / DRAW_SPEED).max(MIN_SEGMENT_DURATION);
+
+            // Lines are hidden until their turn by dashes as long as the line itself
+            let _ = writeln!(
+                svg,
+                r#"<polyline class="line" points="{}" stroke-width="{width}" pathLength="1"><set attributeName="stroke-dasharray" to="1" begin="0s" /><set attributeName="stroke-dashoffset" to="1" begin="0s" /><animate attributeName="stroke-dashoffset" from="1" to="0" begin="{begin:.3}s" dur="{duration:.3}s" fill="freeze" /></polyline>"#,
+                points_svg.trim_start(),
+            );
+            begin += duration;
+        }
+        begin += STROKE_PAUSE;
+    }
+}
+
 /// Group points along a stroke together by width
 fn group_points(stroke: &[Point]) -> Vec<(u16, Vec<&Point>)> {
     let mut groups = vec![];
@@ -11233,4 +11331,90 @@ mod tests {
 
         assert_eq!(balloon.render_svg(), expected);
     }
+
+    #[test]
+    fn test_parse_handwritten_as_animated_svg() {
+        let protobuf_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/handwritten_message/handwriting.bin");
+        let mut proto_data = File::open(protobuf_path).unwrap();
+        let mut data = vec![];
+        proto_data.read_to_end(&mut data).unwrap();
+        let balloon = HandwrittenMessage::from_payload(&data).unwrap();
+
+        let mut expected = String::new();
+        let expected_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/handwritten_message/handwriting_animated.svg");
+        let mut expected_data = File::open(expected_path).unwrap();
+        expected_data.read_to_string(&mut expected).unwrap();
+        assert_eq!(balloon.render_animated_svg(), expected);
+
+        let still = balloon.render_svg();
+        let animated = balloon.render_animated_svg();
+
+        // Same canvas and metadata, with the same lines
+        let header_end = still.find("</style>").unwrap();
+        assert_eq!(animated[..header_end], still[..header_end]);
+        assert_eq!(
+            animated.matches("<polyline").count(),
+            still.matches("<polyline").count()
+        );
+
+        // The first line starts drawing immediately, and later lines wait their turn
+        assert!(animated.contains(r#"begin="0.000s""#));
+        assert_eq!(
+            animated.matches("<animate ").count(),
+            still.matches("<polyline").count()
+        );
+        assert!(animated.ends_with("</svg>\n"));
+    }
+
+    #[test]
+    fn test_parse_handwritten_as_png() {
+        let protobuf_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/handwritten_message/handwriting.bin");
+        let mut proto_data = File::open(protobuf_path).unwrap();
+        let mut data = vec![];
+        proto_data.read_to_end(&mut data).unwrap();
+        let balloon = HandwrittenMessage::from_payload(&data).unwrap();
+
+        let png = balloon.render_png(2.);
+        assert_eq!(
+            png[0..8],
+            [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n']
+        );
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(png[16..20], 1506u32.to_be_bytes());
+        assert_eq!(png[20..24], 486u32.to_be_bytes());
+
+        // Invalid scales fall back to the size of the svg canvas
+        let png = balloon.render_png(f64::NAN);
+        assert_eq!(png[16..20], 753u32.to_be_bytes());
+    }
+
+    #[test]
+    fn test_parse_handwritten_as_png_clamped() {
+        let protobuf_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/handwritten_message/handwriting.bin");
+        let mut proto_data = File::open(protobuf_path).unwrap();
+        let mut data = vec![];
+        proto_data.read_to_end(&mut data).unwrap();
+        let balloon = HandwrittenMessage {
+            height: u16::MAX,
+            width: u16::MAX,
+            ..HandwrittenMessage::from_payload(&data).unwrap()
+        };
+
+        // Large canvases are scaled down to fit, even at the requested scale
+        let png = balloon.render_png(4.);
+        assert_eq!(png[16..20], 4096u32.to_be_bytes());
+        assert_eq!(png[20..24], 4096u32.to_be_bytes());
+    }
 }
@@ -0,0 +1,306 @@
+/*!
+Rasterizes handwritten strokes and encodes them as `png` images without any image libraries.
+
+Pixels store only ink coverage; images are black ink on a transparent background, compressed with
+the fixed Huffman codes from [RFC 1951](https://www.rfc-editor.org/rfc/rfc1951) and run-length matches.
+*/
+
+/// Bytes every `png` file starts with
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// `png` color type for grayscale pixels with an alpha channel
+const GRAYSCALE_ALPHA: u8 = 4;
+
+/// Bytes per grayscale and alpha pixel
+const BYTES_PER_PIXEL: usize = 2;
+
+/// Shortest match deflate can encode
+const MIN_MATCH: usize = 3;
+
+/// Longest match deflate can encode
+const MAX_MATCH: usize = 258;
+
+/// Smallest length encoded by each deflate length symbol, starting at symbol `257`
+const LENGTH_BASE: [usize; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+
+/// Number of extra bits following each deflate length symbol, starting at symbol `257`
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+/// A grid of pixels that strokes are drawn on
+#[derive(Debug)]
+pub(crate) struct Canvas {
+    width: usize,
+    height: usize,
+    /// Ink coverage of each pixel, from `0` (transparent) to `255` (opaque)
+    coverage: Vec<u8>,
+}
+
+impl Canvas {
+    pub(crate) fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            coverage: vec![0; width * height],
+        }
+    }
+
+    /// Draws a round-capped line from `start` to `end`, given as `(x, y, width)`, whose width changes evenly along its length.
+    pub(crate) fn draw_segment(&mut self, start: (f64, f64, f64), end: (f64, f64, f64)) {
+        let (x0, y0, w0) = start;
+        let (x1, y1, w1) = end;
+        // Hairlines still cover part of a pixel
+        let r0 = (w0 / 2.).max(0.5);
+        let r1 = (w1 / 2.).max(0.5);
+        let reach = r0.max(r1) + 1.;
+
+        let left = clamp_index(x0.min(x1) - reach, self.width);
+        let right = clamp_index(x0.max(x1) + reach, self.width);
+        let top = clamp_index(y0.min(y1) - reach, self.height);
+        let bottom = clamp_index(y0.max(y1) + reach, self.height);
+
+        let (dx, dy) = (x1 - x0, y1 - y0);
+        let length_squared = dx * dx + dy * dy;
+
+        for py in top..bottom {
+            for px in left..right {
+                let (cx, cy) = (px as f64 + 0.5, py as f64 + 0.5);
+                // Find how far along the segment the closest point is
+                let t = if length_squared > 0. {
+                    (((cx - x0) * dx + (cy - y0) * dy) / length_squared).clamp(0., 1.)
+                } else {
+                    0.
+                };
+                let distance = (cx - (x0 + t * dx)).hypot(cy - (y0 + t * dy));
+                let radius = r0 + (r1 - r0) * t;
+                let coverage = (radius + 0.5 - distance).clamp(0., 1.);
+
+                let pixel = &mut self.coverage[py * self.width + px];
+                *pixel = (*pixel).max((coverage * 255.).round() as u8);
+            }
+        }
+    }
+
+    /// Encodes the canvas as a `png` image.
+    pub(crate) fn to_png(&self) -> Vec<u8> {
+        // Each row starts with a filter type byte; `0` leaves the row unfiltered
+        let mut scanlines = Vec::with_capacity(self.height * (1 + self.width * BYTES_PER_PIXEL));
+        for row in self.coverage.chunks(self.width.max(1)).take(self.height) {
+            scanlines.push(0);
+            for &alpha in row {
+                scanlines.extend_from_slice(&[0, alpha]);
+            }
+        }
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&(self.width as u32).to_be_bytes());
+        header.extend_from_slice(&(self.height as u32).to_be_bytes());
+        // Bit depth, color type, compression, filter, and interlace methods
+        header.extend_from_slice(&[8, GRAYSCALE_ALPHA, 0, 0, 0]);
+
+        let mut png = PNG_SIGNATURE.to_vec();
+        write_chunk(&mut png, b"IHDR", &header);
+        write_chunk(&mut png, b"IDAT", &zlib_compress(&scanlines));
+        write_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+}
+
+/// Converts a coordinate to a pixel index that fits in `0..=size`.
+fn clamp_index(v: f64, size: usize) -> usize {
+    if v <= 0. {
+        0
+    } else {
+        (v.ceil() as usize).min(size)
+    }
+}
+
+/// Appends a `png` chunk with its length and checksum.
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Computes the `CRC-32` checksum used by `png` chunks.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = u32::MAX;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Computes the `Adler-32` checksum that ends a zlib stream.
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + u32::from(byte)) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+/// Compresses `data` into a zlib stream with a single deflate block.
+fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    let mut bits = BitWriter::default();
+    // Final block, compressed with fixed Huffman codes
+    bits.write(1, 1);
+    bits.write(1, 2);
+
+    let mut idx = 0;
+    while idx < data.len() {
+        // Repeated bytes and repeated pixels are the only matches worth looking for
+        let (distance, length) = (1..=BYTES_PER_PIXEL)
+            .filter(|distance| *distance <= idx)
+            .map(|distance| (distance, match_length(data, idx, distance)))
+            .max_by_key(|(_, length)| *length)
+            .unwrap_or((0, 0));
+
+        if length >= MIN_MATCH {
+            bits.write_length(length);
+            // Distances `1` through `4` are the 5-bit codes `0` through `3`, with no extra bits
+            bits.write_code(distance as u32 - 1, 5);
+            idx += length;
+        } else {
+            bits.write_symbol(u32::from(data[idx]));
+            idx += 1;
+        }
+    }
+    // End of block
+    bits.write_symbol(256);
+
+    // Deflate compression with the default window size, and no preset dictionary
+    let mut out = vec![0x78, 0x01];
+    out.extend(bits.finish());
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+/// Counts how many bytes starting at `idx` repeat the bytes `distance` earlier.
+fn match_length(data: &[u8], idx: usize, distance: usize) -> usize {
+    data[idx..]
+        .iter()
+        .take(MAX_MATCH)
+        .zip(&data[idx - distance..])
+        .take_while(|(current, earlier)| current == earlier)
+        .count()
+}
+
+/// Packs values into bytes starting from the least significant bit, as deflate requires.
+#[derive(Debug, Default)]
+struct BitWriter {
+    out: Vec<u8>,
+    buffer: u32,
+    count: u8,
+}
+
+impl BitWriter {
+    /// Writes the low `count` bits of `value`, least significant bit first.
+    fn write(&mut self, value: u32, count: u8) {
+        self.buffer |= value << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.out.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Writes a Huffman code, which deflate stores most significant bit first.
+    fn write_code(&mut self, code: u32, count: u8) {
+        self.write(code.reverse_bits() >> (32 - count), count);
+    }
+
+    /// Writes a literal byte or length symbol using the fixed Huffman codes.
+    fn write_symbol(&mut self, symbol: u32) {
+        match symbol {
+            0..=143 => self.write_code(0x30 + symbol, 8),
+            144..=255 => self.write_code(0x190 + symbol - 144, 9),
+            256..=279 => self.write_code(symbol - 256, 7),
+            _ => self.write_code(0xC0 + symbol - 280, 8),
+        }
+    }
+
+    /// Writes the symbol and extra bits for a match `length`.
+    fn write_length(&mut self, length: usize) {
+        let idx = LENGTH_BASE
+            .iter()
+            .rposition(|base| *base <= length)
+            .unwrap_or(0);
+        self.write_symbol(257 + idx as u32);
+        self.write((length - LENGTH_BASE[idx]) as u32, LENGTH_EXTRA[idx]);
+    }
+
+    /// Flushes the remaining bits, padding the last byte with zeros.
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.buffer as u8);
+        }
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::message_types::handwriting::raster::{
+        Canvas, PNG_SIGNATURE, adler32, crc32, zlib_compress,
+    };
+
+    #[test]
+    fn can_checksum() {
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    #[test]
+    fn can_compress() {
+        // Matches the output of `zlib.compress(b"a", 1)` in Python
+        assert_eq!(
+            zlib_compress(b"a"),
+            vec![0x78, 0x01, 0x4b, 0x04, 0x00, 0x00, 0x62, 0x00, 0x62]
+        );
+
+        // Long runs are stored as matches
+        assert!(zlib_compress(&[0; 10_000]).len() < 100);
+    }
+
+    #[test]
+    fn can_draw_segment() {
+        let mut canvas = Canvas::new(20, 10);
+        canvas.draw_segment((2., 5., 4.), (18., 5., 4.));
+
+        // Along the line
+        assert_eq!(canvas.coverage[5 * 20 + 10], 255);
+        // Away from the line
+        assert_eq!(canvas.coverage[0], 0);
+        assert_eq!(canvas.coverage[9 * 20 + 10], 0);
+    }
+
+    #[test]
+    fn can_encode_png() {
+        let mut canvas = Canvas::new(3, 2);
+        canvas.draw_segment((0., 0., 1.), (3., 0., 1.));
+        let png = canvas.to_png();
+
+        assert_eq!(png[0..8], PNG_SIGNATURE);
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(png[16..20], 3u32.to_be_bytes());
+        assert_eq!(png[20..24], 2u32.to_be_bytes());
+        assert_eq!(&png[png.len() - 8..png.len() - 4], b"IEND");
+    }
+}
@@ -17,3 +17,4 @@ pub mod text_effects;
 pub mod translation;
 pub mod url;
 pub mod variants;
+pub mod vcard;
@@ -129,6 +129,35 @@ impl<'a> PlacemarkMessage<'a> {
     pub fn get_url(&self) -> Option<&str> {
         self.url.or(self.original_url)
     }
+
+    /// Get the `(latitude, longitude)` of the location from its Maps URL, if the URL includes them
+    ///
+    /// Maps links store the coordinates in the `ll` query parameter, with `sll` and `coordinate`
+    /// used by older and newer versions of the app.
+    #[must_use]
+    pub fn coordinates(&self) -> Option<(f64, f64)> {
+        [self.url, self.original_url]
+            .into_iter()
+            .flatten()
+            .find_map(parse_coordinates)
+    }
+}
+
+/// Read the first valid coordinate pair from the query string of a Maps URL
+fn parse_coordinates(url: &str) -> Option<(f64, f64)> {
+    let (_, query) = url.split_once('?')?;
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .filter(|(key, _)| matches!(*key, "ll" | "sll" | "coordinate"))
+        .find_map(|(_, value)| {
+            let value = value.replace("%2C", ",").replace("%2c", ",");
+            let (latitude, longitude) = value.split_once(',')?;
+            let latitude: f64 = latitude.trim().parse().ok()?;
+            let longitude: f64 = longitude.trim().parse().ok()?;
+            ((-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude))
+                .then_some((latitude, longitude))
+        })
 }
 
 #[cfg(test)]
@@ -208,4 +237,40 @@ mod tests {
 
         assert_eq!(placemark, expected);
     }
+
+    #[test]
+    fn can_get_coordinates() {
+        let plist_path = current_dir()
+            .unwrap()
+            .as_path()
+            .join("test_data/shared_placemark/SharedPlacemark.plist");
+        let plist_data = File::open(plist_path).unwrap();
+        let plist = Value::from_reader(plist_data).unwrap();
+        let parsed = parse_ns_keyed_archiver(&plist).unwrap();
+
+        let balloon = PlacemarkMessage::from_map(&parsed).unwrap();
+        assert_eq!(balloon.coordinates(), Some((33.450858, -118.508212)));
+    }
+
+    #[test]
+    fn can_get_coordinates_fallback() {
+        let balloon = PlacemarkMessage {
+            url: Some("https://maps.apple.com/?q=Somewhere"),
+            original_url: Some("https://maps.apple.com/?coordinate=51.5007%2C-0.1246&q=Big%20Ben"),
+            place_name: None,
+            placemark: Placemark::default(),
+        };
+        assert_eq!(balloon.coordinates(), Some((51.5007, -0.1246)));
+    }
+
+    #[test]
+    fn cant_get_invalid_coordinates() {
+        let balloon = PlacemarkMessage {
+            url: Some("https://maps.apple.com/?ll=123.4,567.8"),
+            original_url: None,
+            place_name: None,
+            placemark: Placemark::default(),
+        };
+        assert_eq!(balloon.coordinates(), None);
+    }
 }
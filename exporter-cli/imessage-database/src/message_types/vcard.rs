@@ -0,0 +1,508 @@
+/*!
+ These are shared contacts, sent as [vCard](https://datatracker.ietf.org/doc/html/rfc6350) (`.vcf`) attachments.
+
+ Versions `2.1`, `3.0`, and `4.0` are supported; properties other than names, organizations,
+ phone numbers, email addresses, and photos are ignored.
+*/
+
+use base64::{Engine as _, engine::general_purpose};
+
+/// A contact parsed from a vCard
+#[derive(Debug, PartialEq, Eq, Default)]
+pub struct ContactCard {
+    /// The contact's formatted name
+    pub name: Option<String>,
+    /// The company or organization the contact belongs to
+    pub organization: Option<String>,
+    /// Phone numbers, in the order they were listed
+    pub phones: Vec<ContactField>,
+    /// Email addresses, in the order they were listed
+    pub emails: Vec<ContactField>,
+    /// The contact's photo, if it is embedded in the card
+    pub photo: Option<ContactPhoto>,
+}
+
+/// A phone number or email address, with the label it was saved with
+#[derive(Debug, PartialEq, Eq)]
+pub struct ContactField {
+    /// A description such as `mobile` or `work`
+    pub label: Option<String>,
+    /// The phone number or email address
+    pub value: String,
+}
+
+/// An image embedded in a vCard
+#[derive(Debug, PartialEq, Eq)]
+pub struct ContactPhoto {
+    /// MIME type of the image, i.e. `image/jpeg`
+    pub mime_type: String,
+    /// The decoded image
+    pub data: Vec<u8>,
+}
+
+impl ContactCard {
+    /// The best name to display for the contact
+    ///
+    /// Falls back to the organization, then the first phone number or email address.
+    #[must_use]
+    pub fn display_name(&self) -> &str {
+        self.name
+            .as_deref()
+            .or(self.organization.as_deref())
+            .or_else(|| self.phones.first().map(|phone| phone.value.as_str()))
+            .or_else(|| self.emails.first().map(|email| email.value.as_str()))
+            .unwrap_or("Unknown Contact")
+    }
+
+    /// Determine if the card has no details worth displaying
+    fn is_empty(&self) -> bool {
+        self.name.is_none()
+            && self.organization.is_none()
+            && self.phones.is_empty()
+            && self.emails.is_empty()
+    }
+}
+
+impl ContactPhoto {
+    /// The image as a `data:` URL, suitable for embedding
+    #[must_use]
+    pub fn data_uri(&self) -> String {
+        format!(
+            "data:{};base64,{}",
+            self.mime_type,
+            general_purpose::STANDARD.encode(&self.data)
+        )
+    }
+}
+
+/// A single `NAME;PARAM=VALUE:value` line from a vCard
+#[derive(Debug)]
+struct Property {
+    /// Group prefix, used by Apple to attach custom labels to a property
+    group: Option<String>,
+    /// Uppercased property name
+    name: String,
+    /// Parameters, as uppercased keys and their values
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    /// Get the values of every parameter named `key`, split on commas
+    fn param_values(&self, key: &str) -> impl Iterator<Item = &str> {
+        self.params
+            .iter()
+            .filter(move |(name, _)| name == key)
+            .flat_map(|(_, values)| values.split(','))
+            .map(|value| value.trim_matches('"'))
+    }
+}
+
+/// Parse every contact in a vCard file
+///
+/// # Example
+///
+/// ```rust
+/// use imessage_database::message_types::vcard::parse_vcards;
+///
+/// let cards = parse_vcards("BEGIN:VCARD\r\nVERSION:3.0\r\nFN:Jane Appleseed\r\nTEL;TYPE=CELL:+1 555 0100\r\nEND:VCARD\r\n");
+/// assert_eq!(cards[0].display_name(), "Jane Appleseed");
+/// ```
+#[must_use]
+pub fn parse_vcards(text: &str) -> Vec<ContactCard> {
+    let mut cards = vec![];
+    let mut current: Option<(ContactCard, Vec<Property>)> = None;
+
+    for line in unfold_lines(text) {
+        let Some(property) = parse_property(&line) else {
+            continue;
+        };
+        match (property.name.as_str(), &mut current) {
+            ("BEGIN", _) if property.value.eq_ignore_ascii_case("VCARD") => {
+                current = Some((ContactCard::default(), vec![]));
+            }
+            ("END", Some(_)) if property.value.eq_ignore_ascii_case("VCARD") => {
+                if let Some((card, properties)) = current.take() {
+                    let card = build_card(card, &properties);
+                    if !card.is_empty() {
+                        cards.push(card);
+                    }
+                }
+            }
+            (_, Some((_, properties))) => properties.push(property),
+            _ => {}
+        }
+    }
+
+    cards
+}
+
+/// Fill in a card from its properties
+fn build_card(mut card: ContactCard, properties: &[Property]) -> ContactCard {
+    // Apple stores custom labels as separate `X-ABLabel` properties in the same group
+    let label_for = |property: &Property| {
+        property
+            .group
+            .as_deref()
+            .and_then(|group| {
+                properties.iter().find(|other| {
+                    other.name == "X-ABLABEL" && other.group.as_deref() == Some(group)
+                })
+            })
+            .map(|label| clean_apple_label(&label.value))
+            .or_else(|| type_label(property))
+    };
+
+    let mut structured_name = None;
+    for property in properties {
+        match property.name.as_str() {
+            "FN" if !property.value.is_empty() => card.name = Some(property.value.clone()),
+            "N" => structured_name = format_structured_name(&property.value),
+            "ORG" => {
+                let organization = split_components(&property.value)
+                    .into_iter()
+                    .filter(|part| !part.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                if !organization.is_empty() {
+                    card.organization = Some(organization);
+                }
+            }
+            "TEL" if !property.value.is_empty() => card.phones.push(ContactField {
+                label: label_for(property),
+                value: property
+                    .value
+                    .strip_prefix("tel:")
+                    .unwrap_or(&property.value)
+                    .to_string(),
+            }),
+            "EMAIL" if !property.value.is_empty() => card.emails.push(ContactField {
+                label: label_for(property),
+                value: property.value.clone(),
+            }),
+            "PHOTO" if card.photo.is_none() => card.photo = parse_photo(property),
+            _ => {}
+        }
+    }
+
+    if card.name.is_none() {
+        card.name = structured_name;
+    }
+    card
+}
+
+/// Join folded lines back together
+///
+/// Lines that start with a space or tab continue the previous line. In `2.1` cards, quoted-printable
+/// values that end with `=` also continue onto the next line.
+fn unfold_lines(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    let mut soft_break = false;
+    for line in text.lines() {
+        let line = line.trim_end_matches('\r');
+        if let Some(continuation) = line.strip_prefix([' ', '\t'])
+            && let Some(previous) = lines.last_mut()
+        {
+            previous.push_str(continuation);
+        } else if soft_break && let Some(previous) = lines.last_mut() {
+            previous.push_str(line);
+        } else {
+            lines.push(line.to_string());
+        }
+
+        soft_break = lines.last().is_some_and(|last| {
+            last.ends_with('=') && last.to_uppercase().contains("QUOTED-PRINTABLE")
+        });
+        if soft_break && let Some(last) = lines.last_mut() {
+            last.pop();
+        }
+    }
+    lines
+}
+
+/// Parse a content line into its group, name, parameters, and decoded value
+fn parse_property(line: &str) -> Option<Property> {
+    // The value starts after the first `:` that is not inside a quoted parameter
+    let mut in_quotes = false;
+    let split = line.char_indices().find_map(|(idx, ch)| match ch {
+        '"' => {
+            in_quotes = !in_quotes;
+            None
+        }
+        ':' if !in_quotes => Some(idx),
+        _ => None,
+    })?;
+    let (head, raw_value) = (&line[..split], &line[split + 1..]);
+
+    let mut parts = head.split(';');
+    let full_name = parts.next()?;
+    let (group, name) = match full_name.rsplit_once('.') {
+        Some((group, name)) => (Some(group.to_string()), name),
+        None => (None, full_name),
+    };
+
+    let params = parts
+        .map(|param| match param.split_once('=') {
+            Some((key, value)) => (key.trim().to_uppercase(), value.to_string()),
+            // `2.1` cards list types without a key, i.e. `TEL;CELL:`
+            None => ("TYPE".to_string(), param.to_string()),
+        })
+        .collect();
+
+    let mut property = Property {
+        group,
+        name: name.trim().to_uppercase(),
+        params,
+        value: String::new(),
+    };
+
+    let quoted_printable = property
+        .param_values("ENCODING")
+        .any(|encoding| encoding.eq_ignore_ascii_case("QUOTED-PRINTABLE"))
+        || property
+            .param_values("TYPE")
+            .any(|kind| kind.eq_ignore_ascii_case("QUOTED-PRINTABLE"));
+    property.value = if quoted_printable {
+        decode_quoted_printable(raw_value)
+    } else {
+        raw_value.to_string()
+    };
+
+    // Structured values keep their escapes until they are split into components
+    if !matches!(property.name.as_str(), "N" | "ORG" | "PHOTO") {
+        property.value = unescape(&property.value);
+    }
+    Some(property)
+}
+
+/// Decode `=XX` hex escapes from a quoted-printable value
+fn decode_quoted_printable(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        if bytes[idx] == b'='
+            && let Some(hex) = value.get(idx + 1..idx + 3)
+            && let Ok(byte) = u8::from_str_radix(hex, 16)
+        {
+            decoded.push(byte);
+            idx += 3;
+        } else {
+            decoded.push(bytes[idx]);
+            idx += 1;
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Replace vCard escape sequences with the characters they represent
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(ch) = chars.next() {
+        if ch == '\\' {
+            match chars.next() {
+                Some('n' | 'N') => out.push('\n'),
+                Some(other) => out.push(other),
+                None => out.push('\\'),
+            }
+        } else {
+            out.push(ch);
+        }
+    }
+    out
+}
+
+/// Split a structured value on unescaped `;`, unescaping each component
+fn split_components(value: &str) -> Vec<String> {
+    let mut components = vec![];
+    let mut current = String::new();
+    let mut escaped = false;
+    for ch in value.chars() {
+        if escaped {
+            current.push('\\');
+            current.push(ch);
+            escaped = false;
+        } else if ch == '\\' {
+            escaped = true;
+        } else if ch == ';' {
+            components.push(unescape(current.trim()));
+            current.clear();
+        } else {
+            current.push(ch);
+        }
+    }
+    components.push(unescape(current.trim()));
+    components
+}
+
+/// Build a display name from an `N` value: `family;given;additional;prefixes;suffixes`
+fn format_structured_name(value: &str) -> Option<String> {
+    let components = split_components(value);
+    let get = |idx: usize| components.get(idx).map_or("", String::as_str);
+    let name = [get(3), get(1), get(2), get(0), get(4)]
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    (!name.is_empty()).then_some(name)
+}
+
+/// Convert Apple's built-in labels, such as `_$!<Mobile>!$_`, to plain text
+fn clean_apple_label(label: &str) -> String {
+    label
+        .trim_start_matches("_$!<")
+        .trim_end_matches(">!$_")
+        .to_lowercase()
+}
+
+/// Build a label from a property's `TYPE` parameters, ignoring types that only describe the format
+fn type_label(property: &Property) -> Option<String> {
+    property
+        .param_values("TYPE")
+        .map(str::to_lowercase)
+        .find(|kind| {
+            !matches!(
+                kind.as_str(),
+                "pref" | "voice" | "internet" | "x400" | "quoted-printable" | "text"
+            ) && !kind.starts_with("x-")
+        })
+        .map(|kind| match kind.as_str() {
+            "cell" => "mobile".to_string(),
+            _ => kind,
+        })
+}
+
+/// Decode an embedded photo
+///
+/// `3.0` cards use `ENCODING=b;TYPE=JPEG`, `2.1` cards use `ENCODING=BASE64;JPEG`, and `4.0` cards use a `data:` URL.
+/// Photos that link to a remote URL are not fetched.
+fn parse_photo(property: &Property) -> Option<ContactPhoto> {
+    let (mime_type, encoded) = if let Some(uri) = property.value.strip_prefix("data:") {
+        let (header, data) = uri.split_once(',')?;
+        let mime_type = header.split(';').next().filter(|mime| !mime.is_empty());
+        (
+            mime_type.unwrap_or("image/jpeg").to_string(),
+            data.to_string(),
+        )
+    } else if property
+        .param_values("ENCODING")
+        .any(|encoding| matches!(encoding.to_lowercase().as_str(), "b" | "base64"))
+    {
+        let kind = property
+            .param_values("TYPE")
+            .map(str::to_lowercase)
+            .find(|kind| !matches!(kind.as_str(), "base64" | "b"))
+            .unwrap_or_else(|| "jpeg".to_string());
+        let mime_type = if kind.contains('/') {
+            kind
+        } else {
+            format!("image/{kind}")
+        };
+        (mime_type, property.value.clone())
+    } else {
+        return None;
+    };
+
+    let encoded: String = encoded.chars().filter(|ch| !ch.is_whitespace()).collect();
+    let data = general_purpose::STANDARD.decode(encoded).ok()?;
+    Some(ContactPhoto { mime_type, data })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::message_types::vcard::{ContactCard, ContactField, ContactPhoto, parse_vcards};
+
+    #[test]
+    fn can_parse_apple_vcard() {
+        let vcard = "BEGIN:VCARD\r\nVERSION:3.0\r\nPRODID:-//Apple Inc.//iPhone OS 17.0//EN\r\nN:Appleseed;Jane;;Dr.;\r\nFN:Jane Appleseed\r\nORG:Apple Inc.;\r\nitem1.TEL;type=pref:+1 (555) 010-0100\r\nitem1.X-ABLabel:_$!<Mobile>!$_\r\nTEL;type=WORK;type=VOICE:+1 555 010 0200\r\nEMAIL;type=INTERNET;type=HOME;type=pref:jane@example.com\r\nPHOTO;ENCODING=b;TYPE=JPEG:/9j/\r\n 4A==\r\nEND:VCARD\r\n";
+
+        let expected = vec![ContactCard {
+            name: Some("Jane Appleseed".to_string()),
+            organization: Some("Apple Inc.".to_string()),
+            phones: vec![
+                ContactField {
+                    label: Some("mobile".to_string()),
+                    value: "+1 (555) 010-0100".to_string(),
+                },
+                ContactField {
+                    label: Some("work".to_string()),
+                    value: "+1 555 010 0200".to_string(),
+                },
+            ],
+            emails: vec![ContactField {
+                label: Some("home".to_string()),
+                value: "jane@example.com".to_string(),
+            }],
+            photo: Some(ContactPhoto {
+                mime_type: "image/jpeg".to_string(),
+                data: vec![0xff, 0xd8, 0xff, 0xe0],
+            }),
+        }];
+
+        assert_eq!(parse_vcards(vcard), expected);
+    }
+
+    #[test]
+    fn can_parse_structured_name() {
+        let vcard =
+            "BEGIN:VCARD\nVERSION:4.0\nN:Doe;John;Q.;Mr.;Jr.\nEMAIL:john@example.com\nEND:VCARD\n";
+
+        let cards = parse_vcards(vcard);
+        assert_eq!(cards[0].name.as_deref(), Some("Mr. John Q. Doe Jr."));
+        assert_eq!(cards[0].emails[0].label, None);
+    }
+
+    #[test]
+    fn can_parse_quoted_printable() {
+        let vcard = "BEGIN:VCARD\r\nVERSION:2.1\r\nFN;CHARSET=UTF-8;ENCODING=QUOTED-PRINTABLE:Jos=C3=A9 =\r\nGarc=C3=ADa\r\nTEL;CELL:5550100\r\nEND:VCARD\r\n";
+
+        let cards = parse_vcards(vcard);
+        assert_eq!(cards[0].name.as_deref(), Some("José García"));
+        assert_eq!(cards[0].phones[0].label.as_deref(), Some("mobile"));
+    }
+
+    #[test]
+    fn can_parse_escapes() {
+        let vcard =
+            "BEGIN:VCARD\nVERSION:3.0\nFN:Smith\\, Anne\nORG:Acme\\; Co;Research\nEND:VCARD\n";
+
+        let cards = parse_vcards(vcard);
+        assert_eq!(cards[0].name.as_deref(), Some("Smith, Anne"));
+        assert_eq!(cards[0].organization.as_deref(), Some("Acme; Co, Research"));
+    }
+
+    #[test]
+    fn can_parse_data_uri_photo() {
+        let vcard =
+            "BEGIN:VCARD\nVERSION:4.0\nFN:A\nPHOTO:data:image/png;base64,iVBORw==\nEND:VCARD\n";
+
+        let cards = parse_vcards(vcard);
+        let photo = cards[0].photo.as_ref().unwrap();
+        assert_eq!(photo.mime_type, "image/png");
+        assert_eq!(photo.data_uri(), "data:image/png;base64,iVBORw==");
+    }
+
+    #[test]
+    fn can_parse_multiple_cards() {
+        let vcard = "BEGIN:VCARD\nFN:One\nEND:VCARD\nBEGIN:VCARD\nFN:Two\nEND:VCARD\nBEGIN:VCARD\nVERSION:3.0\nEND:VCARD\n";
+
+        let cards = parse_vcards(vcard);
+        assert_eq!(cards.len(), 2);
+        assert_eq!(cards[1].display_name(), "Two");
+    }
+
+    #[test]
+    fn can_get_display_name_fallback() {
+        let vcard = "BEGIN:VCARD\nTEL:tel:+15550100\nEND:VCARD\n";
+
+        let cards = parse_vcards(vcard);
+        assert_eq!(cards[0].display_name(), "+15550100");
+    }
+
+    #[test]
+    fn cant_parse_invalid() {
+        assert!(parse_vcards("").is_empty());
+        assert!(parse_vcards("FN:Not in a card").is_empty());
+    }
+}
@@ -16,7 +16,10 @@ use std::{
 
 use crate::{
     error::{attachment::AttachmentError, table::TableError},
-    message_types::sticker::{StickerDecoration, StickerEffect, StickerSource, get_sticker_effect},
+    message_types::{
+        sticker::{StickerDecoration, StickerEffect, StickerSource, get_sticker_effect},
+        vcard::{ContactCard, parse_vcards},
+    },
     tables::{
         diagnostic::AttachmentDiagnostic,
         messages::Message,
@@ -253,6 +256,38 @@ impl Attachment {
         Ok(Some(StickerEffect::default()))
     }
 
+    /// Determine if the attachment is a shared contact
+    #[must_use]
+    pub fn is_vcard(&self) -> bool {
+        matches!(
+            self.mime_type(),
+            MediaType::Text(subtype) if matches!(subtype.to_lowercase().as_str(), "vcard" | "x-vcard" | "directory")
+        ) || self
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("vcf"))
+    }
+
+    /// Parse the contacts in a shared contact attachment
+    ///
+    /// `db_path` is the path to the root of the backup directory.
+    /// This is the same path used by [`get_connection()`](crate::tables::table::get_connection).
+    pub fn get_contact_cards(
+        &self,
+        platform: &Platform,
+        db_path: &Path,
+        custom_attachment_root: Option<&str>,
+    ) -> Result<Option<Vec<ContactCard>>, AttachmentError> {
+        // Handle the non-contact case
+        if !self.is_vcard() {
+            return Ok(None);
+        }
+
+        if let Some(data) = self.as_bytes(platform, db_path, custom_attachment_root)? {
+            return Ok(Some(parse_vcards(&String::from_utf8_lossy(&data))));
+        }
+        Ok(None)
+    }
+
     /// Get the path to an attachment, if it exists
     #[must_use]
     pub fn path(&self) -> Option<&Path> {
@@ -652,6 +687,19 @@ mod tests {
         assert_eq!(attachment.mime_type(), MediaType::Image("png"));
     }
 
+    #[test]
+    fn can_get_is_vcard() {
+        let mut attachment = sample_attachment();
+        assert!(!attachment.is_vcard());
+
+        attachment.mime_type = Some("text/x-vcard".to_string());
+        assert!(attachment.is_vcard());
+
+        attachment.mime_type = None;
+        attachment.filename = Some("a/b/c/Jane Appleseed.vcf".to_string());
+        assert!(attachment.is_vcard());
+    }
+
     #[test]
     fn can_get_mime_type_heic() {
         let mut attachment = sample_attachment();
@@ -729,6 +729,39 @@ impl Message {
         None
     }
 
+    /// Gets the number of seconds between when a message was sent (`date`) and when it was delivered (`date_delivered`).
+    ///
+    /// Only messages the database owner sent are tagged with a delivery date.
+    ///
+    /// `offset` can be provided by [`get_offset`](crate::util::dates::get_offset) or manually.
+    #[must_use]
+    pub fn seconds_until_delivered(&self, offset: i64) -> Option<i64> {
+        if self.is_from_me && self.date_delivered != 0 && self.date != 0 {
+            let seconds =
+                self.date_delivered(offset).ok()?.timestamp() - self.date(offset).ok()?.timestamp();
+            return (seconds >= 0).then_some(seconds);
+        }
+        None
+    }
+
+    /// Gets the number of seconds between when a message was sent (`date`) and when it was read (`date_read`). This can happen in two ways:
+    ///
+    /// - You received a message, then waited to read it
+    /// - You sent a message, and the recipient waited to read it, if they share read receipts
+    ///
+    /// Unlike [`Message::time_until_read`], messages you sent are measured to the date they were read, not the date they were delivered.
+    ///
+    /// `offset` can be provided by [`get_offset`](crate::util::dates::get_offset) or manually.
+    #[must_use]
+    pub fn seconds_until_read(&self, offset: i64) -> Option<i64> {
+        if self.date_read != 0 && self.date != 0 {
+            let seconds =
+                self.date_read(offset).ok()?.timestamp() - self.date(offset).ok()?.timestamp();
+            return (seconds >= 0).then_some(seconds);
+        }
+        None
+    }
+
     // MARK: Bools
     /// `true` if the message is a response to a thread, else `false`
     #[must_use]
@@ -1002,6 +1035,26 @@ impl Message {
             }
         }
 
+        // Query expression filter, narrowed to the parts that can be expressed in SQL
+        if let Some(condition) = context
+            .expression
+            .as_ref()
+            .and_then(|expression| expression.to_sql(include_recoverable))
+        {
+            if !filters.is_empty() {
+                filters.push_str(" AND ");
+            }
+            let _ = write!(filters, " {condition}");
+        }
+
+        // ROWID filter
+        if let Some(rowid) = context.after_rowid {
+            if !filters.is_empty() {
+                filters.push_str(" AND ");
+            }
+            let _ = write!(filters, " m.ROWID > {rowid}");
+        }
+
         if !filters.is_empty() {
             return format!("WHERE {filters}");
         }
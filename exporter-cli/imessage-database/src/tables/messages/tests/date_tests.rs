@@ -47,6 +47,65 @@ mod tests {
         assert_eq!(m.time_until_read(offset), None);
     }
 
+    #[test]
+    fn can_get_seconds_until_delivered() {
+        let offset = get_offset();
+        let mut message = Message::blank();
+        message.is_from_me = true;
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        // May 17, 2022  9:30:31 PM
+        message.date_delivered = 674530231992568192;
+
+        assert_eq!(message.seconds_until_delivered(offset), Some(3649));
+    }
+
+    #[test]
+    fn can_get_seconds_until_delivered_received() {
+        let offset = get_offset();
+        let mut message = Message::blank();
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        // May 17, 2022  9:30:31 PM
+        message.date_delivered = 674530231992568192;
+
+        assert_eq!(message.seconds_until_delivered(offset), None);
+    }
+
+    #[test]
+    fn can_get_seconds_until_read_sent() {
+        let offset = get_offset();
+        let mut message = Message::blank();
+        message.is_from_me = true;
+        // May 17, 2022  8:29:42 PM
+        message.date = 674526582885055488;
+        message.date_delivered = 674526582885055488;
+        // May 17, 2022  9:30:31 PM
+        message.date_read = 674530231992568192;
+
+        assert_eq!(message.seconds_until_read(offset), Some(3649));
+    }
+
+    #[test]
+    fn can_get_seconds_until_read_before_date() {
+        let offset = get_offset();
+        let mut message = Message::blank();
+        // May 17, 2022  9:30:31 PM
+        message.date = 674530231992568192;
+        // May 17, 2022  8:29:42 PM
+        message.date_read = 674526582885055488;
+
+        assert_eq!(message.seconds_until_read(offset), None);
+    }
+
+    #[test]
+    fn can_get_seconds_until_read_none() {
+        let offset = get_offset();
+        let message = Message::blank();
+        assert_eq!(message.seconds_until_read(offset), None);
+        assert_eq!(message.seconds_until_delivered(offset), None);
+    }
+
     #[test]
     fn can_get_date_edited() {
         let offset = get_offset();
@@ -113,6 +113,54 @@ mod exclude_recoverable_tests {
         assert!(statement.contains("m.date >= "));
         assert!(statement.contains("m.date <= "));
     }
+
+    #[test]
+    fn can_generate_filter_statement_expression() {
+        let mut context = QueryContext::default();
+        context.set_expression("from:me OR service:SMS").unwrap();
+
+        let statement = Message::generate_filter_statement(&context, false);
+        assert_eq!(
+            statement,
+            "WHERE  (m.is_from_me = 1 OR m.service = 'SMS' COLLATE NOCASE)"
+        );
+    }
+
+    #[test]
+    fn can_generate_filter_statement_chat_ids_partial_expression() {
+        let mut context = QueryContext::default();
+        context.set_selected_chat_ids(BTreeSet::from([1, 2, 3]));
+        context.set_expression("from:me text:hello").unwrap();
+
+        let statement = Message::generate_filter_statement(&context, false);
+        assert_eq!(
+            statement,
+            "WHERE  c.chat_id IN (1, 2, 3) AND  m.is_from_me = 1"
+        );
+    }
+
+    #[test]
+    fn can_generate_filter_statement_after_rowid() {
+        let mut context = QueryContext::default();
+        context.set_selected_chat_ids(BTreeSet::from([1, 2, 3]));
+        context.set_after_rowid(100);
+
+        let statement = Message::generate_filter_statement(&context, false);
+        assert_eq!(
+            statement,
+            "WHERE  c.chat_id IN (1, 2, 3) AND  m.ROWID > 100"
+        );
+    }
+
+    #[test]
+    fn can_generate_filter_statement_unsupported_expression() {
+        let mut context = QueryContext::default();
+        context.set_expression("is:reply OR text:hello").unwrap();
+        assert!(context.has_filters());
+
+        let statement = Message::generate_filter_statement(&context, false);
+        assert_eq!(statement, "");
+    }
 }
 
 #[cfg(test)]
@@ -213,6 +261,23 @@ mod include_recoverable_tests {
         let statement = Message::generate_filter_statement(&context, true);
         assert_eq!(statement, "");
     }
+
+    #[test]
+    fn can_generate_filter_statement_chat_expression() {
+        let mut context = QueryContext::default();
+        context.set_expression("chat:Family").unwrap();
+        if let Some(expression) = context.expression.as_mut() {
+            expression.resolve_names(&mut |_: &str| BTreeSet::new(), &mut |_: &str| {
+                BTreeSet::from([4, 5])
+            });
+        }
+
+        let statement = Message::generate_filter_statement(&context, true);
+        assert_eq!(
+            statement,
+            "WHERE  (c.chat_id IN (4, 5) OR d.chat_id IN (4, 5))"
+        );
+    }
 }
 
 #[cfg(test)]
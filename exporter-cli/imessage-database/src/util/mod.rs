@@ -8,6 +8,7 @@ pub mod dirs;
 pub mod platform;
 pub mod plist;
 pub mod query_context;
+pub mod query_expression;
 pub mod size;
 pub mod streamtyped;
 pub mod typedstream;
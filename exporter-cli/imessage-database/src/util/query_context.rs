@@ -7,10 +7,13 @@ use chrono::prelude::*;
 
 use crate::{
     error::query_context::QueryContextError,
-    util::dates::{TIMESTAMP_FACTOR, get_offset},
+    util::{
+        dates::{TIMESTAMP_FACTOR, get_offset},
+        query_expression::QueryExpression,
+    },
 };
 
-#[derive(Debug, Default, PartialEq, Eq)]
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
 /// Represents filter configurations for a SQL query.
 pub struct QueryContext {
     /// The start date filter. Only messages sent on or after this date will be included.
@@ -21,6 +24,10 @@ pub struct QueryContext {
     pub selected_handle_ids: Option<BTreeSet<i32>>,
     /// Selected chat IDs
     pub selected_chat_ids: Option<BTreeSet<i32>>,
+    /// Boolean expression that selected messages must satisfy
+    pub expression: Option<QueryExpression>,
+    /// Only messages with a `ROWID` greater than this will be included
+    pub after_rowid: Option<i32>,
 }
 
 impl QueryContext {
@@ -86,8 +93,37 @@ impl QueryContext {
         self.selected_chat_ids = (!selected_chat_ids.is_empty()).then_some(selected_chat_ids);
     }
 
+    /// Populate a [`QueryContext`] with a boolean query expression
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_expression("from:me AND has:attachment");
+    /// ```
+    pub fn set_expression(&mut self, query: &str) -> Result<(), QueryContextError> {
+        self.expression = Some(QueryExpression::parse(query)?);
+        Ok(())
+    }
+
+    /// Populate a [`QueryContext`] with the `ROWID` that selected messages must be newer than
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::query_context::QueryContext;
+    ///
+    /// let mut context = QueryContext::default();
+    /// context.set_after_rowid(1000);
+    /// ```
+    pub fn set_after_rowid(&mut self, rowid: i32) {
+        self.after_rowid = Some(rowid);
+    }
+
     /// Ensure a date string is valid
-    fn sanitize_date(date: &str) -> Option<i64> {
+    pub(crate) fn sanitize_date(date: &str) -> Option<i64> {
         if date.len() < 9 {
             return None;
         }
@@ -136,6 +172,8 @@ impl QueryContext {
             || self.end.is_some()
             || self.selected_chat_ids.is_some()
             || self.selected_handle_ids.is_some()
+            || self.expression.is_some()
+            || self.after_rowid.is_some()
     }
 }
 
@@ -218,6 +256,23 @@ mod use_tests {
         assert!(context.end.is_some());
         assert!(context.has_filters());
     }
+
+    #[test]
+    fn can_create_expression() {
+        let mut context = QueryContext::default();
+        context.set_expression("from:me OR is:reply").unwrap();
+
+        assert!(context.expression.is_some());
+        assert!(context.has_filters());
+    }
+
+    #[test]
+    fn cant_create_bad_expression() {
+        let mut context = QueryContext::default();
+        assert!(context.set_expression("from:me OR").is_err());
+        assert!(context.expression.is_none());
+        assert!(!context.has_filters());
+    }
 }
 
 #[cfg(test)]
@@ -254,6 +309,15 @@ mod id_tests {
         assert!(!qc.has_filters());
     }
 
+    #[test]
+    fn test_can_set_after_rowid() {
+        let mut qc = QueryContext::default();
+        qc.set_after_rowid(100);
+
+        assert_eq!(qc.after_rowid, Some(100));
+        assert!(qc.has_filters());
+    }
+
     #[test]
     fn test_can_set_selected_handle_ids() {
         let mut qc = QueryContext::default();
@@ -0,0 +1,922 @@
+/*!
+ Contains logic for parsing and evaluating boolean message selection expressions.
+
+ A [`QueryExpression`] combines [`Predicate`]s with `AND`, `OR`, `NOT`, and parentheses. Adjacent
+ predicates without an operator between them are joined with `AND`.
+
+ | Predicate | Example | Matches |
+ |---|---|---|
+ | `from:` / `sender:` | `from:me`, `from:"Steve Jobs"` | Messages sent by the database owner or a named participant |
+ | `chat:` | `chat:"Family"`, `chat:42` | Messages in a named chat or a chat `ROWID` |
+ | `date` | `date>=2023-01-01`, `date<2024-01-01`, `date:2023-05-04` | Messages sent relative to a day |
+ | `service:` | `service:SMS` | Messages sent over a specific service |
+ | `text:` | `text:"dinner plans"` | Messages whose text contains a case-insensitive substring |
+ | `has:attachment` | | Messages with at least one attachment |
+ | `is:reply` | | Messages that reply to a thread |
+ | `is:edited` | | Messages that were edited or unsent |
+ | `mime:` | `mime:application/pdf`, `mime:image*` | Messages with an attachment of a matching MIME type; a trailing `*` matches any suffix |
+
+ Predicates that can be represented in SQL are compiled into the `WHERE` clause generated for a
+ [`QueryContext`](crate::util::query_context::QueryContext). When an expression contains predicates
+ that require decoded message data, [`QueryExpression::is_sql_complete()`] returns `false` and callers
+ must post-filter rows with [`QueryExpression::matches()`].
+
+ # Example
+
+ ```
+ use imessage_database::util::query_expression::QueryExpression;
+
+ let expression = QueryExpression::parse("from:me AND (has:attachment OR text:\"hello\")").unwrap();
+ assert!(!expression.is_sql_complete());
+ ```
+*/
+
+use std::{collections::BTreeSet, fmt::Write};
+
+use rusqlite::Connection;
+
+use crate::{
+    error::query_context::QueryContextError,
+    tables::{
+        attachment::Attachment,
+        messages::Message,
+        table::{ATTACHMENT, MESSAGE_ATTACHMENT_JOIN},
+    },
+    util::{dates::TIMESTAMP_FACTOR, query_context::QueryContext},
+};
+
+/// Nanoseconds in a single day, used to expand `date:YYYY-MM-DD` into a range
+const DAY_NANOS: i64 = 86_400 * TIMESTAMP_FACTOR;
+
+// MARK: Expression
+/// A boolean expression tree describing which messages to select
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum QueryExpression {
+    /// Both sides must match
+    And(Box<QueryExpression>, Box<QueryExpression>),
+    /// Either side must match
+    Or(Box<QueryExpression>, Box<QueryExpression>),
+    /// The inner expression must not match
+    Not(Box<QueryExpression>),
+    /// A single test applied to a message
+    Predicate(Predicate),
+}
+
+/// A reference to participants or chats that may not yet be resolved to database row IDs
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Selector {
+    /// The user-provided name, number, email, or ID that still needs to be resolved
+    Name(String),
+    /// The resolved database row IDs
+    Ids(BTreeSet<i32>),
+}
+
+/// Comparison operators supported by the `date` predicate
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Comparison {
+    /// Strictly before the start of the day
+    Before,
+    /// Before the end of the day
+    OnOrBefore,
+    /// During the day
+    On,
+    /// On or after the start of the day
+    OnOrAfter,
+    /// On or after the end of the day
+    After,
+}
+
+/// A single test applied to a message
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Predicate {
+    /// The message was sent by the database owner
+    FromMe,
+    /// The message was sent by one of the selected handles
+    Sender(Selector),
+    /// The message belongs to one of the selected chats
+    Chat(Selector),
+    /// The message date compares to the start of the given day (in database nanoseconds)
+    Date(Comparison, i64),
+    /// The message was sent over the named service, i.e. `iMessage` or `SMS`
+    Service(String),
+    /// The message text contains the given substring, ignoring case
+    Text(String),
+    /// The message has at least one attachment
+    HasAttachment,
+    /// The message is a reply to a thread
+    IsReply,
+    /// The message was edited or unsent
+    IsEdited,
+    /// The message has an attachment whose MIME type matches; a trailing `*` matches any subtype
+    MimeType(String),
+}
+
+impl QueryExpression {
+    /// Parse a query string into a [`QueryExpression`]
+    ///
+    /// `NOT` binds tighter than `AND`, which binds tighter than `OR`.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use imessage_database::util::query_expression::QueryExpression;
+    ///
+    /// let expression = QueryExpression::parse("date>=2023-01-01 NOT service:SMS");
+    /// assert!(expression.is_ok());
+    /// ```
+    pub fn parse(query: &str) -> Result<Self, QueryContextError> {
+        let tokens = tokenize(query)?;
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+        };
+        let expression = parser.parse_or()?;
+        match parser.peek() {
+            None => Ok(expression),
+            Some(token) => Err(QueryContextError::InvalidQuery(format!(
+                "unexpected {token} at position {}",
+                parser.position + 1
+            ))),
+        }
+    }
+
+    /// `true` if every predicate in the expression compiles to SQL, else `false`
+    ///
+    /// If this is `false`, rows returned by the generated SQL are a superset of the
+    /// matching messages and must be filtered with [`Self::matches()`].
+    #[must_use]
+    pub fn is_sql_complete(&self) -> bool {
+        match self {
+            QueryExpression::And(left, right) | QueryExpression::Or(left, right) => {
+                left.is_sql_complete() && right.is_sql_complete()
+            }
+            QueryExpression::Not(inner) => inner.is_sql_complete(),
+            QueryExpression::Predicate(predicate) => predicate.to_sql(false).is_some(),
+        }
+    }
+
+    /// Generate the widest SQL condition that does not exclude any matching message
+    ///
+    /// Returns [`None`] if no part of the expression can narrow the query.
+    #[must_use]
+    pub fn to_sql(&self, include_recoverable: bool) -> Option<String> {
+        match self {
+            QueryExpression::And(left, right) => match (
+                left.to_sql(include_recoverable),
+                right.to_sql(include_recoverable),
+            ) {
+                (Some(left), Some(right)) => Some(format!("({left} AND {right})")),
+                (Some(side), None) | (None, Some(side)) => Some(side),
+                (None, None) => None,
+            },
+            // If either side cannot be expressed, the union cannot be narrowed
+            QueryExpression::Or(left, right) => Some(format!(
+                "({} OR {})",
+                left.to_sql(include_recoverable)?,
+                right.to_sql(include_recoverable)?
+            )),
+            // Negating a partial condition would drop matching rows, so only negate complete ones.
+            // Conditions on columns that can be `NULL`, like a message's service or the chat of a
+            // message that was not deleted, evaluate to `NULL` instead of false, so they are treated
+            // as false before negating them.
+            QueryExpression::Not(inner) => {
+                if inner.is_sql_complete() {
+                    Some(format!(
+                        "NOT IFNULL({}, 0)",
+                        inner.to_sql(include_recoverable)?
+                    ))
+                } else {
+                    None
+                }
+            }
+            QueryExpression::Predicate(predicate) => predicate.to_sql(include_recoverable),
+        }
+    }
+
+    /// Determine if a message satisfies the expression
+    ///
+    /// The message body should already be decoded with [`Message::parse_body()`] and
+    /// [`Message::apply_body()`] so that `text:` predicates have data to match against.
+    pub fn matches(&self, message: &Message, db: &Connection) -> bool {
+        match self {
+            QueryExpression::And(left, right) => {
+                left.matches(message, db) && right.matches(message, db)
+            }
+            QueryExpression::Or(left, right) => {
+                left.matches(message, db) || right.matches(message, db)
+            }
+            QueryExpression::Not(inner) => !inner.matches(message, db),
+            QueryExpression::Predicate(predicate) => predicate.matches(message, db),
+        }
+    }
+
+    /// Resolve every [`Selector::Name`] into row IDs using the provided lookups
+    ///
+    /// `senders` maps a participant name to handle IDs and `chats` maps a chat name to chat IDs.
+    pub fn resolve_names<S, C>(&mut self, senders: &mut S, chats: &mut C)
+    where
+        S: FnMut(&str) -> BTreeSet<i32>,
+        C: FnMut(&str) -> BTreeSet<i32>,
+    {
+        match self {
+            QueryExpression::And(left, right) | QueryExpression::Or(left, right) => {
+                left.resolve_names(senders, chats);
+                right.resolve_names(senders, chats);
+            }
+            QueryExpression::Not(inner) => inner.resolve_names(senders, chats),
+            QueryExpression::Predicate(Predicate::Sender(selector)) => {
+                if let Selector::Name(name) = selector {
+                    *selector = Selector::Ids(senders(name.as_str()));
+                }
+            }
+            QueryExpression::Predicate(Predicate::Chat(selector)) => {
+                if let Selector::Name(name) = selector {
+                    *selector = Selector::Ids(chats(name.as_str()));
+                }
+            }
+            QueryExpression::Predicate(_) => {}
+        }
+    }
+}
+
+// MARK: Predicate
+impl Predicate {
+    /// Generate the SQL condition for this predicate, if it can be expressed in SQL
+    ///
+    /// `is:reply`, `is:edited`, and `text:` are not compiled: their columns are missing from
+    /// older schemas, and most message text lives in the `attributedBody` blob.
+    fn to_sql(&self, include_recoverable: bool) -> Option<String> {
+        match self {
+            Predicate::FromMe => Some(String::from("m.is_from_me = 1")),
+            Predicate::Sender(selector) => Some(match selector {
+                Selector::Ids(ids) if !ids.is_empty() => {
+                    format!("(m.is_from_me = 0 AND m.handle_id IN ({}))", join_ids(ids))
+                }
+                // Unresolved or empty selectors match nothing
+                _ => String::from("0"),
+            }),
+            Predicate::Chat(selector) => Some(match selector {
+                Selector::Ids(ids) if !ids.is_empty() => {
+                    let ids = join_ids(ids);
+                    if include_recoverable {
+                        format!("(c.chat_id IN ({ids}) OR d.chat_id IN ({ids}))")
+                    } else {
+                        format!("c.chat_id IN ({ids})")
+                    }
+                }
+                _ => String::from("0"),
+            }),
+            Predicate::Date(comparison, day) => Some(match comparison {
+                Comparison::Before => format!("m.date < {day}"),
+                Comparison::OnOrBefore => format!("m.date < {}", day + DAY_NANOS),
+                Comparison::On => {
+                    format!("(m.date >= {day} AND m.date < {})", day + DAY_NANOS)
+                }
+                Comparison::OnOrAfter => format!("m.date >= {day}"),
+                Comparison::After => format!("m.date >= {}", day + DAY_NANOS),
+            }),
+            Predicate::Service(service) => Some(format!(
+                "m.service = '{}' COLLATE NOCASE",
+                escape_sql(service)
+            )),
+            Predicate::HasAttachment => Some(format!(
+                "EXISTS (SELECT 1 FROM {MESSAGE_ATTACHMENT_JOIN} j WHERE j.message_id = m.ROWID)"
+            )),
+            Predicate::MimeType(mime) => {
+                let mut condition = String::with_capacity(160);
+                let _ = write!(
+                    condition,
+                    "EXISTS (SELECT 1 FROM {MESSAGE_ATTACHMENT_JOIN} j JOIN {ATTACHMENT} a ON j.attachment_id = a.ROWID WHERE j.message_id = m.ROWID AND "
+                );
+                match mime.strip_suffix('*') {
+                    Some(prefix) => {
+                        let _ = write!(
+                            condition,
+                            "a.mime_type LIKE '{}%' ESCAPE '\\')",
+                            escape_like(prefix)
+                        );
+                    }
+                    None => {
+                        let _ = write!(
+                            condition,
+                            "a.mime_type = '{}' COLLATE NOCASE)",
+                            escape_sql(mime)
+                        );
+                    }
+                }
+                Some(condition)
+            }
+            Predicate::Text(_) | Predicate::IsReply | Predicate::IsEdited => None,
+        }
+    }
+
+    /// Determine if a message satisfies this predicate
+    fn matches(&self, message: &Message, db: &Connection) -> bool {
+        match self {
+            Predicate::FromMe => message.is_from_me(),
+            Predicate::Sender(Selector::Ids(ids)) => {
+                !message.is_from_me() && message.handle_id.is_some_and(|id| ids.contains(&id))
+            }
+            Predicate::Chat(Selector::Ids(ids)) => message
+                .chat_id
+                .or(message.deleted_from)
+                .is_some_and(|id| ids.contains(&id)),
+            Predicate::Sender(Selector::Name(_)) | Predicate::Chat(Selector::Name(_)) => false,
+            Predicate::Date(comparison, day) => match comparison {
+                Comparison::Before => message.date < *day,
+                Comparison::OnOrBefore => message.date < day + DAY_NANOS,
+                Comparison::On => message.date >= *day && message.date < day + DAY_NANOS,
+                Comparison::OnOrAfter => message.date >= *day,
+                Comparison::After => message.date >= day + DAY_NANOS,
+            },
+            Predicate::Service(service) => message
+                .service
+                .as_deref()
+                .is_some_and(|s| s.eq_ignore_ascii_case(service)),
+            Predicate::Text(needle) => message
+                .text
+                .as_deref()
+                .is_some_and(|text| text.to_lowercase().contains(&needle.to_lowercase())),
+            Predicate::HasAttachment => message.has_attachments(),
+            Predicate::IsReply => message.is_reply(),
+            Predicate::IsEdited => message.is_edited() || message.edited_parts.is_some(),
+            Predicate::MimeType(mime) => Attachment::from_message(db, message)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|attachment| attachment.mime_type.as_deref())
+                .any(|candidate| mime_matches(mime, candidate)),
+        }
+    }
+
+    /// Build a predicate from a field name, operator, and value
+    fn build(field: &str, operator: &str, value: String) -> Result<Self, QueryContextError> {
+        let field = field.to_lowercase();
+        if field != "date" && operator != ":" {
+            return Err(QueryContextError::InvalidQuery(format!(
+                "`{field}` only supports `:`, not `{operator}`"
+            )));
+        }
+        if value.is_empty() {
+            return Err(QueryContextError::InvalidQuery(format!(
+                "`{field}` requires a value"
+            )));
+        }
+
+        match field.as_str() {
+            "from" | "sender" => {
+                if value.eq_ignore_ascii_case("me") {
+                    Ok(Predicate::FromMe)
+                } else {
+                    Ok(Predicate::Sender(Selector::Name(value)))
+                }
+            }
+            "chat" => Ok(Predicate::Chat(Selector::Name(value))),
+            "date" => {
+                let comparison = match operator {
+                    "<" => Comparison::Before,
+                    "<=" => Comparison::OnOrBefore,
+                    ":" | "=" => Comparison::On,
+                    ">=" => Comparison::OnOrAfter,
+                    ">" => Comparison::After,
+                    _ => {
+                        return Err(QueryContextError::InvalidQuery(format!(
+                            "unknown date operator `{operator}`"
+                        )));
+                    }
+                };
+                let day = QueryContext::sanitize_date(&value)
+                    .ok_or(QueryContextError::InvalidDate(value))?;
+                Ok(Predicate::Date(comparison, day))
+            }
+            "service" => Ok(Predicate::Service(value)),
+            "text" => Ok(Predicate::Text(value)),
+            "mime" => Ok(Predicate::MimeType(value.to_lowercase())),
+            "has" if value.eq_ignore_ascii_case("attachment") => Ok(Predicate::HasAttachment),
+            "is" if value.eq_ignore_ascii_case("reply") => Ok(Predicate::IsReply),
+            "is" if value.eq_ignore_ascii_case("edited") => Ok(Predicate::IsEdited),
+            "has" | "is" => Err(QueryContextError::InvalidQuery(format!(
+                "unknown value `{value}` for `{field}`"
+            ))),
+            _ => Err(QueryContextError::InvalidQuery(format!(
+                "unknown field `{field}`"
+            ))),
+        }
+    }
+}
+
+// MARK: Helpers
+/// Join a set of IDs for interpolation into an `IN` clause
+fn join_ids(ids: &BTreeSet<i32>) -> String {
+    ids.iter()
+        .map(std::string::ToString::to_string)
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// Escape a value for use inside a single-quoted SQL string literal
+fn escape_sql(value: &str) -> String {
+    value.replace('\'', "''")
+}
+
+/// Escape a value for use inside a single-quoted SQL `LIKE` pattern with `\` as the escape character
+fn escape_like(value: &str) -> String {
+    escape_sql(value)
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Determine if a MIME type pattern matches a concrete MIME type
+fn mime_matches(pattern: &str, candidate: &str) -> bool {
+    let candidate = candidate.to_lowercase();
+    match pattern.strip_suffix('*') {
+        Some(prefix) => candidate.starts_with(prefix),
+        None => candidate == pattern,
+    }
+}
+
+// MARK: Tokenizer
+#[derive(Debug, PartialEq, Eq)]
+enum Token {
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+    Predicate(Predicate),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::And => write!(fmt, "AND"),
+            Token::Or => write!(fmt, "OR"),
+            Token::Not => write!(fmt, "NOT"),
+            Token::Open => write!(fmt, "("),
+            Token::Close => write!(fmt, ")"),
+            Token::Predicate(_) => write!(fmt, "predicate"),
+        }
+    }
+}
+
+/// Read a possibly quoted value starting at the current position
+fn read_value(chars: &[char], position: &mut usize) -> Result<String, QueryContextError> {
+    let mut value = String::new();
+    if chars.get(*position) == Some(&'"') {
+        *position += 1;
+        loop {
+            match chars.get(*position) {
+                Some('"') => {
+                    *position += 1;
+                    return Ok(value);
+                }
+                Some('\\') if chars.get(*position + 1).is_some() => {
+                    value.push(chars[*position + 1]);
+                    *position += 2;
+                }
+                Some(c) => {
+                    value.push(*c);
+                    *position += 1;
+                }
+                None => {
+                    return Err(QueryContextError::InvalidQuery(String::from(
+                        "unterminated quoted value",
+                    )));
+                }
+            }
+        }
+    }
+
+    while let Some(c) = chars.get(*position) {
+        if c.is_whitespace() || *c == '(' || *c == ')' {
+            break;
+        }
+        value.push(*c);
+        *position += 1;
+    }
+    Ok(value)
+}
+
+/// Split a query string into [`Token`]s
+fn tokenize(query: &str) -> Result<Vec<Token>, QueryContextError> {
+    let chars: Vec<char> = query.chars().collect();
+    let mut tokens = Vec::new();
+    let mut position = 0;
+
+    while let Some(&c) = chars.get(position) {
+        match c {
+            c if c.is_whitespace() => position += 1,
+            '(' => {
+                tokens.push(Token::Open);
+                position += 1;
+            }
+            ')' => {
+                tokens.push(Token::Close);
+                position += 1;
+            }
+            _ => {
+                // Read the field name or keyword
+                let start = position;
+                while let Some(c) = chars.get(position) {
+                    if c.is_alphanumeric() || *c == '_' || *c == '-' {
+                        position += 1;
+                    } else {
+                        break;
+                    }
+                }
+                let word: String = chars[start..position].iter().collect();
+
+                // Read the operator, if any
+                let op_start = position;
+                while let Some(c) = chars.get(position) {
+                    if matches!(c, ':' | '<' | '>' | '=') {
+                        position += 1;
+                    } else {
+                        break;
+                    }
+                }
+                let operator: String = chars[op_start..position].iter().collect();
+
+                if operator.is_empty() {
+                    match word.to_uppercase().as_str() {
+                        "AND" => tokens.push(Token::And),
+                        "OR" => tokens.push(Token::Or),
+                        "NOT" => tokens.push(Token::Not),
+                        "" => {
+                            return Err(QueryContextError::InvalidQuery(format!(
+                                "unexpected character `{c}` at position {}",
+                                start + 1
+                            )));
+                        }
+                        _ => {
+                            return Err(QueryContextError::InvalidQuery(format!(
+                                "`{word}` is not a predicate; expected `field:value`"
+                            )));
+                        }
+                    }
+                } else {
+                    let value = read_value(&chars, &mut position)?;
+                    tokens.push(Token::Predicate(Predicate::build(&word, &operator, value)?));
+                }
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+// MARK: Parser
+/// Recursive descent parser over a token stream
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn parse_or(&mut self) -> Result<QueryExpression, QueryContextError> {
+        let mut left = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.position += 1;
+            let right = self.parse_and()?;
+            left = QueryExpression::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<QueryExpression, QueryContextError> {
+        let mut left = self.parse_not()?;
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.position += 1;
+                }
+                // Adjacent terms are implicitly joined with `AND`
+                Some(Token::Not | Token::Open | Token::Predicate(_)) => {}
+                _ => break,
+            }
+            let right = self.parse_not()?;
+            left = QueryExpression::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<QueryExpression, QueryContextError> {
+        if self.peek() == Some(&Token::Not) {
+            self.position += 1;
+            return Ok(QueryExpression::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_term()
+    }
+
+    fn parse_term(&mut self) -> Result<QueryExpression, QueryContextError> {
+        let position = self.position;
+        self.position += 1;
+        match self.tokens.get(position) {
+            Some(Token::Open) => {
+                let inner = self.parse_or()?;
+                if self.peek() == Some(&Token::Close) {
+                    self.position += 1;
+                    Ok(inner)
+                } else {
+                    Err(QueryContextError::InvalidQuery(String::from(
+                        "missing closing parenthesis",
+                    )))
+                }
+            }
+            Some(Token::Predicate(predicate)) => Ok(QueryExpression::Predicate(predicate.clone())),
+            Some(token) => Err(QueryContextError::InvalidQuery(format!(
+                "unexpected {token} at position {}",
+                position + 1
+            ))),
+            None => Err(QueryContextError::InvalidQuery(String::from(
+                "unexpected end of query",
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod parse_tests {
+    use std::collections::BTreeSet;
+
+    use crate::util::query_expression::{Comparison, Predicate, QueryExpression, Selector};
+
+    #[test]
+    fn can_parse_single_predicate() {
+        let expression = QueryExpression::parse("from:me").unwrap();
+        assert_eq!(expression, QueryExpression::Predicate(Predicate::FromMe));
+    }
+
+    #[test]
+    fn can_parse_quoted_value() {
+        let expression = QueryExpression::parse("chat:\"Family Chat\"").unwrap();
+        assert_eq!(
+            expression,
+            QueryExpression::Predicate(Predicate::Chat(Selector::Name(String::from(
+                "Family Chat"
+            ))))
+        );
+    }
+
+    #[test]
+    fn can_parse_precedence() {
+        let expression =
+            QueryExpression::parse("is:reply OR has:attachment AND NOT is:edited").unwrap();
+        assert_eq!(
+            expression,
+            QueryExpression::Or(
+                Box::new(QueryExpression::Predicate(Predicate::IsReply)),
+                Box::new(QueryExpression::And(
+                    Box::new(QueryExpression::Predicate(Predicate::HasAttachment)),
+                    Box::new(QueryExpression::Not(Box::new(QueryExpression::Predicate(
+                        Predicate::IsEdited
+                    )))),
+                )),
+            )
+        );
+    }
+
+    #[test]
+    fn can_parse_parentheses_and_implicit_and() {
+        let expression = QueryExpression::parse("(is:reply or is:edited) service:SMS").unwrap();
+        assert_eq!(
+            expression,
+            QueryExpression::And(
+                Box::new(QueryExpression::Or(
+                    Box::new(QueryExpression::Predicate(Predicate::IsReply)),
+                    Box::new(QueryExpression::Predicate(Predicate::IsEdited)),
+                )),
+                Box::new(QueryExpression::Predicate(Predicate::Service(
+                    String::from("SMS")
+                ))),
+            )
+        );
+    }
+
+    #[test]
+    fn can_parse_date_operators() {
+        let expression = QueryExpression::parse("date>=2020-01-01").unwrap();
+        assert!(matches!(
+            expression,
+            QueryExpression::Predicate(Predicate::Date(Comparison::OnOrAfter, _))
+        ));
+        let expression = QueryExpression::parse("date<2020-01-01").unwrap();
+        assert!(matches!(
+            expression,
+            QueryExpression::Predicate(Predicate::Date(Comparison::Before, _))
+        ));
+    }
+
+    #[test]
+    fn can_resolve_names() {
+        let mut expression = QueryExpression::parse("from:Steve chat:Family").unwrap();
+        expression.resolve_names(&mut |_: &str| BTreeSet::from([1, 2]), &mut |_: &str| {
+            BTreeSet::from([3])
+        });
+        assert_eq!(
+            expression,
+            QueryExpression::And(
+                Box::new(QueryExpression::Predicate(Predicate::Sender(
+                    Selector::Ids(BTreeSet::from([1, 2]))
+                ))),
+                Box::new(QueryExpression::Predicate(Predicate::Chat(Selector::Ids(
+                    BTreeSet::from([3])
+                )))),
+            )
+        );
+    }
+
+    #[test]
+    fn cant_parse_bad_queries() {
+        assert!(QueryExpression::parse("").is_err());
+        assert!(QueryExpression::parse("(from:me").is_err());
+        assert!(QueryExpression::parse("from:me)").is_err());
+        assert!(QueryExpression::parse("from:me AND").is_err());
+        assert!(QueryExpression::parse("color:blue").is_err());
+        assert!(QueryExpression::parse("has:kittens").is_err());
+        assert!(QueryExpression::parse("text>hello").is_err());
+        assert!(QueryExpression::parse("date>=2020-13-45").is_err());
+        assert!(QueryExpression::parse("text:\"unterminated").is_err());
+        assert!(QueryExpression::parse("hello").is_err());
+    }
+}
+
+#[cfg(test)]
+mod sql_tests {
+    use std::collections::BTreeSet;
+
+    use crate::util::query_expression::{Predicate, QueryExpression, Selector};
+
+    #[test]
+    fn can_compile_complete_expression() {
+        let expression = QueryExpression::parse("from:me OR service:SMS").unwrap();
+        assert!(expression.is_sql_complete());
+        assert_eq!(
+            expression.to_sql(false).unwrap(),
+            "(m.is_from_me = 1 OR m.service = 'SMS' COLLATE NOCASE)"
+        );
+    }
+
+    #[test]
+    fn can_compile_partial_and() {
+        let expression = QueryExpression::parse("from:me text:hello").unwrap();
+        assert!(!expression.is_sql_complete());
+        assert_eq!(expression.to_sql(false).unwrap(), "m.is_from_me = 1");
+    }
+
+    #[test]
+    fn cant_compile_partial_or() {
+        let expression = QueryExpression::parse("from:me OR text:hello").unwrap();
+        assert!(expression.to_sql(false).is_none());
+    }
+
+    #[test]
+    fn cant_compile_partial_not() {
+        let expression = QueryExpression::parse("NOT (from:me text:hello)").unwrap();
+        assert!(expression.to_sql(false).is_none());
+    }
+
+    #[test]
+    fn can_compile_chat_recoverable() {
+        let expression =
+            QueryExpression::Predicate(Predicate::Chat(Selector::Ids(BTreeSet::from([1, 2]))));
+        assert_eq!(
+            expression.to_sql(true).unwrap(),
+            "(c.chat_id IN (1, 2) OR d.chat_id IN (1, 2))"
+        );
+        assert_eq!(expression.to_sql(false).unwrap(), "c.chat_id IN (1, 2)");
+    }
+
+    #[test]
+    fn can_compile_unresolved_sender() {
+        let expression = QueryExpression::parse("from:nobody").unwrap();
+        assert_eq!(expression.to_sql(false).unwrap(), "0");
+    }
+
+    #[test]
+    fn can_escape_values() {
+        let expression = QueryExpression::parse("service:\"O'Brien\"").unwrap();
+        assert_eq!(
+            expression.to_sql(false).unwrap(),
+            "m.service = 'O''Brien' COLLATE NOCASE"
+        );
+    }
+
+    #[test]
+    fn can_compile_mime_wildcard() {
+        let expression = QueryExpression::parse("mime:image/*").unwrap();
+        assert!(
+            expression
+                .to_sql(false)
+                .unwrap()
+                .contains("a.mime_type LIKE 'image/%' ESCAPE '\\'")
+        );
+    }
+}
+
+#[cfg(test)]
+mod match_tests {
+    use std::{collections::BTreeSet, env::current_dir};
+
+    use rusqlite::Connection;
+
+    use crate::{
+        tables::{
+            messages::Message,
+            table::{Table, get_connection},
+        },
+        util::{
+            query_context::QueryContext,
+            query_expression::{Predicate, QueryExpression, Selector},
+        },
+    };
+
+    fn test_db() -> Connection {
+        let db_path = current_dir()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("imessage-database/test_data/db/test.db");
+        get_connection(&db_path).unwrap()
+    }
+
+    /// The `ROWID`s of the messages selected by `expression` in SQL and by [`QueryExpression::matches`]
+    fn sql_and_matched_rows(
+        db: &Connection,
+        expression: QueryExpression,
+    ) -> (BTreeSet<i32>, BTreeSet<i32>) {
+        let context = QueryContext::default();
+        let mut statement = Message::stream_rows(db, &context).unwrap();
+        let matched = Message::rows(&mut statement, [])
+            .unwrap()
+            .map(Result::unwrap)
+            .filter(|message| expression.matches(message, db))
+            .map(|message| message.rowid)
+            .collect();
+
+        let mut context = QueryContext::default();
+        context.expression = Some(expression);
+        let mut statement = Message::stream_rows(db, &context).unwrap();
+        let selected = Message::rows(&mut statement, [])
+            .unwrap()
+            .map(|message| message.unwrap().rowid)
+            .collect();
+
+        (selected, matched)
+    }
+
+    #[test]
+    fn can_match_text_and_sender() {
+        let db_path = current_dir()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("imessage-database/test_data/db/test.db");
+        let db = get_connection(&db_path).unwrap();
+
+        let mut message = Message::blank();
+        message.text = Some(String::from("Want to get Dinner tonight?"));
+        message.is_from_me = true;
+
+        let expression = QueryExpression::parse("from:me text:dinner").unwrap();
+        assert!(expression.matches(&message, &db));
+
+        let expression = QueryExpression::parse("from:me NOT text:dinner").unwrap();
+        assert!(!expression.matches(&message, &db));
+
+        let expression = QueryExpression::parse("text:lunch OR is:reply").unwrap();
+        assert!(!expression.matches(&message, &db));
+    }
+
+    #[test]
+    fn can_select_negated_chat_in_sql() {
+        let db = test_db();
+        let expression = QueryExpression::Not(Box::new(QueryExpression::Predicate(
+            Predicate::Chat(Selector::Ids(BTreeSet::from([1]))),
+        )));
+        assert!(expression.is_sql_complete());
+
+        let (selected, matched) = sql_and_matched_rows(&db, expression);
+        assert_eq!(selected.len(), 3);
+        assert_eq!(selected, matched);
+    }
+
+    #[test]
+    fn can_select_negated_service_in_sql() {
+        let db = test_db();
+
+        let expression = QueryExpression::parse("NOT service:SMS").unwrap();
+        let (selected, matched) = sql_and_matched_rows(&db, expression);
+        assert_eq!(selected.len(), 3);
+        assert_eq!(selected, matched);
+
+        let expression = QueryExpression::parse("NOT service:iMessage").unwrap();
+        let (selected, matched) = sql_and_matched_rows(&db, expression);
+        assert!(selected.is_empty());
+        assert_eq!(selected, matched);
+    }
+}
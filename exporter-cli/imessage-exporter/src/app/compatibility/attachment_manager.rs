@@ -3,13 +3,15 @@
 */
 
 use std::{
+    cell::{OnceCell, RefCell},
     fmt::Display,
     fs::{create_dir_all, remove_file, write},
+    io::Result as IoResult,
     path::{Path, PathBuf},
 };
 
 use imessage_database::{
-    message_types::handwriting::HandwrittenMessage,
+    message_types::{digital_touch::DigitalTouchMessage, handwriting::HandwrittenMessage},
     tables::{
         attachment::{Attachment, MediaType},
         messages::Message,
@@ -19,18 +21,37 @@ use imessage_database::{
 use crate::app::{
     compatibility::{
         backup::decrypt_file,
+        conversions::{Conversion, ConversionKind, ConversionPool},
         converters::{
-            audio::audio_copy_convert,
-            common::{copy_raw, update_file_metadata},
-            image::image_copy_convert,
+            audio::audio_output_type,
+            common::{
+                copy_raw, file_times, hard_link_raw, reflink_raw, supports_reflink, symlink_raw,
+                update_file_metadata,
+            },
+            image::image_output_type,
+            metadata::{MediaMetadata, write_media_metadata},
             sticker::sticker_copy_convert,
-            video::video_copy_convert,
+            thumbnail::generate_thumbnail,
+            video::video_output_type,
         },
         error::ConversionError,
-        models::{AudioConverter, Converter, HardwareEncoder, ImageConverter, VideoConverter},
+        models::{
+            AudioConverter, Converter, HardwareEncoder, ImageConverter, MetadataWriter,
+            VideoConverter,
+        },
+        naming::{AttachmentNameTemplate, AttachmentNames, NameFields, original_stem},
+        policy::AttachmentPolicy,
     },
+    manifest::hex_digest,
     runtime::Config,
 };
+use sha1::Sha1;
+
+/// Directory inside the attachments directory that holds attachments stored by content hash
+pub const POOL_DIR: &str = "pool";
+
+/// Pixels per unit of a handwritten message's canvas when it is saved as a PNG
+const HANDWRITING_SCALE: f64 = 2.;
 
 // MARK: Manager
 #[derive(Debug, PartialEq, Eq, Default)]
@@ -39,6 +60,18 @@ pub struct AttachmentManager {
     pub image_converter: Option<ImageConverter>,
     pub audio_converter: Option<AudioConverter>,
     pub video_converter: Option<VideoConverter>,
+    /// Set when the message's context should be embedded in copied media
+    pub metadata_writer: Option<MetadataWriter>,
+    /// Rules for which attachments are exported
+    pub policy: AttachmentPolicy,
+    /// Pattern used to name copied attachments instead of their `ROWID`
+    pub name_template: Option<AttachmentNameTemplate>,
+    /// Runs conversions in the background while messages are rendered
+    pub conversions: ConversionPool,
+    /// Names already given to copied attachments, used to resolve collisions
+    names: RefCell<AttachmentNames>,
+    /// Whether the export directory can clone files, checked the first time it is needed
+    reflinks: OnceCell<bool>,
     hardware_encoder: Option<HardwareEncoder>,
 }
 
@@ -49,9 +82,20 @@ impl AttachmentManager {
             image_converter: ImageConverter::determine(),
             audio_converter: AudioConverter::determine(),
             video_converter: VideoConverter::determine(),
+            metadata_writer: None,
+            policy: AttachmentPolicy::default(),
+            name_template: None,
+            conversions: ConversionPool::default(),
+            names: RefCell::default(),
+            reflinks: OnceCell::new(),
             hardware_encoder: HardwareEncoder::detect(),
         }
     }
+
+    /// Determine if files can be cloned into `export_path`, probing the file system only once
+    pub(crate) fn supports_reflink(&self, export_path: &Path) -> bool {
+        *self.reflinks.get_or_init(|| supports_reflink(export_path))
+    }
 }
 
 impl AttachmentManager {
@@ -75,15 +119,62 @@ impl AttachmentManager {
         } else {
             println!("    Video converter: None");
         }
+
+        if let Some(writer) = &self.metadata_writer {
+            println!("    Metadata writer: {writer}");
+        }
+
+        println!("    Conversion jobs: {}", self.conversions.jobs());
+        if let Some(cache) = self.conversions.cache() {
+            println!("    Conversion cache: {}", cache.dir().display());
+        } else {
+            println!("    Conversion cache: None");
+        }
     }
 
     // MARK: Handwriting
-    /// Handle a handwriting message, optionally writing it to an SVG file
+    /// Handle a handwriting message, optionally writing it to a PNG file
+    ///
+    /// Images are embedded by more viewers than SVG graphics, so they suit exports without a browser.
     pub fn handle_handwriting(
         &self,
         message: &Message,
         handwriting: &HandwrittenMessage,
         config: &Config,
+    ) -> Option<PathBuf> {
+        self.write_rendered(
+            message,
+            &handwriting.id,
+            "png",
+            || handwriting.render_png(HANDWRITING_SCALE),
+            config,
+        )
+    }
+
+    /// Handle a Digital Touch message, optionally writing it to an SVG file
+    pub fn handle_digital_touch(
+        &self,
+        message: &Message,
+        digital_touch: &DigitalTouchMessage,
+        config: &Config,
+    ) -> Option<PathBuf> {
+        self.write_rendered(
+            message,
+            &digital_touch.id,
+            "svg",
+            || digital_touch.render_svg(),
+            config,
+        )
+    }
+
+    /// Write a rendered graphic to the conversation's attachment directory, named after its unique `id`
+    fn write_rendered<C: AsRef<[u8]>>(
+        &self,
+        message: &Message,
+        id: &str,
+        extension: &str,
+        render: impl FnOnce() -> C,
+        config: &Config,
     ) -> Option<PathBuf> {
         if !matches!(self.mode, AttachmentManagerMode::Disabled) {
             // Create a path to copy the file to
@@ -94,11 +185,11 @@ impl AttachmentManager {
             to.push(sub_dir);
 
             // Add the filename
-            // Each handwriting has a unique id, so cache then all in the same place
-            to.push(&handwriting.id);
+            // Each drawing has a unique id, so cache then all in the same place
+            to.push(id);
 
-            // Set the new file's extension to svg
-            to.set_extension("svg");
+            // Set the new file's extension to the rendered format
+            to.set_extension(extension);
             if to.exists() {
                 return Some(to);
             }
@@ -111,8 +202,8 @@ impl AttachmentManager {
                 eprintln!("Unable to create {}: {why}", folder.display());
             }
 
-            // Attempt the svg render
-            if let Err(why) = write(to.to_str()?, handwriting.render_svg()) {
+            // Attempt the render
+            if let Err(why) = write(to.to_str()?, render()) {
                 eprintln!("Unable to write to {}: {why}", to.display());
             }
 
@@ -124,6 +215,21 @@ impl AttachmentManager {
         None
     }
 
+    // MARK: Thumbnails
+    /// Generate a thumbnail for a copied image attachment, if thumbnails are enabled
+    ///
+    /// Returns [`None`] when the full-size image should be displayed instead.
+    pub fn handle_thumbnail(&self, attachment: &Attachment, config: &Config) -> Option<PathBuf> {
+        let format = config.options.thumbnails?;
+        if attachment.is_sticker || !matches!(attachment.mime_type(), MediaType::Image(_)) {
+            return None;
+        }
+        let image = attachment.copied_path.as_deref()?;
+        // Thumbnails are made from the converted file, so it has to be written first
+        self.conversions.wait_for(image);
+        generate_thumbnail(image, format)
+    }
+
     // MARK: Files
     /// Handle an attachment, copying and converting if requested
     ///
@@ -134,14 +240,63 @@ impl AttachmentManager {
         attachment: &'a mut Attachment,
         config: &Config,
     ) -> Result<(), ConversionError> {
-        if !matches!(self.mode, AttachmentManagerMode::Disabled) {
-            if config.options.images_only {
-                match attachment.mime_type() {
-                    MediaType::Image(_) => {}
-                    MediaType::Video(ext) if ext.eq_ignore_ascii_case("heics") => {}
-                    _ => return Ok(()),
+        // Create a path to copy the file to, shared by every attachment with the same content when deduplicating
+        let pooled = config.options.dedupe_attachments;
+        let rowid = attachment.rowid;
+        let stem = self
+            .name_template
+            .as_ref()
+            .map(|template| render_name(template, message, attachment, config));
+        let extension = attachment.extension().map(str::to_string);
+        self.copy_attachment(message, attachment, config, pooled, |from| {
+            match (pooled && !from.is_dir()).then(|| pool_path(config, from)) {
+                Some(Ok(path)) => path,
+                hashed => {
+                    if let Some(Err(why)) = hashed {
+                        eprintln!("Unable to hash {}: {why}", from.display());
+                    }
+                    let mut to = config.attachment_path();
+
+                    // Add the subdirectory
+                    let sub_dir = config.conversation_attachment_path(message.chat_id);
+                    to.push(sub_dir);
+
+                    // Add a stable filename
+                    match stem {
+                        Some(stem) => {
+                            let extension = extension.as_deref().filter(|_| !from.is_dir());
+                            let name = self.names.borrow_mut().claim(&to, &stem, extension, rowid);
+                            to.push(name);
+                        }
+                        None => to.push(rowid.to_string()),
+                    }
+                    to
                 }
             }
+        })
+    }
+
+    /// Copy an attachment to the path built by `destination`, converting if requested
+    ///
+    /// `destination` receives the resolved source file and returns the path to copy it to; the
+    /// original extension is applied to it. When `reuse_converted` is set, a file in the same
+    /// directory with the same name and a different extension is treated as an earlier converted
+    /// copy and reused.
+    ///
+    /// If copied, update attachment's `copied_path` and `mime_type`
+    pub fn copy_attachment<'a>(
+        &'a self,
+        message: &Message,
+        attachment: &'a mut Attachment,
+        config: &Config,
+        reuse_converted: bool,
+        destination: impl FnOnce(&Path) -> PathBuf,
+    ) -> Result<(), ConversionError> {
+        if !matches!(self.mode, AttachmentManagerMode::Disabled) {
+            // Attachments excluded by the export's rules are never copied
+            if !self.policy.allows(attachment) {
+                return Ok(());
+            }
 
             // Resolve the path to the attachment
             let Some(attachment_path) = attachment.resolved_attachment_path(
@@ -184,14 +339,7 @@ impl AttachmentManager {
             }
 
             // Create a path to copy the file to
-            let mut to = config.attachment_path();
-
-            // Add the subdirectory
-            let sub_dir = config.conversation_attachment_path(message.chat_id);
-            to.push(sub_dir);
-
-            // Add a stable filename
-            to.push(attachment.rowid.to_string());
+            let mut to = destination(&from);
 
             // Set the new file's extension to the original one, if provided
             if !from.is_dir()
@@ -200,81 +348,143 @@ impl AttachmentManager {
                 to.set_extension(ext);
             }
 
-            // If the same file was referenced more than once, i.e. in a reply or response that we render twice, escape early
-            if to.exists() {
-                attachment.copied_path = Some(to);
+            // If the same file was referenced more than once, i.e. in a reply or response that we render twice,
+            // or the same content was already stored in the pool, escape early
+            let existing = if to.exists() {
+                Some(to.clone())
+            } else if let Some(converted) = self.conversions.converted(&to) {
+                Some(converted)
+            } else if reuse_converted && !from.is_dir() {
+                find_converted(&to)
+            } else {
+                None
+            };
+            if let Some(existing) = existing {
+                // A file with a different extension was converted when it was first copied
+                if existing != to
+                    && let Some(ext) = existing.extension().and_then(|ext| ext.to_str())
+                    && let Some(mime_type) = converted_mime_type(&attachment.mime_type(), ext)
+                {
+                    attachment.mime_type = Some(mime_type);
+                }
+                attachment.copied_path = Some(existing);
+                if is_temp && let Err(why) = remove_file(&from) {
+                    eprintln!("Unable to remove encrypted file {}: {why}", from.display());
+                }
                 return Ok(());
             }
 
             // If we convert the attachment, we need to update the media type
             let mut new_media_type: Option<MediaType> = None;
+            // Conversions other than stickers run in the background
+            let mut conversion: Option<ConversionKind> = None;
 
             let mime_type = attachment.mime_type();
             match mime_type {
                 MediaType::Image(_) => match self.mode {
                     AttachmentManagerMode::Basic | AttachmentManagerMode::Full => {
-                        match &self.image_converter {
-                            Some(converter) => {
-                                if attachment.is_sticker {
-                                    new_media_type = sticker_copy_convert(
-                                        &from,
-                                        &mut to,
-                                        converter,
-                                        self.video_converter.as_ref(),
-                                        &mime_type,
-                                    );
-                                } else {
-                                    new_media_type =
-                                        image_copy_convert(&from, &mut to, converter, &mime_type);
-                                }
+                        match (self.image_converter, image_output_type(&mime_type)) {
+                            (Some(converter), _) if attachment.is_sticker => {
+                                new_media_type = sticker_copy_convert(
+                                    &from,
+                                    &mut to,
+                                    &converter,
+                                    self.video_converter.as_ref(),
+                                    &mime_type,
+                                );
+                            }
+                            (Some(converter), Some(output_type)) => {
+                                conversion = Some(ConversionKind::Image(converter, output_type));
                             }
-                            None => copy_raw(&from, &to),
+                            _ => copy_raw(&from, &to),
                         }
                     }
-                    AttachmentManagerMode::Clone => copy_raw(&from, &to),
+                    AttachmentManagerMode::Clone
+                    | AttachmentManagerMode::Hardlink
+                    | AttachmentManagerMode::Symlink
+                    | AttachmentManagerMode::Reflink => self.place_raw(&from, &to, is_temp),
                     AttachmentManagerMode::Disabled => unreachable!(),
                 },
                 MediaType::Video(_) => match self.mode {
-                    AttachmentManagerMode::Full => match &self.video_converter {
-                        Some(converter) => {
-                            new_media_type = video_copy_convert(
-                                &from,
-                                &mut to,
-                                converter,
-                                &self.hardware_encoder,
-                                &mime_type,
-                            );
+                    AttachmentManagerMode::Full => {
+                        match (self.video_converter, video_output_type(&mime_type)) {
+                            (Some(converter), Some(_)) => {
+                                conversion =
+                                    Some(ConversionKind::Video(converter, self.hardware_encoder));
+                            }
+                            _ => copy_raw(&from, &to),
                         }
-                        None => copy_raw(&from, &to),
-                    },
-                    AttachmentManagerMode::Clone | AttachmentManagerMode::Basic => {
-                        copy_raw(&from, &to);
                     }
+                    AttachmentManagerMode::Clone
+                    | AttachmentManagerMode::Basic
+                    | AttachmentManagerMode::Hardlink
+                    | AttachmentManagerMode::Symlink
+                    | AttachmentManagerMode::Reflink => self.place_raw(&from, &to, is_temp),
                     AttachmentManagerMode::Disabled => unreachable!(),
                 },
                 MediaType::Audio(_) => match self.mode {
-                    AttachmentManagerMode::Full => match &self.audio_converter {
-                        Some(converter) => {
-                            new_media_type =
-                                audio_copy_convert(&from, &mut to, converter, &mime_type);
+                    AttachmentManagerMode::Full => {
+                        match (self.audio_converter, audio_output_type(&mime_type)) {
+                            (Some(converter), Some(_)) => {
+                                conversion = Some(ConversionKind::Audio(converter));
+                            }
+                            _ => copy_raw(&from, &to),
                         }
-                        None => copy_raw(&from, &to),
-                    },
-                    AttachmentManagerMode::Clone | AttachmentManagerMode::Basic => {
-                        copy_raw(&from, &to);
                     }
+                    AttachmentManagerMode::Clone
+                    | AttachmentManagerMode::Basic
+                    | AttachmentManagerMode::Hardlink
+                    | AttachmentManagerMode::Symlink
+                    | AttachmentManagerMode::Reflink => self.place_raw(&from, &to, is_temp),
                     AttachmentManagerMode::Disabled => unreachable!(),
                 },
-                _ => copy_raw(&from, &to),
+                _ => self.place_raw(&from, &to, is_temp),
             }
 
-            // Update file metadata
-            if is_temp {
-                // If the file was decrypted, we need to update the metadata from the original file
-                update_file_metadata(Path::new(&attachment_path), &to, message, config);
-            } else {
-                // If the file was copied, we need to update the metadata from the source file
-                update_file_metadata(&from, &to, message, config);
+            // The converted file is referenced right away and written by the pool once it is ready
+            if let Some(kind) = conversion {
+                let mut converted = to.clone();
+                converted.set_extension(kind.extension());
+                if let Some(mime_type) = converted_mime_type(&mime_type, kind.extension()) {
+                    attachment.mime_type = Some(mime_type);
+                }
+                attachment.copied_path = Some(converted.clone());
+
+                // Converted files get the times of the original, even if it was decrypted
+                let source = if is_temp {
+                    Path::new(&attachment_path)
+                } else {
+                    from.as_path()
+                };
+                self.conversions.queue(
+                    to.clone(),
+                    Conversion {
+                        kind,
+                        file_times: file_times(source, message, config),
+                        metadata: self.media_metadata(message, attachment, config),
+                        from,
+                        to: converted,
+                        fallback: to,
+                        is_temp,
+                    },
+                );
+                return Ok(());
+            }
+
+            // Embed the message's context in photos and videos; this changes the file times, so it is done first
+            if let Some((metadata, writer)) = self.media_metadata(message, attachment, config) {
+                write_media_metadata(&to, &metadata, &writer);
+            }
+
+            // Update file metadata; linked files are the originals, so changing their times would change the source
+            if !self.mode.shares_original() {
+                if is_temp {
+                    // If the file was decrypted, we need to update the metadata from the original file
+                    update_file_metadata(Path::new(&attachment_path), &to, message, config);
+                } else {
+                    // If the file was copied, we need to update the metadata from the source file
+                    update_file_metadata(&from, &to, message, config);
+                }
             }
             attachment.copied_path = Some(to);
             if let Some(media_type) = new_media_type {
@@ -289,6 +499,112 @@ impl AttachmentManager {
 
         Ok(())
     }
+
+    /// Build the message context to embed in a copied photo or video, if requested
+    fn media_metadata(
+        &self,
+        message: &Message,
+        attachment: &Attachment,
+        config: &Config,
+    ) -> Option<(MediaMetadata, MetadataWriter)> {
+        let writer = self.metadata_writer?;
+        if attachment.is_sticker
+            || !matches!(
+                attachment.mime_type(),
+                MediaType::Image(_) | MediaType::Video(_)
+            )
+        {
+            return None;
+        }
+        Some((config.media_metadata(message)?, writer))
+    }
+
+    /// Put an unconverted attachment at `to`, linking it instead of copying if requested
+    fn place_raw(&self, from: &Path, to: &Path, is_temp: bool) {
+        match self.mode {
+            AttachmentManagerMode::Hardlink => hard_link_raw(from, to),
+            // Decrypted files are removed after they are exported, so a link to one would break
+            AttachmentManagerMode::Symlink if !is_temp => symlink_raw(from, to),
+            AttachmentManagerMode::Reflink
+                if to.parent().is_some_and(|dir| self.supports_reflink(dir)) =>
+            {
+                reflink_raw(from, to);
+            }
+            _ => copy_raw(from, to),
+        }
+    }
+}
+
+// MARK: Names
+/// Render the filename stem for an attachment from the user's template
+fn render_name(
+    template: &AttachmentNameTemplate,
+    message: &Message,
+    attachment: &Attachment,
+    config: &Config,
+) -> String {
+    let (date, time) = match message.date(config.offset) {
+        Ok(date) => (
+            date.format("%Y-%m-%d").to_string(),
+            date.format("%H-%M-%S").to_string(),
+        ),
+        Err(_) => (String::new(), String::new()),
+    };
+    let chat = config.chat_name(message).unwrap_or_default();
+    let original_name = original_stem(attachment);
+
+    template.render(&NameFields {
+        date: &date,
+        time: &time,
+        sender: config.who(
+            message.handle_id,
+            message.is_from_me,
+            &message.destination_caller_id,
+        ),
+        chat: &chat,
+        original_name: &original_name,
+        rowid: attachment.rowid,
+    })
+}
+
+// MARK: Pool
+/// Build the pool path for the file at `from`, named by the SHA-1 of its content
+///
+/// Files are sharded into subdirectories by the first two characters of the hash,
+/// so no single directory grows too large.
+fn pool_path(config: &Config, from: &Path) -> IoResult<PathBuf> {
+    let hash = content_hash(from)?;
+    let mut to = config.attachment_path();
+    to.push(POOL_DIR);
+    to.push(&hash[0..2]);
+    to.push(hash);
+    Ok(to)
+}
+
+/// Compute the hex-encoded SHA-1 of a file's content
+fn content_hash(path: &Path) -> IoResult<String> {
+    hex_digest::<Sha1>(path)
+}
+
+/// Find a pooled copy of `to` that was stored with a different extension after conversion
+fn find_converted(to: &Path) -> Option<PathBuf> {
+    let stem = to.file_stem()?;
+    to.parent()?
+        .read_dir()
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .find(|path| path.file_stem() == Some(stem))
+}
+
+/// Build the MIME type of a converted file, which keeps its original media category
+fn converted_mime_type(original: &MediaType, ext: &str) -> Option<String> {
+    match original {
+        MediaType::Image(_) => Some(MediaType::Image(ext).as_mime_type()),
+        MediaType::Video(_) => Some(MediaType::Video(ext).as_mime_type()),
+        MediaType::Audio(_) => Some(MediaType::Audio(ext).as_mime_type()),
+        _ => None,
+    }
 }
 
 // MARK: Mode
@@ -304,6 +620,12 @@ pub enum AttachmentManagerMode {
     Clone,
     /// Copy and convert all attachments to more compatible formats using a [`Converter`]
     Full,
+    /// Hard link attachments without converting; copies files on a different device
+    Hardlink,
+    /// Symbolically link attachments without converting; the export only works while the originals exist
+    Symlink,
+    /// Clone attachments with copy-on-write without converting; copies files if the file system cannot clone them
+    Reflink,
 }
 
 impl AttachmentManagerMode {
@@ -314,9 +636,22 @@ impl AttachmentManagerMode {
             "basic" => Some(Self::Basic),
             "clone" => Some(Self::Clone),
             "full" => Some(Self::Full),
+            "hardlink" => Some(Self::Hardlink),
+            "symlink" => Some(Self::Symlink),
+            "reflink" => Some(Self::Reflink),
             _ => None,
         }
     }
+
+    /// Whether exported attachments are the original files, so writing to them would alter the source
+    pub fn shares_original(&self) -> bool {
+        matches!(self, Self::Hardlink | Self::Symlink)
+    }
+
+    /// Whether attachments are linked into the export rather than written to it
+    pub fn is_link(&self) -> bool {
+        matches!(self, Self::Hardlink | Self::Symlink | Self::Reflink)
+    }
 }
 
 impl Display for AttachmentManagerMode {
@@ -326,13 +661,34 @@ impl Display for AttachmentManagerMode {
             AttachmentManagerMode::Basic => write!(fmt, "basic"),
             AttachmentManagerMode::Clone => write!(fmt, "clone"),
             AttachmentManagerMode::Full => write!(fmt, "full"),
+            AttachmentManagerMode::Hardlink => write!(fmt, "hardlink"),
+            AttachmentManagerMode::Symlink => write!(fmt, "symlink"),
+            AttachmentManagerMode::Reflink => write!(fmt, "reflink"),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::app::compatibility::attachment_manager::AttachmentManagerMode;
+    use std::fs::{create_dir_all, write};
+
+    use imessage_database::tables::attachment::{Attachment, MediaType};
+
+    use crate::{
+        Config, Options,
+        app::{
+            compatibility::{
+                attachment_manager::{
+                    AttachmentManager, AttachmentManagerMode, content_hash, converted_mime_type,
+                    find_converted,
+                },
+                conversions::ConversionPool,
+                models::ImageConverter,
+            },
+            export_type::ExportType,
+            test_dir::unique_test_dir,
+        },
+    };
 
     #[test]
     fn test_attachment_manager_mode() {
@@ -352,6 +708,120 @@ mod tests {
             AttachmentManagerMode::from_cli("full"),
             Some(AttachmentManagerMode::Full)
         );
+        assert_eq!(
+            AttachmentManagerMode::from_cli("hardlink"),
+            Some(AttachmentManagerMode::Hardlink)
+        );
+        assert_eq!(
+            AttachmentManagerMode::from_cli("symlink"),
+            Some(AttachmentManagerMode::Symlink)
+        );
+        assert_eq!(
+            AttachmentManagerMode::from_cli("reflink"),
+            Some(AttachmentManagerMode::Reflink)
+        );
         assert_eq!(AttachmentManagerMode::from_cli("invalid"), None);
     }
+
+    #[test]
+    fn can_hash_content() {
+        let dir = unique_test_dir("attachment_manager_hash");
+        let first = dir.join("a.txt");
+        let second = dir.join("b.txt");
+        write(&first, b"hello world").unwrap();
+        write(&second, b"hello world").unwrap();
+
+        assert_eq!(
+            content_hash(&first).unwrap(),
+            "2aae6c35c94fcfb415dbe95f408b9ce91ee846ed"
+        );
+        assert_eq!(
+            content_hash(&first).unwrap(),
+            content_hash(&second).unwrap()
+        );
+    }
+
+    #[test]
+    fn can_find_converted() {
+        let dir = unique_test_dir("attachment_manager_converted");
+        let shard = dir.join("2a");
+        create_dir_all(&shard).unwrap();
+        write(shard.join("2aae6c35.jpeg"), b"").unwrap();
+
+        assert_eq!(
+            find_converted(&shard.join("2aae6c35.heic")),
+            Some(shard.join("2aae6c35.jpeg"))
+        );
+        assert_eq!(find_converted(&shard.join("ffffffff.heic")), None);
+    }
+
+    #[test]
+    fn can_get_converted_mime_type() {
+        assert_eq!(
+            converted_mime_type(&MediaType::Image("heic"), "jpeg"),
+            Some(String::from("image/jpeg"))
+        );
+        assert_eq!(
+            converted_mime_type(&MediaType::Video("quicktime"), "mp4"),
+            Some(String::from("video/mp4"))
+        );
+        assert_eq!(
+            converted_mime_type(&MediaType::Application("pdf"), "pdf"),
+            None
+        );
+    }
+
+    #[test]
+    fn can_keep_original_type_when_conversion_fails() {
+        let dir = unique_test_dir("attachment_manager_failed_conversion");
+        let source = dir.join("IMG_1.heic");
+        write(&source, b"not an image").unwrap();
+
+        let mut options = Options::fake_options(ExportType::Html);
+        options.attachment_manager = AttachmentManager {
+            mode: AttachmentManagerMode::Basic,
+            image_converter: Some(ImageConverter::Imagemagick),
+            conversions: ConversionPool::new(1, None),
+            ..AttachmentManager::default()
+        };
+        let config = Config::fake_app(options);
+        let manager = &config.options.attachment_manager;
+        let message = Config::fake_message();
+        let fake_heic = || Attachment {
+            filename: Some(source.to_string_lossy().into_owned()),
+            uti: Some("public.heic".to_string()),
+            mime_type: Some("image/heic".to_string()),
+            transfer_name: Some("IMG_1.heic".to_string()),
+            ..Config::fake_attachment()
+        };
+        let destination = dir.join("out").join("IMG_1");
+
+        // The converted file is referenced while the conversion runs
+        let mut attachment = fake_heic();
+        manager
+            .copy_attachment(&message, &mut attachment, &config, false, |_| {
+                destination.clone()
+            })
+            .unwrap();
+        assert_eq!(attachment.copied_path, Some(dir.join("out/IMG_1.jpeg")));
+        assert_eq!(attachment.mime_type.as_deref(), Some("image/jpeg"));
+
+        // The file cannot be converted, so the original is copied in its place
+        manager.conversions.wait_all();
+        assert!(!dir.join("out/IMG_1.jpeg").exists());
+        assert_eq!(
+            manager.conversions.fallback(&dir.join("out/IMG_1.jpeg")),
+            Some(dir.join("out/IMG_1.heic"))
+        );
+
+        // Rendering the attachment again references the original file and type
+        let mut attachment = fake_heic();
+        manager
+            .copy_attachment(&message, &mut attachment, &config, false, |_| {
+                destination.clone()
+            })
+            .unwrap();
+        assert_eq!(attachment.copied_path, Some(dir.join("out/IMG_1.heic")));
+        assert_eq!(attachment.mime_type.as_deref(), Some("image/heic"));
+    }
 }
@@ -0,0 +1,313 @@
+/*!
+ Keeps converted attachments in a directory shared by every export, so identical files are only converted once.
+
+ Entries are named by the SHA-1 of the source file and the settings used to convert it, so a file
+ exported again, even into a new directory or with different filters, reuses the earlier result.
+
+ Entries are full copies of private attachments, kept outside of any export directory. The cache is
+ limited to [`MAX_CACHE_SIZE`] bytes by removing the least recently used entries, and it is not used
+ for encrypted backups unless a directory is chosen with `--conversion-cache`.
+*/
+
+use std::{
+    fs::{File, remove_file, rename},
+    io::Result as IoResult,
+    path::{Path, PathBuf},
+    process::id,
+    sync::{
+        OnceLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::SystemTime,
+};
+
+use imessage_database::util::dirs::home;
+use sha1::Sha1;
+
+use crate::app::{
+    compatibility::converters::common::{
+        copy_raw, ensure_output_dir, reflink_raw, supports_reflink,
+    },
+    manifest::hex_digest,
+};
+
+/// Name of the directory the cache is stored in, inside the platform's cache directory
+const CACHE_DIR_NAME: &str = "imessage-exporter";
+
+/// Directory inside the cache that holds converted files; the version changes when converter arguments do
+const CONVERSIONS_DIR: &str = "conversions-v1";
+
+/// Most bytes kept in the cache before the least recently used entries are removed, 10 GB
+pub const MAX_CACHE_SIZE: u64 = 10 * 1024 * 1024 * 1024;
+
+/// Number of entries this process has started writing, used to give each one a unique temporary name
+static WRITES: AtomicU64 = AtomicU64::new(0);
+
+/// A directory of converted attachments
+#[derive(Debug, Clone)]
+pub struct ConversionCache {
+    dir: PathBuf,
+    /// Set when the directory is the platform default instead of one the user chose
+    is_default: bool,
+    /// Whether the cache's file system can clone files, checked the first time an entry is copied
+    reflinks: OnceLock<bool>,
+}
+
+impl ConversionCache {
+    pub fn new(dir: PathBuf) -> Self {
+        ConversionCache {
+            dir,
+            is_default: false,
+            reflinks: OnceLock::new(),
+        }
+    }
+
+    /// The platform's cache directory for the exporter
+    ///
+    /// - macOS: `~/Library/Caches/imessage-exporter`
+    /// - Windows: `%LOCALAPPDATA%\imessage-exporter`
+    /// - Otherwise: `$XDG_CACHE_HOME/imessage-exporter`, or `~/.cache/imessage-exporter`
+    pub fn default_dir() -> PathBuf {
+        #[cfg(target_os = "macos")]
+        let root = PathBuf::from(home()).join("Library").join("Caches");
+        #[cfg(windows)]
+        let root =
+            std::env::var_os("LOCALAPPDATA").map_or_else(|| PathBuf::from(home()), PathBuf::from);
+        #[cfg(not(any(target_os = "macos", windows)))]
+        let root = std::env::var_os("XDG_CACHE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map_or_else(|| PathBuf::from(home()).join(".cache"), PathBuf::from);
+        root.join(CACHE_DIR_NAME)
+    }
+
+    /// The directory the cache is stored in
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Determine if the cache is in the platform default directory, rather than one the user chose
+    pub fn is_default(&self) -> bool {
+        self.is_default
+    }
+
+    /// Build the path of the cached result of converting `from` with `settings` into a file with `extension`
+    pub(crate) fn entry(&self, from: &Path, settings: &str, extension: &str) -> IoResult<PathBuf> {
+        let hash = hex_digest::<Sha1>(from)?;
+        let mut entry = self.dir.join(CONVERSIONS_DIR);
+        entry.push(&hash[0..2]);
+        entry.push(format!("{hash}-{settings}.{extension}"));
+        Ok(entry)
+    }
+
+    /// Put the cached file at `entry` at `to`, returning `false` if it has not been cached
+    pub(crate) fn restore(&self, entry: &Path, to: &Path) -> bool {
+        if !entry.is_file() {
+            return false;
+        }
+        self.copy(entry, to);
+
+        // Mark the entry as recently used, so it is kept when the cache is pruned
+        if let Ok(file) = File::options().write(true).open(entry) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+        to.is_file()
+    }
+
+    /// Add the converted file at `converted` to the cache as `entry`
+    ///
+    /// The file is written under a temporary name first, so other exports never read a partial entry.
+    pub(crate) fn store(&self, entry: &Path, converted: &Path) {
+        if ensure_output_dir(entry).is_none() {
+            return;
+        }
+
+        let mut partial = entry.as_os_str().to_owned();
+        partial.push(format!(
+            ".{}-{}.partial",
+            id(),
+            WRITES.fetch_add(1, Ordering::Relaxed)
+        ));
+        let partial = PathBuf::from(partial);
+
+        self.copy(converted, &partial);
+        if let Err(why) = rename(&partial, entry) {
+            eprintln!("Unable to cache {}: {why}", converted.display());
+            let _ = remove_file(&partial);
+        }
+    }
+
+    /// Clone `from` to `to` if the cache's file system supports it, otherwise copy it
+    ///
+    /// Cloning spawns `cp`, so the directory is probed once instead of trying it for every entry.
+    fn copy(&self, from: &Path, to: &Path) {
+        if *self.reflinks.get_or_init(|| supports_reflink(&self.dir)) {
+            reflink_raw(from, to);
+        } else {
+            copy_raw(from, to);
+        }
+    }
+
+    /// Remove the least recently used entries until the cache holds at most `max_size` bytes
+    pub(crate) fn prune(&self, max_size: u64) {
+        let mut entries = vec![];
+        let mut dirs = vec![self.dir.join(CONVERSIONS_DIR)];
+        while let Some(dir) = dirs.pop() {
+            let Ok(read) = dir.read_dir() else {
+                continue;
+            };
+            for entry in read.flatten() {
+                let Ok(meta) = entry.metadata() else {
+                    continue;
+                };
+                if meta.is_dir() {
+                    dirs.push(entry.path());
+                } else if entry.path().extension().is_none_or(|ext| ext != "partial") {
+                    let used = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                    entries.push((used, meta.len(), entry.path()));
+                }
+            }
+        }
+
+        let mut size: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        entries.sort();
+        for (_, len, path) in entries {
+            if size <= max_size {
+                break;
+            }
+            if remove_file(&path).is_ok() {
+                size -= len;
+            }
+        }
+    }
+}
+
+impl Default for ConversionCache {
+    fn default() -> Self {
+        ConversionCache {
+            dir: Self::default_dir(),
+            is_default: true,
+            reflinks: OnceLock::new(),
+        }
+    }
+}
+
+/// Caches are the same if they use the same directory, whether or not it has been probed yet
+impl PartialEq for ConversionCache {
+    fn eq(&self, other: &Self) -> bool {
+        self.dir == other.dir && self.is_default == other.is_default
+    }
+}
+
+impl Eq for ConversionCache {}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{File, read, write},
+        time::{Duration, SystemTime},
+    };
+
+    use crate::app::{
+        compatibility::cache::{CACHE_DIR_NAME, CONVERSIONS_DIR, ConversionCache},
+        test_dir::unique_test_dir,
+    };
+
+    #[test]
+    fn can_get_default_dir() {
+        assert!(ConversionCache::default_dir().ends_with(CACHE_DIR_NAME));
+    }
+
+    #[test]
+    fn can_build_entry() {
+        let dir = unique_test_dir("cache_entry");
+        let from = dir.join("a.heic");
+        write(&from, b"hello world").unwrap();
+
+        let cache = ConversionCache::new(dir.join("cache"));
+        assert_eq!(
+            cache.entry(&from, "magick-jpeg", "jpeg").unwrap(),
+            dir.join("cache")
+                .join(CONVERSIONS_DIR)
+                .join("2a")
+                .join("2aae6c35c94fcfb415dbe95f408b9ce91ee846ed-magick-jpeg.jpeg")
+        );
+        assert!(
+            cache
+                .entry(&dir.join("missing.heic"), "magick-jpeg", "jpeg")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn can_store_and_restore() {
+        let dir = unique_test_dir("cache_store");
+        let converted = dir.join("converted.jpeg");
+        write(&converted, b"converted").unwrap();
+
+        let entry = dir.join("cache").join("ab").join("entry.jpeg");
+        let to = dir.join("export").join("1.jpeg");
+        let cache = ConversionCache::new(dir.join("cache"));
+        assert!(!cache.restore(&entry, &to));
+
+        cache.store(&entry, &converted);
+        assert!(cache.restore(&entry, &to));
+        assert_eq!(read(&to).unwrap(), b"converted");
+    }
+
+    #[test]
+    fn can_probe_reflinks_once() {
+        let dir = unique_test_dir("cache_reflinks");
+        let converted = dir.join("converted.jpeg");
+        write(&converted, b"converted").unwrap();
+
+        let cache = ConversionCache::new(dir.join("cache"));
+        assert_eq!(cache.reflinks.get(), None);
+
+        let entry = dir.join("cache").join("ab").join("entry.jpeg");
+        cache.store(&entry, &converted);
+        let probed = cache.reflinks.get().copied();
+        assert!(probed.is_some());
+
+        // The probe is not repeated, and leaves nothing behind in the cache
+        assert!(cache.restore(&entry, &dir.join("export").join("1.jpeg")));
+        assert_eq!(cache.reflinks.get().copied(), probed);
+        assert!(!dir.join("cache").join(".reflink-probe").exists());
+
+        // Probing does not change which cache this is
+        assert_eq!(cache, ConversionCache::new(dir.join("cache")));
+    }
+
+    #[test]
+    fn can_tell_default_dir() {
+        assert!(ConversionCache::default().is_default());
+        assert!(!ConversionCache::new(ConversionCache::default_dir()).is_default());
+    }
+
+    #[test]
+    fn can_prune_least_recently_used() {
+        let dir = unique_test_dir("cache_prune");
+        let cache = ConversionCache::new(dir.clone());
+        let entries = dir.join(CONVERSIONS_DIR).join("ab");
+        std::fs::create_dir_all(&entries).unwrap();
+
+        let now = SystemTime::now();
+        for (index, age) in [3, 1, 2].into_iter().enumerate() {
+            let path = entries.join(format!("{index}.jpeg"));
+            write(&path, b"12345").unwrap();
+            File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(now - Duration::from_secs(age * 60))
+                .unwrap();
+        }
+
+        cache.prune(10);
+        assert!(!entries.join("0.jpeg").exists());
+        assert!(entries.join("1.jpeg").exists());
+        assert!(entries.join("2.jpeg").exists());
+
+        cache.prune(0);
+        assert_eq!(std::fs::read_dir(&entries).unwrap().count(), 0);
+    }
+}
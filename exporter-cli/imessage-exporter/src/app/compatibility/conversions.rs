@@ -0,0 +1,596 @@
+/*!
+ Runs attachment conversions on a bounded pool of worker threads.
+
+ Converting a file with `sips`, `ImageMagick`, `afconvert`, or `ffmpeg` takes far longer than rendering
+ the message it belongs to, so conversions are queued here while the export continues. Exporters
+ reference the converted file as soon as it is queued, and wait for the pool before the export is complete.
+
+ If a conversion fails, the original file is copied with its original extension instead. Exporters
+ hold back output that references a conversion until it finishes, so they can point at that copy.
+*/
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    fs::{FileTimes, remove_file},
+    num::NonZero,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Condvar, Mutex, MutexGuard, PoisonError,
+        mpsc::{Receiver, SyncSender, sync_channel},
+    },
+    thread::{JoinHandle, available_parallelism, spawn},
+    time::Duration,
+};
+
+use crate::app::{
+    compatibility::{
+        cache::{ConversionCache, MAX_CACHE_SIZE},
+        converters::{
+            audio::convert_caf,
+            common::{copy_raw, set_file_times},
+            image::convert_heic,
+            metadata::{MediaMetadata, write_media_metadata},
+            video::convert_mov,
+        },
+        models::{
+            AudioConverter, AudioType, Converter, HardwareEncoder, ImageConverter, ImageType,
+            MetadataWriter, VideoConverter, VideoType,
+        },
+    },
+    progress::ExportProgress,
+};
+
+/// How often the progress bar is redrawn while waiting for conversions to finish
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The program and output format used for a queued conversion
+#[derive(Debug)]
+pub(crate) enum ConversionKind {
+    Image(ImageConverter, ImageType),
+    Video(VideoConverter, Option<HardwareEncoder>),
+    Audio(AudioConverter),
+}
+
+impl ConversionKind {
+    /// The extension of the converted file
+    pub(crate) fn extension(&self) -> &'static str {
+        match self {
+            ConversionKind::Image(_, output_type) => output_type.to_str(),
+            ConversionKind::Video(..) => VideoType::Mp4.to_str(),
+            ConversionKind::Audio(_) => AudioType::Mp4.to_str(),
+        }
+    }
+
+    /// The program and options that produce the converted file, used to tell cached results apart
+    fn settings(&self) -> String {
+        match self {
+            ConversionKind::Image(converter, output_type) => {
+                format!("{}-{}", converter.name(), output_type.to_str())
+            }
+            ConversionKind::Video(converter, hardware_encoder) => format!(
+                "{}-{}-{}",
+                converter.name(),
+                self.extension(),
+                hardware_encoder.map_or("libx264", |encoder| encoder.codec_name())
+            ),
+            ConversionKind::Audio(converter) => {
+                format!("{}-{}", converter.name(), self.extension())
+            }
+        }
+    }
+}
+
+/// A file waiting to be converted, with everything needed to finish its copy
+#[derive(Debug)]
+pub(crate) struct Conversion {
+    pub kind: ConversionKind,
+    /// The file to convert
+    pub from: PathBuf,
+    /// Where the converted file is written
+    pub to: PathBuf,
+    /// Where the original file is copied if it cannot be converted, with its original extension
+    pub fallback: PathBuf,
+    /// Set when `from` is a decrypted copy that is removed once it is converted
+    pub is_temp: bool,
+    /// Message context to embed in the converted file
+    pub metadata: Option<(MediaMetadata, MetadataWriter)>,
+    /// Access and modification times to give the converted file
+    pub file_times: Option<FileTimes>,
+}
+
+impl Conversion {
+    /// Convert the file, reusing an earlier result from `cache` and copying it unconverted to
+    /// [`Self::fallback`] if the converter fails
+    ///
+    /// Returns `false` if the file could not be converted.
+    fn run(&self, cache: Option<&ConversionCache>) -> bool {
+        let entry = cache.and_then(|cache| {
+            cache
+                .entry(&self.from, &self.kind.settings(), self.kind.extension())
+                .inspect_err(|why| eprintln!("Unable to hash {}: {why}", self.from.display()))
+                .ok()
+        });
+
+        let converted = match &entry {
+            Some(entry) if cache.is_some_and(|cache| cache.restore(entry, &self.to)) => Some(()),
+            _ => {
+                let converted = self.convert();
+                // The cache keeps the converter's output, before any metadata is written to it
+                if converted.is_some()
+                    && let (Some(entry), Some(cache)) = (&entry, cache)
+                {
+                    cache.store(entry, &self.to);
+                }
+                converted
+            }
+        };
+
+        let written = if converted.is_some() {
+            if let Some((metadata, writer)) = &self.metadata {
+                write_media_metadata(&self.to, metadata, writer);
+            }
+            &self.to
+        } else {
+            eprintln!(
+                "Unable to convert {}, copying it unconverted",
+                self.from.display()
+            );
+            // A partial result from the converter must not be mistaken for a converted file later
+            let _ = remove_file(&self.to);
+            copy_raw(&self.from, &self.fallback);
+            &self.fallback
+        };
+
+        if let Some(file_times) = self.file_times {
+            set_file_times(written, file_times);
+        }
+
+        // Remove the temporary file used for decryption, if it exists
+        if self.is_temp
+            && let Err(why) = remove_file(&self.from)
+        {
+            eprintln!(
+                "Unable to remove encrypted file {}: {why}",
+                self.from.display()
+            );
+        }
+        converted.is_some()
+    }
+
+    /// Run the converter for the file
+    fn convert(&self) -> Option<()> {
+        match &self.kind {
+            ConversionKind::Image(converter, output_type) => {
+                convert_heic(&self.from, &self.to, converter, output_type)
+            }
+            ConversionKind::Video(converter, hardware_encoder) => {
+                convert_mov(&self.from, &self.to, converter, hardware_encoder.as_ref())
+            }
+            ConversionKind::Audio(converter) => convert_caf(&self.from, &self.to, converter),
+        }
+    }
+}
+
+/// State shared between the export and the worker threads
+#[derive(Debug, Default)]
+struct Shared {
+    /// Destinations of conversions that have not finished
+    pending: Mutex<HashSet<PathBuf>>,
+    /// Notified every time a conversion finishes
+    finished: Condvar,
+    /// The fallback copy written for each converted path whose conversion failed
+    failed: Mutex<HashMap<PathBuf, PathBuf>>,
+    /// Where converted files are kept for later exports
+    cache: Option<ConversionCache>,
+}
+
+impl Shared {
+    fn pending(&self) -> MutexGuard<'_, HashSet<PathBuf>> {
+        // A worker that panicked cannot leave the set in an invalid state
+        self.pending.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn failed(&self) -> MutexGuard<'_, HashMap<PathBuf, PathBuf>> {
+        self.failed.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Run a conversion, then mark it finished even if the converter panicked
+    fn convert(&self, conversion: &Conversion) {
+        struct Finish<'a>(&'a Shared, &'a Path);
+        impl Drop for Finish<'_> {
+            fn drop(&mut self) {
+                self.0.pending().remove(self.1);
+                self.0.finished.notify_all();
+            }
+        }
+
+        let _finish = Finish(self, &conversion.to);
+        if !conversion.run(self.cache.as_ref()) {
+            self.failed()
+                .insert(conversion.to.clone(), conversion.fallback.clone());
+        }
+    }
+}
+
+/// The threads that run conversions, started when the first conversion is queued
+#[derive(Debug)]
+struct Workers {
+    sender: SyncSender<Conversion>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl Workers {
+    fn spawn(jobs: usize, shared: &Arc<Shared>) -> Self {
+        // At most one conversion waits for each worker, so queued files do not pile up in memory
+        let (sender, receiver) = sync_channel::<Conversion>(jobs);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let handles = (0..jobs)
+            .map(|_| {
+                let receiver = Arc::clone(&receiver);
+                let shared = Arc::clone(shared);
+                spawn(move || work(&receiver, &shared))
+            })
+            .collect();
+        Workers { sender, handles }
+    }
+}
+
+/// Run conversions until the pool is dropped
+fn work(receiver: &Mutex<Receiver<Conversion>>, shared: &Shared) {
+    loop {
+        let next = receiver
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .recv();
+        match next {
+            Ok(conversion) => shared.convert(&conversion),
+            Err(_) => return,
+        }
+    }
+}
+
+/// Converts attachments in the background, running up to [`Self::jobs`] conversions at once
+pub struct ConversionPool {
+    /// Most conversions that run at the same time
+    jobs: usize,
+    /// The converted path for each destination already queued, so repeated references reuse it
+    queued: RefCell<HashMap<PathBuf, PathBuf>>,
+    /// Conversions queued since the pool last finished
+    started: Cell<u64>,
+    /// Converted paths handed to exporters since they were last taken
+    referenced: RefCell<Vec<PathBuf>>,
+    shared: Arc<Shared>,
+    workers: RefCell<Option<Workers>>,
+}
+
+impl ConversionPool {
+    pub fn new(jobs: usize, cache: Option<ConversionCache>) -> Self {
+        ConversionPool {
+            jobs: jobs.max(1),
+            queued: RefCell::default(),
+            started: Cell::new(0),
+            referenced: RefCell::default(),
+            shared: Arc::new(Shared {
+                cache,
+                ..Default::default()
+            }),
+            workers: RefCell::default(),
+        }
+    }
+
+    /// The default number of jobs, one for each available core
+    pub fn default_jobs() -> usize {
+        available_parallelism().map_or(1, NonZero::get)
+    }
+
+    /// Most conversions that run at the same time
+    pub fn jobs(&self) -> usize {
+        self.jobs
+    }
+
+    /// Where converted files are kept for later exports, if anywhere
+    pub fn cache(&self) -> Option<&ConversionCache> {
+        self.shared.cache.as_ref()
+    }
+
+    /// Stop reading and filling the cache, i.e. for attachments decrypted from a backup
+    ///
+    /// Has no effect once a conversion is queued.
+    pub fn disable_cache(&mut self) {
+        if let Some(shared) = Arc::get_mut(&mut self.shared) {
+            shared.cache = None;
+        }
+    }
+
+    /// Get the converted path of a file already queued to be copied to `destination`
+    pub(crate) fn converted(&self, destination: &Path) -> Option<PathBuf> {
+        let converted = self.queued.borrow().get(destination).cloned()?;
+        self.referenced.borrow_mut().push(converted.clone());
+        Some(converted)
+    }
+
+    /// Take the converted paths handed out by [`Self::queue`] and [`Self::converted`] since the
+    /// last call, so the output that references them can wait for their conversions
+    pub(crate) fn take_referenced(&self) -> Vec<PathBuf> {
+        self.referenced.take()
+    }
+
+    /// Determine if the file at `path` is still waiting to be converted
+    pub(crate) fn is_pending(&self, path: &Path) -> bool {
+        self.shared.pending().contains(path)
+    }
+
+    /// Get the unconverted copy written instead of `path`, if its conversion failed
+    pub(crate) fn fallback(&self, path: &Path) -> Option<PathBuf> {
+        self.shared.failed().get(path).cloned()
+    }
+
+    /// Queue a conversion of the file that would have been copied to `destination`
+    ///
+    /// Blocks while every worker is busy and one conversion is already waiting for each of them.
+    pub(crate) fn queue(&self, destination: PathBuf, conversion: Conversion) {
+        self.queued
+            .borrow_mut()
+            .insert(destination, conversion.to.clone());
+        self.shared.pending().insert(conversion.to.clone());
+        self.referenced.borrow_mut().push(conversion.to.clone());
+        self.started.set(self.started.get() + 1);
+
+        let mut workers = self.workers.borrow_mut();
+        let workers = workers.get_or_insert_with(|| Workers::spawn(self.jobs, &self.shared));
+        if let Err(unsent) = workers.sender.send(conversion) {
+            // Every worker has stopped, so convert the file here instead
+            self.shared.convert(&unsent.0);
+        }
+    }
+
+    /// Block until the file at `path` is written, if it is waiting to be converted
+    pub(crate) fn wait_for(&self, path: &Path) {
+        let mut pending = self.shared.pending();
+        while pending.contains(path) {
+            pending = self
+                .shared
+                .finished
+                .wait(pending)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    /// Block until every queued conversion finishes, without showing their progress
+    pub(crate) fn wait_all(&self) {
+        let mut pending = self.shared.pending();
+        while !pending.is_empty() {
+            pending = self
+                .shared
+                .finished
+                .wait(pending)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    /// Block until every queued conversion finishes, showing their progress on `pb`
+    ///
+    /// The cache is pruned afterwards if any conversions were queued.
+    pub fn finish(&self, pb: &ExportProgress) {
+        let total = self.started.replace(0);
+        let mut remaining = self.shared.pending().len() as u64;
+        if remaining == 0 {
+            self.prune_cache(total);
+            return;
+        }
+
+        eprintln!("Finishing {remaining} attachment conversions...");
+        pb.start(total.try_into().unwrap_or(i64::MAX));
+        while remaining > 0 {
+            let (pending, _) = self
+                .shared
+                .finished
+                .wait_timeout(self.shared.pending(), PROGRESS_INTERVAL)
+                .unwrap_or_else(PoisonError::into_inner);
+            remaining = pending.len() as u64;
+            drop(pending);
+            pb.set_position(total.saturating_sub(remaining));
+        }
+        pb.finish();
+        self.prune_cache(total);
+    }
+
+    /// Keep the cache under its size limit after `queued` conversions may have added to it
+    fn prune_cache(&self, queued: u64) {
+        if queued > 0
+            && let Some(cache) = &self.shared.cache
+        {
+            cache.prune(MAX_CACHE_SIZE);
+        }
+    }
+}
+
+impl Default for ConversionPool {
+    fn default() -> Self {
+        Self::new(Self::default_jobs(), Some(ConversionCache::default()))
+    }
+}
+
+impl Drop for ConversionPool {
+    /// Finish every queued conversion before the export exits
+    fn drop(&mut self) {
+        if let Some(Workers { sender, handles }) = self.workers.take() {
+            drop(sender);
+            for handle in handles {
+                let _ = handle.join();
+            }
+        }
+    }
+}
+
+impl Debug for ConversionPool {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt.debug_struct("ConversionPool")
+            .field("jobs", &self.jobs)
+            .field("cache", &self.shared.cache)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Pools are configured only by their number of jobs and their cache
+impl PartialEq for ConversionPool {
+    fn eq(&self, other: &Self) -> bool {
+        self.jobs == other.jobs && self.shared.cache == other.shared.cache
+    }
+}
+
+impl Eq for ConversionPool {}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{read, write},
+        path::PathBuf,
+    };
+
+    use crate::app::{
+        compatibility::{
+            cache::ConversionCache,
+            conversions::{Conversion, ConversionKind, ConversionPool},
+            models::{ImageConverter, ImageType},
+        },
+        progress::ExportProgress,
+        test_dir::unique_test_dir,
+    };
+
+    fn fake_conversion(from: PathBuf, to: PathBuf) -> Conversion {
+        Conversion {
+            kind: ConversionKind::Image(ImageConverter::Imagemagick, ImageType::Jpeg),
+            fallback: to.with_extension("heic"),
+            from,
+            to,
+            is_temp: false,
+            metadata: None,
+            file_times: None,
+        }
+    }
+
+    #[test]
+    fn can_get_extension() {
+        assert_eq!(
+            ConversionKind::Image(ImageConverter::Sips, ImageType::Jpeg).extension(),
+            "jpeg"
+        );
+    }
+
+    #[test]
+    fn can_clamp_jobs() {
+        assert_eq!(ConversionPool::new(0, None).jobs(), 1);
+        assert!(ConversionPool::default().jobs() >= 1);
+    }
+
+    #[test]
+    fn can_disable_cache() {
+        let mut pool = ConversionPool::default();
+        assert!(pool.cache().is_some_and(ConversionCache::is_default));
+        pool.disable_cache();
+        assert_eq!(pool.cache(), None);
+    }
+
+    #[test]
+    fn can_finish_queued_conversions() {
+        let dir = unique_test_dir("conversions_finish");
+        let from = dir.join("in.heic");
+        write(&from, b"not really an image").unwrap();
+
+        let pool = ConversionPool::new(2, None);
+        for index in 0..4 {
+            let destination = dir.join(format!("{index}.heic"));
+            let to = dir.join(format!("{index}.jpeg"));
+            pool.queue(destination, fake_conversion(from.clone(), to));
+        }
+        assert_eq!(
+            pool.converted(&dir.join("0.heic")),
+            Some(dir.join("0.jpeg"))
+        );
+        assert_eq!(pool.converted(&dir.join("4.heic")), None);
+
+        pool.wait_for(&dir.join("0.jpeg"));
+        assert!(!pool.is_pending(&dir.join("0.jpeg")));
+        assert!(dir.join("0.heic").exists());
+
+        pool.finish(&ExportProgress::new(false));
+        // The fake file cannot be converted, so it is copied unconverted with its original extension
+        for index in 0..4 {
+            assert!(!dir.join(format!("{index}.jpeg")).exists());
+            assert_eq!(
+                pool.fallback(&dir.join(format!("{index}.jpeg"))),
+                Some(dir.join(format!("{index}.heic")))
+            );
+            assert_eq!(
+                read(dir.join(format!("{index}.heic"))).unwrap(),
+                b"not really an image"
+            );
+        }
+    }
+
+    #[test]
+    fn can_take_referenced_conversions() {
+        let dir = unique_test_dir("conversions_referenced");
+        let from = dir.join("in.heic");
+        write(&from, b"not really an image").unwrap();
+
+        let pool = ConversionPool::new(1, None);
+        pool.queue(
+            dir.join("0.heic"),
+            fake_conversion(from.clone(), dir.join("0.jpeg")),
+        );
+        assert_eq!(pool.take_referenced(), vec![dir.join("0.jpeg")]);
+        assert!(pool.take_referenced().is_empty());
+
+        // Referencing a queued conversion again also has to wait for it
+        pool.converted(&dir.join("0.heic"));
+        assert_eq!(pool.take_referenced(), vec![dir.join("0.jpeg")]);
+        pool.finish(&ExportProgress::new(false));
+    }
+
+    #[test]
+    fn can_wait_for_every_conversion() {
+        let dir = unique_test_dir("conversions_wait_all");
+        let from = dir.join("in.heic");
+        write(&from, b"not really an image").unwrap();
+
+        let pool = ConversionPool::new(2, None);
+        for index in 0..4 {
+            let destination = dir.join(format!("{index}.heic"));
+            let to = dir.join(format!("{index}.jpeg"));
+            pool.queue(destination, fake_conversion(from.clone(), to));
+        }
+
+        pool.wait_all();
+        for index in 0..4 {
+            assert!(dir.join(format!("{index}.heic")).exists());
+        }
+    }
+
+    #[test]
+    fn can_reuse_cached_conversion() {
+        let dir = unique_test_dir("conversions_cache");
+        let from = dir.join("in.heic");
+        write(&from, b"not really an image").unwrap();
+
+        let cache = ConversionCache::new(dir.join("cache"));
+        let conversion = fake_conversion(from.clone(), dir.join("out.jpeg"));
+        let entry = cache
+            .entry(
+                &from,
+                &conversion.kind.settings(),
+                conversion.kind.extension(),
+            )
+            .unwrap();
+        write(dir.join("converted.jpeg"), b"converted earlier").unwrap();
+        cache.store(&entry, &dir.join("converted.jpeg"));
+
+        let pool = ConversionPool::new(1, Some(cache));
+        pool.queue(dir.join("out.heic"), conversion);
+        pool.finish(&ExportProgress::new(false));
+        assert_eq!(read(dir.join("out.jpeg")).unwrap(), b"converted earlier");
+    }
+}
@@ -2,53 +2,29 @@
  Defines routines for converting audio files.
 */
 
-use std::{
-    ffi::OsStr,
-    path::{Path, PathBuf},
-};
+use std::{ffi::OsStr, path::Path};
 
 use imessage_database::tables::attachment::MediaType;
 
 use crate::app::compatibility::{
-    converters::common::{copy_raw, ensure_output_dir, run_command},
+    converters::common::{ensure_output_dir, run_command},
     models::{AudioConverter, AudioType, Converter},
 };
 
-/// Copy an audio file, converting if possible
+/// The format an audio file is converted to, if it needs converting
 ///
 /// - Attachment `CAF` files convert to `MP4`
 /// - Attachment `AMR` files convert to `MP4`
-/// - Fallback to the original format
-pub(crate) fn audio_copy_convert(
-    from: &Path,
-    to: &mut PathBuf,
-    converter: &AudioConverter,
-    mime_type: &MediaType,
-) -> Option<MediaType<'static>> {
-    if matches!(
+pub(crate) fn audio_output_type(mime_type: &MediaType) -> Option<AudioType> {
+    matches!(
         mime_type,
         MediaType::Audio("caf" | "CAF" | "x-caf; codecs=opus" | "amr" | "AMR")
-    ) {
-        let output_type = AudioType::Mp4;
-
-        // Update extension for conversion
-        let mut converted_path = to.clone();
-        converted_path.set_extension(output_type.to_str());
-
-        if convert_caf(from, &converted_path, converter).is_some() {
-            // If the conversion was successful, update the path
-            *to = converted_path;
-            return Some(MediaType::Audio(output_type.to_str()));
-        }
-        eprintln!("Unable to convert {}", from.display());
-    }
-
-    // Fallback
-    copy_raw(from, to);
-    None
+    )
+    .then_some(AudioType::Mp4)
 }
 
-fn convert_caf(from: &Path, to: &Path, converter: &AudioConverter) -> Option<()> {
+/// Convert a `CAF` or `AMR` audio file to `MP4`
+pub(crate) fn convert_caf(from: &Path, to: &Path, converter: &AudioConverter) -> Option<()> {
     ensure_output_dir(to)?;
 
     let args: Vec<&OsStr> = match converter {
@@ -3,7 +3,9 @@
 */
 use std::{
     ffi::OsStr,
-    fs::{File, FileTimes, copy, create_dir_all, metadata, read_dir},
+    fs::{
+        File, FileTimes, copy, create_dir_all, hard_link, metadata, read_dir, remove_file, write,
+    },
     path::Path,
     process::{Command, Stdio},
     time::{Duration, SystemTime, UNIX_EPOCH},
@@ -46,7 +48,7 @@ where
 }
 
 /// Ensure the parent directory of `to` exists, creating it if necessary.
-pub(super) fn ensure_output_dir(to: &Path) -> Option<()> {
+pub(crate) fn ensure_output_dir(to: &Path) -> Option<()> {
     if let Some(folder) = to.parent()
         && !folder.exists()
         && let Err(why) = create_dir_all(folder)
@@ -59,6 +61,76 @@ pub(super) fn ensure_output_dir(to: &Path) -> Option<()> {
 
 /// Copy a file or directory without altering it
 pub(crate) fn copy_raw(from: &Path, to: &Path) {
+    place_raw(from, to, &copy_file);
+}
+
+/// Hard link a file, or each file in a directory, without altering it
+///
+/// Files that cannot be linked, i.e. because they are on a different device, are copied instead.
+pub(crate) fn hard_link_raw(from: &Path, to: &Path) {
+    place_raw(from, to, &|from, to| {
+        if hard_link(from, to).is_err() {
+            copy_file(from, to);
+        }
+    });
+}
+
+/// Clone a file, or each file in a directory, sharing its data until either copy changes
+///
+/// Files that cannot be cloned, i.e. because the file system does not support copy-on-write, are copied instead.
+pub(crate) fn reflink_raw(from: &Path, to: &Path) {
+    place_raw(from, to, &|from, to| {
+        if !reflink_file(from, to) {
+            copy_file(from, to);
+        }
+    });
+}
+
+/// Create a symbolic link to a file or directory
+///
+/// If the link cannot be created, i.e. because the platform requires elevated permissions, the data is copied instead.
+pub(crate) fn symlink_raw(from: &Path, to: &Path) {
+    if ensure_output_dir(to).is_none() {
+        return;
+    }
+
+    // Relative targets would resolve against the export directory
+    let target = from.canonicalize().unwrap_or_else(|_| from.to_path_buf());
+    #[cfg(unix)]
+    let result = std::os::unix::fs::symlink(&target, to);
+    #[cfg(windows)]
+    let result = if target.is_dir() {
+        std::os::windows::fs::symlink_dir(&target, to)
+    } else {
+        std::os::windows::fs::symlink_file(&target, to)
+    };
+    #[cfg(not(any(unix, windows)))]
+    let result: std::io::Result<()> = Err(std::io::ErrorKind::Unsupported.into());
+
+    if result.is_err() {
+        copy_raw(from, to);
+    }
+}
+
+/// Determine if two paths are on the same device, so files can be linked between them
+#[cfg(unix)]
+pub(crate) fn same_device(first: &Path, second: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (metadata(first), metadata(second)) {
+        (Ok(first), Ok(second)) => first.dev() == second.dev(),
+        _ => false,
+    }
+}
+
+/// Determine if two paths are on the same device, so files can be linked between them
+#[cfg(not(unix))]
+pub(crate) fn same_device(_: &Path, _: &Path) -> bool {
+    false
+}
+
+/// Walk a file or directory, calling `place_file` for each file to put it at the matching path under `to`
+fn place_raw(from: &Path, to: &Path, place_file: &dyn Fn(&Path, &Path)) {
     if from.is_dir() {
         // Ensure the directory tree exists
         if let Err(why) = create_dir_all(to) {
@@ -66,7 +138,7 @@ pub(crate) fn copy_raw(from: &Path, to: &Path) {
             return;
         }
 
-        // Iterate over the directory entries and copy them recursively
+        // Iterate over the directory entries and place them recursively
         match read_dir(from) {
             Ok(entries) => {
                 for entry_result in entries {
@@ -74,7 +146,7 @@ pub(crate) fn copy_raw(from: &Path, to: &Path) {
                         Ok(entry) => {
                             let from_path = entry.path();
                             let to_path = to.join(entry.file_name());
-                            copy_raw(&from_path, &to_path);
+                            place_raw(&from_path, &to_path, place_file);
                         }
                         Err(why) => {
                             eprintln!("Failed to read item in {}: {why}", from.display());
@@ -88,62 +160,106 @@ pub(crate) fn copy_raw(from: &Path, to: &Path) {
         }
     } else {
         // Ensure the directory tree exists
-        if let Some(folder) = to.parent()
-            && !folder.exists()
-            && let Err(why) = create_dir_all(folder)
-        {
-            eprintln!("Unable to create {}: {why}", folder.display());
+        if ensure_output_dir(to).is_none() {
             return;
         }
+        place_file(from, to);
+    }
+}
 
-        if let Err(why) = copy(from, to) {
-            eprintln!(
-                "Unable to copy {} to {}: {why}",
-                from.display(),
-                to.display()
-            );
-        }
+/// Copy a single file, assuming its directory exists
+fn copy_file(from: &Path, to: &Path) {
+    if let Err(why) = copy(from, to) {
+        eprintln!(
+            "Unable to copy {} to {}: {why}",
+            from.display(),
+            to.display()
+        );
     }
 }
 
+/// Determine if the file system `dir` is on can clone files, by cloning a probe file inside it
+///
+/// Cloning a file spawns `cp`, so this is checked once per export instead of trying every file.
+pub(crate) fn supports_reflink(dir: &Path) -> bool {
+    let probe = dir.join(".reflink-probe");
+    let clone = dir.join(".reflink-probe-clone");
+    let supported = create_dir_all(dir).is_ok()
+        && write(&probe, b"probe").is_ok()
+        && reflink_file(&probe, &clone);
+    let _ = remove_file(&probe);
+    let _ = remove_file(&clone);
+    supported
+}
+
+/// Clone a single file with the system `cp`, returning `false` if the file system cannot clone it
+fn reflink_file(from: &Path, to: &Path) -> bool {
+    // `cp -c` uses `clonefile` on macOS; GNU `cp` fails instead of copying with `--reflink=always`
+    let flag = if cfg!(target_os = "macos") {
+        "-c"
+    } else if cfg!(target_os = "linux") {
+        "--reflink=always"
+    } else {
+        return false;
+    };
+
+    Command::new("cp")
+        .arg(flag)
+        .arg(from)
+        .arg(to)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .stdin(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
 /// Update the metadata of a copied file, falling back to the original file's metadata if necessary
 pub(crate) fn update_file_metadata(from: &Path, to: &Path, message: &Message, config: &Config) {
     if to.is_dir() {
         return;
     }
 
-    // Update file metadata
-    if let Ok(metadata) = metadata(from) {
-        // The modification time is the message's date, otherwise the original file's modification time
-        let mtime = match message.date(config.offset) {
-            Ok(date) => unix_to_system_time(date.timestamp(), date.timestamp_subsec_nanos())
-                .or_else(|| metadata.modified().ok()),
-            Err(_) => metadata.modified().ok(),
-        };
-
-        // The new last access time comes from the metadata of the original file
-        let atime = metadata.accessed().ok();
-
-        if let (Some(atime), Some(mtime)) = (atime, mtime) {
-            // On Unix, `set_times` uses `futimens`, which does not require the file
-            // descriptor to have write access. On Windows, `SetFileTime` requires
-            // `FILE_WRITE_ATTRIBUTES`, so the file must be opened with write access.
-            #[cfg(unix)]
-            let file_result = File::open(to);
-            #[cfg(not(unix))]
-            let file_result = File::options().write(true).open(to);
-            match file_result {
-                Ok(file) => {
-                    let file_times = FileTimes::new().set_accessed(atime).set_modified(mtime);
-                    if let Err(why) = file.set_times(file_times) {
-                        eprintln!("Unable to update {} metadata: {why}", to.display());
-                    }
-                }
-                Err(why) => {
-                    eprintln!("Unable to open {} to update metadata: {why}", to.display());
-                }
+    if let Some(file_times) = file_times(from, message, config) {
+        set_file_times(to, file_times);
+    }
+}
+
+/// Build the times to give a copy of `from`, so they can be applied after the copy is written
+pub(crate) fn file_times(from: &Path, message: &Message, config: &Config) -> Option<FileTimes> {
+    let metadata = metadata(from).ok()?;
+
+    // The modification time is the message's date, otherwise the original file's modification time
+    let mtime = match message.date(config.offset) {
+        Ok(date) => unix_to_system_time(date.timestamp(), date.timestamp_subsec_nanos())
+            .or_else(|| metadata.modified().ok()),
+        Err(_) => metadata.modified().ok(),
+    }?;
+
+    // The new last access time comes from the metadata of the original file
+    let atime = metadata.accessed().ok()?;
+
+    Some(FileTimes::new().set_accessed(atime).set_modified(mtime))
+}
+
+/// Set the access and modification times of the file at `to`
+pub(crate) fn set_file_times(to: &Path, file_times: FileTimes) {
+    // On Unix, `set_times` uses `futimens`, which does not require the file
+    // descriptor to have write access. On Windows, `SetFileTime` requires
+    // `FILE_WRITE_ATTRIBUTES`, so the file must be opened with write access.
+    #[cfg(unix)]
+    let file_result = File::open(to);
+    #[cfg(not(unix))]
+    let file_result = File::options().write(true).open(to);
+    match file_result {
+        Ok(file) => {
+            if let Err(why) = file.set_times(file_times) {
+                eprintln!("Unable to update {} metadata: {why}", to.display());
             }
         }
+        Err(why) => {
+            eprintln!("Unable to open {} to update metadata: {why}", to.display());
+        }
     }
 }
 
@@ -158,3 +274,96 @@ fn unix_to_system_time(secs: i64, nanos: u32) -> Option<SystemTime> {
             .checked_add(Duration::from_nanos(u64::from(nanos)))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{read_to_string, write};
+
+    use crate::app::{
+        compatibility::converters::common::{
+            copy_raw, hard_link_raw, reflink_raw, supports_reflink, symlink_raw,
+        },
+        test_dir::unique_test_dir,
+    };
+
+    #[test]
+    fn can_copy_raw_directory() {
+        let dir = unique_test_dir("copy_raw");
+        let from = dir.join("from");
+        std::fs::create_dir_all(from.join("nested")).unwrap();
+        write(from.join("nested").join("a.txt"), "a").unwrap();
+
+        copy_raw(&from, &dir.join("to"));
+        assert_eq!(
+            read_to_string(dir.join("to").join("nested").join("a.txt")).unwrap(),
+            "a"
+        );
+    }
+
+    #[test]
+    fn can_hard_link_raw() {
+        let dir = unique_test_dir("hard_link_raw");
+        let from = dir.join("a.txt");
+        write(&from, "a").unwrap();
+
+        let to = dir.join("out").join("a.txt");
+        hard_link_raw(&from, &to);
+        assert_eq!(read_to_string(&to).unwrap(), "a");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            assert_eq!(
+                std::fs::metadata(&from).unwrap().ino(),
+                std::fs::metadata(&to).unwrap().ino()
+            );
+        }
+    }
+
+    #[test]
+    fn can_symlink_raw() {
+        let dir = unique_test_dir("symlink_raw");
+        let from = dir.join("a.txt");
+        write(&from, "a").unwrap();
+
+        let to = dir.join("out").join("a.txt");
+        symlink_raw(&from, &to);
+        assert_eq!(read_to_string(&to).unwrap(), "a");
+        #[cfg(unix)]
+        assert!(to.symlink_metadata().unwrap().file_type().is_symlink());
+    }
+
+    #[test]
+    fn can_reflink_raw_or_copy() {
+        let dir = unique_test_dir("reflink_raw");
+        let from = dir.join("a.txt");
+        write(&from, "a").unwrap();
+
+        let to = dir.join("out").join("a.txt");
+        reflink_raw(&from, &to);
+        assert_eq!(read_to_string(&to).unwrap(), "a");
+
+        // A clone is a separate file
+        write(&to, "b").unwrap();
+        assert_eq!(read_to_string(&from).unwrap(), "a");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn can_detect_same_device() {
+        use crate::app::compatibility::converters::common::same_device;
+
+        let dir = unique_test_dir("same_device");
+        assert!(same_device(&dir, &dir.join("..")));
+        assert!(!same_device(&dir, &dir.join("missing")));
+    }
+
+    #[test]
+    fn can_probe_reflink_support() {
+        let dir = unique_test_dir("reflink_probe");
+
+        // Either result is valid for the test file system, but the probe must clean up after itself
+        supports_reflink(&dir);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+    }
+}
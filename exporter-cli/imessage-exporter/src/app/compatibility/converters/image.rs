@@ -2,46 +2,20 @@
  Defines routines for converting image files.
 */
 
-use std::{
-    ffi::OsStr,
-    path::{Path, PathBuf},
-};
+use std::{ffi::OsStr, path::Path};
 
 use imessage_database::tables::attachment::MediaType;
 
 use crate::app::compatibility::{
-    converters::common::{copy_raw, ensure_output_dir, run_command},
+    converters::common::{ensure_output_dir, run_command},
     models::{Converter, ImageConverter, ImageType},
 };
 
-/// Copy an image file, converting if possible
+/// The format an image file is converted to, if it needs converting
 ///
 /// - Attachment `HEIC` files convert to `JPEG`
-/// - Fallback to the original format
-pub(crate) fn image_copy_convert(
-    from: &Path,
-    to: &mut PathBuf,
-    converter: &ImageConverter,
-    mime_type: &MediaType,
-) -> Option<MediaType<'static>> {
-    if matches!(mime_type, MediaType::Image("heic" | "HEIC")) {
-        let output_type = ImageType::Jpeg;
-
-        // Update extension for conversion
-        let mut converted_path = to.clone();
-        converted_path.set_extension(output_type.to_str());
-
-        if convert_heic(from, &converted_path, converter, &output_type).is_some() {
-            // If the conversion was successful, update the path
-            *to = converted_path;
-            return Some(MediaType::Image(output_type.to_str()));
-        }
-        eprintln!("Unable to convert {}", from.display());
-    }
-
-    // Fallback
-    copy_raw(from, to);
-    None
+pub(crate) fn image_output_type(mime_type: &MediaType) -> Option<ImageType> {
+    matches!(mime_type, MediaType::Image("heic" | "HEIC")).then_some(ImageType::Jpeg)
 }
 
 /// Convert a HEIC image file to the provided format
@@ -54,7 +28,7 @@ pub(crate) fn image_copy_convert(
 /// of failing, `sips` will create a file called `fake` in `/`. Subsequent writes
 /// by `sips` to the same location will not fail, but since it is a file instead
 /// of a directory, this will fail for non-`sips` copies.
-fn convert_heic(
+pub(crate) fn convert_heic(
     from: &Path,
     to: &Path,
     converter: &ImageConverter,
@@ -0,0 +1,244 @@
+/*!
+ Defines routines for embedding message context into copied media files.
+
+ Photo libraries sort imports by the date stored inside each file, not by the file's
+ modification time, so the message date, sender, and chat are written as `EXIF`, `XMP`,
+ and `QuickTime` tags.
+*/
+
+use std::{
+    fs::{remove_file, rename},
+    path::Path,
+};
+
+use crate::app::compatibility::{
+    converters::common::run_command,
+    models::{Converter, MetadataWriter},
+};
+
+/// File extensions `exiftool` can write `EXIF` and `XMP` tags into
+const IMAGE_EXTENSIONS: [&str; 5] = ["jpg", "jpeg", "heic", "heif", "png"];
+
+/// File extensions of containers that store `QuickTime` metadata
+const VIDEO_EXTENSIONS: [&str; 3] = ["mp4", "m4v", "mov"];
+
+/// Context about a message to embed in its attachments
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct MediaMetadata {
+    /// Local date the message was sent, as `YYYY:MM:DD HH:MM:SS`
+    pub date: String,
+    /// Offset of [`Self::date`] from UTC, as `+HH:MM`
+    pub offset: String,
+    /// Date the message was sent in UTC, as `YYYY-MM-DDTHH:MM:SSZ`
+    pub utc: String,
+    /// Name of the message's sender
+    pub sender: String,
+    /// Name of the chat the message was sent in
+    pub chat: Option<String>,
+}
+
+impl MediaMetadata {
+    /// A sentence describing where the file came from, stored as its description
+    fn description(&self) -> String {
+        match &self.chat {
+            Some(chat) => format!("Sent by {} in {chat}", self.sender),
+            None => format!("Sent by {}", self.sender),
+        }
+    }
+
+    /// The local date with its UTC offset, as `YYYY:MM:DD HH:MM:SS+HH:MM`
+    fn date_with_offset(&self) -> String {
+        format!("{}{}", self.date, self.offset)
+    }
+}
+
+/// Write `metadata` into the file at `path`, if its format supports it
+///
+/// Files that cannot hold metadata, or that the available writer cannot modify, are left as they are.
+pub(crate) fn write_media_metadata(path: &Path, metadata: &MediaMetadata, writer: &MetadataWriter) {
+    let Some(ext) = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_lowercase)
+    else {
+        return;
+    };
+
+    if IMAGE_EXTENSIONS.contains(&ext.as_str()) {
+        if matches!(writer, MetadataWriter::Exiftool) {
+            write_with_exiftool(path, &image_args(metadata));
+        }
+    } else if VIDEO_EXTENSIONS.contains(&ext.as_str()) {
+        match writer {
+            MetadataWriter::Exiftool => write_with_exiftool(path, &video_args(metadata)),
+            MetadataWriter::Ffmpeg => write_with_ffmpeg(path, &ext, metadata),
+        }
+    }
+}
+
+/// `exiftool` tag assignments for still images
+fn image_args(metadata: &MediaMetadata) -> Vec<String> {
+    let description = metadata.description();
+    let mut args = vec![
+        format!("-EXIF:DateTimeOriginal={}", metadata.date),
+        format!("-EXIF:CreateDate={}", metadata.date),
+        format!("-EXIF:OffsetTimeOriginal={}", metadata.offset),
+        format!("-EXIF:OffsetTimeDigitized={}", metadata.offset),
+        format!("-EXIF:Artist={}", metadata.sender),
+        format!("-EXIF:ImageDescription={description}"),
+        format!("-XMP-photoshop:DateCreated={}", metadata.date_with_offset()),
+        format!("-XMP-dc:Creator={}", metadata.sender),
+        format!("-XMP-dc:Description={description}"),
+    ];
+    if let Some(chat) = &metadata.chat {
+        args.push(format!("-XMP-dc:Subject={chat}"));
+    }
+    args
+}
+
+/// `exiftool` tag assignments for `QuickTime` based containers
+fn video_args(metadata: &MediaMetadata) -> Vec<String> {
+    let description = metadata.description();
+    let mut args = vec![
+        // QuickTime dates are stored in UTC; this converts the local time using its offset
+        "-api".to_string(),
+        "QuickTimeUTC".to_string(),
+        format!("-QuickTime:CreateDate={}", metadata.date_with_offset()),
+        format!("-QuickTime:MediaCreateDate={}", metadata.date_with_offset()),
+        format!("-QuickTime:TrackCreateDate={}", metadata.date_with_offset()),
+        format!("-Keys:CreationDate={}", metadata.date_with_offset()),
+        format!("-Keys:Author={}", metadata.sender),
+        format!("-Keys:Description={description}"),
+        format!("-XMP-dc:Creator={}", metadata.sender),
+        format!("-XMP-dc:Description={description}"),
+    ];
+    if let Some(chat) = &metadata.chat {
+        args.push(format!("-Keys:Album={chat}"));
+        args.push(format!("-XMP-dc:Subject={chat}"));
+    }
+    args
+}
+
+/// `ffmpeg` arguments that copy every stream of `from` into `to` with new container metadata
+fn ffmpeg_args(from: &Path, to: &Path, metadata: &MediaMetadata) -> Vec<String> {
+    let mut args = vec![
+        "-i".to_string(),
+        from.display().to_string(),
+        "-map".to_string(),
+        "0".to_string(),
+        "-c".to_string(),
+        "copy".to_string(),
+        "-map_metadata".to_string(),
+        "0".to_string(),
+        "-metadata".to_string(),
+        format!("creation_time={}", metadata.utc),
+        "-metadata".to_string(),
+        format!("artist={}", metadata.sender),
+        "-metadata".to_string(),
+        format!("comment={}", metadata.description()),
+    ];
+    if let Some(chat) = &metadata.chat {
+        args.push("-metadata".to_string());
+        args.push(format!("album={chat}"));
+    }
+    args.extend([
+        "-movflags".to_string(),
+        "use_metadata_tags".to_string(),
+        "-y".to_string(),
+        to.display().to_string(),
+    ]);
+    args
+}
+
+/// Edit the tags of `path` in place
+fn write_with_exiftool(path: &Path, tags: &[String]) {
+    let mut args = vec![
+        "-overwrite_original".to_string(),
+        "-quiet".to_string(),
+        // Ignore minor errors, such as malformed maker notes written by other apps
+        "-m".to_string(),
+    ];
+    args.extend_from_slice(tags);
+    args.push(path.display().to_string());
+
+    if run_command(MetadataWriter::Exiftool.name(), args).is_none() {
+        eprintln!("Unable to write metadata to {}", path.display());
+    }
+}
+
+/// Remux `path` without re-encoding, then replace it with the result
+fn write_with_ffmpeg(path: &Path, ext: &str, metadata: &MediaMetadata) {
+    let temp = path.with_extension(format!("metadata.{ext}"));
+
+    if run_command(
+        MetadataWriter::Ffmpeg.name(),
+        ffmpeg_args(path, &temp, metadata),
+    )
+    .is_none()
+    {
+        eprintln!("Unable to write metadata to {}", path.display());
+        let _ = remove_file(&temp);
+        return;
+    }
+
+    if let Err(why) = rename(&temp, path) {
+        eprintln!("Unable to replace {}: {why}", path.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::app::compatibility::converters::metadata::{
+        MediaMetadata, ffmpeg_args, image_args, video_args,
+    };
+
+    fn fake_metadata(chat: Option<&str>) -> MediaMetadata {
+        MediaMetadata {
+            date: "2023:05:01 09:30:00".to_string(),
+            offset: "-07:00".to_string(),
+            utc: "2023-05-01T16:30:00Z".to_string(),
+            sender: "Jane Doe".to_string(),
+            chat: chat.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn can_build_image_args() {
+        let args = image_args(&fake_metadata(Some("Family")));
+        assert!(args.contains(&"-EXIF:DateTimeOriginal=2023:05:01 09:30:00".to_string()));
+        assert!(args.contains(&"-EXIF:OffsetTimeOriginal=-07:00".to_string()));
+        assert!(args.contains(&"-XMP-photoshop:DateCreated=2023:05:01 09:30:00-07:00".to_string()));
+        assert!(args.contains(&"-XMP-dc:Description=Sent by Jane Doe in Family".to_string()));
+        assert!(args.contains(&"-XMP-dc:Subject=Family".to_string()));
+    }
+
+    #[test]
+    fn can_build_image_args_no_chat() {
+        let args = image_args(&fake_metadata(None));
+        assert!(args.contains(&"-XMP-dc:Description=Sent by Jane Doe".to_string()));
+        assert!(!args.iter().any(|arg| arg.starts_with("-XMP-dc:Subject")));
+    }
+
+    #[test]
+    fn can_build_video_args() {
+        let args = video_args(&fake_metadata(Some("Family")));
+        assert_eq!(&args[..2], ["-api", "QuickTimeUTC"]);
+        assert!(args.contains(&"-QuickTime:CreateDate=2023:05:01 09:30:00-07:00".to_string()));
+        assert!(args.contains(&"-Keys:Album=Family".to_string()));
+    }
+
+    #[test]
+    fn can_build_ffmpeg_args() {
+        let args = ffmpeg_args(
+            Path::new("a.mp4"),
+            Path::new("a.metadata.mp4"),
+            &fake_metadata(None),
+        );
+        assert!(args.contains(&"creation_time=2023-05-01T16:30:00Z".to_string()));
+        assert!(args.contains(&"artist=Jane Doe".to_string()));
+        assert!(!args.iter().any(|arg| arg.starts_with("album=")));
+        assert_eq!(args.last().unwrap(), "a.metadata.mp4");
+    }
+}
@@ -5,5 +5,7 @@
 pub mod audio;
 pub mod common;
 pub mod image;
+pub mod metadata;
 pub mod sticker;
+pub mod thumbnail;
 pub mod video;
@@ -0,0 +1,200 @@
+/*!
+ Defines routines for generating downscaled previews of image attachments.
+
+ Thumbnails are decoded and encoded in-process, so they do not require `sips` or `ImageMagick`.
+ Only formats the decoder supports are thumbnailed; `HEIC` files are thumbnailed after they
+ are converted to `JPEG` by the `basic` and `full` copy methods.
+*/
+
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use image::{DynamicImage, ImageFormat, ImageReader};
+
+use crate::app::compatibility::converters::common::ensure_output_dir;
+
+/// Directory inside each attachment directory that holds thumbnails
+pub const THUMBNAIL_DIR: &str = "thumbnails";
+
+/// Longest edge of a generated thumbnail, in pixels
+pub const THUMBNAIL_SIZE: u32 = 640;
+
+/// Image formats a thumbnail can be encoded as
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ThumbnailFormat {
+    /// Lossy `JPEG`, the smallest and most compatible option
+    Jpeg,
+    /// Lossless `WebP`, which keeps transparency
+    Webp,
+}
+
+impl ThumbnailFormat {
+    /// Create an instance of the enum given user input
+    pub fn from_cli(format: &str) -> Option<Self> {
+        match format.to_lowercase().as_str() {
+            "jpeg" | "jpg" => Some(Self::Jpeg),
+            "webp" => Some(Self::Webp),
+            _ => None,
+        }
+    }
+
+    /// The file extension used for thumbnails of this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ThumbnailFormat::Jpeg => "jpg",
+            ThumbnailFormat::Webp => "webp",
+        }
+    }
+}
+
+impl Display for ThumbnailFormat {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ThumbnailFormat::Jpeg => write!(fmt, "jpeg"),
+            ThumbnailFormat::Webp => write!(fmt, "webp"),
+        }
+    }
+}
+
+/// Build the path of the thumbnail for a copied attachment at `image`
+///
+/// Thumbnails live in [`THUMBNAIL_DIR`] next to the file they preview and are named after it,
+/// keeping its extension so files that differ only by extension do not share a thumbnail.
+pub(crate) fn thumbnail_path(image: &Path, format: ThumbnailFormat) -> Option<PathBuf> {
+    let mut name = image.file_name()?.to_os_string();
+    name.push(".");
+    name.push(format.extension());
+    Some(image.parent()?.join(THUMBNAIL_DIR).join(name))
+}
+
+/// Generate a thumbnail for the image at `from`, returning its path
+///
+/// Returns [`None`] if the image cannot be decoded, is animated, or is already no larger than
+/// a thumbnail, in which case the original should be displayed instead.
+pub(crate) fn generate_thumbnail(from: &Path, format: ThumbnailFormat) -> Option<PathBuf> {
+    let to = thumbnail_path(from, format)?;
+    if to.exists() {
+        return Some(to);
+    }
+
+    let reader = ImageReader::open(from).ok()?.with_guessed_format().ok()?;
+    // Downscaling keeps only the first frame, so animations are displayed as they are
+    if matches!(reader.format(), None | Some(ImageFormat::Gif)) {
+        return None;
+    }
+
+    let image = match reader.decode() {
+        Ok(image) => image,
+        Err(why) => {
+            eprintln!("Unable to decode {} for a thumbnail: {why}", from.display());
+            return None;
+        }
+    };
+    if image.width() <= THUMBNAIL_SIZE && image.height() <= THUMBNAIL_SIZE {
+        return None;
+    }
+
+    ensure_output_dir(&to)?;
+    let thumbnail = image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+    let result = match format {
+        // JPEG has no alpha channel
+        ThumbnailFormat::Jpeg => {
+            DynamicImage::from(thumbnail.to_rgb8()).save_with_format(&to, ImageFormat::Jpeg)
+        }
+        ThumbnailFormat::Webp => {
+            DynamicImage::from(thumbnail.to_rgba8()).save_with_format(&to, ImageFormat::WebP)
+        }
+    };
+
+    match result {
+        Ok(()) => Some(to),
+        Err(why) => {
+            eprintln!("Unable to write thumbnail {}: {why}", to.display());
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use image::{ImageReader, RgbImage};
+
+    use crate::app::{
+        compatibility::converters::thumbnail::{
+            THUMBNAIL_SIZE, ThumbnailFormat, generate_thumbnail, thumbnail_path,
+        },
+        test_dir::unique_test_dir,
+    };
+
+    #[test]
+    fn can_parse_thumbnail_format() {
+        assert_eq!(
+            ThumbnailFormat::from_cli("JPEG"),
+            Some(ThumbnailFormat::Jpeg)
+        );
+        assert_eq!(
+            ThumbnailFormat::from_cli("jpg"),
+            Some(ThumbnailFormat::Jpeg)
+        );
+        assert_eq!(
+            ThumbnailFormat::from_cli("webp"),
+            Some(ThumbnailFormat::Webp)
+        );
+        assert_eq!(ThumbnailFormat::from_cli("png"), None);
+    }
+
+    #[test]
+    fn can_get_thumbnail_path() {
+        assert_eq!(
+            thumbnail_path(Path::new("attachments/1/42.jpeg"), ThumbnailFormat::Webp),
+            Some(PathBuf::from("attachments/1/thumbnails/42.jpeg.webp"))
+        );
+    }
+
+    #[test]
+    fn can_get_thumbnail_path_same_stem() {
+        assert_ne!(
+            thumbnail_path(Path::new("attachments/1/IMG_1.heic"), ThumbnailFormat::Jpeg),
+            thumbnail_path(Path::new("attachments/1/IMG_1.png"), ThumbnailFormat::Jpeg)
+        );
+    }
+
+    #[test]
+    fn can_generate_thumbnail() {
+        let dir = unique_test_dir("thumbnail");
+        let image = dir.join("large.png");
+        RgbImage::new(THUMBNAIL_SIZE * 2, THUMBNAIL_SIZE)
+            .save(&image)
+            .unwrap();
+
+        let thumbnail = generate_thumbnail(&image, ThumbnailFormat::Jpeg).unwrap();
+        assert_eq!(thumbnail, dir.join("thumbnails").join("large.png.jpg"));
+
+        let decoded = ImageReader::open(&thumbnail).unwrap().decode().unwrap();
+        assert_eq!(decoded.width(), THUMBNAIL_SIZE);
+        assert_eq!(decoded.height(), THUMBNAIL_SIZE / 2);
+    }
+
+    #[test]
+    fn cant_generate_thumbnail_small() {
+        let dir = unique_test_dir("thumbnail_small");
+        let image = dir.join("small.png");
+        RgbImage::new(10, 10).save(&image).unwrap();
+
+        assert_eq!(generate_thumbnail(&image, ThumbnailFormat::Webp), None);
+        assert!(!dir.join("thumbnails").exists());
+    }
+
+    #[test]
+    fn cant_generate_thumbnail_undecodable() {
+        let dir = unique_test_dir("thumbnail_invalid");
+        let image = dir.join("image.heic");
+        std::fs::write(&image, b"not an image").unwrap();
+
+        assert_eq!(generate_thumbnail(&image, ThumbnailFormat::Jpeg), None);
+    }
+}
@@ -2,46 +2,20 @@
  Defines routines for converting video files.
 */
 
-use std::{
-    ffi::OsStr,
-    path::{Path, PathBuf},
-};
+use std::{ffi::OsStr, path::Path};
 
 use imessage_database::tables::attachment::MediaType;
 
 use crate::app::compatibility::{
-    converters::common::{copy_raw, ensure_output_dir, run_command},
+    converters::common::{ensure_output_dir, run_command},
     models::{Converter, HardwareEncoder, VideoConverter, VideoType},
 };
 
-/// Copy a video file, converting if possible
+/// The format a video file is converted to, if it needs converting
 ///
 /// - Attachment `MOV` files convert to `MP4`
-/// - Fallback to the original format
-pub(crate) fn video_copy_convert(
-    from: &Path,
-    to: &mut PathBuf,
-    converter: &VideoConverter,
-    hardware_encoder: &Option<HardwareEncoder>,
-    mime_type: &MediaType,
-) -> Option<MediaType<'static>> {
-    if matches!(mime_type, MediaType::Video("mov" | "MOV" | "quicktime")) {
-        let output_type = VideoType::Mp4;
-
-        // Update extension for conversion
-        let mut converted_path = to.clone();
-        converted_path.set_extension(output_type.to_str());
-
-        if convert_mov(from, &converted_path, converter, hardware_encoder.as_ref()).is_some() {
-            *to = converted_path;
-            return Some(MediaType::Video(output_type.to_str()));
-        }
-        eprintln!("Unable to convert {from:?}");
-    }
-
-    // Fallback
-    copy_raw(from, to);
-    None
+pub(crate) fn video_output_type(mime_type: &MediaType) -> Option<VideoType> {
+    matches!(mime_type, MediaType::Video("mov" | "MOV" | "quicktime")).then_some(VideoType::Mp4)
 }
 
 /// Build ffmpeg arguments for remuxing without re-encoding
@@ -90,7 +64,7 @@ fn build_encode_args<'a>(
 }
 
 // Convert a video file by attempting remuxing, falling back to hardware-accelerated or software re-encode
-fn convert_mov(
+pub(crate) fn convert_mov(
     from: &Path,
     to: &Path,
     converter: &VideoConverter,
@@ -4,6 +4,10 @@
 
 pub mod attachment_manager;
 pub mod backup;
+pub mod cache;
+pub mod conversions;
 pub mod converters;
 pub mod error;
 pub mod models;
+pub mod naming;
+pub mod policy;
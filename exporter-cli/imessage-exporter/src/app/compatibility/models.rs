@@ -19,7 +19,7 @@ pub trait Converter {
         Self: Sized;
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum ImageType {
     Jpeg,
     Gif,
@@ -27,7 +27,7 @@ pub enum ImageType {
 }
 
 impl ImageType {
-    pub fn to_str(&self) -> &'static str {
+    pub fn to_str(self) -> &'static str {
         match self {
             Self::Jpeg => "jpeg",
             Self::Gif => "gif",
@@ -36,33 +36,33 @@ impl ImageType {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum VideoType {
     Mp4,
 }
 
 impl VideoType {
-    pub fn to_str(&self) -> &'static str {
+    pub fn to_str(self) -> &'static str {
         match self {
             Self::Mp4 => "mp4",
         }
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum AudioType {
     Mp4,
 }
 
 impl AudioType {
-    pub fn to_str(&self) -> &'static str {
+    pub fn to_str(self) -> &'static str {
         match self {
             Self::Mp4 => "mp4",
         }
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 /// Program used to convert/encode images
 pub enum ImageConverter {
     /// macOS Builtin
@@ -96,7 +96,7 @@ impl Display for ImageConverter {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 /// Program used to convert/encode audio
 pub enum AudioConverter {
     /// macOS Builtin
@@ -130,7 +130,7 @@ impl Display for AudioConverter {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 /// Program used to convert/encode videos
 pub enum VideoConverter {
     Ffmpeg,
@@ -158,8 +158,44 @@ impl Display for VideoConverter {
     }
 }
 
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+/// Program used to write metadata into copied media
+pub enum MetadataWriter {
+    /// Writes images and videos
+    Exiftool,
+    /// Writes videos only
+    Ffmpeg,
+}
+
+impl Converter for MetadataWriter {
+    fn determine() -> Option<MetadataWriter> {
+        if exists(MetadataWriter::Exiftool.name()) {
+            return Some(MetadataWriter::Exiftool);
+        }
+        if exists(MetadataWriter::Ffmpeg.name()) {
+            eprintln!("exiftool not found, metadata will only be written to videos!");
+            return Some(MetadataWriter::Ffmpeg);
+        }
+        eprintln!("No metadata writer found, metadata will not be written to attachments!");
+        None
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            MetadataWriter::Exiftool => "exiftool",
+            MetadataWriter::Ffmpeg => "ffmpeg",
+        }
+    }
+}
+
+impl Display for MetadataWriter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}", self.name())
+    }
+}
+
 /// Define supported hardware-based H.264 encoders
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum HardwareEncoder {
     /// NVIDIA GPU-accelerated H.264 encoder (`NVENC`)
     Nvenc,
@@ -0,0 +1,308 @@
+/*!
+ Defines how copied attachments are named when the user provides a filename template.
+*/
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use imessage_database::tables::attachment::Attachment;
+
+use crate::app::sanitizers::sanitize_filename;
+
+/// Placeholders supported in attachment filename templates
+pub const SUPPORTED_NAME_PLACEHOLDERS: &str =
+    "{date}, {time}, {sender}, {chat}, {original_name}, {rowid}";
+
+/// Longest rendered name, in bytes, before the extension and any collision suffix
+const MAX_NAME_LENGTH: usize = 128;
+
+/// A value substituted into an attachment filename template
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Placeholder {
+    /// The date the message was sent, as `YYYY-MM-DD`
+    Date,
+    /// The time the message was sent, as `HH-MM-SS`
+    Time,
+    /// The name of the message's sender
+    Sender,
+    /// The name of the chat the message was sent in
+    Chat,
+    /// The name the attachment was sent with, without its extension
+    OriginalName,
+    /// The attachment's `ROWID`, which is unique in the database
+    Rowid,
+}
+
+impl Placeholder {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "date" => Some(Self::Date),
+            "time" => Some(Self::Time),
+            "sender" => Some(Self::Sender),
+            "chat" => Some(Self::Chat),
+            "original_name" => Some(Self::OriginalName),
+            "rowid" => Some(Self::Rowid),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Segment {
+    Text(String),
+    Value(Placeholder),
+}
+
+/// The values available to an attachment filename template
+#[derive(Debug, Default)]
+pub struct NameFields<'a> {
+    pub date: &'a str,
+    pub time: &'a str,
+    pub sender: &'a str,
+    pub chat: &'a str,
+    pub original_name: &'a str,
+    pub rowid: i32,
+}
+
+/// The name the attachment was sent with, without its extension
+pub fn original_stem(attachment: &Attachment) -> String {
+    attachment
+        .transfer_name
+        .as_deref()
+        .or_else(|| attachment.filename())
+        .and_then(|name| Path::new(name).file_stem())
+        .map_or_else(
+            || attachment.rowid.to_string(),
+            |stem| stem.to_string_lossy().into_owned(),
+        )
+}
+
+/// A user-provided pattern for naming copied attachments, i.e. `{date} {sender} {original_name}`
+#[derive(Debug, PartialEq, Eq)]
+pub struct AttachmentNameTemplate {
+    source: String,
+    segments: Vec<Segment>,
+}
+
+impl AttachmentNameTemplate {
+    /// Parse a template, returning a description of the problem if it is invalid
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let mut segments = vec![];
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                segments.push(Segment::Text(rest[..start].to_string()));
+            }
+            let Some(end) = rest[start..].find('}') else {
+                return Err(format!("unclosed placeholder in \"{template}\""));
+            };
+            let name = &rest[start + 1..start + end];
+            let Some(placeholder) = Placeholder::from_name(name) else {
+                return Err(format!(
+                    "{{{name}}} is not a valid placeholder! Must be one of <{SUPPORTED_NAME_PLACEHOLDERS}>"
+                ));
+            };
+            segments.push(Segment::Value(placeholder));
+            rest = &rest[start + end + 1..];
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Text(rest.to_string()));
+        }
+
+        if !segments
+            .iter()
+            .any(|segment| matches!(segment, Segment::Value(_)))
+        {
+            return Err(format!(
+                "\"{template}\" does not contain a placeholder! Use at least one of <{SUPPORTED_NAME_PLACEHOLDERS}>"
+            ));
+        }
+
+        Ok(Self {
+            source: template.to_string(),
+            segments,
+        })
+    }
+
+    /// Whether every rendered name is unique, because the template includes the attachment's `ROWID`
+    pub fn is_unique(&self) -> bool {
+        self.segments.contains(&Segment::Value(Placeholder::Rowid))
+    }
+
+    /// Render a sanitized filename stem, falling back to the `ROWID` if nothing remains
+    pub fn render(&self, fields: &NameFields) -> String {
+        let mut name = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Text(text) => name.push_str(text),
+                Segment::Value(Placeholder::Date) => name.push_str(fields.date),
+                Segment::Value(Placeholder::Time) => name.push_str(fields.time),
+                Segment::Value(Placeholder::Sender) => name.push_str(fields.sender),
+                Segment::Value(Placeholder::Chat) => name.push_str(fields.chat),
+                Segment::Value(Placeholder::OriginalName) => name.push_str(fields.original_name),
+                Segment::Value(Placeholder::Rowid) => name.push_str(&fields.rowid.to_string()),
+            }
+        }
+
+        name.truncate(name.floor_char_boundary(MAX_NAME_LENGTH));
+        let name = sanitize_filename(name.trim());
+        if name.is_empty() {
+            fields.rowid.to_string()
+        } else {
+            name
+        }
+    }
+}
+
+impl Display for AttachmentNameTemplate {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(fmt, "{}", self.source)
+    }
+}
+
+/// Names given to copied attachments in each directory during an export
+#[derive(Debug, PartialEq, Eq, Default)]
+pub struct AttachmentNames {
+    /// Lowercased filename stems in each directory, mapped to the `ROWID` of the attachment that has it
+    claimed: HashMap<PathBuf, HashMap<String, i32>>,
+}
+
+impl AttachmentNames {
+    /// Reserve a filename in `dir` for the attachment with `rowid`, returning the name to use
+    ///
+    /// An attachment asking again receives the name it was given before. If another attachment
+    /// already has the name, the `ROWID` is appended, which keeps the result the same on every run.
+    /// Names are compared without case, since macOS and Windows file systems ignore it, and without
+    /// their extension, since converting an attachment changes it.
+    pub fn claim(&mut self, dir: &Path, stem: &str, extension: Option<&str>, rowid: i32) -> String {
+        let claimed = self.claimed.entry(dir.to_path_buf()).or_default();
+        let mut suffix = String::new();
+        let mut copy = 1;
+        loop {
+            let name = format!("{stem}{suffix}");
+            let owner = claimed.entry(name.to_lowercase()).or_insert(rowid);
+            if *owner == rowid {
+                return match extension {
+                    Some(ext) => format!("{name}.{ext}"),
+                    None => name,
+                };
+            }
+            suffix = if copy == 1 {
+                format!(" ({rowid})")
+            } else {
+                format!(" ({rowid}-{copy})")
+            };
+            copy += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{
+        Config,
+        app::compatibility::naming::{
+            AttachmentNameTemplate, AttachmentNames, NameFields, original_stem,
+        },
+    };
+
+    fn fake_fields() -> NameFields<'static> {
+        NameFields {
+            date: "2023-05-01",
+            time: "09-30-00",
+            sender: "Jane Doe",
+            chat: "Family",
+            original_name: "IMG_0001",
+            rowid: 42,
+        }
+    }
+
+    #[test]
+    fn can_render_template() {
+        let template =
+            AttachmentNameTemplate::parse("{date} {time} - {sender} - {original_name}").unwrap();
+        assert_eq!(
+            template.render(&fake_fields()),
+            "2023-05-01 09-30-00 - Jane Doe - IMG_0001"
+        );
+        assert!(!template.is_unique());
+    }
+
+    #[test]
+    fn can_render_template_rowid() {
+        let template = AttachmentNameTemplate::parse("{chat}_{rowid}").unwrap();
+        assert_eq!(template.render(&fake_fields()), "Family_42");
+        assert!(template.is_unique());
+    }
+
+    #[test]
+    fn can_sanitize_rendered_template() {
+        let template = AttachmentNameTemplate::parse("{sender}").unwrap();
+        let fields = NameFields {
+            sender: "a/b:c.",
+            ..fake_fields()
+        };
+        assert_eq!(template.render(&fields), "a_b_c");
+    }
+
+    #[test]
+    fn can_render_empty_template() {
+        let template = AttachmentNameTemplate::parse("{chat}").unwrap();
+        let fields = NameFields {
+            chat: "",
+            ..fake_fields()
+        };
+        assert_eq!(template.render(&fields), "42");
+    }
+
+    #[test]
+    fn cant_parse_invalid_template() {
+        assert!(AttachmentNameTemplate::parse("{name}").is_err());
+        assert!(AttachmentNameTemplate::parse("{date").is_err());
+        assert!(AttachmentNameTemplate::parse("photo").is_err());
+    }
+
+    #[test]
+    fn can_get_original_stem() {
+        let mut attachment = Config::fake_attachment();
+        assert_eq!(original_stem(&attachment), "d");
+
+        attachment.transfer_name = None;
+        attachment.filename = None;
+        assert_eq!(original_stem(&attachment), "0");
+    }
+
+    #[test]
+    fn can_claim_names() {
+        let mut names = AttachmentNames::default();
+        let dir = Path::new("attachments/1");
+
+        assert_eq!(names.claim(dir, "IMG", Some("jpg"), 1), "IMG.jpg");
+        // The same attachment rendered again keeps its name
+        assert_eq!(names.claim(dir, "IMG", Some("jpg"), 1), "IMG.jpg");
+        assert_eq!(names.claim(dir, "img", Some("JPG"), 2), "img (2).JPG");
+        // A different extension does not make a name unique, since conversions change it
+        assert_eq!(names.claim(dir, "IMG", Some("png"), 3), "IMG (3).png");
+
+        // Names are only unique within a directory
+        assert_eq!(
+            names.claim(Path::new("attachments/2"), "IMG", Some("jpg"), 4),
+            "IMG.jpg"
+        );
+    }
+
+    #[test]
+    fn can_claim_converted_and_unconverted_names() {
+        let mut names = AttachmentNames::default();
+        let dir = Path::new("attachments/1");
+
+        // `IMG_1.heic` is converted to `IMG_1.jpeg`, so a separate `IMG_1.jpeg` cannot use that name
+        assert_eq!(names.claim(dir, "IMG_1", Some("heic"), 1), "IMG_1.heic");
+        assert_eq!(names.claim(dir, "IMG_1", Some("jpeg"), 2), "IMG_1 (2).jpeg");
+    }
+}
@@ -0,0 +1,218 @@
+/*!
+ Defines rules that decide which attachments are exported.
+
+ Attachments that do not pass are not copied; exporters show their name and size in their place.
+*/
+
+use imessage_database::tables::attachment::{Attachment, MediaType};
+
+/// Units accepted by [`parse_size`], matching the ones used to display file sizes
+const SIZE_UNITS: [(&str, u64); 5] = [
+    ("TB", 1 << 40),
+    ("GB", 1 << 30),
+    ("MB", 1 << 20),
+    ("KB", 1 << 10),
+    ("B", 1),
+];
+
+/// Rules for which attachments are exported
+#[derive(Debug, PartialEq, Eq, Default)]
+pub struct AttachmentPolicy {
+    /// Only include images, GIFs, and HEIC sequences
+    pub images_only: bool,
+    /// Largest attachment to include, in bytes
+    pub max_size: Option<u64>,
+    /// Lowercased file extensions to exclude, without the leading `.`
+    pub skip_types: Vec<String>,
+    /// Lowercased MIME types to include, i.e. `image/png` or `audio/*`
+    pub include_mime: Vec<String>,
+}
+
+impl AttachmentPolicy {
+    /// Determine if an attachment should be exported
+    ///
+    /// Stickers are always exported, since they are part of how a message is displayed.
+    pub fn allows(&self, attachment: &Attachment) -> bool {
+        if attachment.is_sticker {
+            return true;
+        }
+
+        let media_type = attachment.mime_type();
+        if self.images_only {
+            match media_type {
+                MediaType::Image(_) => {}
+                MediaType::Video(ext) if ext.eq_ignore_ascii_case("heics") => {}
+                _ => return false,
+            }
+        }
+
+        if let Some(max_size) = self.max_size
+            && u64::try_from(attachment.total_bytes).unwrap_or(0) > max_size
+        {
+            return false;
+        }
+
+        if !self.skip_types.is_empty()
+            && let Some(ext) = attachment.extension()
+            && self.skip_types.contains(&ext.to_lowercase())
+        {
+            return false;
+        }
+
+        if !self.include_mime.is_empty() {
+            let mime = media_type.as_mime_type().to_lowercase();
+            return self
+                .include_mime
+                .iter()
+                .any(|pattern| mime_matches(pattern, &mime));
+        }
+
+        true
+    }
+}
+
+/// Determine if a MIME type matches a pattern, where `*` matches any type or subtype
+fn mime_matches(pattern: &str, mime: &str) -> bool {
+    let (category, subtype) = mime.split_once('/').unwrap_or((mime, ""));
+    // Parameters, such as `; codecs=opus`, are not part of the type
+    let subtype = subtype.split(';').next().unwrap_or(subtype).trim();
+    match pattern.split_once('/') {
+        Some((pattern_category, pattern_subtype)) => {
+            (pattern_category == "*" || pattern_category == category)
+                && (pattern_subtype == "*" || pattern_subtype == subtype)
+        }
+        None => pattern == "*" || pattern == category,
+    }
+}
+
+/// Parse a size such as `50MB`, `1.5 GB`, or `2048` into bytes
+pub fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim().to_uppercase();
+    let (number, unit) = SIZE_UNITS
+        .iter()
+        .find_map(|(name, unit)| Some((size.strip_suffix(name)?, *unit)))
+        .unwrap_or((&size, 1));
+    let number: f64 = number.trim().parse().ok()?;
+    if !number.is_finite() || number < 0. {
+        return None;
+    }
+    Some((number * unit as f64) as u64)
+}
+
+/// Split a comma-separated list into lowercased, non-empty entries
+pub fn parse_list(list: &str, trim: &[char]) -> Vec<String> {
+    list.split(',')
+        .map(|item| item.trim().trim_start_matches(trim).to_lowercase())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Config,
+        app::compatibility::policy::{AttachmentPolicy, mime_matches, parse_list, parse_size},
+    };
+
+    #[test]
+    fn can_parse_size() {
+        assert_eq!(parse_size("2048"), Some(2048));
+        assert_eq!(parse_size("50MB"), Some(50 * 1024 * 1024));
+        assert_eq!(parse_size("1.5 gb"), Some(1536 * 1024 * 1024));
+        assert_eq!(parse_size("10KB"), Some(10 * 1024));
+        assert_eq!(parse_size("12B"), Some(12));
+    }
+
+    #[test]
+    fn cant_parse_invalid_size() {
+        assert_eq!(parse_size("MB"), None);
+        assert_eq!(parse_size("-5MB"), None);
+        assert_eq!(parse_size("five"), None);
+    }
+
+    #[test]
+    fn can_parse_list() {
+        assert_eq!(parse_list(" .MOV, heics,,", &['.']), vec!["mov", "heics"]);
+    }
+
+    #[test]
+    fn can_match_mime() {
+        assert!(mime_matches("image/*", "image/png"));
+        assert!(mime_matches("image", "image/png"));
+        assert!(mime_matches("audio/x-caf", "audio/x-caf; codecs=opus"));
+        assert!(mime_matches("*/*", "application/pdf"));
+        assert!(!mime_matches("image/*", "video/mp4"));
+        assert!(!mime_matches("image/jpeg", "image/png"));
+    }
+
+    #[test]
+    fn can_allow_by_default() {
+        let attachment = Config::fake_attachment();
+        assert!(AttachmentPolicy::default().allows(&attachment));
+    }
+
+    #[test]
+    fn can_skip_large() {
+        let mut attachment = Config::fake_attachment();
+        attachment.total_bytes = 2048;
+        let policy = AttachmentPolicy {
+            max_size: Some(1024),
+            ..Default::default()
+        };
+        assert!(!policy.allows(&attachment));
+
+        attachment.total_bytes = 1024;
+        assert!(policy.allows(&attachment));
+    }
+
+    #[test]
+    fn can_skip_types() {
+        let attachment = Config::fake_attachment();
+        let policy = AttachmentPolicy {
+            skip_types: vec!["jpg".to_string()],
+            ..Default::default()
+        };
+        assert!(!policy.allows(&attachment));
+    }
+
+    #[test]
+    fn can_include_mime() {
+        let mut attachment = Config::fake_attachment();
+        let policy = AttachmentPolicy {
+            include_mime: vec!["audio/*".to_string()],
+            ..Default::default()
+        };
+        assert!(!policy.allows(&attachment));
+
+        attachment.mime_type = Some("audio/mp4".to_string());
+        assert!(policy.allows(&attachment));
+    }
+
+    #[test]
+    fn can_allow_stickers() {
+        let mut attachment = Config::fake_attachment();
+        attachment.is_sticker = true;
+        let policy = AttachmentPolicy {
+            images_only: true,
+            max_size: Some(0),
+            ..Default::default()
+        };
+        assert!(policy.allows(&attachment));
+    }
+
+    #[test]
+    fn can_skip_images_only() {
+        let mut attachment = Config::fake_attachment();
+        let policy = AttachmentPolicy {
+            images_only: true,
+            ..Default::default()
+        };
+        assert!(policy.allows(&attachment));
+
+        attachment.mime_type = Some("video/quicktime".to_string());
+        assert!(!policy.allows(&attachment));
+
+        attachment.mime_type = Some("video/heics".to_string());
+        assert!(policy.allows(&attachment));
+    }
+}
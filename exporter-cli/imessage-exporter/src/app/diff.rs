@@ -0,0 +1,557 @@
+/*!
+ Compares two databases, or two iOS backups, and reports what changed between them for each chat.
+
+ Messages are matched by `GUID`, which the Messages app keeps when a database is synced or restored
+ from a backup. Chats are matched by their `chat_identifier` for the same reason; the chat `ROWID`
+ and handle `ROWID`s are assigned locally and differ between copies of the same history.
+
+ The database passed with `--db-path` is the baseline. For every chat, the report lists messages
+ that exist in only one of the databases, messages that were edited or unsent in the compared
+ database since the baseline was taken, and attachments from the baseline that are no longer in
+ the compared database or whose files are no longer on disk.
+*/
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
+
+use clap::ArgMatches;
+use imessage_database::{
+    message_types::edited::EditStatus,
+    tables::{
+        attachment::Attachment,
+        messages::Message,
+        table::{ORPHANED, Table},
+    },
+    util::{dates::format as format_date, platform::Platform},
+};
+
+use crate::{
+    app::{error::RuntimeError, options::Options, runtime::Config},
+    exporters::shared::driver::apply_body,
+};
+
+/// Maximum number of characters of message text shown in the report
+const PREVIEW_LENGTH: usize = 80;
+
+/// An attachment on a message, as it exists in one database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttachmentSummary {
+    /// The name the attachment was sent with
+    pub name: String,
+    /// Whether the file exists on disk, or `None` if it cannot be checked, i.e. in an encrypted backup
+    pub on_disk: Option<bool>,
+}
+
+/// The parts of a message that are compared between databases
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageSummary {
+    /// The `chat_identifier` of the message's chat, or an empty string if it has none
+    pub chat: String,
+    /// The name of the chat used in the report
+    pub chat_name: String,
+    /// The message date as stored in the database, used for ordering
+    pub timestamp: i64,
+    /// The message date, already formatted
+    pub date: String,
+    /// The name of the sender
+    pub sender: String,
+    /// The message text, if any
+    pub text: Option<String>,
+    /// The number of times any part of the message was edited
+    pub edits: usize,
+    /// The number of message parts that were unsent
+    pub unsent: usize,
+    /// The message's attachments
+    pub attachments: Vec<AttachmentSummary>,
+}
+
+impl MessageSummary {
+    /// Summarize a message whose body has already been applied
+    fn new(message: &Message, (chat, chat_name): (String, String), config: &Config) -> Self {
+        let (edits, unsent) = message
+            .edited_parts
+            .as_ref()
+            .map(|edited| {
+                edited
+                    .parts
+                    .iter()
+                    .fold((0, 0), |(edits, unsent), part| match part.status {
+                        EditStatus::Edited => (edits + part.edit_history.len(), unsent),
+                        EditStatus::Unsent => (edits, unsent + 1),
+                        EditStatus::Original => (edits, unsent),
+                    })
+            })
+            .unwrap_or_default();
+
+        Self {
+            chat,
+            chat_name,
+            timestamp: message.date,
+            date: message
+                .date(config.offset)
+                .map(|date| format_date(&date))
+                .unwrap_or_default(),
+            sender: config
+                .who(
+                    message.handle_id,
+                    message.is_from_me,
+                    &message.destination_caller_id,
+                )
+                .to_string(),
+            text: message.text.clone(),
+            edits,
+            unsent,
+            attachments: summarize_attachments(message, config),
+        }
+    }
+
+    /// A single line describing the message for the report
+    fn preview(&self) -> String {
+        let text = match &self.text {
+            Some(text) => {
+                let text = text.replace(['\n', '\r'], " ");
+                match text.char_indices().nth(PREVIEW_LENGTH) {
+                    Some((end, _)) => format!("{}...", &text[..end]),
+                    None => text,
+                }
+            }
+            None if !self.attachments.is_empty() => format!(
+                "<{} attachment{}>",
+                self.attachments.len(),
+                if self.attachments.len() == 1 { "" } else { "s" }
+            ),
+            None => String::from("<no text>"),
+        };
+        format!("{}  {}: {text}", self.date, self.sender)
+    }
+}
+
+/// Why an attachment from the baseline is missing from the compared database
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MissingReason {
+    /// The attachment row no longer exists
+    Removed,
+    /// The attachment row exists, but its file is not on disk
+    FileMissing,
+}
+
+/// Everything that differs between the two databases for a single chat
+#[derive(Debug, Default)]
+pub struct ChatDifferences<'a> {
+    /// The name of the chat used in the report
+    pub name: String,
+    /// Messages that exist only in the baseline
+    pub removed: Vec<&'a MessageSummary>,
+    /// Messages that exist only in the compared database
+    pub added: Vec<&'a MessageSummary>,
+    /// Messages edited since the baseline, as they appear in the baseline and the compared database
+    pub edited: Vec<(&'a MessageSummary, &'a MessageSummary)>,
+    /// Messages unsent since the baseline, as they appear in the baseline
+    pub unsent: Vec<&'a MessageSummary>,
+    /// Attachments from the baseline missing from the compared database
+    pub missing_attachments: Vec<(&'a MessageSummary, &'a str, MissingReason)>,
+}
+
+impl ChatDifferences<'_> {
+    fn is_empty(&self) -> bool {
+        self.removed.is_empty()
+            && self.added.is_empty()
+            && self.edited.is_empty()
+            && self.unsent.is_empty()
+            && self.missing_attachments.is_empty()
+    }
+}
+
+/// Compare the database in `options` with the one passed to `--compare` and print the differences
+///
+/// `args` are parsed a second time to build the options for the compared database, so both
+/// databases are read with the same filters.
+pub fn compare_databases(options: Options, args: &ArgMatches) -> Result<(), RuntimeError> {
+    let Some(other_path) = options.compare.clone() else {
+        return Ok(());
+    };
+    let before_path = options.db_path.clone();
+
+    let mut other_options = Options::from_args(args)?;
+    other_options.platform = Platform::determine(&other_path)?;
+    other_options.db_path = other_path.clone();
+
+    eprintln!("Reading {}...", before_path.display());
+    let before = summarize(open(options)?)?;
+    eprintln!("Reading {}...", other_path.display());
+    let after = summarize(open(other_options)?)?;
+
+    let differences = compare(&before, &after);
+    print_report(&before_path, &other_path, &differences);
+    Ok(())
+}
+
+/// Create a [`Config`] for one side of the comparison, resolving its filters
+fn open(options: Options) -> Result<Config, RuntimeError> {
+    let mut config = Config::new(options)?;
+    config.resolve_filtered_handles();
+    config.resolve_query_expression();
+    Ok(config)
+}
+
+/// Summarize every message selected by the config's filters, keyed by `GUID`
+fn summarize(config: Config) -> Result<HashMap<String, MessageSummary>, RuntimeError> {
+    let db = config.data_source.db();
+    let context = &config.options.query_context;
+    let post_filter = context
+        .expression
+        .as_ref()
+        .is_some_and(|expression| !expression.is_sql_complete());
+
+    let mut summaries = HashMap::new();
+    // Chat names are built from participant names, so they are generated once per chat
+    let mut chats: HashMap<Option<i32>, (String, String)> = HashMap::new();
+    let mut statement = Message::stream_rows(db, context)?;
+    for message in Message::rows(&mut statement, [])? {
+        let mut msg = message?;
+        // Messages in more than one chat are returned once per chat
+        if summaries.contains_key(&msg.guid) {
+            continue;
+        }
+        apply_body(&mut msg, db);
+
+        if post_filter
+            && let Some(expression) = &context.expression
+            && !expression.matches(&msg, db)
+        {
+            continue;
+        }
+
+        let chat = chats
+            .entry(msg.chat_id.or(msg.deleted_from))
+            .or_insert_with(|| match config.conversation(&msg) {
+                Some((chatroom, _)) => {
+                    (chatroom.chat_identifier.clone(), config.filename(chatroom))
+                }
+                None => (String::new(), ORPHANED.to_string()),
+            })
+            .clone();
+        summaries.insert(msg.guid.clone(), MessageSummary::new(&msg, chat, &config));
+    }
+    Ok(summaries)
+}
+
+/// Summarize the attachments on a message and check whether their files are on disk
+fn summarize_attachments(message: &Message, config: &Config) -> Vec<AttachmentSummary> {
+    if !message.has_attachments() {
+        return vec![];
+    }
+    let Ok(attachments) = Attachment::from_message(config.data_source.db(), message) else {
+        return vec![];
+    };
+
+    // Files in encrypted backups are not stored at their resolved paths
+    let can_check = config.data_source.backup.is_none();
+    attachments
+        .iter()
+        .map(|attachment| AttachmentSummary {
+            name: attachment
+                .filename()
+                .or(attachment.transfer_name.as_deref())
+                .unwrap_or("unknown")
+                .to_string(),
+            on_disk: can_check.then(|| {
+                attachment
+                    .resolved_attachment_path(
+                        &config.options.platform,
+                        &config.options.db_path,
+                        config.options.attachment_root.as_deref(),
+                    )
+                    .is_some_and(|path| Path::new(&path).exists())
+            }),
+        })
+        .collect()
+}
+
+/// Find the differences between two sets of message summaries, grouped by chat
+///
+/// Chats are keyed by their `chat_identifier` and named as they are in the baseline, when possible.
+pub fn compare<'a>(
+    before: &'a HashMap<String, MessageSummary>,
+    after: &'a HashMap<String, MessageSummary>,
+) -> BTreeMap<String, ChatDifferences<'a>> {
+    let mut chats: BTreeMap<String, ChatDifferences<'a>> = BTreeMap::new();
+
+    for (guid, old) in before {
+        let chat = chat_entry(&mut chats, old);
+        let Some(new) = after.get(guid) else {
+            chat.removed.push(old);
+            continue;
+        };
+
+        if new.unsent > old.unsent {
+            chat.unsent.push(old);
+        } else if new.edits > old.edits || new.text != old.text {
+            chat.edited.push((old, new));
+        }
+
+        for attachment in &old.attachments {
+            match new.attachments.iter().find(|a| a.name == attachment.name) {
+                // A removed row is reported even if its file was never on disk
+                None => {
+                    chat.missing_attachments
+                        .push((old, &attachment.name, MissingReason::Removed));
+                }
+                // A missing file is only reported if the baseline had it
+                Some(current)
+                    if attachment.on_disk == Some(true) && current.on_disk == Some(false) =>
+                {
+                    chat.missing_attachments.push((
+                        old,
+                        &attachment.name,
+                        MissingReason::FileMissing,
+                    ));
+                }
+                Some(_) => {}
+            }
+        }
+    }
+
+    for (guid, new) in after {
+        if !before.contains_key(guid) {
+            chat_entry(&mut chats, new).added.push(new);
+        }
+    }
+
+    chats.retain(|_, chat| !chat.is_empty());
+    for chat in chats.values_mut() {
+        chat.removed.sort_by_key(|m| m.timestamp);
+        chat.added.sort_by_key(|m| m.timestamp);
+        chat.edited.sort_by_key(|(m, _)| m.timestamp);
+        chat.unsent.sort_by_key(|m| m.timestamp);
+        chat.missing_attachments
+            .sort_by_key(|(m, _, _)| m.timestamp);
+    }
+    chats
+}
+
+/// Get the differences for a message's chat, naming the chat after the first message seen in it
+fn chat_entry<'c, 'a>(
+    chats: &'c mut BTreeMap<String, ChatDifferences<'a>>,
+    summary: &MessageSummary,
+) -> &'c mut ChatDifferences<'a> {
+    let chat = chats.entry(summary.chat.clone()).or_default();
+    if chat.name.is_empty() {
+        chat.name.clone_from(&summary.chat_name);
+    }
+    chat
+}
+
+/// Print the differences for each chat, followed by totals
+fn print_report(before: &Path, after: &Path, chats: &BTreeMap<String, ChatDifferences>) {
+    let before = before.display();
+    let after = after.display();
+    println!("Comparing {before} (baseline) with {after}");
+
+    if chats.is_empty() {
+        println!("\nNo differences found.");
+        return;
+    }
+
+    let mut totals = [0; 5];
+    for chat in chats.values() {
+        println!("\n{}", chat.name);
+        print_section(
+            &format!("Only in {before}"),
+            chat.removed.iter().map(|m| m.preview()),
+        );
+        print_section(
+            &format!("Only in {after}"),
+            chat.added.iter().map(|m| m.preview()),
+        );
+        print_section(
+            "Edited",
+            chat.edited.iter().map(|(old, new)| {
+                format!(
+                    "{}\n      now: {}",
+                    old.preview(),
+                    new.text.as_deref().unwrap_or("<no text>")
+                )
+            }),
+        );
+        print_section("Unsent", chat.unsent.iter().map(|m| m.preview()));
+        print_section(
+            "Missing attachments",
+            chat.missing_attachments
+                .iter()
+                .map(|(m, name, reason)| match reason {
+                    MissingReason::Removed => {
+                        format!(
+                            "{}  {}: {name} (no longer in the database)",
+                            m.date, m.sender
+                        )
+                    }
+                    MissingReason::FileMissing => {
+                        format!("{}  {}: {name} (file is not on disk)", m.date, m.sender)
+                    }
+                }),
+        );
+
+        totals[0] += chat.removed.len();
+        totals[1] += chat.added.len();
+        totals[2] += chat.edited.len();
+        totals[3] += chat.unsent.len();
+        totals[4] += chat.missing_attachments.len();
+    }
+
+    let [removed, added, edited, unsent, missing] = totals;
+    println!(
+        "\n{} chats differ: {removed} messages only in {before}, {added} only in {after}, {edited} edited, {unsent} unsent, {missing} attachments missing",
+        chats.len()
+    );
+}
+
+/// Print a titled list of lines, if there are any
+fn print_section(title: &str, lines: impl ExactSizeIterator<Item = String>) {
+    if lines.len() == 0 {
+        return;
+    }
+    println!("  {title}: {}", lines.len());
+    for line in lines {
+        println!("    {line}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::app::diff::{AttachmentSummary, MessageSummary, MissingReason, compare};
+
+    fn summary(chat: &str, text: &str) -> MessageSummary {
+        MessageSummary {
+            chat: chat.to_string(),
+            chat_name: format!("{chat} name"),
+            timestamp: 0,
+            date: String::new(),
+            sender: String::from("Me"),
+            text: Some(text.to_string()),
+            edits: 0,
+            unsent: 0,
+            attachments: vec![],
+        }
+    }
+
+    fn attachment(name: &str, on_disk: Option<bool>) -> AttachmentSummary {
+        AttachmentSummary {
+            name: name.to_string(),
+            on_disk,
+        }
+    }
+
+    #[test]
+    fn can_compare_identical() {
+        let before = HashMap::from([("a".to_string(), summary("chat", "hello"))]);
+        let after = before.clone();
+
+        assert!(compare(&before, &after).is_empty());
+    }
+
+    #[test]
+    fn can_find_added_and_removed() {
+        let before = HashMap::from([
+            ("a".to_string(), summary("chat", "kept")),
+            ("b".to_string(), summary("chat", "removed")),
+        ]);
+        let after = HashMap::from([
+            ("a".to_string(), summary("chat", "kept")),
+            ("c".to_string(), summary("other", "added")),
+        ]);
+
+        let differences = compare(&before, &after);
+        assert_eq!(differences.len(), 2);
+
+        let chat = &differences["chat"];
+        assert_eq!(chat.name, "chat name");
+        assert_eq!(chat.removed.len(), 1);
+        assert_eq!(chat.removed[0].text.as_deref(), Some("removed"));
+        assert!(chat.added.is_empty());
+
+        let other = &differences["other"];
+        assert_eq!(other.added.len(), 1);
+        assert!(other.removed.is_empty());
+    }
+
+    #[test]
+    fn can_find_edited_and_unsent() {
+        let before = HashMap::from([
+            ("a".to_string(), summary("chat", "typo")),
+            ("b".to_string(), summary("chat", "oops")),
+        ]);
+        let mut edited = summary("chat", "fixed");
+        edited.edits = 1;
+        let mut unsent = summary("chat", "");
+        unsent.text = None;
+        unsent.unsent = 1;
+        let after = HashMap::from([("a".to_string(), edited), ("b".to_string(), unsent)]);
+
+        let differences = compare(&before, &after);
+        let chat = &differences["chat"];
+        assert_eq!(chat.edited.len(), 1);
+        assert_eq!(chat.edited[0].1.text.as_deref(), Some("fixed"));
+        assert_eq!(chat.unsent.len(), 1);
+        assert_eq!(chat.unsent[0].text.as_deref(), Some("oops"));
+    }
+
+    #[test]
+    fn can_find_missing_attachments() {
+        let mut old = summary("chat", "photos");
+        old.attachments = vec![
+            attachment("kept.jpg", Some(true)),
+            attachment("deleted.jpg", Some(true)),
+            attachment("removed.jpg", Some(true)),
+            attachment("never_downloaded.jpg", Some(false)),
+        ];
+        let mut new = summary("chat", "photos");
+        new.attachments = vec![
+            attachment("kept.jpg", Some(true)),
+            attachment("deleted.jpg", Some(false)),
+            attachment("never_downloaded.jpg", Some(false)),
+        ];
+        let before = HashMap::from([("a".to_string(), old)]);
+        let after = HashMap::from([("a".to_string(), new)]);
+
+        let differences = compare(&before, &after);
+        let missing = &differences["chat"].missing_attachments;
+        assert_eq!(missing.len(), 2);
+        assert!(missing.contains(&(&before["a"], "deleted.jpg", MissingReason::FileMissing)));
+        assert!(missing.contains(&(&before["a"], "removed.jpg", MissingReason::Removed)));
+    }
+
+    #[test]
+    fn can_find_removed_attachments_without_files() {
+        let mut old = summary("chat", "photos");
+        old.attachments = vec![
+            attachment("never_downloaded.jpg", Some(false)),
+            attachment("encrypted.jpg", None),
+        ];
+        let new = summary("chat", "photos");
+        let before = HashMap::from([("a".to_string(), old)]);
+        let after = HashMap::from([("a".to_string(), new)]);
+
+        let differences = compare(&before, &after);
+        let missing = &differences["chat"].missing_attachments;
+        assert_eq!(missing.len(), 2);
+        assert!(missing.contains(&(&before["a"], "never_downloaded.jpg", MissingReason::Removed)));
+        assert!(missing.contains(&(&before["a"], "encrypted.jpg", MissingReason::Removed)));
+    }
+
+    #[test]
+    fn cant_check_attachments_in_encrypted_backups() {
+        let mut old = summary("chat", "photo");
+        old.attachments = vec![attachment("photo.jpg", None)];
+        let mut new = summary("chat", "photo");
+        new.attachments = vec![attachment("photo.jpg", None)];
+        let before = HashMap::from([("a".to_string(), old)]);
+        let after = HashMap::from([("a".to_string(), new)]);
+
+        assert!(compare(&before, &after).is_empty());
+    }
+}
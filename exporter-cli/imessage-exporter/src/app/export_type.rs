@@ -11,6 +11,8 @@ pub enum ExportType {
     Html,
     /// Text file export
     Txt,
+    /// Media-only export, organized into folders by person and month
+    Gallery,
 }
 
 impl ExportType {
@@ -19,6 +21,7 @@ impl ExportType {
         match format.to_lowercase().as_str() {
             "txt" => Some(Self::Txt),
             "html" => Some(Self::Html),
+            "gallery" => Some(Self::Gallery),
             _ => None,
         }
     }
@@ -28,6 +31,8 @@ impl ExportType {
         match self {
             ExportType::Html => ".html",
             ExportType::Txt => ".txt",
+            // Gallery exports use chat names for directories
+            ExportType::Gallery => "",
         }
     }
 }
@@ -37,6 +42,7 @@ impl Display for ExportType {
         match self {
             ExportType::Txt => write!(fmt, "txt"),
             ExportType::Html => write!(fmt, "html"),
+            ExportType::Gallery => write!(fmt, "gallery"),
         }
     }
 }
@@ -68,6 +74,18 @@ mod tests {
         assert!(matches!(ExportType::from_cli("tXt"), Some(ExportType::Txt)));
     }
 
+    #[test]
+    fn can_parse_gallery_any_case() {
+        assert!(matches!(
+            ExportType::from_cli("gallery"),
+            Some(ExportType::Gallery)
+        ));
+        assert!(matches!(
+            ExportType::from_cli("Gallery"),
+            Some(ExportType::Gallery)
+        ));
+    }
+
     #[test]
     fn cant_parse_invalid() {
         assert!(ExportType::from_cli("pdf").is_none());
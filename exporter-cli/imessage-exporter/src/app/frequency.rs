@@ -0,0 +1,757 @@
+/*!
+ Finds the most used words, phrases, emoji, and tapbacks for each conversation and each participant.
+
+ Message text is read after the body is decoded, so it includes messages whose text is only stored
+ in `attributedBody`. Text is split into lowercase words, leaving out links, numbers, and
+ punctuation. Phrases are two and three word sequences that are not split by punctuation, and are
+ only listed if they were used more than once.
+
+ Stopwords are left out of the word lists, and phrases made up only of stopwords are left out of
+ the phrase lists. A built-in list of English stopwords is used unless a file of stopwords is
+ provided, which replaces it.
+
+ Tapbacks are counted for the person who sent them, and removed tapbacks are not counted.
+*/
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    fs::read_to_string,
+    io::Error as IoError,
+    path::Path,
+};
+
+use imessage_database::{
+    message_types::variants::{Tapback, TapbackAction, Variant},
+    tables::{
+        messages::Message,
+        table::{ORPHANED, UNKNOWN},
+    },
+};
+use serde::Serialize;
+
+use crate::{
+    app::{
+        error::RuntimeError,
+        runtime::Config,
+        stats::{Person, StatsFormat, render_table, truncate},
+    },
+    exporters::shared::driver::{for_each_message, progress_bar},
+};
+
+/// Number of entries in each list in JSON output
+const TOP_ITEMS: usize = 25;
+
+/// Number of rows in each table
+const TABLE_ROWS: usize = 10;
+
+/// Phrases used fewer times than this are not listed
+const MIN_PHRASE_USES: u64 = 2;
+
+/// Maximum number of characters of a word or phrase shown in the table
+const ITEM_LENGTH: usize = 24;
+
+/// Common English words that are left out of the word lists unless a stopword file is provided
+const STOPWORDS: &[&str] = &[
+    "a", "about", "after", "again", "all", "also", "am", "an", "and", "any", "are", "aren't", "as",
+    "at", "be", "because", "been", "before", "being", "but", "by", "can", "can't", "could",
+    "couldn't", "did", "didn't", "do", "does", "doesn't", "doing", "don't", "down", "for", "from",
+    "get", "got", "had", "has", "have", "haven't", "having", "he", "he's", "her", "here", "hers",
+    "him", "his", "how", "i", "i'd", "i'll", "i'm", "i've", "if", "in", "into", "is", "isn't",
+    "it", "it's", "its", "just", "let's", "me", "more", "my", "no", "not", "now", "of", "off",
+    "oh", "ok", "okay", "on", "one", "only", "or", "our", "out", "over", "re", "s", "she", "she's",
+    "so", "some", "than", "that", "that's", "the", "their", "them", "then", "there", "there's",
+    "these", "they", "they're", "this", "those", "to", "too", "up", "us", "very", "was", "wasn't",
+    "we", "we're", "were", "what", "what's", "when", "where", "which", "who", "why", "will",
+    "with", "won't", "would", "yeah", "yes", "you", "you'll", "you're", "your",
+];
+
+/// The words, phrases, and emoji in a message's text
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Tokens {
+    /// Every word, including stopwords
+    words: Vec<String>,
+    /// Runs of words that are not split by punctuation
+    phrases: Vec<Vec<String>>,
+    emoji: Vec<String>,
+}
+
+impl Tokens {
+    /// Split message text into words, phrases, and emoji
+    fn new(text: &str) -> Self {
+        let mut tokens = Tokens::default();
+        let mut phrase: Vec<String> = vec![];
+
+        for token in text.split_whitespace() {
+            // Links are not words, and split the phrase around them
+            if token.contains("://") || token.to_lowercase().starts_with("www.") {
+                end_phrase(&mut phrase, &mut tokens.phrases);
+                continue;
+            }
+
+            let mut word = String::new();
+            let mut chars = token.chars().peekable();
+            while let Some(ch) = chars.next() {
+                if ch.is_alphanumeric() || is_apostrophe(ch) {
+                    word.push(ch);
+                    continue;
+                }
+                // Punctuation and emoji split phrases
+                finish_word(&mut word, &mut phrase, &mut tokens.words);
+                if let Some(emoji) = take_emoji(ch, &mut chars) {
+                    tokens.emoji.push(emoji);
+                }
+                end_phrase(&mut phrase, &mut tokens.phrases);
+            }
+            if !finish_word(&mut word, &mut phrase, &mut tokens.words) {
+                end_phrase(&mut phrase, &mut tokens.phrases);
+            }
+        }
+        end_phrase(&mut phrase, &mut tokens.phrases);
+        tokens
+    }
+}
+
+/// Store the current phrase and start a new one
+fn end_phrase(phrase: &mut Vec<String>, phrases: &mut Vec<Vec<String>>) {
+    if !phrase.is_empty() {
+        phrases.push(std::mem::take(phrase));
+    }
+}
+
+/// Add the word being built to the current phrase, returning `false` if it was not a word
+///
+/// Numbers are not words, so they split the phrase around them.
+fn finish_word(word: &mut String, phrase: &mut Vec<String>, words: &mut Vec<String>) -> bool {
+    if word.is_empty() {
+        return true;
+    }
+    let cleaned = word
+        .trim_matches(is_apostrophe)
+        .to_lowercase()
+        .replace('\u{2019}', "'");
+    word.clear();
+
+    if cleaned.chars().any(char::is_alphabetic) {
+        words.push(cleaned.clone());
+        phrase.push(cleaned);
+        true
+    } else {
+        cleaned.is_empty()
+    }
+}
+
+fn is_apostrophe(ch: char) -> bool {
+    matches!(ch, '\'' | '\u{2019}')
+}
+
+// MARK: Emoji
+/// Determine if a character can start an emoji
+///
+/// This covers the pictographic blocks and the older symbols that are drawn as emoji, not every
+/// character with the `Extended_Pictographic` property.
+fn is_pictographic(ch: char) -> bool {
+    matches!(ch as u32,
+        0x00A9 | 0x00AE | 0x203C | 0x2049 | 0x2122 | 0x2139
+        | 0x2194..=0x21AA
+        | 0x231A..=0x23FF
+        | 0x24C2
+        | 0x25AA..=0x25FE
+        | 0x2600..=0x27BF
+        | 0x2934 | 0x2935
+        | 0x2B05..=0x2B55
+        | 0x3030 | 0x303D | 0x3297 | 0x3299
+        | 0x1F000..=0x1FAFF
+    )
+}
+
+/// Determine if a character changes how the emoji before it is drawn
+fn is_emoji_modifier(ch: char) -> bool {
+    matches!(ch as u32,
+        // Variation selectors
+        0xFE0E | 0xFE0F
+        // Skin tones
+        | 0x1F3FB..=0x1F3FF
+        // Keycap
+        | 0x20E3
+        // Tags, used in subdivision flags
+        | 0xE0020..=0xE007F
+    )
+}
+
+fn is_regional_indicator(ch: char) -> bool {
+    matches!(ch as u32, 0x1F1E6..=0x1F1FF)
+}
+
+/// Read the rest of the emoji that starts with `first`, if it starts one
+///
+/// Emoji joined with zero width joiners, modified by skin tones, and flags made of two regional
+/// indicators are each read as a single emoji.
+fn take_emoji(
+    first: char,
+    chars: &mut std::iter::Peekable<impl Iterator<Item = char>>,
+) -> Option<String> {
+    if !is_pictographic(first) {
+        return None;
+    }
+    let mut emoji = String::from(first);
+
+    // Flags are a pair of regional indicators
+    if is_regional_indicator(first) {
+        if let Some(second) = chars.next_if(|ch| is_regional_indicator(*ch)) {
+            emoji.push(second);
+        }
+        return Some(emoji);
+    }
+
+    loop {
+        if let Some(modifier) = chars.next_if(|ch| is_emoji_modifier(*ch)) {
+            emoji.push(modifier);
+        } else if chars.next_if_eq(&'\u{200D}').is_some() {
+            emoji.push('\u{200D}');
+            match chars.next_if(|ch| is_pictographic(*ch)) {
+                Some(joined) => emoji.push(joined),
+                None => {
+                    emoji.pop();
+                    break;
+                }
+            }
+        } else {
+            break;
+        }
+    }
+    // Text presentation selectors mean the character was not drawn as an emoji
+    (!emoji.ends_with('\u{FE0E}')).then_some(emoji)
+}
+
+// MARK: Counts
+/// Running totals for the messages in a chat, or sent by a person
+#[derive(Debug, Default)]
+struct Counts {
+    messages: u64,
+    words: u64,
+    /// Uses of each word that is not a stopword
+    word_uses: HashMap<String, u64>,
+    bigrams: HashMap<String, u64>,
+    trigrams: HashMap<String, u64>,
+    emoji: HashMap<String, u64>,
+    tapbacks: HashMap<String, u64>,
+}
+
+impl Counts {
+    /// Count the words, phrases, and emoji in a message
+    fn add_text(&mut self, tokens: &Tokens, stopwords: &HashSet<String>) {
+        self.messages += 1;
+        self.words += tokens.words.len() as u64;
+        for word in &tokens.words {
+            if !stopwords.contains(word) {
+                *self.word_uses.entry(word.clone()).or_default() += 1;
+            }
+        }
+        for phrase in &tokens.phrases {
+            for (size, uses) in [(2, &mut self.bigrams), (3, &mut self.trigrams)] {
+                for window in phrase.windows(size) {
+                    if window.iter().all(|word| stopwords.contains(word)) {
+                        continue;
+                    }
+                    *uses.entry(window.join(" ")).or_default() += 1;
+                }
+            }
+        }
+        for emoji in &tokens.emoji {
+            *self.emoji.entry(emoji.clone()).or_default() += 1;
+        }
+    }
+
+    fn add_tapback(&mut self, tapback: &str) {
+        *self.tapbacks.entry(tapback.to_string()).or_default() += 1;
+    }
+
+    /// Build the lists of the most used items
+    fn summarize(&self) -> Frequencies {
+        Frequencies {
+            messages: self.messages,
+            words: self.words,
+            top_words: top(&self.word_uses, 1),
+            top_bigrams: top(&self.bigrams, MIN_PHRASE_USES),
+            top_trigrams: top(&self.trigrams, MIN_PHRASE_USES),
+            top_emoji: top(&self.emoji, 1),
+            top_tapbacks: top(&self.tapbacks, 1),
+        }
+    }
+}
+
+/// The most used entries in `uses` that were used at least `min_uses` times, most used first
+fn top(uses: &HashMap<String, u64>, min_uses: u64) -> Vec<Ranked> {
+    let mut ranked: Vec<(&String, &u64)> = uses
+        .iter()
+        .filter(|(_, count)| **count >= min_uses)
+        .collect();
+    ranked.sort_by(|(left, left_count), (right, right_count)| {
+        right_count.cmp(left_count).then_with(|| left.cmp(right))
+    });
+    ranked
+        .into_iter()
+        .take(TOP_ITEMS)
+        .map(|(text, count)| Ranked {
+            text: text.clone(),
+            count: *count,
+        })
+        .collect()
+}
+
+/// The messages sent in a chat
+#[derive(Debug)]
+struct ChatCounts {
+    name: String,
+    total: Counts,
+    /// The messages each person sent in the chat
+    people: HashMap<Person, Counts>,
+}
+
+/// The messages a person sent
+#[derive(Debug)]
+struct PersonCounts {
+    name: String,
+    total: Counts,
+}
+
+// MARK: Output
+#[derive(Debug, Serialize)]
+struct Ranked {
+    text: String,
+    count: u64,
+}
+
+/// The most used items in the messages of a chat, or sent by a person
+#[derive(Debug, Serialize)]
+struct Frequencies {
+    /// Number of messages with text
+    messages: u64,
+    /// Number of words, including stopwords
+    words: u64,
+    top_words: Vec<Ranked>,
+    top_bigrams: Vec<Ranked>,
+    top_trigrams: Vec<Ranked>,
+    top_emoji: Vec<Ranked>,
+    top_tapbacks: Vec<Ranked>,
+}
+
+#[derive(Debug, Serialize)]
+struct NamedFrequencies<'a> {
+    name: &'a str,
+    #[serde(flatten)]
+    frequencies: Frequencies,
+    /// The frequencies for each person in a chat
+    #[serde(skip_serializing_if = "Option::is_none")]
+    people: Option<Vec<NamedFrequencies<'a>>>,
+}
+
+#[derive(Debug, Serialize)]
+struct Report<'a> {
+    everyone: Frequencies,
+    people: Vec<NamedFrequencies<'a>>,
+    chats: Vec<NamedFrequencies<'a>>,
+}
+
+pub struct Frequency<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Words left out of the word lists
+    stopwords: HashSet<String>,
+    /// Every message in the selected chats
+    everyone: Counts,
+    /// The messages in each chat, keyed by deduplicated chat ID
+    chats: HashMap<Option<i32>, ChatCounts>,
+    /// The messages each person sent across every chat
+    people: HashMap<Person, PersonCounts>,
+}
+
+impl<'a> Frequency<'a> {
+    pub fn new(config: &'a Config) -> Self {
+        Frequency {
+            config,
+            stopwords: STOPWORDS.iter().map(ToString::to_string).collect(),
+            everyone: Counts::default(),
+            chats: HashMap::new(),
+            people: HashMap::new(),
+        }
+    }
+
+    /// Count the words in every message selected by the query filters, then print the results in `format`
+    pub fn run(&mut self, format: StatsFormat) -> Result<(), RuntimeError> {
+        let config = self.config;
+
+        if let Some(path) = &config.options.stopwords {
+            self.stopwords = read_stopwords(path)?;
+        }
+
+        eprintln!("Counting words...");
+        let pb = progress_bar(config);
+        // Words are read from the message body, so it is always decoded
+        for_each_message(config, &pb, true, |msg| {
+            if !msg.is_announcement() {
+                self.add(&msg);
+            }
+            Ok(())
+        })?;
+
+        let report = self.report();
+        match format {
+            StatsFormat::Table => print!("{}", render_tables(&report)),
+            StatsFormat::Json => println!(
+                "{}",
+                serde_json::to_string_pretty(&report)
+                    .map_err(|why| RuntimeError::DiskError(IoError::other(why)))?
+            ),
+        }
+        Ok(())
+    }
+
+    /// Count a message toward everyone, its chat, its sender, and its sender in that chat
+    fn add(&mut self, message: &Message) {
+        let config = self.config;
+        let person = Person::of(config, message);
+        let chat_id = config.conversation(message).map(|(_, id)| *id);
+
+        let chat = self.chats.entry(chat_id).or_insert_with(|| ChatCounts {
+            name: config
+                .chat_name(message)
+                .unwrap_or_else(|| ORPHANED.to_string()),
+            total: Counts::default(),
+            people: HashMap::new(),
+        });
+        let sender = self.people.entry(person).or_insert_with(|| PersonCounts {
+            name: person.name(config, message),
+            total: Counts::default(),
+        });
+        let counts = [
+            &mut self.everyone,
+            &mut chat.total,
+            chat.people.entry(person).or_default(),
+            &mut sender.total,
+        ];
+
+        if message.is_tapback() {
+            if let Variant::Tapback(_, TapbackAction::Added, tapback) = message.variant()
+                && !matches!(tapback, Tapback::Emoji(None))
+            {
+                let tapback = tapback.to_string();
+                for count in counts {
+                    count.add_tapback(&tapback);
+                }
+            }
+        } else if let Some(text) = &message.text {
+            let tokens = Tokens::new(text);
+            for count in counts {
+                count.add_text(&tokens, &self.stopwords);
+            }
+        }
+    }
+
+    /// Build the lists for everyone, each person, and each chat, most active first
+    fn report(&self) -> Report<'_> {
+        let name_of = |person: &Person| {
+            self.people
+                .get(person)
+                .map_or(UNKNOWN, |counts| counts.name.as_str())
+        };
+
+        let mut people: Vec<NamedFrequencies> = self
+            .people
+            .values()
+            .map(|person| NamedFrequencies {
+                name: &person.name,
+                frequencies: person.total.summarize(),
+                people: None,
+            })
+            .collect();
+        sort_by_messages(&mut people);
+
+        let mut chats: Vec<NamedFrequencies> = self
+            .chats
+            .values()
+            .map(|chat| {
+                let mut people: Vec<NamedFrequencies> = chat
+                    .people
+                    .iter()
+                    .map(|(person, counts)| NamedFrequencies {
+                        name: name_of(person),
+                        frequencies: counts.summarize(),
+                        people: None,
+                    })
+                    .collect();
+                sort_by_messages(&mut people);
+                NamedFrequencies {
+                    name: &chat.name,
+                    frequencies: chat.total.summarize(),
+                    people: Some(people),
+                }
+            })
+            .collect();
+        sort_by_messages(&mut chats);
+
+        Report {
+            everyone: self.everyone.summarize(),
+            people,
+            chats,
+        }
+    }
+}
+
+/// Read a file of stopwords, separated by whitespace, skipping lines that start with `#`
+fn read_stopwords(path: &Path) -> Result<HashSet<String>, RuntimeError> {
+    Ok(parse_stopwords(&read_to_string(path)?))
+}
+
+fn parse_stopwords(contents: &str) -> HashSet<String> {
+    contents
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .flat_map(str::split_whitespace)
+        .map(|word| word.to_lowercase().replace('\u{2019}', "'"))
+        .collect()
+}
+
+/// Sort with the most messages first
+fn sort_by_messages(entries: &mut [NamedFrequencies]) {
+    entries.sort_by(|left, right| {
+        right
+            .frequencies
+            .messages
+            .cmp(&left.frequencies.messages)
+            .then_with(|| left.name.cmp(right.name))
+    });
+}
+
+// MARK: Tables
+/// Render a table of the most used items for everyone, then each person, then each chat
+fn render_tables(report: &Report) -> String {
+    let mut out = String::new();
+    render_section(&mut out, "Everyone", &report.everyone);
+    for person in &report.people {
+        render_section(&mut out, person.name, &person.frequencies);
+    }
+    for chat in &report.chats {
+        render_section(&mut out, chat.name, &chat.frequencies);
+    }
+    out
+}
+
+/// Render the most used items side by side, one rank per row
+fn render_section(out: &mut String, name: &str, frequencies: &Frequencies) {
+    let _ = writeln!(
+        out,
+        "{name}: {} messages, {} words",
+        frequencies.messages, frequencies.words
+    );
+
+    let lists = [
+        &frequencies.top_words,
+        &frequencies.top_bigrams,
+        &frequencies.top_trigrams,
+        &frequencies.top_emoji,
+        &frequencies.top_tapbacks,
+    ];
+    let rows: Vec<Vec<String>> = (0..TABLE_ROWS)
+        .take_while(|rank| lists.iter().any(|list| list.len() > *rank))
+        .map(|rank| {
+            let mut row = vec![(rank + 1).to_string()];
+            for list in lists {
+                match list.get(rank) {
+                    Some(item) => {
+                        row.push(truncate(&item.text, ITEM_LENGTH));
+                        row.push(item.count.to_string());
+                    }
+                    None => row.extend([String::new(), String::new()]),
+                }
+            }
+            row
+        })
+        .collect();
+
+    out.push_str(&render_table(
+        &[
+            "#",
+            "Word",
+            "Uses",
+            "Two Words",
+            "Uses",
+            "Three Words",
+            "Uses",
+            "Emoji",
+            "Uses",
+            "Tapback",
+            "Uses",
+        ],
+        &rows,
+    ));
+    out.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use crate::app::frequency::{
+        Counts, STOPWORDS, Tokens, parse_stopwords, render_section, take_emoji, top,
+    };
+
+    fn stopwords() -> HashSet<String> {
+        STOPWORDS.iter().map(ToString::to_string).collect()
+    }
+
+    fn emoji(text: &str) -> Vec<String> {
+        Tokens::new(text).emoji
+    }
+
+    #[test]
+    fn can_split_words() {
+        let tokens = Tokens::new("Don’t forget: it's Jane's 30th birthday!");
+        assert_eq!(
+            tokens.words,
+            vec!["don't", "forget", "it's", "jane's", "30th", "birthday"]
+        );
+        assert_eq!(
+            tokens.phrases,
+            vec![
+                vec!["don't", "forget"],
+                vec!["it's", "jane's", "30th", "birthday"]
+            ]
+        );
+    }
+
+    #[test]
+    fn can_split_words_without_links_or_numbers() {
+        let tokens = Tokens::new("see https://example.com/a-b at 5 pm \u{FFFC}");
+        assert_eq!(tokens.words, vec!["see", "at", "pm"]);
+        assert_eq!(tokens.phrases, vec![vec!["see"], vec!["at"], vec!["pm"]]);
+    }
+
+    #[test]
+    fn can_find_emoji() {
+        assert_eq!(emoji("lol😂😂"), vec!["😂", "😂"]);
+        assert_eq!(emoji("nice 👍🏽!"), vec!["👍🏽"]);
+        assert_eq!(emoji("❤️ you"), vec!["❤\u{FE0F}"]);
+    }
+
+    #[test]
+    fn can_find_joined_emoji() {
+        assert_eq!(emoji("👩‍👩‍👧 family"), vec!["👩\u{200D}👩\u{200D}👧"]);
+    }
+
+    #[test]
+    fn can_find_flag_emoji() {
+        assert_eq!(emoji("🇺🇸🇯🇵"), vec!["🇺🇸", "🇯🇵"]);
+    }
+
+    #[test]
+    fn cant_find_emoji_in_text_presentation() {
+        assert!(emoji("©\u{FE0E} 2023").is_empty());
+        let mut chars = "b".chars().peekable();
+        assert_eq!(take_emoji('a', &mut chars), None);
+    }
+
+    #[test]
+    fn can_count_text() {
+        let stopwords = stopwords();
+        let mut counts = Counts::default();
+        counts.add_text(&Tokens::new("See you at the beach!"), &stopwords);
+        counts.add_text(
+            &Tokens::new("The beach was great, see you at the beach soon 🏖️"),
+            &stopwords,
+        );
+
+        let frequencies = counts.summarize();
+        assert_eq!(frequencies.messages, 2);
+        assert_eq!(frequencies.words, 15);
+        assert_eq!(frequencies.top_words[0].text, "beach");
+        assert_eq!(frequencies.top_words[0].count, 3);
+        assert!(frequencies.top_words.iter().all(|word| word.text != "the"));
+        // Only phrases used more than once are listed
+        assert_eq!(frequencies.top_bigrams.len(), 2);
+        assert_eq!(frequencies.top_bigrams[0].text, "the beach");
+        assert_eq!(frequencies.top_bigrams[1].text, "see you");
+        assert_eq!(frequencies.top_trigrams.len(), 2);
+        assert_eq!(frequencies.top_trigrams[0].text, "at the beach");
+        assert_eq!(frequencies.top_emoji[0].text, "🏖\u{FE0F}");
+    }
+
+    #[test]
+    fn cant_count_stopword_phrases() {
+        let mut counts = Counts::default();
+        for _ in 0..2 {
+            counts.add_text(&Tokens::new("it is what it is"), &stopwords());
+        }
+        let frequencies = counts.summarize();
+        assert!(frequencies.top_words.is_empty());
+        assert!(frequencies.top_bigrams.is_empty());
+        assert!(frequencies.top_trigrams.is_empty());
+    }
+
+    #[test]
+    fn can_count_tapbacks() {
+        let mut counts = Counts::default();
+        counts.add_tapback("Loved");
+        counts.add_tapback("Laughed");
+        counts.add_tapback("Loved");
+
+        let frequencies = counts.summarize();
+        assert_eq!(frequencies.messages, 0);
+        assert_eq!(frequencies.top_tapbacks[0].text, "Loved");
+        assert_eq!(frequencies.top_tapbacks[0].count, 2);
+    }
+
+    #[test]
+    fn can_rank_ties_alphabetically() {
+        let uses = HashMap::from([
+            ("b".to_string(), 2),
+            ("a".to_string(), 2),
+            ("c".to_string(), 5),
+            ("d".to_string(), 1),
+        ]);
+        let ranked = top(&uses, 2);
+        let texts: Vec<&str> = ranked.iter().map(|item| item.text.as_str()).collect();
+        assert_eq!(texts, vec!["c", "a", "b"]);
+    }
+
+    #[test]
+    fn can_parse_stopwords() {
+        let parsed = parse_stopwords("# Spanish\nel la\nLOS\n  # indented comment\nqué");
+        assert_eq!(
+            parsed,
+            HashSet::from([
+                "el".to_string(),
+                "la".to_string(),
+                "los".to_string(),
+                "qué".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn can_render_section() {
+        let mut counts = Counts::default();
+        counts.add_text(&Tokens::new("pizza tonight? pizza tonight!"), &stopwords());
+        counts.add_tapback("Liked");
+
+        let mut out = String::new();
+        render_section(&mut out, "Jane", &counts.summarize());
+        let mut lines = out.lines();
+        assert_eq!(lines.next(), Some("Jane: 1 messages, 4 words"));
+        assert!(
+            lines
+                .next()
+                .unwrap()
+                .starts_with("#  Word     Uses  Two Words      Uses")
+        );
+        lines.next();
+        assert_eq!(
+            lines.next(),
+            Some(
+                "1  pizza       2  pizza tonight     2                                  Liked       1"
+            )
+        );
+        assert_eq!(lines.next(), Some("2  tonight     2"));
+    }
+}
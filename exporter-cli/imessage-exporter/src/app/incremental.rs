@@ -0,0 +1,543 @@
+/*!
+ Contains the state file used to append new messages to an existing export and to resume interrupted ones.
+
+ After every export, the exporter records the newest message it wrote for each chat in
+ [`STATE_FILENAME`] inside the export directory. A later `--incremental` run loads that file,
+ skips every message at or below the recorded `ROWID`, and appends the rest to the existing
+ chat files. Attachments are only copied for the messages that are written, so existing files
+ in the attachments directory are left alone.
+
+ While an export runs, the exporter periodically saves a [`Checkpoint`] to the same file with the
+ position reached in the message stream and the length of every file written so far. Files are
+ recorded in [`JOURNAL_FILENAME`] with their original length the first time they are opened, so a
+ `--resume` run can roll every file back to the last checkpoint before continuing.
+*/
+
+use std::{
+    collections::BTreeMap,
+    fs::{File, OpenOptions, read_to_string, remove_file, rename, write},
+    io::{Error as IoError, Write},
+    path::{Path, PathBuf},
+};
+
+use imessage_database::tables::messages::Message;
+use serde::{Deserialize, Serialize};
+
+use crate::app::{error::RuntimeError, options::Options};
+
+/// Name of the state file written into the export directory
+pub const STATE_FILENAME: &str = ".imessage-export-state.json";
+
+/// Name of the journal of files opened by the current export
+pub const JOURNAL_FILENAME: &str = ".imessage-export-journal";
+
+/// The version of the exporter that wrote the state file
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The newest message written for a single chat
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatCheckpoint {
+    /// The highest message `ROWID` written for the chat
+    pub last_rowid: i32,
+    /// The date of the newest message written for the chat
+    pub last_date: i64,
+    /// The number of messages written for the chat
+    #[serde(default)]
+    pub messages: u64,
+}
+
+impl ChatCheckpoint {
+    /// Advance the checkpoint to include `message`
+    fn record(&mut self, message: &Message) {
+        self.last_rowid = self.last_rowid.max(message.rowid);
+        self.last_date = self.last_date.max(message.date);
+        self.messages += 1;
+    }
+
+    /// Advance the checkpoint to include another checkpoint
+    fn merge(&mut self, other: &ChatCheckpoint) {
+        self.last_rowid = self.last_rowid.max(other.last_rowid);
+        self.last_date = self.last_date.max(other.last_date);
+        self.messages += other.messages;
+    }
+}
+
+/// Progress of an export that has not finished yet
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Checkpoint {
+    /// The number of rows consumed from the message stream
+    pub position: u64,
+    /// The `ROWID` of the last row consumed from the message stream
+    pub last_rowid: i32,
+    /// The length of each file written by the export, keyed by file name
+    pub files: BTreeMap<String, u64>,
+    /// The newest message written for each chat during the export
+    pub chats: BTreeMap<i32, ChatCheckpoint>,
+    /// The newest message written during the export that does not belong to any chat
+    pub orphaned: Option<ChatCheckpoint>,
+}
+
+/// The options that affect how messages are rendered, recorded so later runs can detect changes
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordedOptions {
+    /// The export file format
+    pub format: String,
+    /// The attachment copy method
+    pub copy_method: String,
+    /// The source database platform
+    pub platform: String,
+    /// The source database path
+    pub db_path: String,
+    /// The custom name used for the database owner, if any
+    pub custom_name: Option<String>,
+    /// Whether the database owner's caller ID was used
+    pub use_caller_id: bool,
+    /// Whether only image attachments were included
+    pub images_only: bool,
+    /// Whether lazy loading was disabled for HTML images
+    pub no_lazy: bool,
+    /// The message filters, which determine the rows in the message stream
+    #[serde(default)]
+    pub filters: String,
+    /// Whether attachments were stored by content hash
+    #[serde(default)]
+    pub dedupe_attachments: bool,
+    /// The format of HTML image thumbnails, if they were generated
+    #[serde(default)]
+    pub thumbnails: Option<String>,
+    /// Whether message context was written into copied media
+    #[serde(default)]
+    pub embed_metadata: bool,
+    /// The template used to name copied attachments, if any
+    #[serde(default)]
+    pub attachment_name: Option<String>,
+    /// The rules for which attachments were exported
+    #[serde(default)]
+    pub attachment_policy: String,
+}
+
+impl RecordedOptions {
+    /// Capture the rendering-relevant parts of the current [`Options`]
+    pub fn from_options(options: &Options) -> Self {
+        Self {
+            format: options
+                .export_type
+                .as_ref()
+                .map(ToString::to_string)
+                .unwrap_or_default(),
+            copy_method: options.attachment_manager.mode.to_string(),
+            platform: options.platform.to_string(),
+            db_path: options.db_path.display().to_string(),
+            custom_name: options.custom_name.clone(),
+            use_caller_id: options.use_caller_id,
+            images_only: options.images_only,
+            no_lazy: options.no_lazy,
+            filters: format!("{:?}", options.query_context),
+            dedupe_attachments: options.dedupe_attachments,
+            thumbnails: options.thumbnails.as_ref().map(ToString::to_string),
+            embed_metadata: options.embed_metadata,
+            attachment_name: options
+                .attachment_manager
+                .name_template
+                .as_ref()
+                .map(ToString::to_string),
+            attachment_policy: format!("{:?}", options.attachment_manager.policy),
+        }
+    }
+}
+
+/// Progress of an export directory, persisted between runs in [`STATE_FILENAME`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IncrementalState {
+    /// The version of the exporter that last wrote to the export directory
+    pub version: String,
+    /// The options used for the last run
+    pub options: RecordedOptions,
+    /// The newest message written for each chat by finished exports, keyed by chat `ROWID`
+    pub chats: BTreeMap<i32, ChatCheckpoint>,
+    /// The newest message written by finished exports that does not belong to any chat
+    pub orphaned: Option<ChatCheckpoint>,
+    /// Progress of the current export, if it has not finished yet
+    #[serde(default)]
+    pub checkpoint: Option<Checkpoint>,
+}
+
+impl IncrementalState {
+    /// Create an empty state for a fresh export
+    pub fn new(options: &Options) -> Self {
+        Self {
+            version: VERSION.to_string(),
+            options: RecordedOptions::from_options(options),
+            chats: BTreeMap::new(),
+            orphaned: None,
+            checkpoint: None,
+        }
+    }
+
+    /// Get the path to the state file in an export directory
+    pub fn path(export_path: &Path) -> PathBuf {
+        export_path.join(STATE_FILENAME)
+    }
+
+    /// Load the state for the export directory in `options`, or create an empty one if none exists
+    ///
+    /// Fails if the recorded export format differs from the current one, since the
+    /// new messages could not be appended to the existing files. When resuming, the
+    /// state must contain a [`Checkpoint`] and the options must be unchanged; otherwise,
+    /// it must not contain one, since the interrupted export left partial files behind.
+    pub fn load(options: &Options) -> Result<Self, RuntimeError> {
+        let path = Self::path(&options.export_path);
+        if !path.exists() {
+            if options.resume {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "Export path {} does not contain an export to resume!",
+                    options.export_path.display()
+                )));
+            }
+            return Ok(Self::new(options));
+        }
+
+        let contents = read_to_string(&path).map_err(RuntimeError::DiskError)?;
+        let mut state: Self = serde_json::from_str(&contents).map_err(|why| {
+            RuntimeError::InvalidOptions(format!(
+                "Unable to read export state file {}: {why}",
+                path.display()
+            ))
+        })?;
+
+        let current = RecordedOptions::from_options(options);
+        if state.options.format != current.format {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Export path {} contains a \"{}\" export; it cannot be continued as \"{}\"",
+                options.export_path.display(),
+                state.options.format,
+                current.format
+            )));
+        }
+
+        match (options.resume, state.checkpoint.is_some()) {
+            (true, false) => {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "The export in {} already finished; there is nothing to resume!",
+                    options.export_path.display()
+                )));
+            }
+            (false, true) => {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "The export in {} was interrupted; pass --resume to finish it first!",
+                    options.export_path.display()
+                )));
+            }
+            (true, true) if state.options != current => {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "Options differ from the interrupted export in {}; it must be resumed with the same options!",
+                    options.export_path.display()
+                )));
+            }
+            _ => {}
+        }
+
+        if state.options != current {
+            eprintln!(
+                "Options differ from the previous export in {}; new messages may render differently.",
+                options.export_path.display()
+            );
+        }
+        if state.version != VERSION {
+            eprintln!(
+                "Previous export was written by version {}; continuing with version {VERSION}.",
+                state.version
+            );
+        }
+
+        state.version = VERSION.to_string();
+        state.options = current;
+        Ok(state)
+    }
+
+    /// Write the state file into `export_path`
+    ///
+    /// The file is written to a temporary path and renamed into place so an interrupted
+    /// write never leaves a truncated state file behind.
+    pub fn save(&self, export_path: &Path) -> Result<(), RuntimeError> {
+        let path = Self::path(export_path);
+        let temp_path = path.with_extension("json.tmp");
+        let contents = serde_json::to_string_pretty(self)
+            .map_err(|why| RuntimeError::DiskError(IoError::other(why)))?;
+        write(&temp_path, contents)?;
+        rename(&temp_path, &path)?;
+        Ok(())
+    }
+
+    /// Determine if `message` was already written by a previous, finished run
+    pub fn is_exported(&self, message: &Message) -> bool {
+        let checkpoint = match message.chat_id.or(message.deleted_from) {
+            Some(chat_id) => self.chats.get(&chat_id),
+            None => self.orphaned.as_ref(),
+        };
+        checkpoint.is_some_and(|checkpoint| message.rowid <= checkpoint.last_rowid)
+    }
+
+    /// Record that `message` was written in the current run
+    pub fn record(&mut self, message: &Message) {
+        let current = self.checkpoint.get_or_insert_with(Checkpoint::default);
+        let checkpoint = match message.chat_id.or(message.deleted_from) {
+            Some(chat_id) => current.chats.entry(chat_id).or_default(),
+            None => current.orphaned.get_or_insert_with(ChatCheckpoint::default),
+        };
+        checkpoint.record(message);
+    }
+
+    /// Record the position reached in the message stream and the current length of each written file
+    pub fn set_checkpoint(&mut self, position: u64, last_rowid: i32, files: BTreeMap<String, u64>) {
+        let checkpoint = self.checkpoint.get_or_insert_with(Checkpoint::default);
+        checkpoint.position = position;
+        checkpoint.last_rowid = last_rowid;
+        checkpoint.files = files;
+    }
+
+    /// Get the number of rows to skip and the `ROWID` of the last skipped row when resuming
+    pub fn resume_position(&self) -> Option<(u64, i32)> {
+        self.checkpoint
+            .as_ref()
+            .filter(|checkpoint| checkpoint.position > 0)
+            .map(|checkpoint| (checkpoint.position, checkpoint.last_rowid))
+    }
+
+    /// Mark the current run as finished, merging its progress into the per-chat state
+    pub fn finish(&mut self) {
+        if let Some(checkpoint) = self.checkpoint.take() {
+            for (chat_id, chat) in &checkpoint.chats {
+                self.chats.entry(*chat_id).or_default().merge(chat);
+            }
+            if let Some(orphaned) = &checkpoint.orphaned {
+                self.orphaned
+                    .get_or_insert_with(ChatCheckpoint::default)
+                    .merge(orphaned);
+            }
+        }
+    }
+
+    /// Roll every file written by the interrupted run back to its length at the last checkpoint
+    ///
+    /// Files first opened after the checkpoint are restored to the length recorded in the journal,
+    /// and files the interrupted run created are removed so their headers are written again.
+    pub fn restore_files(&self, export_path: &Path) -> Result<(), RuntimeError> {
+        let Some(checkpoint) = &self.checkpoint else {
+            return Ok(());
+        };
+
+        let mut lengths = read_journal(export_path)?;
+        lengths.extend(
+            checkpoint
+                .files
+                .iter()
+                .map(|(name, length)| (name.clone(), *length)),
+        );
+
+        for (name, length) in lengths {
+            let path = export_path.join(&name);
+            if !path.exists() {
+                continue;
+            }
+            if length == 0 {
+                remove_file(&path)?;
+            } else {
+                OpenOptions::new()
+                    .write(true)
+                    .open(&path)?
+                    .set_len(length)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// MARK: Journal
+/// Record the length of a file the first time the current export opens it
+pub fn append_journal(export_path: &Path, name: &str, length: u64) -> Result<(), RuntimeError> {
+    let mut journal = File::options()
+        .append(true)
+        .create(true)
+        .open(export_path.join(JOURNAL_FILENAME))?;
+    writeln!(journal, "{length}\t{name}")?;
+    journal.flush()?;
+    Ok(())
+}
+
+/// Read the original length of every file opened by the current export, keyed by file name
+fn read_journal(export_path: &Path) -> Result<BTreeMap<String, u64>, RuntimeError> {
+    let path = export_path.join(JOURNAL_FILENAME);
+    let mut lengths = BTreeMap::new();
+    if !path.exists() {
+        return Ok(lengths);
+    }
+
+    for line in read_to_string(path)?.lines() {
+        if let Some((length, name)) = line.split_once('\t')
+            && let Ok(length) = length.parse::<u64>()
+        {
+            // Keep the earliest entry, since a resumed run journals files again when it reopens them
+            lengths.entry(name.to_string()).or_insert(length);
+        }
+    }
+    Ok(lengths)
+}
+
+/// Remove the journal once the export finishes
+pub fn clear_journal(export_path: &Path) -> Result<(), RuntimeError> {
+    let path = export_path.join(JOURNAL_FILENAME);
+    if path.exists() {
+        remove_file(path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeMap,
+        fs::{read_to_string, write},
+    };
+
+    use imessage_database::tables::messages::Message;
+
+    use crate::{
+        Config,
+        app::{
+            export_type::ExportType,
+            incremental::{IncrementalState, STATE_FILENAME, append_journal},
+            options::Options,
+        },
+    };
+
+    fn message(rowid: i32, chat_id: Option<i32>, date: i64) -> Message {
+        let mut message = Config::fake_message();
+        message.rowid = rowid;
+        message.chat_id = chat_id;
+        message.date = date;
+        message
+    }
+
+    #[test]
+    fn can_record_and_skip_messages() {
+        let options = Options::fake_options(ExportType::Txt);
+        let mut state = IncrementalState::new(&options);
+
+        state.record(&message(10, Some(1), 100));
+        state.record(&message(5, None, 50));
+
+        // Messages only count as exported once the run finishes
+        assert!(!state.is_exported(&message(10, Some(1), 100)));
+        state.finish();
+
+        assert!(state.is_exported(&message(10, Some(1), 100)));
+        assert!(state.is_exported(&message(9, Some(1), 90)));
+        assert!(!state.is_exported(&message(11, Some(1), 110)));
+        assert!(!state.is_exported(&message(3, Some(2), 30)));
+        assert!(state.is_exported(&message(5, None, 50)));
+        assert!(!state.is_exported(&message(6, None, 60)));
+        assert_eq!(state.chats.get(&1).unwrap().last_date, 100);
+        assert_eq!(state.chats.get(&1).unwrap().messages, 1);
+        assert!(state.checkpoint.is_none());
+    }
+
+    #[test]
+    fn can_save_and_load() {
+        let options = Options::fake_options(ExportType::Txt);
+        let mut state = IncrementalState::new(&options);
+        state.record(&message(42, Some(3), 1000));
+        state.finish();
+        state.save(&options.export_path).unwrap();
+
+        assert!(options.export_path.join(STATE_FILENAME).exists());
+        let loaded = IncrementalState::load(&options).unwrap();
+        assert_eq!(loaded, state);
+    }
+
+    #[test]
+    fn cant_load_different_format() {
+        let options = Options::fake_options(ExportType::Txt);
+        IncrementalState::new(&options)
+            .save(&options.export_path)
+            .unwrap();
+
+        let mut html_options = Options::fake_options(ExportType::Html);
+        html_options.export_path = options.export_path;
+        assert!(IncrementalState::load(&html_options).is_err());
+    }
+
+    #[test]
+    fn can_load_missing() {
+        let options = Options::fake_options(ExportType::Html);
+        let state = IncrementalState::load(&options).unwrap();
+        assert!(state.chats.is_empty());
+        assert!(state.orphaned.is_none());
+    }
+
+    #[test]
+    fn cant_resume_missing() {
+        let mut options = Options::fake_options(ExportType::Html);
+        options.resume = true;
+        assert!(IncrementalState::load(&options).is_err());
+    }
+
+    #[test]
+    fn cant_resume_finished() {
+        let mut options = Options::fake_options(ExportType::Txt);
+        IncrementalState::new(&options)
+            .save(&options.export_path)
+            .unwrap();
+
+        options.resume = true;
+        assert!(IncrementalState::load(&options).is_err());
+    }
+
+    #[test]
+    fn cant_continue_interrupted_without_resume() {
+        let mut options = Options::fake_options(ExportType::Txt);
+        let mut state = IncrementalState::new(&options);
+        state.record(&message(1, Some(1), 10));
+        state.set_checkpoint(1, 1, BTreeMap::new());
+        state.save(&options.export_path).unwrap();
+
+        assert!(IncrementalState::load(&options).is_err());
+
+        options.resume = true;
+        let loaded = IncrementalState::load(&options).unwrap();
+        assert_eq!(loaded.resume_position(), Some((1, 1)));
+    }
+
+    #[test]
+    fn can_restore_files() {
+        let options = Options::fake_options(ExportType::Txt);
+        let export_path = &options.export_path;
+
+        // Existing file, opened before the checkpoint
+        write(export_path.join("a.txt"), "old\nnew\nlost\n").unwrap();
+        append_journal(export_path, "a.txt", 4).unwrap();
+        // Existing file, first opened after the checkpoint
+        write(export_path.join("b.txt"), "old\nlost\n").unwrap();
+        append_journal(export_path, "b.txt", 4).unwrap();
+        // New file, first opened after the checkpoint
+        write(export_path.join("c.txt"), "lost\n").unwrap();
+        append_journal(export_path, "c.txt", 0).unwrap();
+        // Reopened by a previous resume; the original length wins
+        append_journal(export_path, "b.txt", 9).unwrap();
+
+        let mut state = IncrementalState::new(&options);
+        state.set_checkpoint(2, 7, BTreeMap::from([(String::from("a.txt"), 8)]));
+        state.restore_files(export_path).unwrap();
+
+        assert_eq!(
+            read_to_string(export_path.join("a.txt")).unwrap(),
+            "old\nnew\n"
+        );
+        assert_eq!(read_to_string(export_path.join("b.txt")).unwrap(), "old\n");
+        assert!(!export_path.join("c.txt").exists());
+    }
+}
@@ -0,0 +1,717 @@
+/*!
+ Measures how quickly messages are delivered, read, and answered in each conversation and each month.
+
+ Delivery times are measured for messages we sent, from the date sent to `date_delivered`. Read
+ times are measured from the date sent to `date_read`, for messages we sent that were read by
+ someone who shares read receipts, and for messages we received and read. Not every message is
+ tagged with these dates: when several messages are sent before any are read, only the most recent
+ one is tagged.
+
+ A reply is a message sent after a message from the other side of the chat, where our messages are
+ one side and every other participant is the other. It is measured from the most recent message
+ of the other side, and replies sent more than a day later are not counted. Unlike `--stats`, a
+ message between two other participants of a group chat is not a reply.
+
+ A silence is the time from the first message that went unanswered to the reply that answered it.
+ Silences are flagged when they last at least an hour and more than ten times the usual reply time
+ of the side that was expected to reply in that chat, or more than a day if that side never replied
+ within a day.
+*/
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+    io::Error as IoError,
+};
+
+use chrono::{DateTime, Datelike, Local};
+use imessage_database::tables::{messages::Message, table::ORPHANED};
+use serde::Serialize;
+
+use crate::{
+    app::{
+        error::RuntimeError,
+        runtime::Config,
+        stats::{
+            Person, REPLY_WINDOW, StatsFormat, format_seconds, median, render_table, truncate,
+        },
+    },
+    exporters::shared::driver::{for_each_message, progress_bar},
+};
+
+/// Silences shorter than this many seconds are never flagged
+const MIN_SILENCE: i64 = 60 * 60;
+
+/// Silences are flagged when they last more than this many times the usual reply time
+const SILENCE_FACTOR: f64 = 10.;
+
+/// Percentile of each set of times reported alongside the median
+const PERCENTILE: usize = 90;
+
+/// Number of silences listed in the table
+const TABLE_SILENCES: usize = 25;
+
+/// Maximum number of characters of a name shown in the table
+const NAME_LENGTH: usize = 32;
+
+/// A year and month, as in `(2023, 1)`
+type Month = (i32, u32);
+
+/// Which side of a conversation sent a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Side {
+    /// The owner of the database
+    Me,
+    /// Every other participant
+    Them,
+}
+
+impl Side {
+    fn of(person: Person) -> Self {
+        match person {
+            Person::Me => Side::Me,
+            _ => Side::Them,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Side::Me => "me",
+            Side::Them => "them",
+        }
+    }
+}
+
+/// Seconds measured for each message in a chat or month
+#[derive(Debug, Default)]
+struct Samples {
+    messages: u64,
+    /// Time until messages we sent were delivered
+    delivered: Vec<i64>,
+    /// Time until messages we sent were read by the recipient
+    read_by_them: Vec<i64>,
+    /// Time until we read messages we received
+    read_by_me: Vec<i64>,
+    /// Time we took to reply to the other side
+    my_replies: Vec<i64>,
+    /// Time the other side took to reply to us
+    their_replies: Vec<i64>,
+}
+
+impl Samples {
+    fn add(&mut self, message: &Measured) {
+        self.messages += 1;
+        if let Some(seconds) = message.delivered {
+            self.delivered.push(seconds);
+        }
+        if let Some(seconds) = message.read {
+            match message.side {
+                Side::Me => self.read_by_them.push(seconds),
+                Side::Them => self.read_by_me.push(seconds),
+            }
+        }
+        if let Some(seconds) = message.reply {
+            match message.side {
+                Side::Me => self.my_replies.push(seconds),
+                Side::Them => self.their_replies.push(seconds),
+            }
+        }
+    }
+
+    /// The times each side took to reply
+    fn replies(&self, side: Side) -> &[i64] {
+        match side {
+            Side::Me => &self.my_replies,
+            Side::Them => &self.their_replies,
+        }
+    }
+
+    fn summarize(&self) -> Latencies {
+        Latencies {
+            messages: self.messages,
+            delivered: Summary::new(&self.delivered),
+            read_by_them: Summary::new(&self.read_by_them),
+            read_by_me: Summary::new(&self.read_by_me),
+            my_replies: Summary::new(&self.my_replies),
+            their_replies: Summary::new(&self.their_replies),
+        }
+    }
+}
+
+/// The times measured for a single message
+#[derive(Debug)]
+struct Measured {
+    side: Side,
+    delivered: Option<i64>,
+    read: Option<i64>,
+    reply: Option<i64>,
+}
+
+/// The messages sent in a chat
+#[derive(Debug)]
+struct ChatSamples {
+    name: String,
+    samples: Samples,
+}
+
+/// The side that sent the most recent messages in a chat, waiting for the other side to reply
+#[derive(Debug, Clone, Copy)]
+struct Turn {
+    side: Side,
+    /// When the first message in this turn was sent
+    started: DateTime<Local>,
+    /// Unix timestamp of the most recent message in this turn
+    latest: i64,
+}
+
+/// A long wait for a reply, before it is compared to the chat's usual reply time
+#[derive(Debug)]
+struct Gap {
+    chat_id: Option<i32>,
+    /// The side that was expected to reply
+    waiting_on: Side,
+    started: DateTime<Local>,
+    ended: DateTime<Local>,
+}
+
+impl Gap {
+    fn seconds(&self) -> i64 {
+        self.ended.timestamp() - self.started.timestamp()
+    }
+}
+
+// MARK: Output
+/// The number of times measured, with their median and percentile, in seconds
+#[derive(Debug, PartialEq, Serialize)]
+struct Summary {
+    count: usize,
+    median_seconds: Option<f64>,
+    p90_seconds: Option<f64>,
+}
+
+impl Summary {
+    fn new(values: &[i64]) -> Self {
+        Summary {
+            count: values.len(),
+            median_seconds: median(values),
+            p90_seconds: percentile(values, PERCENTILE),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Latencies {
+    messages: u64,
+    /// Time until messages we sent were delivered
+    delivered: Summary,
+    /// Time until messages we sent were read by the recipient
+    read_by_them: Summary,
+    /// Time until we read messages we received
+    read_by_me: Summary,
+    /// Time we took to reply to the other side
+    my_replies: Summary,
+    /// Time the other side took to reply to us
+    their_replies: Summary,
+}
+
+#[derive(Debug, Serialize)]
+struct ChatReport<'a> {
+    name: &'a str,
+    #[serde(flatten)]
+    latencies: Latencies,
+    /// Number of long silences flagged in the chat
+    silences: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct MonthReport {
+    month: String,
+    #[serde(flatten)]
+    latencies: Latencies,
+}
+
+#[derive(Debug, Serialize)]
+struct Silence<'a> {
+    chat: &'a str,
+    /// The side that was expected to reply
+    waiting_on: Side,
+    /// When the first unanswered message was sent
+    start: String,
+    /// When the reply was sent
+    end: String,
+    seconds: i64,
+    /// The median time the side that was expected to reply usually takes in the chat
+    usual_seconds: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+struct Report<'a> {
+    total: Latencies,
+    chats: Vec<ChatReport<'a>>,
+    months: Vec<MonthReport>,
+    /// Long silences, longest first
+    silences: Vec<Silence<'a>>,
+}
+
+pub struct Latency<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Every message counted
+    total: Samples,
+    /// The messages in each chat, keyed by deduplicated chat ID
+    chats: HashMap<Option<i32>, ChatSamples>,
+    /// The messages sent each month
+    months: BTreeMap<Month, Samples>,
+    /// The side waiting for a reply in each chat
+    turns: HashMap<Option<i32>, Turn>,
+    /// Waits for a reply that were long enough to be flagged as silences
+    gaps: Vec<Gap>,
+}
+
+impl<'a> Latency<'a> {
+    pub fn new(config: &'a Config) -> Self {
+        Latency {
+            config,
+            total: Samples::default(),
+            chats: HashMap::new(),
+            months: BTreeMap::new(),
+            turns: HashMap::new(),
+            gaps: vec![],
+        }
+    }
+
+    /// Measure every message selected by the query filters, then print the results in `format`
+    pub fn run(&mut self, format: StatsFormat) -> Result<(), RuntimeError> {
+        let config = self.config;
+
+        eprintln!("Measuring response times...");
+        let pb = progress_bar(config);
+        for_each_message(config, &pb, false, |msg| {
+            if msg.is_tapback() || msg.is_announcement() {
+                return Ok(());
+            }
+            if let Ok(date) = msg.date(config.offset) {
+                self.add(&msg, date);
+            }
+            Ok(())
+        })?;
+
+        let report = self.report();
+        match format {
+            StatsFormat::Table => print!("{}", render_tables(&report)),
+            StatsFormat::Json => println!(
+                "{}",
+                serde_json::to_string_pretty(&report)
+                    .map_err(|why| RuntimeError::DiskError(IoError::other(why)))?
+            ),
+        }
+        Ok(())
+    }
+
+    /// Measure a message toward its chat, the month it was sent, and the total
+    fn add(&mut self, message: &Message, date: DateTime<Local>) {
+        let config = self.config;
+        let side = Side::of(Person::of(config, message));
+        let chat_id = config.conversation(message).map(|(_, id)| *id);
+
+        // Messages from the other side end the current turn and are replies to its latest message
+        let timestamp = date.timestamp();
+        let mut reply = None;
+        let turn = match self.turns.get(&chat_id) {
+            Some(turn) if turn.side == side => Turn {
+                latest: turn.latest.max(timestamp),
+                ..*turn
+            },
+            previous => {
+                if let Some(turn) = previous {
+                    let seconds = timestamp - turn.latest;
+                    reply = (0..=REPLY_WINDOW).contains(&seconds).then_some(seconds);
+                    if timestamp - turn.started.timestamp() >= MIN_SILENCE {
+                        self.gaps.push(Gap {
+                            chat_id,
+                            waiting_on: side,
+                            started: turn.started,
+                            ended: date,
+                        });
+                    }
+                }
+                Turn {
+                    side,
+                    started: date,
+                    latest: timestamp,
+                }
+            }
+        };
+        self.turns.insert(chat_id, turn);
+
+        let measured = Measured {
+            side,
+            delivered: message.seconds_until_delivered(config.offset),
+            read: message.seconds_until_read(config.offset),
+            reply,
+        };
+
+        self.total.add(&measured);
+        self.months
+            .entry((date.year(), date.month()))
+            .or_default()
+            .add(&measured);
+        self.chats
+            .entry(chat_id)
+            .or_insert_with(|| ChatSamples {
+                name: config
+                    .chat_name(message)
+                    .unwrap_or_else(|| ORPHANED.to_string()),
+                samples: Samples::default(),
+            })
+            .samples
+            .add(&measured);
+    }
+
+    /// Summarize each chat and month, and flag the silences that are unusually long for their chat
+    fn report(&self) -> Report<'_> {
+        let mut silences: Vec<Silence> = vec![];
+        let mut flagged: HashMap<Option<i32>, usize> = HashMap::new();
+        for gap in &self.gaps {
+            let Some(chat) = self.chats.get(&gap.chat_id) else {
+                continue;
+            };
+            let usual = median(chat.samples.replies(gap.waiting_on));
+            if is_silence(gap.seconds(), usual) {
+                *flagged.entry(gap.chat_id).or_default() += 1;
+                silences.push(Silence {
+                    chat: &chat.name,
+                    waiting_on: gap.waiting_on,
+                    start: gap.started.to_rfc3339(),
+                    end: gap.ended.to_rfc3339(),
+                    seconds: gap.seconds(),
+                    usual_seconds: usual,
+                });
+            }
+        }
+        silences.sort_by(|left, right| {
+            right
+                .seconds
+                .cmp(&left.seconds)
+                .then_with(|| left.start.cmp(&right.start))
+        });
+
+        let mut chats: Vec<ChatReport> = self
+            .chats
+            .iter()
+            .map(|(chat_id, chat)| ChatReport {
+                name: &chat.name,
+                latencies: chat.samples.summarize(),
+                silences: flagged.get(chat_id).copied().unwrap_or(0),
+            })
+            .collect();
+        chats.sort_by(|left, right| {
+            right
+                .latencies
+                .messages
+                .cmp(&left.latencies.messages)
+                .then_with(|| left.name.cmp(right.name))
+        });
+
+        let months = self
+            .months
+            .iter()
+            .map(|((year, month), samples)| MonthReport {
+                month: format!("{year}-{month:02}"),
+                latencies: samples.summarize(),
+            })
+            .collect();
+
+        Report {
+            total: self.total.summarize(),
+            chats,
+            months,
+            silences,
+        }
+    }
+}
+
+/// Determine if a wait of `seconds` for a reply is unusually long, given the usual reply time
+fn is_silence(seconds: i64, usual: Option<f64>) -> bool {
+    let threshold = usual.map_or(REPLY_WINDOW as f64, |usual| {
+        (usual * SILENCE_FACTOR).max(MIN_SILENCE as f64)
+    });
+    seconds >= MIN_SILENCE && seconds as f64 > threshold
+}
+
+/// The value that `percent` percent of `values` are at or below, using the nearest rank
+fn percentile(values: &[i64], percent: usize) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_unstable();
+    let rank = (sorted.len() * percent).div_ceil(100).max(1);
+    Some(sorted[rank - 1] as f64)
+}
+
+// MARK: Tables
+/// Render tables of the median times for each chat and month, then the longest silences
+fn render_tables(report: &Report) -> String {
+    let headers = [
+        "Name",
+        "Messages",
+        "Delivered",
+        "Read by Them",
+        "Read by Me",
+        "My Reply",
+        "Their Reply",
+    ];
+
+    let mut chat_headers = headers.to_vec();
+    chat_headers.push("Silences");
+    let chats: Vec<Vec<String>> = report
+        .chats
+        .iter()
+        .map(|chat| {
+            let mut row = table_row(chat.name, &chat.latencies);
+            row.push(chat.silences.to_string());
+            row
+        })
+        .collect();
+
+    let mut month_headers = headers.to_vec();
+    month_headers[0] = "Month";
+    let months: Vec<Vec<String>> = report
+        .months
+        .iter()
+        .map(|month| table_row(&month.month, &month.latencies))
+        .collect();
+
+    let silences: Vec<Vec<String>> = report
+        .silences
+        .iter()
+        .take(TABLE_SILENCES)
+        .map(|silence| {
+            vec![
+                truncate(silence.chat, NAME_LENGTH),
+                silence.waiting_on.name().to_string(),
+                silence
+                    .start
+                    .get(..16)
+                    .unwrap_or(&silence.start)
+                    .replace('T', " "),
+                format_seconds(silence.seconds as f64),
+                silence
+                    .usual_seconds
+                    .map_or_else(|| "-".to_string(), format_seconds),
+            ]
+        })
+        .collect();
+
+    let mut out = String::from("Median times\n");
+    out.push_str(&render_table(
+        &headers,
+        &[table_row("Everyone", &report.total)],
+    ));
+    let _ = write!(out, "\nChats: {}\n", chats.len());
+    out.push_str(&render_table(&chat_headers, &chats));
+    let _ = write!(out, "\nMonths: {}\n", months.len());
+    out.push_str(&render_table(&month_headers, &months));
+    let _ = write!(out, "\nLong silences: {}\n", report.silences.len());
+    out.push_str(&render_table(
+        &["Chat", "Waiting On", "Since", "Length", "Usual Reply"],
+        &silences,
+    ));
+    out
+}
+
+/// Summarize the median times for a chat or month in one table row
+fn table_row(name: &str, latencies: &Latencies) -> Vec<String> {
+    let median = |summary: &Summary| {
+        summary
+            .median_seconds
+            .map_or_else(|| "-".to_string(), format_seconds)
+    };
+    vec![
+        truncate(name, NAME_LENGTH),
+        latencies.messages.to_string(),
+        median(&latencies.delivered),
+        median(&latencies.read_by_them),
+        median(&latencies.read_by_me),
+        median(&latencies.my_replies),
+        median(&latencies.their_replies),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, TimeZone};
+
+    use crate::app::{
+        export_type::ExportType,
+        latency::{
+            Latencies, Latency, Measured, Samples, Side, Summary, is_silence, percentile, table_row,
+        },
+        options::Options,
+        runtime::Config,
+    };
+
+    fn measured(
+        side: Side,
+        delivered: Option<i64>,
+        read: Option<i64>,
+        reply: Option<i64>,
+    ) -> Measured {
+        Measured {
+            side,
+            delivered,
+            read,
+            reply,
+        }
+    }
+
+    #[test]
+    fn can_find_percentile() {
+        assert_eq!(percentile(&[], 90), None);
+        assert_eq!(percentile(&[7], 90), Some(7.));
+        assert_eq!(percentile(&[5, 1, 4, 2, 3], 50), Some(3.));
+        let values: Vec<i64> = (1..=20).collect();
+        assert_eq!(percentile(&values, 90), Some(18.));
+    }
+
+    #[test]
+    fn can_flag_silence() {
+        // Shorter than an hour is never a silence
+        assert!(!is_silence(59 * 60, Some(10.)));
+        // Longer than ten times the usual reply
+        assert!(is_silence(2 * 60 * 60, Some(60.)));
+        assert!(!is_silence(2 * 60 * 60, Some(30. * 60.)));
+        // Without a usual reply, only waits longer than a day
+        assert!(!is_silence(12 * 60 * 60, None));
+        assert!(is_silence(25 * 60 * 60, None));
+    }
+
+    #[test]
+    fn can_add_samples() {
+        let mut samples = Samples::default();
+        samples.add(&measured(Side::Me, Some(2), Some(60), Some(30)));
+        samples.add(&measured(Side::Them, None, Some(10), Some(90)));
+        samples.add(&measured(Side::Them, None, None, None));
+
+        assert_eq!(samples.messages, 3);
+        assert_eq!(samples.delivered, vec![2]);
+        assert_eq!(samples.read_by_them, vec![60]);
+        assert_eq!(samples.read_by_me, vec![10]);
+        assert_eq!(samples.replies(Side::Me), &[30]);
+        assert_eq!(samples.replies(Side::Them), &[90]);
+    }
+
+    #[test]
+    fn can_summarize_samples() {
+        let mut samples = Samples::default();
+        for seconds in [60, 120, 600] {
+            samples.add(&measured(Side::Them, None, None, Some(seconds)));
+        }
+        let latencies: Latencies = samples.summarize();
+
+        assert_eq!(
+            latencies.their_replies,
+            Summary {
+                count: 3,
+                median_seconds: Some(120.),
+                p90_seconds: Some(600.),
+            }
+        );
+        assert_eq!(
+            latencies.my_replies,
+            Summary {
+                count: 0,
+                median_seconds: None,
+                p90_seconds: None,
+            }
+        );
+    }
+
+    #[test]
+    fn can_build_table_row() {
+        let mut samples = Samples::default();
+        samples.add(&measured(Side::Me, Some(3), None, Some(45 * 60)));
+        samples.add(&measured(Side::Them, None, Some(90), None));
+
+        assert_eq!(
+            table_row("Support", &samples.summarize()),
+            vec!["Support", "2", "3s", "-", "1m", "45m", "-"]
+        );
+    }
+
+    #[test]
+    fn can_measure_group_chat_replies() {
+        let options = Options::fake_options(ExportType::Html);
+        let mut config = Config::fake_app(options);
+        config.real_participants.insert(1, 1);
+        config.real_participants.insert(2, 2);
+        let mut latency = Latency::new(&config);
+
+        let mut mine = Config::fake_message();
+        mine.is_from_me = true;
+        let mut first = Config::fake_message();
+        first.handle_id = Some(1);
+        let mut second = Config::fake_message();
+        second.handle_id = Some(2);
+
+        let start = 1_700_000_000;
+        for (offset, message) in [
+            (0, &first),
+            // Another participant answering is still the same side
+            (60, &second),
+            (90, &mine),
+            (150, &first),
+        ] {
+            let date = Local.timestamp_opt(start + offset, 0).unwrap();
+            latency.add(message, date);
+        }
+
+        let report = latency.report();
+        assert_eq!(report.total.my_replies.count, 1);
+        // Our reply is measured from the most recent message of the other side
+        assert_eq!(report.total.my_replies.median_seconds, Some(30.));
+        assert_eq!(report.total.their_replies.count, 1);
+        assert_eq!(report.total.their_replies.median_seconds, Some(60.));
+    }
+
+    #[test]
+    fn can_flag_long_silence() {
+        let options = Options::fake_options(ExportType::Html);
+        let config = Config::fake_app(options);
+        let mut latency = Latency::new(&config);
+
+        let mut mine = Config::fake_message();
+        mine.is_from_me = true;
+        let theirs = Config::fake_message();
+
+        // We usually reply within a minute, then once take three hours
+        let start = 1_700_000_000;
+        for (offset, message) in [
+            (0, &theirs),
+            (60, &mine),
+            (120, &theirs),
+            (180, &mine),
+            (240, &theirs),
+            (300, &mine),
+            (360, &theirs),
+            (420, &theirs),
+            (360 + 3 * 60 * 60, &mine),
+        ] {
+            let date = Local.timestamp_opt(start + offset, 0).unwrap();
+            latency.add(message, date);
+        }
+
+        let report = latency.report();
+        assert_eq!(report.total.my_replies.count, 4);
+        assert_eq!(report.total.their_replies.count, 3);
+        assert_eq!(report.silences.len(), 1);
+
+        let silence = &report.silences[0];
+        assert_eq!(silence.waiting_on, Side::Me);
+        // The silence starts at the first unanswered message
+        assert_eq!(silence.seconds, 3 * 60 * 60);
+        assert_eq!(report.chats[0].silences, 1);
+    }
+}
@@ -0,0 +1,557 @@
+/*!
+ Contains the manifest that records the contents of an export so it can be verified later.
+
+ After every export, the exporter writes [`MANIFEST_FILENAME`] into the export directory. It lists
+ every generated file and copied attachment with its size and SHA-256, the source database with
+ its own size and SHA-256, the options used, the tool version, and the number of messages written
+ for each chat. `--verify` rehashes the export directory and reports every file that is missing,
+ modified, or not listed in the manifest.
+
+ The state and journal files used by `--incremental` and `--resume` change between runs without
+ changing the export itself, so they are not listed.
+*/
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{Display, Formatter, Result as FmtResult},
+    fs::{File, read_to_string, rename, write},
+    io::{Error as IoError, Read, Result as IoResult},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::app::{
+    error::RuntimeError,
+    incremental::{IncrementalState, JOURNAL_FILENAME, RecordedOptions, STATE_FILENAME},
+    runtime::Config,
+};
+
+/// Name of the manifest file written into the export directory
+pub const MANIFEST_FILENAME: &str = "manifest.json";
+
+/// The version of the exporter that wrote the manifest
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// A file in the export directory
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileEntry {
+    /// Path relative to the export directory, using `/` as the separator
+    pub path: String,
+    /// Size in bytes
+    pub size: u64,
+    /// Hex-encoded SHA-256 of the content
+    pub sha256: String,
+}
+
+/// The database the export was created from
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceDatabase {
+    /// Path to the database file
+    pub path: String,
+    /// Size in bytes when the export started
+    pub size: u64,
+    /// Hex-encoded SHA-256 of the content when the export started
+    pub sha256: String,
+}
+
+impl SourceDatabase {
+    /// Hash the database used by `config`
+    pub fn from_config(config: &Config) -> Result<Self, RuntimeError> {
+        let path = config.options.get_db_path();
+        let size = path.metadata()?.len();
+        Ok(Self {
+            path: path.display().to_string(),
+            size,
+            sha256: hex_digest::<Sha256>(&path)?,
+        })
+    }
+}
+
+/// Hashes kept between the manifests written during one export, so `--watch` only rehashes
+/// the files that changed since the last one
+#[derive(Debug, Clone)]
+pub struct ManifestCache {
+    /// The source database, hashed when the export started
+    source: SourceDatabase,
+    /// Each file already hashed, keyed by its path relative to the export directory
+    files: HashMap<String, HashedFile>,
+}
+
+/// A file's hash along with the metadata that shows it has not changed since
+#[derive(Debug, Clone)]
+struct HashedFile {
+    modified: SystemTime,
+    entry: FileEntry,
+}
+
+/// The number of messages written for a single chat
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatEntry {
+    /// The chat `ROWID`, or `None` for messages that do not belong to any chat
+    pub chat_id: Option<i32>,
+    /// The export file the chat was written to
+    pub file: String,
+    /// The number of messages written for the chat
+    pub messages: u64,
+}
+
+/// The contents of an export directory, persisted in [`MANIFEST_FILENAME`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Manifest {
+    /// The version of the exporter that wrote the manifest
+    pub version: String,
+    /// When the manifest was written, in seconds since the Unix epoch
+    pub created: u64,
+    /// The database the export was created from
+    pub source: SourceDatabase,
+    /// The options used for the export
+    pub options: RecordedOptions,
+    /// The number of messages written for each chat
+    pub chats: Vec<ChatEntry>,
+    /// Every file in the export directory
+    pub files: Vec<FileEntry>,
+}
+
+impl Manifest {
+    /// Get the path to the manifest in an export directory
+    pub fn path(export_path: &Path) -> PathBuf {
+        export_path.join(MANIFEST_FILENAME)
+    }
+
+    /// Describe the export written with `config`, using the message counts recorded in `state`
+    ///
+    /// Files are hashed again unless their size and modification time match when `cache` last
+    /// hashed them, since a file can change without changing its size.
+    fn build(
+        config: &Config,
+        state: &IncrementalState,
+        cache: &mut ManifestCache,
+        orphaned_name: &str,
+    ) -> Result<Self, RuntimeError> {
+        let export_path = &config.options.export_path;
+
+        let mut chats: Vec<ChatEntry> = state
+            .chats
+            .iter()
+            .map(|(chat_id, chat)| ChatEntry {
+                chat_id: Some(*chat_id),
+                file: config
+                    .chatrooms
+                    .get(chat_id)
+                    .map_or_else(|| orphaned_name.to_string(), |chat| config.filename(chat)),
+                messages: chat.messages,
+            })
+            .collect();
+        if let Some(orphaned) = &state.orphaned {
+            chats.push(ChatEntry {
+                chat_id: None,
+                file: orphaned_name.to_string(),
+                messages: orphaned.messages,
+            });
+        }
+
+        Ok(Self {
+            version: VERSION.to_string(),
+            created: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or_default(),
+            source: cache.source.clone(),
+            options: RecordedOptions::from_options(&config.options),
+            chats,
+            files: hash_files(export_path, &mut cache.files)?,
+        })
+    }
+
+    /// Read the manifest from `export_path`
+    pub fn load(export_path: &Path) -> Result<Self, RuntimeError> {
+        let path = Self::path(export_path);
+        let contents = read_to_string(&path)?;
+        serde_json::from_str(&contents).map_err(|why| {
+            RuntimeError::InvalidOptions(format!(
+                "Unable to read manifest {}: {why}",
+                path.display()
+            ))
+        })
+    }
+
+    /// Write the manifest into `export_path`
+    ///
+    /// The file is written to a temporary path and renamed into place so an interrupted
+    /// write never leaves a truncated manifest behind.
+    pub fn save(&self, export_path: &Path) -> Result<(), RuntimeError> {
+        let path = Self::path(export_path);
+        let temp_path = path.with_extension("json.tmp");
+        let contents = serde_json::to_string_pretty(self)
+            .map_err(|why| RuntimeError::DiskError(IoError::other(why)))?;
+        write(&temp_path, contents)?;
+        rename(&temp_path, &path)?;
+        Ok(())
+    }
+
+    /// Rehash every file in `export_path` and compare it against the manifest
+    pub fn verify(&self, export_path: &Path) -> Result<Vec<Problem>, RuntimeError> {
+        let mut problems = vec![];
+
+        for entry in &self.files {
+            let path = export_path.join(&entry.path);
+            if !path.is_file() {
+                problems.push(Problem::Missing(entry.path.clone()));
+            } else if path.metadata()?.len() != entry.size
+                || hex_digest::<Sha256>(&path)? != entry.sha256
+            {
+                problems.push(Problem::Modified(entry.path.clone()));
+            }
+        }
+
+        let listed: HashSet<&str> = self.files.iter().map(|entry| entry.path.as_str()).collect();
+        for path in list_files(export_path)? {
+            if !listed.contains(path.as_str()) {
+                problems.push(Problem::Unexpected(path));
+            }
+        }
+
+        Ok(problems)
+    }
+}
+
+/// A difference between an export directory and its manifest
+#[derive(Debug, PartialEq, Eq)]
+pub enum Problem {
+    /// A file listed in the manifest does not exist
+    Missing(String),
+    /// A file's size or content differs from the manifest
+    Modified(String),
+    /// A file exists that the manifest does not list
+    Unexpected(String),
+}
+
+impl Display for Problem {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Problem::Missing(path) => write!(fmt, "Missing: {path}"),
+            Problem::Modified(path) => write!(fmt, "Modified: {path}"),
+            Problem::Unexpected(path) => write!(fmt, "Not in manifest: {path}"),
+        }
+    }
+}
+
+/// Write the manifest for the export written with `config`
+///
+/// The source database is only hashed the first time this is called for an export, so
+/// repeated calls while watching reuse the hash from when the export started. Exported files
+/// are only hashed again if they changed since the last call.
+pub fn write_manifest(
+    config: &Config,
+    state: &IncrementalState,
+    cache: &mut Option<ManifestCache>,
+    orphaned_name: &str,
+) -> Result<(), RuntimeError> {
+    let cached = match cache.take() {
+        Some(cached) => cached,
+        None => ManifestCache {
+            source: SourceDatabase::from_config(config)?,
+            files: HashMap::new(),
+        },
+    };
+    let cached = cache.insert(cached);
+    Manifest::build(config, state, cached, orphaned_name)?.save(&config.options.export_path)
+}
+
+/// Recheck the export in `export_path` against its manifest, printing every problem found
+pub fn verify_export(export_path: &Path) -> Result<(), RuntimeError> {
+    if !Manifest::path(export_path).exists() {
+        return Err(RuntimeError::InvalidOptions(format!(
+            "Export path {} does not contain a {MANIFEST_FILENAME}!",
+            export_path.display()
+        )));
+    }
+
+    let manifest = Manifest::load(export_path)?;
+    eprintln!(
+        "Verifying {} files in {}...",
+        manifest.files.len(),
+        export_path.display()
+    );
+
+    let problems = manifest.verify(export_path)?;
+    if problems.is_empty() {
+        println!("Export matches its manifest.");
+        return Ok(());
+    }
+
+    for problem in &problems {
+        println!("{problem}");
+    }
+    Err(RuntimeError::InvalidOptions(format!(
+        "{} files do not match the manifest!",
+        problems.len()
+    )))
+}
+
+/// Compute the hex-encoded digest of a file's content
+pub fn hex_digest<D: Digest>(path: &Path) -> IoResult<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = D::new();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+/// Hash every file in `export_path`, reusing the hashes in `hashed` for files whose size and
+/// modification time have not changed
+fn hash_files(
+    export_path: &Path,
+    hashed: &mut HashMap<String, HashedFile>,
+) -> Result<Vec<FileEntry>, RuntimeError> {
+    let mut files = vec![];
+
+    for path in list_files(export_path)? {
+        let full_path = export_path.join(&path);
+        let metadata = full_path.metadata()?;
+        let size = metadata.len();
+        let modified = metadata.modified()?;
+        match hashed.get(&path) {
+            Some(file) if file.modified == modified && file.entry.size == size => {
+                files.push(file.entry.clone());
+            }
+            _ => {
+                let sha256 = hex_digest::<Sha256>(&full_path)?;
+                let entry = FileEntry {
+                    path: path.clone(),
+                    size,
+                    sha256,
+                };
+                hashed.insert(
+                    path,
+                    HashedFile {
+                        modified,
+                        entry: entry.clone(),
+                    },
+                );
+                files.push(entry);
+            }
+        }
+    }
+    hashed.retain(|path, _| files.binary_search_by(|file| file.path.cmp(path)).is_ok());
+
+    Ok(files)
+}
+
+/// List every file in `export_path` that belongs in the manifest, relative to `export_path` and sorted
+///
+/// Symlinks to files, like attachments exported with `--copy-method symlink`, are listed by
+/// their own path and hashed by the content of the file they point to. Symlinks to directories
+/// and broken symlinks are not followed.
+fn list_files(export_path: &Path) -> Result<Vec<String>, RuntimeError> {
+    let mut files = vec![];
+    let mut dirs = vec![export_path.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        for entry in dir.read_dir()?.flatten() {
+            let path = entry.path();
+            let file_type = entry.file_type()?;
+            let is_file = file_type.is_file()
+                || (file_type.is_symlink() && path.metadata().is_ok_and(|meta| meta.is_file()));
+            if file_type.is_dir() {
+                dirs.push(path);
+            } else if is_file && let Ok(relative) = path.strip_prefix(export_path) {
+                let relative = relative
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                if !is_bookkeeping(&relative) {
+                    files.push(relative);
+                }
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Determine if a file is export bookkeeping rather than part of the export
+fn is_bookkeeping(relative_path: &str) -> bool {
+    [MANIFEST_FILENAME, STATE_FILENAME, JOURNAL_FILENAME]
+        .iter()
+        .any(|name| relative_path == *name || relative_path == format!("{name}.tmp"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        fs::{create_dir_all, remove_file, write},
+    };
+
+    use sha2::Sha256;
+
+    use crate::app::{
+        incremental::STATE_FILENAME,
+        manifest::{MANIFEST_FILENAME, Manifest, Problem, hash_files, hex_digest},
+        test_dir::unique_test_dir,
+    };
+
+    fn export_dir(label: &str) -> std::path::PathBuf {
+        let dir = unique_test_dir(label);
+        create_dir_all(dir.join("attachments/1")).unwrap();
+        write(dir.join("Chat.html"), "<html></html>").unwrap();
+        write(dir.join("attachments/1/10.jpeg"), "image").unwrap();
+        write(dir.join(STATE_FILENAME), "{}").unwrap();
+        dir
+    }
+
+    fn manifest_for(dir: &std::path::Path) -> Manifest {
+        Manifest {
+            files: hash_files(dir, &mut HashMap::new()).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn can_hash_sha256() {
+        let dir = unique_test_dir("manifest_sha256");
+        let path = dir.join("file.txt");
+        write(&path, "hello world").unwrap();
+
+        assert_eq!(
+            hex_digest::<Sha256>(&path).unwrap(),
+            "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"
+        );
+    }
+
+    #[test]
+    fn can_list_export_files() {
+        let dir = export_dir("manifest_list");
+        let manifest = manifest_for(&dir);
+
+        let paths: Vec<&str> = manifest
+            .files
+            .iter()
+            .map(|entry| entry.path.as_str())
+            .collect();
+        assert_eq!(paths, ["Chat.html", "attachments/1/10.jpeg"]);
+        assert_eq!(manifest.files[0].size, 13);
+    }
+
+    #[test]
+    fn can_verify_unchanged() {
+        let dir = export_dir("manifest_unchanged");
+        let manifest = manifest_for(&dir);
+        manifest.save(&dir).unwrap();
+
+        assert!(dir.join(MANIFEST_FILENAME).exists());
+        let loaded = Manifest::load(&dir).unwrap();
+        assert_eq!(loaded, manifest);
+        assert!(loaded.verify(&dir).unwrap().is_empty());
+    }
+
+    #[test]
+    fn can_verify_changes() {
+        let dir = export_dir("manifest_changes");
+        let manifest = manifest_for(&dir);
+
+        write(dir.join("Chat.html"), "<html>edited</html>").unwrap();
+        remove_file(dir.join("attachments/1/10.jpeg")).unwrap();
+        write(dir.join("Other.html"), "<html></html>").unwrap();
+
+        assert_eq!(
+            manifest.verify(&dir).unwrap(),
+            vec![
+                Problem::Modified(String::from("Chat.html")),
+                Problem::Missing(String::from("attachments/1/10.jpeg")),
+                Problem::Unexpected(String::from("Other.html")),
+            ]
+        );
+    }
+
+    #[test]
+    fn can_rehash_same_size_attachments() {
+        let dir = export_dir("manifest_rehash");
+        let previous = manifest_for(&dir);
+
+        // Same size as the original content
+        write(dir.join("attachments/1/10.jpeg"), "photo").unwrap();
+        let files = hash_files(&dir, &mut HashMap::new()).unwrap();
+        assert_eq!(files[1].size, previous.files[1].size);
+        assert_ne!(files[1].sha256, previous.files[1].sha256);
+    }
+
+    #[test]
+    fn can_reuse_unchanged_hashes() {
+        let dir = export_dir("manifest_reuse");
+        let mut hashed = HashMap::new();
+        hash_files(&dir, &mut hashed).unwrap();
+
+        // A hash is reused while the file's size and modification time are unchanged
+        hashed.get_mut("Chat.html").unwrap().entry.sha256 = String::from("cached");
+        hashed
+            .get_mut("attachments/1/10.jpeg")
+            .unwrap()
+            .entry
+            .sha256 = String::from("cached");
+        write(dir.join("attachments/1/10.jpeg"), "a larger image").unwrap();
+        remove_file(dir.join("Chat.html")).unwrap();
+
+        let files = hash_files(&dir, &mut hashed).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(
+            files[0].sha256,
+            hex_digest::<Sha256>(&dir.join("attachments/1/10.jpeg")).unwrap()
+        );
+        // Files that no longer exist are forgotten
+        assert!(!hashed.contains_key("Chat.html"));
+
+        write(dir.join("Chat.html"), "<html></html>").unwrap();
+        hashed
+            .get_mut("attachments/1/10.jpeg")
+            .unwrap()
+            .entry
+            .sha256 = String::from("cached");
+        let files = hash_files(&dir, &mut hashed).unwrap();
+        assert_eq!(files[1].sha256, "cached");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn can_list_symlinked_attachments() {
+        let dir = export_dir("manifest_symlink");
+        let target = dir.join("source.jpeg");
+        write(&target, "linked").unwrap();
+        std::os::unix::fs::symlink(&target, dir.join("attachments/1/11.jpeg")).unwrap();
+        std::os::unix::fs::symlink(dir.join("missing.jpeg"), dir.join("attachments/1/12.jpeg"))
+            .unwrap();
+
+        let files = hash_files(&dir, &mut HashMap::new()).unwrap();
+        let linked = files
+            .iter()
+            .find(|entry| entry.path == "attachments/1/11.jpeg")
+            .unwrap();
+        assert_eq!(linked.size, 6);
+        assert_eq!(linked.sha256, hex_digest::<Sha256>(&target).unwrap());
+        assert!(
+            !files
+                .iter()
+                .any(|entry| entry.path == "attachments/1/12.jpeg")
+        );
+    }
+}
@@ -1,13 +1,20 @@
 pub mod compatibility;
 pub mod contacts;
 pub mod data_source;
+pub mod diff;
 pub mod error;
 pub mod escaping;
 pub mod export_type;
+pub mod frequency;
+pub mod incremental;
+pub mod latency;
+pub mod manifest;
 pub mod options;
 pub mod progress;
 pub mod runtime;
 pub mod sanitizers;
+pub mod stats;
+pub mod watch;
 
 #[cfg(test)]
 pub mod test_dir;
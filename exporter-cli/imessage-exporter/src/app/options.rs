@@ -15,10 +15,27 @@ use imessage_database::{
     },
 };
 
-use crate::app::{
-    compatibility::attachment_manager::{AttachmentManager, AttachmentManagerMode},
-    error::RuntimeError,
-    export_type::ExportType,
+use crate::{
+    app::{
+        compatibility::{
+            attachment_manager::{AttachmentManager, AttachmentManagerMode},
+            cache::ConversionCache,
+            conversions::ConversionPool,
+            converters::thumbnail::{THUMBNAIL_DIR, ThumbnailFormat},
+            models::{Converter, MetadataWriter},
+            naming::{AttachmentNameTemplate, SUPPORTED_NAME_PLACEHOLDERS},
+            policy::{AttachmentPolicy, parse_list, parse_size},
+        },
+        error::RuntimeError,
+        export_type::ExportType,
+        incremental::IncrementalState,
+        stats::StatsFormat,
+    },
+    exporters::{
+        links::{LINKS_FILE, LinkFormat},
+        locations::{LOCATIONS_DIR, LocationFormat},
+        report::REPORT_FILE,
+    },
 };
 
 // MARK: Constants
@@ -46,11 +63,39 @@ pub const OPTION_CUSTOM_CONTACTS_DB_PATH: &str = "contacts-path";
 pub const OPTION_NO_PROGRESS: &str = "no-progress";
 pub const OPTION_LIST_CONTACTS: &str = "list-contacts";
 pub const OPTION_IGNORE_VIDEOS: &str = "images-only";
+pub const OPTION_QUERY: &str = "query";
+pub const OPTION_INCREMENTAL: &str = "incremental";
+pub const OPTION_RESUME: &str = "resume";
+pub const OPTION_WATCH: &str = "watch";
+pub const OPTION_DEDUPE_ATTACHMENTS: &str = "dedupe-attachments";
+pub const OPTION_VERIFY: &str = "verify";
+pub const OPTION_COMPARE: &str = "compare";
+pub const OPTION_THUMBNAILS: &str = "thumbnails";
+pub const OPTION_EMBED_METADATA: &str = "embed-metadata";
+pub const OPTION_ATTACHMENT_NAME: &str = "attachment-name";
+pub const OPTION_MAX_ATTACHMENT_SIZE: &str = "max-attachment-size";
+pub const OPTION_SKIP_TYPES: &str = "skip-types";
+pub const OPTION_INCLUDE_MIME: &str = "include-mime";
+pub const OPTION_JOBS: &str = "jobs";
+pub const OPTION_CONVERSION_CACHE: &str = "conversion-cache";
+pub const OPTION_NO_CONVERSION_CACHE: &str = "no-conversion-cache";
+pub const OPTION_LOCATIONS: &str = "locations";
+pub const OPTION_LINKS: &str = "links";
+pub const OPTION_STATS: &str = "stats";
+pub const OPTION_FREQUENCY: &str = "frequency";
+pub const OPTION_STOPWORDS: &str = "stopwords";
+pub const OPTION_REPORT: &str = "report";
+pub const OPTION_LATENCY: &str = "latency";
 
 // Other CLI Text
-pub const SUPPORTED_FILE_TYPES: &str = "txt, html";
+pub const SUPPORTED_FILE_TYPES: &str = "txt, html, gallery";
 pub const SUPPORTED_PLATFORMS: &str = "macOS, iOS";
-pub const SUPPORTED_ATTACHMENT_MANAGER_MODES: &str = "clone, basic, full, disabled";
+pub const SUPPORTED_ATTACHMENT_MANAGER_MODES: &str =
+    "clone, basic, full, hardlink, symlink, reflink, disabled";
+pub const SUPPORTED_THUMBNAIL_FORMATS: &str = "jpeg, webp";
+pub const SUPPORTED_LOCATION_FORMATS: &str = "geojson, kml, gpx";
+pub const SUPPORTED_LINK_FORMATS: &str = "json, csv";
+pub const SUPPORTED_STATS_FORMATS: &str = "table, json";
 pub const ABOUT: &str = concat!(
     "The `imessage-exporter` binary exports iMessage data to\n",
     "`txt` or `html` formats. It can also run diagnostics\n",
@@ -96,6 +141,36 @@ pub struct Options {
     pub show_progress: bool,
     /// If true, only include image attachments in the export
     pub images_only: bool,
+    /// If true, append only messages newer than the previous export in the same directory
+    pub incremental: bool,
+    /// If true, continue an interrupted export in the same directory from its last checkpoint
+    pub resume: bool,
+    /// If true, keep following the database after the export and append new messages as they arrive
+    pub watch: bool,
+    /// If true, store copied attachments once per unique content and share them between chats
+    pub dedupe_attachments: bool,
+    /// An optional export directory to check against its manifest
+    pub verify: Option<PathBuf>,
+    /// An optional second database or backup to compare the first one with
+    pub compare: Option<PathBuf>,
+    /// The format of downscaled previews for images in HTML exports, if enabled
+    pub thumbnails: Option<ThumbnailFormat>,
+    /// If true, write the message date, sender, and chat into copied photos and videos
+    pub embed_metadata: bool,
+    /// The format to export shared locations in, if enabled
+    pub locations: Option<LocationFormat>,
+    /// The format to list shared links in, if enabled
+    pub links: Option<LinkFormat>,
+    /// The format to print message statistics in, if enabled
+    pub stats: Option<StatsFormat>,
+    /// The format to print word, phrase, emoji, and tapback frequencies in, if enabled
+    pub frequency: Option<StatsFormat>,
+    /// An optional file of stopwords that replaces the built-in list
+    pub stopwords: Option<PathBuf>,
+    /// If true, also write a page of charts that summarize the exported messages
+    pub report: bool,
+    /// The format to print delivery, read, and reply times in, if enabled
+    pub latency: Option<StatsFormat>,
 }
 
 // Override Debug default impl to avoid printing the cleartext password if it's set
@@ -124,6 +199,21 @@ impl std::fmt::Debug for Options {
             .field("contacts_path", &self.contacts_path)
             .field("show_progress", &self.show_progress)
             .field("images_only", &self.images_only)
+            .field("incremental", &self.incremental)
+            .field("resume", &self.resume)
+            .field("watch", &self.watch)
+            .field("dedupe_attachments", &self.dedupe_attachments)
+            .field("verify", &self.verify)
+            .field("compare", &self.compare)
+            .field("thumbnails", &self.thumbnails)
+            .field("embed_metadata", &self.embed_metadata)
+            .field("locations", &self.locations)
+            .field("links", &self.links)
+            .field("stats", &self.stats)
+            .field("frequency", &self.frequency)
+            .field("stopwords", &self.stopwords)
+            .field("report", &self.report)
+            .field("latency", &self.latency)
             .finish()
     }
 }
@@ -151,6 +241,29 @@ impl Options {
         let contacts_path: Option<&String> = args.get_one(OPTION_CUSTOM_CONTACTS_DB_PATH);
         let show_progress = !args.get_flag(OPTION_NO_PROGRESS);
         let images_only = args.get_flag(OPTION_IGNORE_VIDEOS);
+        let query: Option<&String> = args.get_one(OPTION_QUERY);
+        let incremental = args.get_flag(OPTION_INCREMENTAL);
+        let resume = args.get_flag(OPTION_RESUME);
+        let watch = args.get_flag(OPTION_WATCH);
+        let dedupe_attachments = args.get_flag(OPTION_DEDUPE_ATTACHMENTS);
+        let verify: Option<&String> = args.get_one(OPTION_VERIFY);
+        let compare: Option<&String> = args.get_one(OPTION_COMPARE);
+        let thumbnail_format: Option<&String> = args.get_one(OPTION_THUMBNAILS);
+        let embed_metadata = args.get_flag(OPTION_EMBED_METADATA);
+        let attachment_name: Option<&String> = args.get_one(OPTION_ATTACHMENT_NAME);
+        let max_attachment_size: Option<&String> = args.get_one(OPTION_MAX_ATTACHMENT_SIZE);
+        let skip_types: Option<&String> = args.get_one(OPTION_SKIP_TYPES);
+        let include_mime: Option<&String> = args.get_one(OPTION_INCLUDE_MIME);
+        let jobs: Option<&String> = args.get_one(OPTION_JOBS);
+        let conversion_cache: Option<&String> = args.get_one(OPTION_CONVERSION_CACHE);
+        let no_conversion_cache = args.get_flag(OPTION_NO_CONVERSION_CACHE);
+        let location_format: Option<&String> = args.get_one(OPTION_LOCATIONS);
+        let link_format: Option<&String> = args.get_one(OPTION_LINKS);
+        let stats_format: Option<&String> = args.get_one(OPTION_STATS);
+        let frequency_format: Option<&String> = args.get_one(OPTION_FREQUENCY);
+        let stopwords: Option<&String> = args.get_one(OPTION_STOPWORDS);
+        let report = args.get_flag(OPTION_REPORT);
+        let latency_format: Option<&String> = args.get_one(OPTION_LATENCY);
 
         // Build the export type
         let export_type: Option<ExportType> = match export_file_type {
@@ -164,18 +277,46 @@ impl Options {
 
         // Anything in here requires `--format`
         if export_file_type.is_none() {
+            // Filters and sender names also apply when comparing databases or computing statistics
+            let comparing = compare.is_some()
+                || stats_format.is_some()
+                || frequency_format.is_some()
+                || latency_format.is_some();
             let format_deps = [
                 (attachment_manager_type.is_some(), OPTION_ATTACHMENT_MANAGER),
                 (user_export_path.is_some(), OPTION_EXPORT_PATH),
                 (no_lazy, OPTION_DISABLE_LAZY_LOADING),
-                (start_date.is_some(), OPTION_START_DATE),
-                (end_date.is_some(), OPTION_END_DATE),
-                (custom_name.is_some(), OPTION_CUSTOM_NAME),
-                (use_caller_id, OPTION_USE_CALLER_ID),
-                (conversation_filter.is_some(), OPTION_CONVERSATION_FILTER),
-                (selected_chat_ids.is_some(), OPTION_SELECTED_CHAT_IDS),
+                (start_date.is_some() && !comparing, OPTION_START_DATE),
+                (end_date.is_some() && !comparing, OPTION_END_DATE),
+                (custom_name.is_some() && !comparing, OPTION_CUSTOM_NAME),
+                (use_caller_id && !comparing, OPTION_USE_CALLER_ID),
+                (
+                    conversation_filter.is_some() && !comparing,
+                    OPTION_CONVERSATION_FILTER,
+                ),
+                (
+                    selected_chat_ids.is_some() && !comparing,
+                    OPTION_SELECTED_CHAT_IDS,
+                ),
                 (!show_progress, OPTION_NO_PROGRESS),
                 (images_only, OPTION_IGNORE_VIDEOS),
+                (query.is_some() && !comparing, OPTION_QUERY),
+                (incremental, OPTION_INCREMENTAL),
+                (resume, OPTION_RESUME),
+                (watch, OPTION_WATCH),
+                (dedupe_attachments, OPTION_DEDUPE_ATTACHMENTS),
+                (thumbnail_format.is_some(), OPTION_THUMBNAILS),
+                (embed_metadata, OPTION_EMBED_METADATA),
+                (attachment_name.is_some(), OPTION_ATTACHMENT_NAME),
+                (max_attachment_size.is_some(), OPTION_MAX_ATTACHMENT_SIZE),
+                (skip_types.is_some(), OPTION_SKIP_TYPES),
+                (include_mime.is_some(), OPTION_INCLUDE_MIME),
+                (jobs.is_some(), OPTION_JOBS),
+                (conversion_cache.is_some(), OPTION_CONVERSION_CACHE),
+                (no_conversion_cache, OPTION_NO_CONVERSION_CACHE),
+                (location_format.is_some(), OPTION_LOCATIONS),
+                (link_format.is_some(), OPTION_LINKS),
+                (report, OPTION_REPORT),
             ];
             for (set, opt) in format_deps {
                 if set {
@@ -200,6 +341,29 @@ impl Options {
             (selected_chat_ids.is_some(), OPTION_SELECTED_CHAT_IDS),
             (!show_progress, OPTION_NO_PROGRESS),
             (images_only, OPTION_IGNORE_VIDEOS),
+            (query.is_some(), OPTION_QUERY),
+            (incremental, OPTION_INCREMENTAL),
+            (resume, OPTION_RESUME),
+            (watch, OPTION_WATCH),
+            (dedupe_attachments, OPTION_DEDUPE_ATTACHMENTS),
+            (verify.is_some(), OPTION_VERIFY),
+            (compare.is_some(), OPTION_COMPARE),
+            (thumbnail_format.is_some(), OPTION_THUMBNAILS),
+            (embed_metadata, OPTION_EMBED_METADATA),
+            (attachment_name.is_some(), OPTION_ATTACHMENT_NAME),
+            (max_attachment_size.is_some(), OPTION_MAX_ATTACHMENT_SIZE),
+            (skip_types.is_some(), OPTION_SKIP_TYPES),
+            (include_mime.is_some(), OPTION_INCLUDE_MIME),
+            (jobs.is_some(), OPTION_JOBS),
+            (conversion_cache.is_some(), OPTION_CONVERSION_CACHE),
+            (no_conversion_cache, OPTION_NO_CONVERSION_CACHE),
+            (location_format.is_some(), OPTION_LOCATIONS),
+            (link_format.is_some(), OPTION_LINKS),
+            (stats_format.is_some(), OPTION_STATS),
+            (frequency_format.is_some(), OPTION_FREQUENCY),
+            (stopwords.is_some(), OPTION_STOPWORDS),
+            (report, OPTION_REPORT),
+            (latency_format.is_some(), OPTION_LATENCY),
         ];
         for (set, opt) in diag_conflicts {
             if diagnostic && set {
@@ -209,6 +373,95 @@ impl Options {
             }
         }
 
+        // Verifying an export does not read the database, so it cannot run alongside anything that does
+        if verify.is_some() {
+            let verify_conflicts = [
+                (export_file_type.is_some(), OPTION_EXPORT_TYPE),
+                (list_contacts, OPTION_LIST_CONTACTS),
+                (compare.is_some(), OPTION_COMPARE),
+                (stats_format.is_some(), OPTION_STATS),
+                (frequency_format.is_some(), OPTION_FREQUENCY),
+                (latency_format.is_some(), OPTION_LATENCY),
+            ];
+            for (set, opt) in verify_conflicts {
+                if set {
+                    return Err(RuntimeError::InvalidOptions(format!(
+                        "--{OPTION_VERIFY} is enabled; --{opt} is disallowed"
+                    )));
+                }
+            }
+        }
+
+        // Comparing databases writes a report instead of an export
+        if compare.is_some() {
+            let compare_conflicts = [
+                (export_file_type.is_some(), OPTION_EXPORT_TYPE),
+                (list_contacts, OPTION_LIST_CONTACTS),
+                (stats_format.is_some(), OPTION_STATS),
+                (frequency_format.is_some(), OPTION_FREQUENCY),
+                (latency_format.is_some(), OPTION_LATENCY),
+            ];
+            for (set, opt) in compare_conflicts {
+                if set {
+                    return Err(RuntimeError::InvalidOptions(format!(
+                        "--{OPTION_COMPARE} is enabled; --{opt} is disallowed"
+                    )));
+                }
+            }
+        }
+
+        // Statistics are printed instead of writing an export
+        if stats_format.is_some() {
+            let stats_conflicts = [
+                (export_file_type.is_some(), OPTION_EXPORT_TYPE),
+                (list_contacts, OPTION_LIST_CONTACTS),
+            ];
+            for (set, opt) in stats_conflicts {
+                if set {
+                    return Err(RuntimeError::InvalidOptions(format!(
+                        "--{OPTION_STATS} is enabled; --{opt} is disallowed"
+                    )));
+                }
+            }
+        }
+
+        // Word frequencies are printed instead of writing an export
+        if frequency_format.is_some() {
+            let frequency_conflicts = [
+                (export_file_type.is_some(), OPTION_EXPORT_TYPE),
+                (list_contacts, OPTION_LIST_CONTACTS),
+                (stats_format.is_some(), OPTION_STATS),
+            ];
+            for (set, opt) in frequency_conflicts {
+                if set {
+                    return Err(RuntimeError::InvalidOptions(format!(
+                        "--{OPTION_FREQUENCY} is enabled; --{opt} is disallowed"
+                    )));
+                }
+            }
+        } else if stopwords.is_some() {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Option --{OPTION_STOPWORDS} is enabled, which requires --{OPTION_FREQUENCY}"
+            )));
+        }
+
+        // Response times are printed instead of writing an export
+        if latency_format.is_some() {
+            let latency_conflicts = [
+                (export_file_type.is_some(), OPTION_EXPORT_TYPE),
+                (list_contacts, OPTION_LIST_CONTACTS),
+                (stats_format.is_some(), OPTION_STATS),
+                (frequency_format.is_some(), OPTION_FREQUENCY),
+            ];
+            for (set, opt) in latency_conflicts {
+                if set {
+                    return Err(RuntimeError::InvalidOptions(format!(
+                        "--{OPTION_LATENCY} is enabled; --{opt} is disallowed"
+                    )));
+                }
+            }
+        }
+
         // Prevent custom_name vs. use_caller_id collision
         if custom_name.is_some() && use_caller_id {
             return Err(RuntimeError::InvalidOptions(format!(
@@ -237,6 +490,11 @@ impl Options {
         if let Some(chat_ids) = selected_chat_ids {
             query_context.set_selected_chat_ids(parse_selected_chat_ids(chat_ids)?);
         }
+        if let Some(query) = query
+            && let Err(why) = query_context.set_expression(query)
+        {
+            return Err(RuntimeError::InvalidOptions(format!("{why}")));
+        }
 
         // We have to allocate a PathBuf here because it can be created from data owned by this function in the default state
         let db_path = match user_path {
@@ -261,6 +519,13 @@ impl Options {
             )));
         }
 
+        // Only a live macOS database receives new messages
+        if watch && !matches!(platform, Platform::macOS) {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "--{OPTION_WATCH} is enabled; it can only be used with macOS databases."
+            )));
+        }
+
         // Validate that the custom attachment root exists, if provided
         if let Some(path) = attachment_root {
             let custom_attachment_path = PathBuf::from(path);
@@ -304,16 +569,227 @@ impl Options {
                     "{manager} is not a valid attachment manager mode! Must be one of <{SUPPORTED_ATTACHMENT_MANAGER_MODES}>"
                 )))?
             }
+            // A gallery is made of copied attachments, converted so browsers can display them
+            None if matches!(export_type, Some(ExportType::Gallery)) => AttachmentManagerMode::Basic,
             None => AttachmentManagerMode::default(),
         };
 
+        // Gallery exports copy every file into a dated tree and do not write message files
+        if matches!(export_type, Some(ExportType::Gallery)) {
+            if matches!(attachment_manager_mode, AttachmentManagerMode::Disabled) {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "--{OPTION_EXPORT_TYPE} gallery requires --{OPTION_ATTACHMENT_MANAGER} to copy attachments"
+                )));
+            }
+            let gallery_conflicts = [
+                (incremental, OPTION_INCREMENTAL),
+                (resume, OPTION_RESUME),
+                (watch, OPTION_WATCH),
+                (dedupe_attachments, OPTION_DEDUPE_ATTACHMENTS),
+                (attachment_name.is_some(), OPTION_ATTACHMENT_NAME),
+            ];
+            for (set, opt) in gallery_conflicts {
+                if set {
+                    return Err(RuntimeError::InvalidOptions(format!(
+                        "--{OPTION_EXPORT_TYPE} gallery is enabled; --{opt} is disallowed"
+                    )));
+                }
+            }
+        }
+
+        // The report reuses the HTML export's stylesheet and sits next to its chat files
+        if report && !matches!(export_type, Some(ExportType::Html)) {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "--{OPTION_REPORT} is enabled, which requires --{OPTION_EXPORT_TYPE} html"
+            )));
+        }
+
+        // Deduplication only applies to copied attachments
+        if dedupe_attachments && matches!(attachment_manager_mode, AttachmentManagerMode::Disabled)
+        {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "--{OPTION_DEDUPE_ATTACHMENTS} is enabled, which requires --{OPTION_ATTACHMENT_MANAGER} to copy attachments"
+            )));
+        }
+
+        // Metadata is written into copies, which are shared between messages when deduplicating
+        if embed_metadata {
+            if matches!(attachment_manager_mode, AttachmentManagerMode::Disabled) {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "--{OPTION_EMBED_METADATA} is enabled, which requires --{OPTION_ATTACHMENT_MANAGER} to copy attachments"
+                )));
+            }
+            if dedupe_attachments {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "--{OPTION_EMBED_METADATA} is enabled; --{OPTION_DEDUPE_ATTACHMENTS} is disallowed"
+                )));
+            }
+            // Writing tags into a linked file would write them into the original
+            if attachment_manager_mode.shares_original() {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "--{OPTION_EMBED_METADATA} is enabled; --{OPTION_ATTACHMENT_MANAGER} {attachment_manager_mode} is disallowed"
+                )));
+            }
+        }
+
+        // Build the rules for which attachments are exported
+        let policy = AttachmentPolicy {
+            images_only,
+            max_size: match max_attachment_size {
+                Some(size) => Some(parse_size(size).ok_or(RuntimeError::InvalidOptions(format!(
+                    "{size} is not a valid size for --{OPTION_MAX_ATTACHMENT_SIZE}! Use a number of bytes or a size like 500KB or 50MB"
+                )))?),
+                None => None,
+            },
+            skip_types: skip_types.map(|types| parse_list(types, &['.'])).unwrap_or_default(),
+            include_mime: include_mime.map(|types| parse_list(types, &[])).unwrap_or_default(),
+        };
+
+        // Build the attachment filename template; pooled attachments are named by their content instead
+        let name_template = match attachment_name {
+            Some(template) => {
+                if matches!(attachment_manager_mode, AttachmentManagerMode::Disabled) {
+                    return Err(RuntimeError::InvalidOptions(format!(
+                        "--{OPTION_ATTACHMENT_NAME} is enabled, which requires --{OPTION_ATTACHMENT_MANAGER} to copy attachments"
+                    )));
+                }
+                if dedupe_attachments {
+                    return Err(RuntimeError::InvalidOptions(format!(
+                        "--{OPTION_ATTACHMENT_NAME} is enabled; --{OPTION_DEDUPE_ATTACHMENTS} is disallowed"
+                    )));
+                }
+                let template = AttachmentNameTemplate::parse(template).map_err(|why| {
+                    RuntimeError::InvalidOptions(format!(
+                        "Invalid --{OPTION_ATTACHMENT_NAME}: {why}"
+                    ))
+                })?;
+                // Collisions are resolved in the order attachments are copied, which a later run cannot see
+                if (incremental || resume) && !template.is_unique() {
+                    return Err(RuntimeError::InvalidOptions(format!(
+                        "--{OPTION_ATTACHMENT_NAME} must include {{rowid}} when --{OPTION_INCREMENTAL} or --{OPTION_RESUME} is enabled"
+                    )));
+                }
+                Some(template)
+            }
+            None => None,
+        };
+
+        // Build the thumbnail format; thumbnails are only displayed by HTML pages and are made from copied images
+        let thumbnails = match thumbnail_format {
+            Some(format) => {
+                if !matches!(export_type, Some(ExportType::Html | ExportType::Gallery)) {
+                    return Err(RuntimeError::InvalidOptions(format!(
+                        "--{OPTION_THUMBNAILS} is enabled, which requires --{OPTION_EXPORT_TYPE} html or gallery"
+                    )));
+                }
+                if matches!(attachment_manager_mode, AttachmentManagerMode::Disabled) {
+                    return Err(RuntimeError::InvalidOptions(format!(
+                        "--{OPTION_THUMBNAILS} is enabled, which requires --{OPTION_ATTACHMENT_MANAGER} to copy attachments"
+                    )));
+                }
+                Some(ThumbnailFormat::from_cli(format).ok_or(RuntimeError::InvalidOptions(format!(
+                    "{format} is not a valid thumbnail format! Must be one of <{SUPPORTED_THUMBNAIL_FORMATS}>"
+                )))?)
+            }
+            None => None,
+        };
+
+        // Build the conversion pool; only converting copy methods use it
+        let converts = matches!(
+            attachment_manager_mode,
+            AttachmentManagerMode::Basic | AttachmentManagerMode::Full
+        );
+        for (set, opt) in [
+            (jobs.is_some(), OPTION_JOBS),
+            (conversion_cache.is_some(), OPTION_CONVERSION_CACHE),
+            (no_conversion_cache, OPTION_NO_CONVERSION_CACHE),
+        ] {
+            if set && !converts {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "--{opt} is enabled, which requires --{OPTION_ATTACHMENT_MANAGER} basic or full"
+                )));
+            }
+        }
+        let jobs = match jobs {
+            Some(jobs) => match jobs.parse::<usize>() {
+                Ok(jobs) if jobs > 0 => jobs,
+                _ => {
+                    return Err(RuntimeError::InvalidOptions(format!(
+                        "{jobs} is not a valid number for --{OPTION_JOBS}! Use a whole number of at least 1"
+                    )));
+                }
+            },
+            None => ConversionPool::default_jobs(),
+        };
+        let cache = match (conversion_cache, no_conversion_cache) {
+            (Some(_), true) => {
+                return Err(RuntimeError::InvalidOptions(format!(
+                    "--{OPTION_NO_CONVERSION_CACHE} is enabled; --{OPTION_CONVERSION_CACHE} is disallowed"
+                )));
+            }
+            (Some(dir), false) => Some(ConversionCache::new(PathBuf::from(dir))),
+            (None, false) => Some(ConversionCache::default()),
+            (None, true) => None,
+        };
+
+        // Build the shared location format
+        let locations = match location_format {
+            Some(format) => Some(LocationFormat::from_cli(format).ok_or(RuntimeError::InvalidOptions(format!(
+                "{format} is not a valid location format! Must be one of <{SUPPORTED_LOCATION_FORMATS}>"
+            )))?),
+            None => None,
+        };
+
+        // Build the shared link format
+        let links = match link_format {
+            Some(format) => Some(LinkFormat::from_cli(format).ok_or(
+                RuntimeError::InvalidOptions(format!(
+                    "{format} is not a valid link format! Must be one of <{SUPPORTED_LINK_FORMATS}>"
+                )),
+            )?),
+            None => None,
+        };
+
+        // Build the statistics format
+        let stats = match stats_format {
+            Some(format) => Some(StatsFormat::from_cli(format).ok_or(RuntimeError::InvalidOptions(format!(
+                "{format} is not a valid statistics format! Must be one of <{SUPPORTED_STATS_FORMATS}>"
+            )))?),
+            None => None,
+        };
+        let frequency = match frequency_format {
+            Some(format) => Some(StatsFormat::from_cli(format).ok_or(RuntimeError::InvalidOptions(format!(
+                "{format} is not a valid frequency format! Must be one of <{SUPPORTED_STATS_FORMATS}>"
+            )))?),
+            None => None,
+        };
+        let latency = match latency_format {
+            Some(format) => Some(StatsFormat::from_cli(format).ok_or(RuntimeError::InvalidOptions(format!(
+                "{format} is not a valid latency format! Must be one of <{SUPPORTED_STATS_FORMATS}>"
+            )))?),
+            None => None,
+        };
+
         // Validate the provided export path
-        let export_path = validate_path(user_export_path, export_type.as_ref())?;
+        let export_path = validate_path(
+            user_export_path,
+            export_type.as_ref(),
+            incremental || resume,
+        )?;
+
+        // Only look for a metadata writer when it is needed, since most systems do not have one
+        let mut attachment_manager = AttachmentManager::from(attachment_manager_mode);
+        if embed_metadata {
+            attachment_manager.metadata_writer = MetadataWriter::determine();
+        }
+        attachment_manager.name_template = name_template;
+        attachment_manager.policy = policy;
+        attachment_manager.conversions = ConversionPool::new(jobs, cache);
 
         Ok(Options {
             db_path,
             attachment_root: attachment_root.cloned(),
-            attachment_manager: AttachmentManager::from(attachment_manager_mode),
+            attachment_manager,
             diagnostic,
             list_contacts,
             export_type,
@@ -329,6 +805,21 @@ impl Options {
             contacts_path: contacts_path.cloned().map(PathBuf::from),
             show_progress,
             images_only,
+            incremental,
+            resume,
+            watch,
+            dedupe_attachments,
+            verify: verify.map(PathBuf::from),
+            compare: compare.map(PathBuf::from),
+            thumbnails,
+            embed_metadata,
+            locations,
+            links,
+            stats,
+            frequency,
+            stopwords: stopwords.map(PathBuf::from),
+            report,
+            latency,
         })
     }
 
@@ -343,15 +834,24 @@ impl Options {
 
 /// Ensure export path is empty or does not contain files of the existing export type
 ///
+/// Incremental and resumed exports may reuse a directory that contains files of the same type,
+/// as long as a previous export recorded its state there.
+///
 /// We have to allocate a `PathBuf` here because it can be created from data owned by this function in the default state
 fn validate_path(
     export_path: Option<&String>,
     export_type: Option<&ExportType>,
+    incremental: bool,
 ) -> Result<PathBuf, RuntimeError> {
     // Build a path from the user-provided data or the default location
     let resolved_path =
         PathBuf::from(export_path.unwrap_or(&format!("{}/{DEFAULT_OUTPUT_DIR}", home())));
 
+    // Incremental exports append to the files recorded in the state file
+    if incremental && IncrementalState::path(&resolved_path).exists() {
+        return Ok(resolved_path);
+    }
+
     // If there is an export type selected, ensure we do not overwrite files of the same type
     if let Some(export_type) = export_type
         && resolved_path.exists()
@@ -418,7 +918,7 @@ fn get_command() -> Command {
             Arg::new(OPTION_EXPORT_TYPE)
             .short('f')
             .long(OPTION_EXPORT_TYPE)
-            .help("Specify a single file format to export messages into\n")
+            .help("Specify a single file format to export messages into\n`gallery` copies only images, videos, and audio into folders by person and month, with a page for each\n")
             .display_order(2)
             .value_name(SUPPORTED_FILE_TYPES),
         )
@@ -426,7 +926,7 @@ fn get_command() -> Command {
             Arg::new(OPTION_ATTACHMENT_MANAGER)
             .short('c')
             .long(OPTION_ATTACHMENT_MANAGER)
-            .help(format!("Specify an optional method to use when copying message attachments\n`clone` will copy all files without converting anything\n`basic` will copy all files and convert HEIC images to JPEG\n`full` will copy all files and convert HEIC files to JPEG, CAF to MP4, and MOV to MP4\nIf omitted, the default is `{}`\nImageMagick is required to convert images on non-macOS platforms\nffmpeg is required to convert audio on non-macOS platforms and video on all platforms\n", AttachmentManagerMode::default()))
+            .help(format!("Specify an optional method to use when copying message attachments\n`clone` will copy all files without converting anything\n`basic` will copy all files and convert HEIC images to JPEG\n`full` will copy all files and convert HEIC files to JPEG, CAF to MP4, and MOV to MP4\n`hardlink` will hard link all files without converting anything, copying files on another drive\n`symlink` will link to all files without converting anything; the export only works while the originals exist\n`reflink` will clone all files without converting anything on file systems that support copy-on-write, like APFS and Btrfs, and copy them otherwise\nIf omitted, the default is `{}`\nImageMagick is required to convert images on non-macOS platforms\nffmpeg is required to convert audio on non-macOS platforms and video on all platforms\n", AttachmentManagerMode::default()))
             .display_order(3)
             .value_name(SUPPORTED_ATTACHMENT_MANAGER_MODES),
         )
@@ -554,6 +1054,216 @@ fn get_command() -> Command {
                 .action(ArgAction::SetTrue)
                 .display_order(19),
         )
+        .arg(
+            Arg::new(OPTION_QUERY)
+                .short('q')
+                .long(OPTION_QUERY)
+                .help("Only export messages matching a boolean query
+Combine predicates with AND, OR, NOT, and parentheses; adjacent predicates are joined with AND
+Predicates: from:, chat:, date:, date<, date<=, date>, date>=, service:, text:, has:attachment, is:reply, is:edited, mime:
+Example: `-q 'from:me AND (has:attachment OR date>=2023-01-01)'`
+")
+                .display_order(20)
+                .value_name("query"),
+        )
+        .arg(
+            Arg::new(OPTION_INCREMENTAL)
+                .long(OPTION_INCREMENTAL)
+                .help("Append only new messages to an existing export\nThe export directory records the newest message written for each chat\nLater runs with this flag skip everything already exported and copy only new attachments\n")
+                .action(ArgAction::SetTrue)
+                .display_order(21),
+        )
+        .arg(
+            Arg::new(OPTION_RESUME)
+                .long(OPTION_RESUME)
+                .help("Continue an interrupted export from its last checkpoint\nMust be run with the same options and export path as the interrupted export\nFiles are rolled back to the checkpoint and attachments that were already copied are reused\n")
+                .action(ArgAction::SetTrue)
+                .display_order(22),
+        )
+        .arg(
+            Arg::new(OPTION_WATCH)
+                .long(OPTION_WATCH)
+                .help("Keep running after the export and append new messages as they arrive\nPolls the database and its write-ahead log for changes\nMessages are held back until their attachments finish downloading\n")
+                .action(ArgAction::SetTrue)
+                .display_order(23),
+        )
+        .arg(
+            Arg::new(OPTION_DEDUPE_ATTACHMENTS)
+                .long(OPTION_DEDUPE_ATTACHMENTS)
+                .help("Store each unique attachment once, named by the SHA-1 of its content\nCopies are kept in the `pool` directory and shared by every chat that references them\nRequires a --copy-method that copies attachments\n")
+                .action(ArgAction::SetTrue)
+                .display_order(24),
+        )
+        .arg(
+            Arg::new(OPTION_VERIFY)
+                .long(OPTION_VERIFY)
+                .help("Check an export directory against its manifest.json and exit\nReports every file that is missing, modified, or not listed in the manifest\n")
+                .value_name("path")
+                .display_order(25),
+        )
+        .arg(
+            Arg::new(OPTION_COMPARE)
+                .long(OPTION_COMPARE)
+                .help(format!("Compare the database at --{OPTION_DB_PATH} with a second database or iOS backup and exit\nReports, for each chat, messages present in only one of them, messages edited or unsent since the first,\nand attachments from the first that are missing from the second\nFilters such as --{OPTION_START_DATE}, --{OPTION_CONVERSATION_FILTER}, and --{OPTION_QUERY} apply to both\n"))
+                .value_name("path")
+                .display_order(26),
+        )
+        .arg(
+            Arg::new(OPTION_THUMBNAILS)
+                .long(OPTION_THUMBNAILS)
+                .help(format!("Generate downscaled previews of image attachments for HTML and gallery exports\nImages link to their full-size copies; previews are stored in a `{THUMBNAIL_DIR}` directory next to them\nRequires --{OPTION_EXPORT_TYPE} html or gallery and a --{OPTION_ATTACHMENT_MANAGER} that copies attachments\nDoes not require ImageMagick\n"))
+                .value_name(SUPPORTED_THUMBNAIL_FORMATS)
+                .display_order(27),
+        )
+        .arg(
+            Arg::new(OPTION_EMBED_METADATA)
+                .long(OPTION_EMBED_METADATA)
+                .help(format!("Write the message date, sender, and chat into copied photos and videos
+JPEG, HEIC, and PNG files get EXIF and XMP tags; MP4 and MOV files get QuickTime tags
+Requires exiftool; without it, only videos are tagged, using ffmpeg
+Requires a --{OPTION_ATTACHMENT_MANAGER} that copies attachments
+"))
+                .action(ArgAction::SetTrue)
+                .display_order(28),
+        )
+        .arg(
+            Arg::new(OPTION_ATTACHMENT_NAME)
+                .long(OPTION_ATTACHMENT_NAME)
+                .help(format!("Name copied attachments with a template instead of their database ID
+Supports {SUPPORTED_NAME_PLACEHOLDERS}
+Names that collide within a chat's folder, ignoring their extension, have the ID appended; the original extension is kept
+Must include {{rowid}} when --{OPTION_INCREMENTAL} or --{OPTION_RESUME} is enabled
+Requires a --{OPTION_ATTACHMENT_MANAGER} that copies attachments
+"))
+                .value_name("template")
+                .display_order(29),
+        )
+        .arg(
+            Arg::new(OPTION_MAX_ATTACHMENT_SIZE)
+                .long(OPTION_MAX_ATTACHMENT_SIZE)
+                .help("Skip attachments larger than a size, such as 500KB or 50MB\nSkipped attachments are shown by name and size instead of being exported\n")
+                .value_name("size")
+                .display_order(30),
+        )
+        .arg(
+            Arg::new(OPTION_SKIP_TYPES)
+                .long(OPTION_SKIP_TYPES)
+                .help("Skip attachments with any of these comma-separated file extensions, such as mov,heics\nSkipped attachments are shown by name and size instead of being exported\n")
+                .value_name("extensions")
+                .display_order(31),
+        )
+        .arg(
+            Arg::new(OPTION_INCLUDE_MIME)
+                .long(OPTION_INCLUDE_MIME)
+                .help("Only export attachments with any of these comma-separated MIME types, such as image/*,audio/*\nSkipped attachments are shown by name and size instead of being exported\nStickers are always exported\n")
+                .value_name("types")
+                .display_order(32),
+        )
+        .arg(
+            Arg::new(OPTION_JOBS)
+                .long(OPTION_JOBS)
+                .help(format!("The number of attachment conversions to run at once while messages are exported
+If omitted, the default is the number of available CPU cores
+Requires --{OPTION_ATTACHMENT_MANAGER} basic or full
+"))
+                .value_name("num")
+                .display_order(33),
+        )
+        .arg(
+            Arg::new(OPTION_CONVERSION_CACHE)
+                .long(OPTION_CONVERSION_CACHE)
+                .help(format!("Specify an optional directory to keep converted attachments in, so later exports reuse them instead of converting again
+Files are matched by their content and the converter used, so the cache is shared by every export
+The cache keeps full copies of converted attachments, and its least recently used files are removed once it holds more than 10 GB
+If omitted, the default directory is {}, which is not used for encrypted backups
+Requires --{OPTION_ATTACHMENT_MANAGER} basic or full
+", ConversionCache::default_dir().display()))
+                .value_name("path")
+                .display_order(34),
+        )
+        .arg(
+            Arg::new(OPTION_NO_CONVERSION_CACHE)
+                .long(OPTION_NO_CONVERSION_CACHE)
+                .help(format!("Convert every attachment again instead of reusing or keeping cached conversions
+Requires --{OPTION_ATTACHMENT_MANAGER} basic or full
+"))
+                .action(ArgAction::SetTrue)
+                .display_order(35),
+        )
+        .arg(
+            Arg::new(OPTION_LOCATIONS)
+                .long(OPTION_LOCATIONS)
+                .help(format!("Also export every shared location as a map file in this format
+Each chat's locations are written to the `{LOCATIONS_DIR}` directory, and all of them to a `{LOCATIONS_DIR}` file in the export root
+Only places shared from Maps have coordinates; GPX files leave out Check In, Find My, and location sharing messages
+Requires --{OPTION_EXPORT_TYPE}
+"))
+                .value_name(SUPPORTED_LOCATION_FORMATS)
+                .display_order(36),
+        )
+        .arg(
+            Arg::new(OPTION_LINKS)
+                .long(OPTION_LINKS)
+                .help(format!("Also export every shared link to a `{LINKS_FILE}.html` bookmarks file and a listing in this format
+Each distinct URL is listed once with its preview title, summary, site name, and every time it was shared
+Links are read from rich link previews, hyperlinks in formatted text, and web addresses in plain text
+Requires --{OPTION_EXPORT_TYPE}
+"))
+                .value_name(SUPPORTED_LINK_FORMATS)
+                .display_order(37),
+        )
+        .arg(
+            Arg::new(OPTION_STATS)
+                .long(OPTION_STATS)
+                .help(format!("Print message statistics for each chat and person and exit
+Counts messages, words, and attachments, and finds first and last dates, busiest days, active hours and weekdays, and reply times
+`table` prints a summary; `json` includes hour and weekday histograms and each person's statistics in every chat
+Filters such as --{OPTION_START_DATE}, --{OPTION_CONVERSATION_FILTER}, and --{OPTION_QUERY} apply
+"))
+                .value_name(SUPPORTED_STATS_FORMATS)
+                .display_order(38),
+        )
+        .arg(
+            Arg::new(OPTION_FREQUENCY)
+                .long(OPTION_FREQUENCY)
+                .help(format!("Print the most used words, phrases, emoji, and tapbacks for everyone, each person, and each chat and exit
+Links, numbers, and stopwords are left out; phrases are listed if they were used more than once
+Filters such as --{OPTION_START_DATE}, --{OPTION_END_DATE}, and --{OPTION_CONVERSATION_FILTER} apply
+"))
+                .value_name(SUPPORTED_STATS_FORMATS)
+                .display_order(39),
+        )
+        .arg(
+            Arg::new(OPTION_STOPWORDS)
+                .long(OPTION_STOPWORDS)
+                .help(format!("Path to a file of words to leave out of --{OPTION_FREQUENCY}, separated by spaces or new lines
+Replaces the built-in list of English stopwords; lines that start with `#` are ignored
+Requires --{OPTION_FREQUENCY}
+"))
+                .value_name("path")
+                .display_order(40),
+        )
+        .arg(
+            Arg::new(OPTION_REPORT)
+                .long(OPTION_REPORT)
+                .help(format!("Also write a `{REPORT_FILE}` page with charts of messages over time, activity by weekday and hour, reply times, top contacts, and attachments
+The charts are drawn without JavaScript, so the page works offline
+Requires --{OPTION_EXPORT_TYPE} html
+"))
+                .action(ArgAction::SetTrue)
+                .display_order(41),
+        )
+        .arg(
+            Arg::new(OPTION_LATENCY)
+                .long(OPTION_LATENCY)
+                .help(format!("Print delivery, read, and reply times for each chat and month, and flag long silences, then exit
+Reports the median time until messages are delivered and read, and how long each side takes to reply
+Silences are waits for a reply over an hour and ten times longer than the usual reply in that chat
+Filters such as --{OPTION_START_DATE}, --{OPTION_END_DATE}, and --{OPTION_CONVERSATION_FILTER} apply
+"))
+                .value_name(SUPPORTED_STATS_FORMATS)
+                .display_order(42),
+        )
 }
 
 fn parse_selected_chat_ids(raw: &str) -> Result<BTreeSet<i32>, RuntimeError> {
@@ -617,6 +1327,21 @@ impl Options {
             contacts_path: None,
             show_progress: true,
             images_only: false,
+            incremental: false,
+            resume: false,
+            watch: false,
+            dedupe_attachments: false,
+            verify: None,
+            compare: None,
+            thumbnails: None,
+            embed_metadata: false,
+            locations: None,
+            links: None,
+            stats: None,
+            frequency: None,
+            stopwords: None,
+            report: false,
+            latency: None,
         }
     }
 }
@@ -628,17 +1353,26 @@ pub fn from_command_line() -> ArgMatches {
 
 #[cfg(test)]
 mod arg_tests {
-    use std::collections::BTreeSet;
+    use std::{collections::BTreeSet, path::PathBuf};
 
     use imessage_database::util::{
         dirs::default_db_path, platform::Platform, query_context::QueryContext,
     };
 
-    use crate::app::{
-        compatibility::attachment_manager::{AttachmentManager, AttachmentManagerMode},
-        export_type::ExportType,
-        options::{Options, get_command, validate_path},
-        test_dir::unique_test_dir,
+    use crate::{
+        app::{
+            compatibility::{
+                attachment_manager::{AttachmentManager, AttachmentManagerMode},
+                cache::ConversionCache,
+                converters::thumbnail::ThumbnailFormat,
+                policy::AttachmentPolicy,
+            },
+            export_type::ExportType,
+            options::{Options, get_command, validate_path},
+            stats::StatsFormat,
+            test_dir::unique_test_dir,
+        },
+        exporters::{links::LinkFormat, locations::LocationFormat},
     };
 
     #[test]
@@ -658,7 +1392,7 @@ mod arg_tests {
             diagnostic: true,
             list_contacts: false,
             export_type: None,
-            export_path: validate_path(None, None).unwrap(),
+            export_path: validate_path(None, None, false).unwrap(),
             query_context: QueryContext::default(),
             no_lazy: false,
             custom_name: None,
@@ -670,6 +1404,21 @@ mod arg_tests {
             contacts_path: None,
             show_progress: true,
             images_only: false,
+            incremental: false,
+            resume: false,
+            watch: false,
+            dedupe_attachments: false,
+            verify: None,
+            compare: None,
+            thumbnails: None,
+            embed_metadata: false,
+            locations: None,
+            links: None,
+            stats: None,
+            frequency: None,
+            stopwords: None,
+            report: false,
+            latency: None,
         };
 
         assert_eq!(actual, expected);
@@ -743,7 +1492,7 @@ mod arg_tests {
             diagnostic: false,
             list_contacts: false,
             export_type: Some(ExportType::Html),
-            export_path: validate_path(Some(&dir_str), None).unwrap(),
+            export_path: validate_path(Some(&dir_str), None, false).unwrap(),
             query_context: QueryContext::default(),
             no_lazy: false,
             custom_name: None,
@@ -755,6 +1504,21 @@ mod arg_tests {
             contacts_path: None,
             show_progress: true,
             images_only: false,
+            incremental: false,
+            resume: false,
+            watch: false,
+            dedupe_attachments: false,
+            verify: None,
+            compare: None,
+            thumbnails: None,
+            embed_metadata: false,
+            locations: None,
+            links: None,
+            stats: None,
+            frequency: None,
+            stopwords: None,
+            report: false,
+            latency: None,
         };
 
         assert_eq!(actual, expected);
@@ -777,7 +1541,7 @@ mod arg_tests {
             diagnostic: false,
             list_contacts: false,
             export_type: Some(ExportType::Txt),
-            export_path: validate_path(None, None).unwrap(),
+            export_path: validate_path(None, None, false).unwrap(),
             query_context: QueryContext::default(),
             no_lazy: true,
             custom_name: None,
@@ -789,6 +1553,21 @@ mod arg_tests {
             contacts_path: None,
             show_progress: true,
             images_only: false,
+            incremental: false,
+            resume: false,
+            watch: false,
+            dedupe_attachments: false,
+            verify: None,
+            compare: None,
+            thumbnails: None,
+            embed_metadata: false,
+            locations: None,
+            links: None,
+            stats: None,
+            frequency: None,
+            stopwords: None,
+            report: false,
+            latency: None,
         };
 
         assert_eq!(actual, expected);
@@ -860,7 +1639,7 @@ mod arg_tests {
             diagnostic: false,
             list_contacts: false,
             export_type: Some(ExportType::Txt),
-            export_path: validate_path(None, None).unwrap(),
+            export_path: validate_path(None, None, false).unwrap(),
             query_context: QueryContext::default(),
             no_lazy: false,
             custom_name: None,
@@ -872,6 +1651,21 @@ mod arg_tests {
             contacts_path: None,
             show_progress: true,
             images_only: false,
+            incremental: false,
+            resume: false,
+            watch: false,
+            dedupe_attachments: false,
+            verify: None,
+            compare: None,
+            thumbnails: None,
+            embed_metadata: false,
+            locations: None,
+            links: None,
+            stats: None,
+            frequency: None,
+            stopwords: None,
+            report: false,
+            latency: None,
         };
 
         assert_eq!(actual, expected);
@@ -902,7 +1696,7 @@ mod arg_tests {
             diagnostic: false,
             list_contacts: false,
             export_type: Some(ExportType::Txt),
-            export_path: validate_path(None, None).unwrap(),
+            export_path: validate_path(None, None, false).unwrap(),
             query_context: QueryContext::default(),
             no_lazy: false,
             custom_name: None,
@@ -914,6 +1708,21 @@ mod arg_tests {
             contacts_path: None,
             show_progress: true,
             images_only: false,
+            incremental: false,
+            resume: false,
+            watch: false,
+            dedupe_attachments: false,
+            verify: None,
+            compare: None,
+            thumbnails: None,
+            embed_metadata: false,
+            locations: None,
+            links: None,
+            stats: None,
+            frequency: None,
+            stopwords: None,
+            report: false,
+            latency: None,
         };
 
         assert_eq!(actual, expected);
@@ -960,7 +1769,7 @@ mod arg_tests {
             diagnostic: false,
             list_contacts: false,
             export_type: Some(ExportType::Txt),
-            export_path: validate_path(None, None).unwrap(),
+            export_path: validate_path(None, None, false).unwrap(),
             query_context: QueryContext::default(),
             no_lazy: false,
             custom_name: Some("Name".to_string()),
@@ -972,6 +1781,21 @@ mod arg_tests {
             contacts_path: None,
             show_progress: true,
             images_only: false,
+            incremental: false,
+            resume: false,
+            watch: false,
+            dedupe_attachments: false,
+            verify: None,
+            compare: None,
+            thumbnails: None,
+            embed_metadata: false,
+            locations: None,
+            links: None,
+            stats: None,
+            frequency: None,
+            stopwords: None,
+            report: false,
+            latency: None,
         };
 
         assert_eq!(actual, expected);
@@ -994,7 +1818,7 @@ mod arg_tests {
             diagnostic: false,
             list_contacts: false,
             export_type: Some(ExportType::Txt),
-            export_path: validate_path(None, None).unwrap(),
+            export_path: validate_path(None, None, false).unwrap(),
             query_context: QueryContext::default(),
             no_lazy: false,
             custom_name: None,
@@ -1006,6 +1830,21 @@ mod arg_tests {
             contacts_path: None,
             show_progress: true,
             images_only: false,
+            incremental: false,
+            resume: false,
+            watch: false,
+            dedupe_attachments: false,
+            verify: None,
+            compare: None,
+            thumbnails: None,
+            embed_metadata: false,
+            locations: None,
+            links: None,
+            stats: None,
+            frequency: None,
+            stopwords: None,
+            report: false,
+            latency: None,
         };
 
         assert_eq!(actual, expected);
@@ -1029,7 +1868,7 @@ mod arg_tests {
             diagnostic: false,
             list_contacts: false,
             export_type: Some(ExportType::Txt),
-            export_path: validate_path(None, None).unwrap(),
+            export_path: validate_path(None, None, false).unwrap(),
             query_context: QueryContext::default(),
             no_lazy: false,
             custom_name: None,
@@ -1041,6 +1880,21 @@ mod arg_tests {
             contacts_path: None,
             show_progress: true,
             images_only: false,
+            incremental: false,
+            resume: false,
+            watch: false,
+            dedupe_attachments: false,
+            verify: None,
+            compare: None,
+            thumbnails: None,
+            embed_metadata: false,
+            locations: None,
+            links: None,
+            stats: None,
+            frequency: None,
+            stopwords: None,
+            report: false,
+            latency: None,
         };
 
         assert_eq!(actual, expected);
@@ -1063,7 +1917,7 @@ mod arg_tests {
             diagnostic: false,
             list_contacts: false,
             export_type: Some(ExportType::Txt),
-            export_path: validate_path(None, None).unwrap(),
+            export_path: validate_path(None, None, false).unwrap(),
             query_context: QueryContext::default(),
             no_lazy: false,
             custom_name: None,
@@ -1075,6 +1929,21 @@ mod arg_tests {
             contacts_path: None,
             show_progress: true,
             images_only: false,
+            incremental: false,
+            resume: false,
+            watch: false,
+            dedupe_attachments: false,
+            verify: None,
+            compare: None,
+            thumbnails: None,
+            embed_metadata: false,
+            locations: None,
+            links: None,
+            stats: None,
+            frequency: None,
+            stopwords: None,
+            report: false,
+            latency: None,
         };
 
         assert_eq!(actual, expected);
@@ -1097,7 +1966,7 @@ mod arg_tests {
             diagnostic: false,
             list_contacts: false,
             export_type: Some(ExportType::Txt),
-            export_path: validate_path(None, None).unwrap(),
+            export_path: validate_path(None, None, false).unwrap(),
             query_context: QueryContext::default(),
             no_lazy: false,
             custom_name: None,
@@ -1109,6 +1978,21 @@ mod arg_tests {
             contacts_path: None,
             show_progress: true,
             images_only: false,
+            incremental: false,
+            resume: false,
+            watch: false,
+            dedupe_attachments: false,
+            verify: None,
+            compare: None,
+            thumbnails: None,
+            embed_metadata: false,
+            locations: None,
+            links: None,
+            stats: None,
+            frequency: None,
+            stopwords: None,
+            report: false,
+            latency: None,
         };
 
         assert_eq!(actual, expected);
@@ -1223,7 +2107,7 @@ mod arg_tests {
             diagnostic: false,
             list_contacts: false,
             export_type: Some(ExportType::Txt),
-            export_path: validate_path(None, None).unwrap(),
+            export_path: validate_path(None, None, false).unwrap(),
             query_context: QueryContext::default(),
             no_lazy: false,
             custom_name: None,
@@ -1235,6 +2119,21 @@ mod arg_tests {
             contacts_path: None,
             show_progress: true,
             images_only: false,
+            incremental: false,
+            resume: false,
+            watch: false,
+            dedupe_attachments: false,
+            verify: None,
+            compare: None,
+            thumbnails: None,
+            embed_metadata: false,
+            locations: None,
+            links: None,
+            stats: None,
+            frequency: None,
+            stopwords: None,
+            report: false,
+            latency: None,
         };
 
         assert_eq!(actual, expected);
@@ -1303,59 +2202,732 @@ mod arg_tests {
         let args = get_command().get_matches_from(["imessage-exporter", "-d", "--no-progress"]);
         assert!(Options::from_args(&args).is_err());
     }
-}
-
-#[cfg(test)]
-mod path_tests {
-    use std::fs;
-    use std::io::Write;
-    use std::path::PathBuf;
-
-    use crate::app::{
-        export_type::ExportType,
-        options::{DEFAULT_OUTPUT_DIR, validate_path},
-        test_dir::unique_test_dir,
-    };
-
-    use imessage_database::util::dirs::home;
 
     #[test]
-    fn can_validate_empty() {
-        let dir = unique_test_dir("validate-empty");
-        let dir_str = dir.to_string_lossy().into_owned();
-        let export_path = Some(&dir_str);
-        let export_type = Some(ExportType::Txt);
+    fn can_build_option_incremental() {
+        let args =
+            get_command().get_matches_from(["imessage-exporter", "-f", "txt", "--incremental"]);
+        let actual = Options::from_args(&args).unwrap();
+        assert!(actual.incremental);
+    }
 
-        let result = validate_path(export_path, export_type.as_ref());
+    #[test]
+    fn cant_build_option_incremental_no_export_type() {
+        let args = get_command().get_matches_from(["imessage-exporter", "--incremental"]);
+        assert!(Options::from_args(&args).is_err());
+    }
 
-        assert_eq!(result.unwrap(), dir);
+    #[test]
+    fn can_build_option_resume() {
+        let args = get_command().get_matches_from(["imessage-exporter", "-f", "txt", "--resume"]);
+        let actual = Options::from_args(&args).unwrap();
+        assert!(actual.resume);
     }
 
     #[test]
-    fn can_validate_different_type() {
-        let dir = unique_test_dir("validate-different-type");
-        let dir_str = dir.to_string_lossy().into_owned();
-        let export_path = Some(&dir_str);
-        let export_type = Some(ExportType::Txt);
+    fn cant_build_option_resume_with_diagnostics() {
+        let args = get_command().get_matches_from(["imessage-exporter", "-d", "--resume"]);
+        assert!(Options::from_args(&args).is_err());
+    }
 
-        let result = validate_path(export_path, export_type.as_ref());
+    #[test]
+    fn can_build_option_watch() {
+        let args = get_command().get_matches_from(["imessage-exporter", "-f", "txt", "--watch"]);
+        let actual = Options::from_args(&args).unwrap();
+        assert!(actual.watch);
+    }
 
-        let mut fake = dir.clone();
-        fake.push("fake1.html");
-        let mut file = fs::File::create(&fake).unwrap();
-        file.write_all(&[]).unwrap();
+    #[test]
+    fn cant_build_option_watch_ios() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "-a",
+            "iOS",
+            "--watch",
+        ]);
+        assert!(Options::from_args(&args).is_err());
+    }
 
-        assert_eq!(result.unwrap(), dir);
+    #[test]
+    fn cant_build_option_watch_no_export_type() {
+        let args = get_command().get_matches_from(["imessage-exporter", "--watch"]);
+        assert!(Options::from_args(&args).is_err());
     }
 
     #[test]
-    fn can_validate_same_type() {
-        let dir = unique_test_dir("validate-same-type");
-        let dir_str = dir.to_string_lossy().into_owned();
-        let export_path = Some(&dir_str);
+    fn can_build_option_dedupe_attachments() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "-c",
+            "clone",
+            "--dedupe-attachments",
+        ]);
+        let actual = Options::from_args(&args).unwrap();
+        assert!(actual.dedupe_attachments);
+    }
+
+    #[test]
+    fn cant_build_option_dedupe_attachments_without_copying() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--dedupe-attachments",
+        ]);
+        assert!(Options::from_args(&args).is_err());
+    }
+
+    #[test]
+    fn can_build_option_verify() {
+        let args = get_command().get_matches_from(["imessage-exporter", "--verify", "export"]);
+        let actual = Options::from_args(&args).unwrap();
+        assert_eq!(actual.verify, Some(PathBuf::from("export")));
+    }
+
+    #[test]
+    fn cant_build_option_verify_with_export_type() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--verify",
+            "export",
+        ]);
+        assert!(Options::from_args(&args).is_err());
+    }
+
+    #[test]
+    fn can_build_option_compare() {
+        let args = get_command().get_matches_from(["imessage-exporter", "--compare", "old.db"]);
+        let actual = Options::from_args(&args).unwrap();
+        assert_eq!(actual.compare, Some(PathBuf::from("old.db")));
+    }
+
+    #[test]
+    fn can_build_option_compare_with_filters() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "--compare",
+            "old.db",
+            "-s",
+            "2020-01-01",
+            "--query",
+            "from:me",
+        ]);
+        let actual = Options::from_args(&args).unwrap();
+        assert!(actual.query_context.start.is_some());
+        assert!(actual.query_context.expression.is_some());
+    }
+
+    #[test]
+    fn cant_build_option_compare_with_export_type() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "-f",
+            "html",
+            "--compare",
+            "old.db",
+        ]);
+        assert!(Options::from_args(&args).is_err());
+    }
+
+    #[test]
+    fn can_build_option_thumbnails() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-c",
+            "clone",
+            "--thumbnails",
+            "webp",
+        ]);
+        let actual = Options::from_args(&args).unwrap();
+        assert_eq!(actual.thumbnails, Some(ThumbnailFormat::Webp));
+    }
+
+    #[test]
+    fn cant_build_option_thumbnails_txt() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "-c",
+            "clone",
+            "--thumbnails",
+            "jpeg",
+        ]);
+        assert!(Options::from_args(&args).is_err());
+    }
+
+    #[test]
+    fn cant_build_option_thumbnails_without_copying() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "-f",
+            "html",
+            "--thumbnails",
+            "jpeg",
+        ]);
+        assert!(Options::from_args(&args).is_err());
+    }
+
+    #[test]
+    fn cant_build_option_thumbnails_invalid_format() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-c",
+            "clone",
+            "--thumbnails",
+            "png",
+        ]);
+        assert!(Options::from_args(&args).is_err());
+    }
+
+    #[test]
+    fn can_build_option_embed_metadata() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-c",
+            "clone",
+            "--embed-metadata",
+        ]);
+        let actual = Options::from_args(&args).unwrap();
+        assert!(actual.embed_metadata);
+    }
+
+    #[test]
+    fn cant_build_option_embed_metadata_without_copying() {
+        let args =
+            get_command().get_matches_from(["imessage-exporter", "-f", "html", "--embed-metadata"]);
+        assert!(Options::from_args(&args).is_err());
+    }
+
+    #[test]
+    fn cant_build_option_embed_metadata_dedupe() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-c",
+            "clone",
+            "--dedupe-attachments",
+            "--embed-metadata",
+        ]);
+        assert!(Options::from_args(&args).is_err());
+    }
+
+    #[test]
+    fn can_build_option_attachment_name() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-c",
+            "clone",
+            "--attachment-name",
+            "{date} {sender} {original_name}",
+        ]);
+        let actual = Options::from_args(&args).unwrap();
+        assert_eq!(
+            actual
+                .attachment_manager
+                .name_template
+                .map(|template| template.to_string()),
+            Some("{date} {sender} {original_name}".to_string())
+        );
+    }
+
+    #[test]
+    fn cant_build_option_attachment_name_invalid() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-c",
+            "clone",
+            "--attachment-name",
+            "{name}",
+        ]);
+        assert!(Options::from_args(&args).is_err());
+    }
+
+    #[test]
+    fn cant_build_option_attachment_name_incremental_without_rowid() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-c",
+            "clone",
+            "--incremental",
+            "--attachment-name",
+            "{date}",
+        ]);
+        assert!(Options::from_args(&args).is_err());
+    }
+
+    #[test]
+    fn can_build_option_hardlink() {
+        let args =
+            get_command().get_matches_from(["imessage-exporter", "-f", "html", "-c", "hardlink"]);
+        let actual = Options::from_args(&args).unwrap();
+        assert_eq!(
+            actual.attachment_manager.mode,
+            AttachmentManagerMode::Hardlink
+        );
+    }
+
+    #[test]
+    fn can_build_option_jobs() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-c",
+            "full",
+            "--jobs",
+            "3",
+        ]);
+        let actual = Options::from_args(&args).unwrap();
+        assert_eq!(actual.attachment_manager.conversions.jobs(), 3);
+    }
+
+    #[test]
+    fn cant_build_option_jobs_without_converting() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-c",
+            "clone",
+            "--jobs",
+            "3",
+        ]);
+        assert!(Options::from_args(&args).is_err());
+    }
+
+    #[test]
+    fn can_build_option_conversion_cache() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-c",
+            "basic",
+            "--conversion-cache",
+            "/tmp/cache",
+        ]);
+        let actual = Options::from_args(&args).unwrap();
+        assert_eq!(
+            actual.attachment_manager.conversions.cache(),
+            Some(&ConversionCache::new(PathBuf::from("/tmp/cache")))
+        );
+    }
+
+    #[test]
+    fn can_build_option_no_conversion_cache() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-c",
+            "full",
+            "--no-conversion-cache",
+        ]);
+        let actual = Options::from_args(&args).unwrap();
+        assert_eq!(actual.attachment_manager.conversions.cache(), None);
+    }
+
+    #[test]
+    fn cant_build_option_conversion_cache_disabled() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-c",
+            "full",
+            "--conversion-cache",
+            "/tmp/cache",
+            "--no-conversion-cache",
+        ]);
+        assert!(Options::from_args(&args).is_err());
+    }
+
+    #[test]
+    fn can_build_option_locations() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "--locations",
+            "KML",
+        ]);
+        let actual = Options::from_args(&args).unwrap();
+        assert_eq!(actual.locations, Some(LocationFormat::Kml));
+    }
+
+    #[test]
+    fn cant_build_option_locations_invalid() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "-f",
+            "html",
+            "--locations",
+            "shapefile",
+        ]);
+        assert!(Options::from_args(&args).is_err());
+    }
+
+    #[test]
+    fn cant_build_option_locations_without_format() {
+        let args = get_command().get_matches_from(["imessage-exporter", "--locations", "geojson"]);
+        assert!(Options::from_args(&args).is_err());
+    }
+
+    #[test]
+    fn can_build_option_links() {
+        let args =
+            get_command().get_matches_from(["imessage-exporter", "-f", "html", "--links", "CSV"]);
+        let actual = Options::from_args(&args).unwrap();
+        assert_eq!(actual.links, Some(LinkFormat::Csv));
+    }
+
+    #[test]
+    fn cant_build_option_links_invalid() {
+        let args =
+            get_command().get_matches_from(["imessage-exporter", "-f", "txt", "--links", "xml"]);
+        assert!(Options::from_args(&args).is_err());
+    }
+
+    #[test]
+    fn cant_build_option_links_without_format() {
+        let args = get_command().get_matches_from(["imessage-exporter", "--links", "json"]);
+        assert!(Options::from_args(&args).is_err());
+    }
+
+    #[test]
+    fn can_build_option_stats() {
+        let args = get_command().get_matches_from(["imessage-exporter", "--stats", "JSON"]);
+        let actual = Options::from_args(&args).unwrap();
+        assert_eq!(actual.stats, Some(StatsFormat::Json));
+    }
+
+    #[test]
+    fn can_build_option_stats_with_filters() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "--stats",
+            "table",
+            "-s",
+            "2020-01-01",
+            "-t",
+            "Jane",
+        ]);
+        let actual = Options::from_args(&args).unwrap();
+        assert!(actual.query_context.start.is_some());
+        assert_eq!(actual.conversation_filter, Some("Jane".to_string()));
+    }
+
+    #[test]
+    fn cant_build_option_stats_invalid() {
+        let args = get_command().get_matches_from(["imessage-exporter", "--stats", "csv"]);
+        assert!(Options::from_args(&args).is_err());
+    }
+
+    #[test]
+    fn cant_build_option_stats_with_export_type() {
+        let args =
+            get_command().get_matches_from(["imessage-exporter", "-f", "txt", "--stats", "table"]);
+        assert!(Options::from_args(&args).is_err());
+    }
+
+    #[test]
+    fn can_build_option_frequency() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "--frequency",
+            "table",
+            "--stopwords",
+            "stopwords.txt",
+            "-s",
+            "2023-01-01",
+            "-e",
+            "2024-01-01",
+        ]);
+        let actual = Options::from_args(&args).unwrap();
+        assert_eq!(actual.frequency, Some(StatsFormat::Table));
+        assert_eq!(actual.stopwords, Some(PathBuf::from("stopwords.txt")));
+        assert!(actual.query_context.start.is_some());
+        assert!(actual.query_context.end.is_some());
+    }
+
+    #[test]
+    fn cant_build_option_frequency_with_stats() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "--frequency",
+            "json",
+            "--stats",
+            "json",
+        ]);
+        assert!(Options::from_args(&args).is_err());
+    }
+
+    #[test]
+    fn can_build_option_report() {
+        let args = get_command().get_matches_from(["imessage-exporter", "-f", "html", "--report"]);
+        let actual = Options::from_args(&args).unwrap();
+        assert!(actual.report);
+    }
+
+    #[test]
+    fn cant_build_option_report_txt() {
+        let args = get_command().get_matches_from(["imessage-exporter", "-f", "txt", "--report"]);
+        assert!(Options::from_args(&args).is_err());
+    }
+
+    #[test]
+    fn cant_build_option_report_without_format() {
+        let args = get_command().get_matches_from(["imessage-exporter", "--report"]);
+        assert!(Options::from_args(&args).is_err());
+    }
+
+    #[test]
+    fn can_build_option_latency() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "--latency",
+            "json",
+            "-t",
+            "+15558675309",
+        ]);
+        let actual = Options::from_args(&args).unwrap();
+        assert_eq!(actual.latency, Some(StatsFormat::Json));
+        assert!(actual.conversation_filter.is_some());
+    }
+
+    #[test]
+    fn cant_build_option_latency_with_format() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "--latency",
+            "table",
+            "-f",
+            "txt",
+        ]);
+        assert!(Options::from_args(&args).is_err());
+    }
+
+    #[test]
+    fn cant_build_option_latency_invalid() {
+        let args = get_command().get_matches_from(["imessage-exporter", "--latency", "csv"]);
+        assert!(Options::from_args(&args).is_err());
+    }
+
+    #[test]
+    fn cant_build_option_stopwords_without_frequency() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "--stats",
+            "table",
+            "--stopwords",
+            "stopwords.txt",
+        ]);
+        assert!(Options::from_args(&args).is_err());
+    }
+
+    #[test]
+    fn cant_build_option_jobs_invalid() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-c",
+            "basic",
+            "--jobs",
+            "0",
+        ]);
+        assert!(Options::from_args(&args).is_err());
+    }
+
+    #[test]
+    fn cant_build_option_embed_metadata_symlink() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-c",
+            "symlink",
+            "--embed-metadata",
+        ]);
+        assert!(Options::from_args(&args).is_err());
+    }
+
+    #[test]
+    fn can_build_option_attachment_policy() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "-f",
+            "html",
+            "-v",
+            "--max-attachment-size",
+            "50MB",
+            "--skip-types",
+            ".MOV, heics",
+            "--include-mime",
+            "image/*,audio/*",
+        ]);
+        let actual = Options::from_args(&args).unwrap();
+        assert_eq!(
+            actual.attachment_manager.policy,
+            AttachmentPolicy {
+                images_only: true,
+                max_size: Some(50 * 1024 * 1024),
+                skip_types: vec!["mov".to_string(), "heics".to_string()],
+                include_mime: vec!["image/*".to_string(), "audio/*".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn cant_build_option_max_attachment_size_invalid() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "-f",
+            "html",
+            "--max-attachment-size",
+            "large",
+        ]);
+        assert!(Options::from_args(&args).is_err());
+    }
+
+    #[test]
+    fn cant_build_option_skip_types_without_format() {
+        let args = get_command().get_matches_from(["imessage-exporter", "--skip-types", "mov"]);
+        assert!(Options::from_args(&args).is_err());
+    }
+
+    #[test]
+    fn can_build_option_gallery() {
+        let args = get_command().get_matches_from(["imessage-exporter", "-f", "gallery"]);
+        let actual = Options::from_args(&args).unwrap();
+        assert_eq!(actual.export_type, Some(ExportType::Gallery));
+        assert_eq!(actual.attachment_manager.mode, AttachmentManagerMode::Basic);
+    }
+
+    #[test]
+    fn cant_build_option_gallery_without_copying() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "-f",
+            "gallery",
+            "-c",
+            "disabled",
+        ]);
+        assert!(Options::from_args(&args).is_err());
+    }
+
+    #[test]
+    fn cant_build_option_gallery_incremental() {
+        let args =
+            get_command().get_matches_from(["imessage-exporter", "-f", "gallery", "--incremental"]);
+        assert!(Options::from_args(&args).is_err());
+    }
+
+    #[test]
+    fn can_build_option_query() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "-q",
+            "from:me AND has:attachment",
+        ]);
+        let actual = Options::from_args(&args).unwrap();
+        assert!(actual.query_context.expression.is_some());
+        assert!(actual.query_context.has_filters());
+    }
+
+    #[test]
+    fn cant_build_option_invalid_query() {
+        let args = get_command().get_matches_from([
+            "imessage-exporter",
+            "-f",
+            "txt",
+            "-q",
+            "from:me AND (",
+        ]);
+        assert!(Options::from_args(&args).is_err());
+    }
+
+    #[test]
+    fn cant_build_option_query_no_export_type() {
+        let args = get_command().get_matches_from(["imessage-exporter", "-q", "from:me"]);
+        assert!(Options::from_args(&args).is_err());
+    }
+}
+
+#[cfg(test)]
+mod path_tests {
+    use std::fs;
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    use crate::app::{
+        export_type::ExportType,
+        incremental::STATE_FILENAME,
+        options::{DEFAULT_OUTPUT_DIR, validate_path},
+        test_dir::unique_test_dir,
+    };
+
+    use imessage_database::util::dirs::home;
+
+    #[test]
+    fn can_validate_empty() {
+        let dir = unique_test_dir("validate-empty");
+        let dir_str = dir.to_string_lossy().into_owned();
+        let export_path = Some(&dir_str);
         let export_type = Some(ExportType::Txt);
 
-        let result = validate_path(export_path, export_type.as_ref());
+        let result = validate_path(export_path, export_type.as_ref(), false);
+
+        assert_eq!(result.unwrap(), dir);
+    }
+
+    #[test]
+    fn can_validate_different_type() {
+        let dir = unique_test_dir("validate-different-type");
+        let dir_str = dir.to_string_lossy().into_owned();
+        let export_path = Some(&dir_str);
+        let export_type = Some(ExportType::Txt);
+
+        let result = validate_path(export_path, export_type.as_ref(), false);
+
+        let mut fake = dir.clone();
+        fake.push("fake1.html");
+        let mut file = fs::File::create(&fake).unwrap();
+        file.write_all(&[]).unwrap();
+
+        assert_eq!(result.unwrap(), dir);
+    }
+
+    #[test]
+    fn can_validate_same_type() {
+        let dir = unique_test_dir("validate-same-type");
+        let dir_str = dir.to_string_lossy().into_owned();
+        let export_path = Some(&dir_str);
+        let export_type = Some(ExportType::Txt);
+
+        let result = validate_path(export_path, export_type.as_ref(), false);
 
         let mut fake = dir.clone();
         fake.push("fake2.txt");
@@ -1365,12 +2937,40 @@ mod path_tests {
         assert_eq!(result.unwrap(), dir);
     }
 
+    #[test]
+    fn cant_validate_same_type_existing() {
+        let dir = unique_test_dir("validate-same-type-existing");
+        let dir_str = dir.to_string_lossy().into_owned();
+        fs::File::create(dir.join("fake3.txt")).unwrap();
+
+        let export_type = Some(ExportType::Txt);
+
+        assert!(validate_path(Some(&dir_str), export_type.as_ref(), false).is_err());
+        assert!(validate_path(Some(&dir_str), export_type.as_ref(), true).is_err());
+    }
+
+    #[test]
+    fn can_validate_same_type_incremental() {
+        let dir = unique_test_dir("validate-same-type-incremental");
+        let dir_str = dir.to_string_lossy().into_owned();
+        fs::File::create(dir.join("fake4.txt")).unwrap();
+        fs::File::create(dir.join(STATE_FILENAME)).unwrap();
+
+        let export_type = Some(ExportType::Txt);
+
+        assert!(validate_path(Some(&dir_str), export_type.as_ref(), false).is_err());
+        assert_eq!(
+            validate_path(Some(&dir_str), export_type.as_ref(), true).unwrap(),
+            dir
+        );
+    }
+
     #[test]
     fn can_validate_none() {
         let export_path = None;
         let export_type = None;
 
-        let result = validate_path(export_path, export_type);
+        let result = validate_path(export_path, export_type, false);
 
         assert_eq!(
             result.unwrap(),
@@ -30,11 +30,26 @@ use imessage_database::{
 use crate::{
     HTML, TXT,
     app::{
-        compatibility::attachment_manager::AttachmentManagerMode, contacts::Name,
-        data_source::DataSource, error::RuntimeError, export_type::ExportType, options::Options,
+        compatibility::{
+            attachment_manager::AttachmentManagerMode,
+            cache::ConversionCache,
+            converters::{common::same_device, metadata::MediaMetadata},
+        },
+        contacts::Name,
+        data_source::DataSource,
+        error::RuntimeError,
+        export_type::ExportType,
+        frequency::Frequency,
+        latency::Latency,
+        options::Options,
         sanitizers::sanitize_filename,
+        stats::Statistics,
+        watch::DatabaseWatcher,
+    },
+    exporters::{
+        gallery::Gallery,
+        shared::driver::{ExportState, run_export, watch_export},
     },
-    exporters::shared::driver::run_export,
 };
 
 // Maximum length for filenames
@@ -114,6 +129,19 @@ impl Config {
         }
     }
 
+    /// Determine if the cached chats and participants include everything needed to render a message
+    pub fn is_cached(&self, message: &Message) -> bool {
+        let chat_cached = message
+            .chat_id
+            .or(message.deleted_from)
+            .is_none_or(|chat_id| self.chatrooms.contains_key(&chat_id));
+        let handle_cached = message.is_from_me
+            || message.handle_id.is_none_or(|handle_id| {
+                handle_id == 0 || self.real_participants.contains_key(&handle_id)
+            });
+        chat_cached && handle_cached
+    }
+
     /// Get the attachment path for the current session
     pub fn attachment_path(&self) -> PathBuf {
         let mut path = self.options.export_path.clone();
@@ -213,6 +241,14 @@ impl Config {
         sanitize_filename(&filename)
     }
 
+    /// Get the filename for the chat a message belongs to, or [`ORPHANED`] if it has none
+    pub(crate) fn chat_filename(&self, message: &Message) -> String {
+        match self.conversation(message) {
+            Some((chatroom, _)) => self.filename(chatroom),
+            None => ORPHANED.to_string(),
+        }
+    }
+
     /// Generate a filename from a set of participants, truncating if the name is too long
     ///
     /// - All names:
@@ -259,45 +295,67 @@ impl Config {
     // MARK: Init
     /// Create a new instance of the application
     ///
-    pub fn new(options: Options) -> Result<Config, RuntimeError> {
+    pub fn new(mut options: Options) -> Result<Config, RuntimeError> {
         let data_source = DataSource::from(&options)?;
 
+        // Attachments decrypted from a backup are only cached in a directory the user chose
+        let conversions = &mut options.attachment_manager.conversions;
+        if data_source
+            .backup
+            .as_ref()
+            .is_some_and(|backup| backup.is_encrypted())
+            && conversions.cache().is_some_and(ConversionCache::is_default)
+        {
+            conversions.disable_cache();
+        }
+
+        let mut config = Config {
+            chatrooms: HashMap::new(),
+            real_chatrooms: HashMap::new(),
+            chatroom_participants: HashMap::new(),
+            real_participants: HashMap::new(),
+            participants: HashMap::new(),
+            tapbacks: HashMap::new(),
+            translated_messages: HashSet::new(),
+            options,
+            offset: get_offset(),
+            data_source,
+        };
+        config.build_caches()?;
+        Ok(config)
+    }
+
+    /// Cache the chats, participants, tapbacks, and translations from the database
+    fn build_caches(&mut self) -> Result<(), RuntimeError> {
+        let db = self.data_source.db();
+
         eprintln!("Building cache...");
         eprintln!("  [1/5] Caching chats...");
-        let chatrooms = Chat::cache(data_source.db())?;
+        self.chatrooms = Chat::cache(db)?;
 
         eprintln!("  [2/5] Caching chatrooms...");
-        let chatroom_participants = ChatToHandle::cache(data_source.db())?;
-        let chat_handle_lookup = ChatToHandle::get_chat_lookup_map(data_source.db())?;
-        let real_chatrooms = ChatToHandle::dedupe(&chatroom_participants, &chat_handle_lookup)?;
+        self.chatroom_participants = ChatToHandle::cache(db)?;
+        let chat_handle_lookup = ChatToHandle::get_chat_lookup_map(db)?;
+        self.real_chatrooms =
+            ChatToHandle::dedupe(&self.chatroom_participants, &chat_handle_lookup)?;
 
         eprintln!("  [3/5] Caching participants...");
-        let participants = Handle::cache(data_source.db())?;
-        let real_participants = Handle::dedupe(&participants);
-        let participants_map = data_source
+        let participants = Handle::cache(db)?;
+        self.real_participants = Handle::dedupe(&participants);
+        self.participants = self
+            .data_source
             .contacts_index
-            .build_participants_map(&participants, &real_participants);
+            .build_participants_map(&participants, &self.real_participants);
 
         eprintln!("  [4/5] Caching tapbacks...");
-        let tapbacks = Message::cache(data_source.db())?;
+        self.tapbacks = Message::cache(db)?;
 
         eprintln!("  [5/5] Caching translations...");
         // Translations are not available in older database versions, so we default to an empty set
-        let translated_messages = Message::cache_translations(data_source.db()).unwrap_or_default();
+        self.translated_messages = Message::cache_translations(db).unwrap_or_default();
         eprintln!("Cache built!");
 
-        Ok(Config {
-            chatrooms,
-            real_chatrooms,
-            chatroom_participants,
-            real_participants,
-            participants: participants_map,
-            tapbacks,
-            translated_messages,
-            options,
-            offset: get_offset(),
-            data_source,
-        })
+        Ok(())
     }
 
     // MARK: Filters
@@ -448,6 +506,75 @@ impl Config {
         }
     }
 
+    /// Resolve the participant and chat names in the `--query` expression, if provided, to database IDs
+    ///
+    /// Chats that were deduplicated into the same conversation are all selected, so a `chat:`
+    /// predicate matches every copy of the conversation.
+    pub(crate) fn resolve_query_expression(&mut self) {
+        let Some(mut expression) = self.options.query_context.expression.take() else {
+            return;
+        };
+
+        expression.resolve_names(
+            &mut |name: &str| self.resolve_query_sender(name),
+            &mut |name: &str| self.resolve_query_chat(name),
+        );
+
+        self.options.query_context.expression = Some(expression);
+    }
+
+    /// Find the handle IDs for a participant name, number, or email used in a `from:` predicate
+    fn resolve_query_sender(&self, name: &str) -> BTreeSet<i32> {
+        let clean_filter = self.normalize_identifier(name);
+        let mut handle_ids = BTreeSet::new();
+
+        for participant in self.participants.values() {
+            let clean_handle = self.normalize_identifier(&participant.details);
+            if self.identifiers_match(&clean_filter, &clean_handle) || participant.contains(name) {
+                handle_ids.extend(&participant.handle_ids);
+            }
+        }
+
+        if handle_ids.is_empty() {
+            eprintln!("No participants were found matching `{name}` in the query.");
+        }
+        handle_ids
+    }
+
+    /// Find the chat IDs for a chat name, identifier, or `ROWID` used in a `chat:` predicate
+    fn resolve_query_chat(&self, name: &str) -> BTreeSet<i32> {
+        let clean_filter = self.normalize_identifier(name);
+        let rowid = name.parse::<i32>().ok();
+
+        let matched_real_ids: BTreeSet<i32> = self
+            .chatrooms
+            .values()
+            .filter(|chat| {
+                rowid == Some(chat.rowid)
+                    || chat
+                        .display_name()
+                        .is_some_and(|display_name| display_name.eq_ignore_ascii_case(name))
+                    || self.identifiers_match(
+                        &clean_filter,
+                        &self.normalize_identifier(&chat.chat_identifier),
+                    )
+            })
+            .filter_map(|chat| self.real_chatrooms.get(&chat.rowid).copied())
+            .collect();
+
+        let chat_ids: BTreeSet<i32> = self
+            .real_chatrooms
+            .iter()
+            .filter(|(_, real_id)| matched_real_ids.contains(real_id))
+            .map(|(chat_id, _)| *chat_id)
+            .collect();
+
+        if chat_ids.is_empty() {
+            eprintln!("No chats were found matching `{name}` in the query.");
+        }
+        chat_ids
+    }
+
     fn normalize_identifier(&self, identifier: &str) -> String {
         let mut normalized = identifier.replace(['+', ' ', '(', ')', '-', '.'], "");
 
@@ -517,6 +644,39 @@ impl Config {
         }
     }
 
+    /// Determine if attachments will be linked into the export without using more disk space
+    ///
+    /// Hard links and clones are only possible on the device the attachments are stored on, and
+    /// files decrypted from a backup are always written out. Clones also need a file system that
+    /// supports copy-on-write, otherwise the files are copied.
+    fn links_attachments(&self) -> bool {
+        let mode = &self.options.attachment_manager.mode;
+        if !mode.is_link()
+            || self
+                .data_source
+                .backup
+                .as_ref()
+                .is_some_and(|backup| backup.is_encrypted())
+        {
+            return false;
+        }
+        if matches!(mode, AttachmentManagerMode::Symlink) {
+            return true;
+        }
+
+        let source = self
+            .options
+            .attachment_root
+            .as_deref()
+            .map_or(self.options.db_path.as_path(), Path::new);
+        same_device(source, &self.options.export_path)
+            && (!matches!(mode, AttachmentManagerMode::Reflink)
+                || self
+                    .options
+                    .attachment_manager
+                    .supports_reflink(&self.options.export_path))
+    }
+
     /// Ensure there is available disk space for the requested export
     fn ensure_free_space(&self) -> Result<(), RuntimeError> {
         // Export size is usually about 6% the size of the db;
@@ -533,7 +693,11 @@ impl Config {
         let free_space_at_location = available_space(&self.options.export_path)?;
 
         // Validate that there is enough disk space free to write the export
-        if let AttachmentManagerMode::Disabled = self.options.attachment_manager.mode {
+        if matches!(
+            self.options.attachment_manager.mode,
+            AttachmentManagerMode::Disabled
+        ) || self.links_attachments()
+        {
             if estimated_export_size >= free_space_at_location {
                 return Err(RuntimeError::NotEnoughAvailableSpace(
                     estimated_export_size,
@@ -700,32 +864,49 @@ impl Config {
         Ok(())
     }
 
+    /// Ensure that if we want to filter on things, we have stuff to filter for
+    fn ensure_filter_matches(&self) -> Result<(), RuntimeError> {
+        if let Some(filters) = &self.options.conversation_filter
+            && !self.options.query_context.has_filters()
+        {
+            return Err(RuntimeError::InvalidOptions(format!(
+                "Selected filter `{filters}` does not match any participants!"
+            )));
+        }
+        Ok(())
+    }
+
     // MARK: Startup
     /// Start the app given the provided set of options. This will either run
     /// diagnostic tests on the database or export data to the specified file type.
-    pub fn start(&self) -> Result<(), RuntimeError> {
+    pub fn start(&mut self) -> Result<(), RuntimeError> {
         if self.options.diagnostic {
             self.run_diagnostic()?;
         } else if self.options.list_contacts {
             self.list_contacts_and_chats()?;
+        } else if let Some(format) = self.options.stats {
+            self.ensure_filter_matches()?;
+            // Statistics are written to stdout, so nothing is printed after them
+            return Statistics::new(self).run(format);
+        } else if let Some(format) = self.options.frequency {
+            self.ensure_filter_matches()?;
+            return Frequency::new(self).run(format);
+        } else if let Some(format) = self.options.latency {
+            self.ensure_filter_matches()?;
+            return Latency::new(self).run(format);
         } else if let Some(export_type) = &self.options.export_type {
-            // Ensure that if we want to filter on things, we have stuff to filter for
-            if let Some(filters) = &self.options.conversation_filter
-                && !self.options.query_context.has_filters()
-            {
-                return Err(RuntimeError::InvalidOptions(format!(
-                    "Selected filter `{filters}` does not match any participants!"
-                )));
-            }
+            self.ensure_filter_matches()?;
 
             // Ensure the path we want to export to exists
             create_dir_all(&self.options.export_path)?;
 
             // Ensure the path we want to copy attachments to exists, if requested
+            // Gallery exports copy attachments into their own directory tree instead
             if !matches!(
                 self.options.attachment_manager.mode,
                 AttachmentManagerMode::Disabled
-            ) {
+            ) && !matches!(export_type, ExportType::Gallery)
+            {
                 create_dir_all(self.attachment_path())?;
             }
 
@@ -738,12 +919,19 @@ impl Config {
             let _ = raise_fd_limit();
 
             // Create exporter, pass it data we care about, then kick it off
-            match export_type {
-                ExportType::Html => {
-                    run_export(&mut HTML::new(self)?)?;
-                }
-                ExportType::Txt => {
-                    run_export(&mut TXT::new(self)?)?;
+            if self.options.watch {
+                self.watch()?;
+            } else {
+                match export_type {
+                    ExportType::Html => {
+                        run_export(&mut HTML::new(self)?)?;
+                    }
+                    ExportType::Txt => {
+                        run_export(&mut TXT::new(self)?)?;
+                    }
+                    ExportType::Gallery => {
+                        Gallery::new(self).run()?;
+                    }
                 }
             }
         }
@@ -751,6 +939,53 @@ impl Config {
         Ok(())
     }
 
+    /// Export the database, then keep following it and append new messages as they arrive.
+    ///
+    /// Runs until the process is stopped. When a new message belongs to a chat or participant
+    /// that is missing from the caches, the caches are rebuilt and the open files are handed to
+    /// a new exporter. Filters are resolved once, against the chats that existed at startup.
+    fn watch(&mut self) -> Result<(), RuntimeError> {
+        let mut watcher = DatabaseWatcher::new(&self.options.get_db_path(), self.data_source.db())?;
+        let mut state: Option<ExportState> = None;
+
+        loop {
+            state = Some(match self.options.export_type {
+                Some(ExportType::Html) => {
+                    let mut writer = match state.take() {
+                        Some(state) => HTML {
+                            config: self,
+                            state,
+                        },
+                        None => {
+                            let mut writer = HTML::new(self)?;
+                            run_export(&mut writer)?;
+                            writer
+                        }
+                    };
+                    watch_export(&mut writer, &mut watcher)?;
+                    writer.state
+                }
+                Some(ExportType::Txt) => {
+                    let mut writer = match state.take() {
+                        Some(state) => TXT {
+                            config: self,
+                            state,
+                        },
+                        None => {
+                            let mut writer = TXT::new(self)?;
+                            run_export(&mut writer)?;
+                            writer
+                        }
+                    };
+                    watch_export(&mut writer, &mut watcher)?;
+                    writer.state
+                }
+                Some(ExportType::Gallery) | None => return Ok(()),
+            });
+            self.build_caches()?;
+        }
+    }
+
     /// List all contacts and group chats with message counts and latest dates.
     fn list_contacts_and_chats(&self) -> Result<(), TableError> {
         let sql = "
@@ -1008,6 +1243,38 @@ impl Config {
         UNKNOWN
     }
 
+    /// Build the context embedded in the media attachments of a message
+    pub(crate) fn media_metadata(&self, message: &Message) -> Option<MediaMetadata> {
+        let date = message.date(self.offset).ok()?;
+        Some(MediaMetadata {
+            date: date.format("%Y:%m:%d %H:%M:%S").to_string(),
+            offset: date.format("%:z").to_string(),
+            utc: date.naive_utc().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            sender: self
+                .who(
+                    message.handle_id,
+                    message.is_from_me,
+                    &message.destination_caller_id,
+                )
+                .to_string(),
+            chat: self.chat_name(message),
+        })
+    }
+
+    /// Get a readable name for the chat a message was sent in
+    ///
+    /// Uses the chat's assigned name, otherwise a list of its members.
+    pub(crate) fn chat_name(&self, message: &Message) -> Option<String> {
+        let (chatroom, _) = self.conversation(message)?;
+        match chatroom.display_name() {
+            Some(name) => Some(name.to_string()),
+            None => self
+                .chatroom_participants
+                .get(&chatroom.rowid)
+                .map(|participants| self.filename_from_participants(participants)),
+        }
+    }
+
     /// Resolve a participant name from a handle ID
     fn resolve_participant(&self, handle_id: i32) -> Option<&Name> {
         if let Some(internal_id) = self.real_participants.get(&handle_id) {
@@ -1116,7 +1383,7 @@ mod filename_tests {
         let mut options = Options::fake_options(crate::app::export_type::ExportType::Html);
         // Disable the export
         options.export_type = None;
-        let app = Config::fake_app(options);
+        let mut app = Config::fake_app(options);
         app.start().unwrap();
     }
 
@@ -1870,3 +2137,97 @@ mod chat_filter_tests {
         );
     }
 }
+
+#[cfg(test)]
+mod query_expression_tests {
+    use std::collections::BTreeSet;
+
+    use imessage_database::{
+        tables::chat::Chat,
+        util::query_expression::{Predicate, QueryExpression, Selector},
+    };
+
+    use crate::{
+        Config, Options,
+        app::{contacts::Name, export_type::ExportType},
+    };
+
+    fn fake_chat(rowid: i32, chat_identifier: &str, display_name: Option<&str>) -> Chat {
+        Chat {
+            rowid,
+            chat_identifier: chat_identifier.to_string(),
+            service_name: None,
+            display_name: display_name.map(String::from),
+        }
+    }
+
+    #[test]
+    fn can_resolve_query_sender() {
+        let mut options = Options::fake_options(ExportType::Html);
+        options
+            .query_context
+            .set_expression("from:\"Person 10\"")
+            .unwrap();
+
+        let mut app = Config::fake_app(options);
+        app.participants.insert(10, Name::fake_name("Person 10"));
+        app.participants.insert(11, Name::fake_name("Person 11"));
+        for (id, participant) in app.participants.iter_mut() {
+            participant.handle_ids.insert(*id);
+        }
+
+        app.resolve_query_expression();
+
+        assert_eq!(
+            app.options.query_context.expression,
+            Some(QueryExpression::Predicate(Predicate::Sender(
+                Selector::Ids(BTreeSet::from([10]))
+            )))
+        );
+    }
+
+    #[test]
+    fn can_resolve_query_chat_with_duplicates() {
+        let mut options = Options::fake_options(ExportType::Html);
+        options.query_context.set_expression("chat:family").unwrap();
+
+        let mut app = Config::fake_app(options);
+        app.chatrooms
+            .insert(1, fake_chat(1, "chat123", Some("Family")));
+        app.chatrooms.insert(2, fake_chat(2, "chat123", None));
+        app.chatrooms
+            .insert(3, fake_chat(3, "chat456", Some("Work")));
+        app.real_chatrooms.insert(1, 1);
+        app.real_chatrooms.insert(2, 1);
+        app.real_chatrooms.insert(3, 3);
+
+        app.resolve_query_expression();
+
+        assert_eq!(
+            app.options.query_context.expression,
+            Some(QueryExpression::Predicate(Predicate::Chat(Selector::Ids(
+                BTreeSet::from([1, 2])
+            ))))
+        );
+    }
+
+    #[test]
+    fn can_resolve_query_chat_rowid() {
+        let mut options = Options::fake_options(ExportType::Html);
+        options.query_context.set_expression("chat:3").unwrap();
+
+        let mut app = Config::fake_app(options);
+        app.chatrooms
+            .insert(3, fake_chat(3, "chat456", Some("Work")));
+        app.real_chatrooms.insert(3, 3);
+
+        app.resolve_query_expression();
+
+        assert_eq!(
+            app.options.query_context.expression,
+            Some(QueryExpression::Predicate(Predicate::Chat(Selector::Ids(
+                BTreeSet::from([3])
+            ))))
+        );
+    }
+}
@@ -0,0 +1,792 @@
+/*!
+ Computes message statistics for each conversation and each participant.
+
+ Chats and participants are combined the same way they are in exports, using the deduplicated chats
+ and handles built when the app starts. For each chat, each person in that chat, and each person
+ across every chat, the report counts messages, words, and attachments by type, finds the first and
+ last message dates and the busiest days, builds hour-of-day and weekday histograms, and measures
+ how long replies take.
+
+ A reply is a message sent after a message from someone else in the same chat. Messages sent more
+ than a day after the one before them start a new conversation and are not counted as replies.
+ Tapbacks and announcements are not counted as messages.
+*/
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::{Display, Write},
+    io::Error as IoError,
+};
+
+use chrono::{DateTime, Datelike, Local, NaiveDate, Timelike};
+use imessage_database::tables::{
+    attachment::{Attachment, MediaType},
+    messages::Message,
+    table::{ORPHANED, UNKNOWN},
+};
+use serde::Serialize;
+
+use crate::{
+    app::{error::RuntimeError, runtime::Config},
+    exporters::shared::driver::{for_each_message, progress_bar},
+};
+
+/// Messages sent more than this many seconds after the previous message are not counted as replies
+pub(crate) const REPLY_WINDOW: i64 = 24 * 60 * 60;
+
+/// Number of busiest days listed for each chat and person
+const BUSIEST_DAYS: usize = 5;
+
+/// Maximum number of characters of a name shown in the table
+const NAME_LENGTH: usize = 32;
+
+/// Abbreviated names of the days of the week, starting on Monday
+pub(crate) const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Ways the statistics can be printed
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum StatsFormat {
+    /// Tables that summarize each chat and person
+    Table,
+    /// Every statistic, as JSON
+    Json,
+}
+
+impl StatsFormat {
+    /// Create an instance of the enum given user input
+    pub fn from_cli(format: &str) -> Option<Self> {
+        match format.to_lowercase().as_str() {
+            "table" => Some(Self::Table),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+impl Display for StatsFormat {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StatsFormat::Table => write!(fmt, "table"),
+            StatsFormat::Json => write!(fmt, "json"),
+        }
+    }
+}
+
+/// Someone who sends messages, after handles that belong to the same person are combined
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Person {
+    /// The owner of the database
+    Me,
+    /// A participant, by deduplicated handle ID
+    Contact(i32),
+    /// A message that was not sent by us and has no handle
+    Unknown,
+}
+
+impl Person {
+    /// Determine who sent a message
+    pub(crate) fn of(config: &Config, message: &Message) -> Self {
+        if message.is_from_me() {
+            return Person::Me;
+        }
+        message
+            .handle_id
+            .and_then(|handle_id| config.real_participants.get(&handle_id))
+            .map_or(Person::Unknown, |id| Person::Contact(*id))
+    }
+
+    /// The name shown for the sender of a message
+    pub(crate) fn name(self, config: &Config, message: &Message) -> String {
+        match self {
+            Person::Unknown => UNKNOWN.to_string(),
+            _ => config
+                .who(
+                    message.handle_id,
+                    message.is_from_me(),
+                    &message.destination_caller_id,
+                )
+                .to_string(),
+        }
+    }
+}
+
+/// The parts of a message that are counted
+#[derive(Debug, Clone)]
+struct Counted {
+    date: DateTime<Local>,
+    words: u64,
+    /// The kind of each attachment on the message
+    attachments: Vec<&'static str>,
+}
+
+/// Running totals for the messages in a chat, or sent by a person
+#[derive(Debug, Default)]
+struct Tally {
+    messages: u64,
+    words: u64,
+    /// Number of attachments of each kind
+    attachments: BTreeMap<&'static str, u64>,
+    first: Option<DateTime<Local>>,
+    last: Option<DateTime<Local>>,
+    /// Number of messages sent each day
+    days: HashMap<NaiveDate, u64>,
+    /// Number of messages sent in each hour of the day
+    hours: [u64; 24],
+    /// Number of messages sent on each day of the week, starting on Monday
+    weekdays: [u64; 7],
+    /// Seconds between each reply and the message before it
+    replies: Vec<i64>,
+}
+
+impl Tally {
+    /// Count a message, and the time it took to send if it was a reply
+    fn add(&mut self, message: &Counted, reply: Option<i64>) {
+        self.messages += 1;
+        self.words += message.words;
+        for kind in &message.attachments {
+            *self.attachments.entry(*kind).or_default() += 1;
+        }
+
+        if self.first.is_none_or(|first| message.date < first) {
+            self.first = Some(message.date);
+        }
+        if self.last.is_none_or(|last| message.date > last) {
+            self.last = Some(message.date);
+        }
+
+        *self.days.entry(message.date.date_naive()).or_default() += 1;
+        self.hours[message.date.hour() as usize] += 1;
+        self.weekdays[message.date.weekday().num_days_from_monday() as usize] += 1;
+
+        if let Some(seconds) = reply {
+            self.replies.push(seconds);
+        }
+    }
+
+    /// Build the statistics for everything counted so far
+    fn summarize(&self) -> Activity {
+        let mut busiest_days: Vec<(&NaiveDate, &u64)> = self.days.iter().collect();
+        busiest_days.sort_by(|(left_day, left), (right_day, right)| {
+            right.cmp(left).then_with(|| left_day.cmp(right_day))
+        });
+
+        Activity {
+            messages: self.messages,
+            words: self.words,
+            attachments: self.attachments.clone(),
+            first_message: self.first.map(|date| date.to_rfc3339()),
+            last_message: self.last.map(|date| date.to_rfc3339()),
+            busiest_days: busiest_days
+                .into_iter()
+                .take(BUSIEST_DAYS)
+                .map(|(date, messages)| DayCount {
+                    date: date.to_string(),
+                    messages: *messages,
+                })
+                .collect(),
+            hours: self.hours,
+            weekdays: self.weekdays,
+            replies: Replies {
+                count: self.replies.len(),
+                average_seconds: average(&self.replies),
+                median_seconds: median(&self.replies),
+            },
+        }
+    }
+}
+
+/// Finds replies from the sender and time of the most recent message in each chat
+#[derive(Debug, Default)]
+pub(crate) struct ReplyTimer {
+    /// The sender and Unix timestamp of the most recent message in each chat
+    latest: HashMap<Option<i32>, (Person, i64)>,
+}
+
+impl ReplyTimer {
+    /// Record a message, returning the seconds since the message before it if it is a reply
+    ///
+    /// Messages from someone other than the previous sender are replies, unless they were sent
+    /// more than [`REPLY_WINDOW`] later.
+    pub(crate) fn reply(
+        &mut self,
+        chat_id: Option<i32>,
+        person: Person,
+        timestamp: i64,
+    ) -> Option<i64> {
+        self.latest
+            .insert(chat_id, (person, timestamp))
+            .and_then(|(previous, at)| {
+                let seconds = timestamp - at;
+                (previous != person && (0..=REPLY_WINDOW).contains(&seconds)).then_some(seconds)
+            })
+    }
+}
+
+/// The messages sent in a chat
+#[derive(Debug)]
+struct ChatTally {
+    name: String,
+    total: Tally,
+    /// The messages each person sent in the chat
+    people: HashMap<Person, Tally>,
+}
+
+/// The messages a person sent
+#[derive(Debug)]
+struct PersonTally {
+    name: String,
+    total: Tally,
+    /// The chats the person sent messages in
+    chats: HashSet<Option<i32>>,
+}
+
+// MARK: Output
+/// Statistics for the messages in a chat, or sent by a person
+#[derive(Debug, Serialize)]
+struct Activity {
+    messages: u64,
+    words: u64,
+    /// Number of attachments of each kind
+    attachments: BTreeMap<&'static str, u64>,
+    first_message: Option<String>,
+    last_message: Option<String>,
+    /// The days with the most messages, busiest first
+    busiest_days: Vec<DayCount>,
+    /// Number of messages sent in each hour of the day, starting at midnight
+    hours: [u64; 24],
+    /// Number of messages sent on each day of the week, starting on Monday
+    weekdays: [u64; 7],
+    replies: Replies,
+}
+
+impl Activity {
+    fn attachment_count(&self) -> u64 {
+        self.attachments.values().sum()
+    }
+
+    /// The hour of the day with the most messages
+    fn peak_hour(&self) -> Option<usize> {
+        peak(&self.hours)
+    }
+
+    /// The day of the week with the most messages
+    fn peak_weekday(&self) -> Option<&'static str> {
+        peak(&self.weekdays).map(|day| WEEKDAYS[day])
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct DayCount {
+    date: String,
+    messages: u64,
+}
+
+/// How long replies took, in seconds
+#[derive(Debug, Serialize)]
+struct Replies {
+    count: usize,
+    average_seconds: Option<f64>,
+    median_seconds: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+struct ChatReport<'a> {
+    name: &'a str,
+    #[serde(flatten)]
+    activity: Activity,
+    /// Statistics for each person that sent messages in the chat
+    people: Vec<PersonReport<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct PersonReport<'a> {
+    name: &'a str,
+    /// The number of chats the person sent messages in, for people across every chat
+    #[serde(skip_serializing_if = "Option::is_none")]
+    chats: Option<usize>,
+    #[serde(flatten)]
+    activity: Activity,
+}
+
+#[derive(Debug, Serialize)]
+struct Report<'a> {
+    chats: Vec<ChatReport<'a>>,
+    people: Vec<PersonReport<'a>>,
+}
+
+pub struct Statistics<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// The messages in each chat, keyed by deduplicated chat ID
+    chats: HashMap<Option<i32>, ChatTally>,
+    /// The messages each person sent across every chat
+    people: HashMap<Person, PersonTally>,
+    /// The time each reply took
+    replies: ReplyTimer,
+}
+
+impl<'a> Statistics<'a> {
+    pub fn new(config: &'a Config) -> Self {
+        Statistics {
+            config,
+            chats: HashMap::new(),
+            people: HashMap::new(),
+            replies: ReplyTimer::default(),
+        }
+    }
+
+    /// Count every message selected by the query filters, then print the statistics in `format`
+    pub fn run(&mut self, format: StatsFormat) -> Result<(), RuntimeError> {
+        let config = self.config;
+        let db = config.data_source.db();
+
+        eprintln!("Computing statistics...");
+        let pb = progress_bar(config);
+        // Words are counted in the message body, so it is always decoded
+        for_each_message(config, &pb, true, |msg| {
+            if msg.is_tapback() || msg.is_announcement() {
+                return Ok(());
+            }
+            let Ok(date) = msg.date(config.offset) else {
+                return Ok(());
+            };
+            let attachments = Attachment::from_message(db, &msg)?
+                .iter()
+                .map(|attachment| attachment_kind(&attachment.mime_type()))
+                .collect();
+            let counted = Counted {
+                date,
+                words: msg.text.as_deref().map_or(0, count_words),
+                attachments,
+            };
+            self.add(&msg, &counted);
+            Ok(())
+        })?;
+
+        let report = self.report();
+        match format {
+            StatsFormat::Table => print!("{}", render_tables(&report)),
+            StatsFormat::Json => println!(
+                "{}",
+                serde_json::to_string_pretty(&report)
+                    .map_err(|why| RuntimeError::DiskError(IoError::other(why)))?
+            ),
+        }
+        Ok(())
+    }
+
+    /// Count a message toward its chat, its sender, and its sender in that chat
+    fn add(&mut self, message: &Message, counted: &Counted) {
+        let config = self.config;
+        let person = Person::of(config, message);
+        let chat_id = config.conversation(message).map(|(_, id)| *id);
+
+        let reply = self
+            .replies
+            .reply(chat_id, person, counted.date.timestamp());
+
+        let chat = self.chats.entry(chat_id).or_insert_with(|| ChatTally {
+            name: config
+                .chat_name(message)
+                .unwrap_or_else(|| ORPHANED.to_string()),
+            total: Tally::default(),
+            people: HashMap::new(),
+        });
+        chat.total.add(counted, reply);
+        chat.people.entry(person).or_default().add(counted, reply);
+
+        let sender = self.people.entry(person).or_insert_with(|| PersonTally {
+            name: person.name(config, message),
+            total: Tally::default(),
+            chats: HashSet::new(),
+        });
+        sender.total.add(counted, reply);
+        sender.chats.insert(chat_id);
+    }
+
+    /// Build the statistics for each chat and person, most active first
+    fn report(&self) -> Report<'_> {
+        let name_of = |person: &Person| {
+            self.people
+                .get(person)
+                .map_or(UNKNOWN, |tally| tally.name.as_str())
+        };
+
+        let mut chats: Vec<ChatReport> = self
+            .chats
+            .values()
+            .map(|chat| {
+                let mut people: Vec<PersonReport> = chat
+                    .people
+                    .iter()
+                    .map(|(person, tally)| PersonReport {
+                        name: name_of(person),
+                        chats: None,
+                        activity: tally.summarize(),
+                    })
+                    .collect();
+                sort_people(&mut people);
+                ChatReport {
+                    name: &chat.name,
+                    activity: chat.total.summarize(),
+                    people,
+                }
+            })
+            .collect();
+        chats.sort_by(|left, right| {
+            right
+                .activity
+                .messages
+                .cmp(&left.activity.messages)
+                .then_with(|| left.name.cmp(right.name))
+        });
+
+        let mut people: Vec<PersonReport> = self
+            .people
+            .values()
+            .map(|person| PersonReport {
+                name: &person.name,
+                chats: Some(person.chats.len()),
+                activity: person.total.summarize(),
+            })
+            .collect();
+        sort_people(&mut people);
+
+        Report { chats, people }
+    }
+}
+
+/// Sort people with the most messages first
+fn sort_people(people: &mut [PersonReport]) {
+    people.sort_by(|left, right| {
+        right
+            .activity
+            .messages
+            .cmp(&left.activity.messages)
+            .then_with(|| left.name.cmp(right.name))
+    });
+}
+
+/// Group an attachment's MIME type into the kinds shown in the statistics
+pub(crate) fn attachment_kind(mime_type: &MediaType) -> &'static str {
+    match mime_type {
+        MediaType::Image(_) => "images",
+        MediaType::Video(_) => "videos",
+        MediaType::Audio(_) => "audio",
+        MediaType::Text(_) | MediaType::Application(_) => "documents",
+        MediaType::Other(_) | MediaType::Unknown => "other",
+    }
+}
+
+/// Count the words in message text, skipping attachment placeholders and punctuation
+fn count_words(text: &str) -> u64 {
+    text.split_whitespace()
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .count() as u64
+}
+
+/// The index of the largest value, or `None` if every value is zero
+fn peak(counts: &[u64]) -> Option<usize> {
+    counts
+        .iter()
+        .enumerate()
+        .filter(|(_, count)| **count > 0)
+        .max_by(|(left_idx, left), (right_idx, right)| {
+            // Prefer the earliest index when counts are tied
+            left.cmp(right).then_with(|| right_idx.cmp(left_idx))
+        })
+        .map(|(idx, _)| idx)
+}
+
+fn average(values: &[i64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    Some(values.iter().sum::<i64>() as f64 / values.len() as f64)
+}
+
+pub(crate) fn median(values: &[i64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_unstable();
+    let middle = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        Some((sorted[middle - 1] + sorted[middle]) as f64 / 2.0)
+    } else {
+        Some(sorted[middle] as f64)
+    }
+}
+
+/// Format a number of seconds as a short duration, i.e. `45s`, `12m`, or `3h 5m`
+pub(crate) fn format_seconds(seconds: f64) -> String {
+    let seconds = seconds.round() as i64;
+    match seconds {
+        ..60 => format!("{seconds}s"),
+        60..3600 => format!("{}m", seconds / 60),
+        _ => format!("{}h {}m", seconds / 3600, (seconds % 3600) / 60),
+    }
+}
+
+// MARK: Tables
+/// Render the summary tables for chats and people
+fn render_tables(report: &Report) -> String {
+    let headers = [
+        "Name",
+        "Messages",
+        "Words",
+        "Attachments",
+        "First",
+        "Last",
+        "Busiest Day",
+        "Peak Hour",
+        "Peak Day",
+        "Median Reply",
+    ];
+
+    let chats: Vec<Vec<String>> = report
+        .chats
+        .iter()
+        .map(|chat| table_row(chat.name, &chat.activity))
+        .collect();
+    let people: Vec<Vec<String>> = report
+        .people
+        .iter()
+        .map(|person| table_row(person.name, &person.activity))
+        .collect();
+
+    let mut out = format!("Chats: {}\n", chats.len());
+    out.push_str(&render_table(&headers, &chats));
+    let _ = write!(out, "\nPeople: {}\n", people.len());
+    out.push_str(&render_table(&headers, &people));
+    out
+}
+
+/// Summarize a chat or person in one table row
+fn table_row(name: &str, activity: &Activity) -> Vec<String> {
+    let date = |date: &Option<String>| {
+        date.as_deref()
+            .and_then(|date| date.get(..10))
+            .unwrap_or("-")
+            .to_string()
+    };
+    vec![
+        truncate(name, NAME_LENGTH),
+        activity.messages.to_string(),
+        activity.words.to_string(),
+        activity.attachment_count().to_string(),
+        date(&activity.first_message),
+        date(&activity.last_message),
+        activity.busiest_days.first().map_or_else(
+            || "-".to_string(),
+            |day| format!("{} ({})", day.date, day.messages),
+        ),
+        activity
+            .peak_hour()
+            .map_or_else(|| "-".to_string(), |hour| format!("{hour:02}:00")),
+        activity.peak_weekday().unwrap_or("-").to_string(),
+        activity
+            .replies
+            .median_seconds
+            .map_or_else(|| "-".to_string(), format_seconds),
+    ]
+}
+
+/// Render rows as a plain text table, with columns of numbers aligned right and the rest aligned left
+pub(crate) fn render_table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = headers
+        .iter()
+        .map(|header| header.chars().count())
+        .collect();
+    let mut numeric = vec![!rows.is_empty(); headers.len()];
+    // The first column names each row
+    numeric[0] = false;
+    for row in rows {
+        for (idx, cell) in row.iter().enumerate() {
+            widths[idx] = widths[idx].max(cell.chars().count());
+            numeric[idx] &= cell.chars().all(|ch| ch.is_ascii_digit());
+        }
+    }
+
+    let mut out = String::new();
+    let mut write_row = |cells: &mut dyn Iterator<Item = &str>| {
+        let line = cells
+            .zip(&widths)
+            .enumerate()
+            .map(|(idx, (cell, width))| {
+                if numeric[idx] {
+                    format!("{cell:>width$}")
+                } else {
+                    format!("{cell:<width$}")
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("  ");
+        out.push_str(line.trim_end());
+        out.push('\n');
+    };
+
+    write_row(&mut headers.iter().copied());
+    let rule: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+    write_row(&mut rule.iter().map(String::as_str));
+    for row in rows {
+        write_row(&mut row.iter().map(String::as_str));
+    }
+    out
+}
+
+/// Shorten text to at most `length` characters, marking where it was cut
+pub(crate) fn truncate(text: &str, length: usize) -> String {
+    match text.char_indices().nth(length.saturating_sub(1)) {
+        Some((end, _)) if text.chars().count() > length => format!("{}…", &text[..end]),
+        _ => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, TimeZone};
+
+    use crate::app::stats::{
+        Counted, Person, REPLY_WINDOW, ReplyTimer, StatsFormat, Tally, count_words, format_seconds,
+        median, peak, render_table, truncate,
+    };
+
+    fn counted(day: u32, hour: u32, words: u64, attachments: Vec<&'static str>) -> Counted {
+        Counted {
+            date: Local
+                .with_ymd_and_hms(2023, 10, day, hour, 15, 0)
+                .single()
+                .unwrap(),
+            words,
+            attachments,
+        }
+    }
+
+    #[test]
+    fn can_parse_format_any_case() {
+        assert_eq!(StatsFormat::from_cli("Table"), Some(StatsFormat::Table));
+        assert_eq!(StatsFormat::from_cli("JSON"), Some(StatsFormat::Json));
+        assert_eq!(StatsFormat::from_cli("csv"), None);
+    }
+
+    #[test]
+    fn can_tally_messages() {
+        let mut tally = Tally::default();
+        // Saturday the 14th, twice, then Monday the 16th
+        tally.add(&counted(14, 9, 3, vec!["images", "images"]), None);
+        tally.add(&counted(14, 21, 5, vec![]), Some(120));
+        tally.add(&counted(16, 21, 1, vec!["audio"]), Some(60));
+
+        let activity = tally.summarize();
+        assert_eq!(activity.messages, 3);
+        assert_eq!(activity.words, 9);
+        assert_eq!(activity.attachments.get("images"), Some(&2));
+        assert_eq!(activity.attachment_count(), 3);
+        assert!(
+            activity
+                .first_message
+                .as_deref()
+                .unwrap()
+                .starts_with("2023-10-14T09:15:00")
+        );
+        assert!(
+            activity
+                .last_message
+                .as_deref()
+                .unwrap()
+                .starts_with("2023-10-16T21:15:00")
+        );
+        assert_eq!(activity.busiest_days[0].date, "2023-10-14");
+        assert_eq!(activity.busiest_days[0].messages, 2);
+        assert_eq!(activity.hours[21], 2);
+        assert_eq!(activity.weekdays, [1, 0, 0, 0, 0, 2, 0]);
+        assert_eq!(activity.peak_hour(), Some(21));
+        assert_eq!(activity.peak_weekday(), Some("Sat"));
+        assert_eq!(activity.replies.count, 2);
+        assert_eq!(activity.replies.average_seconds, Some(90.0));
+        assert_eq!(activity.replies.median_seconds, Some(90.0));
+    }
+
+    #[test]
+    fn can_summarize_empty_tally() {
+        let activity = Tally::default().summarize();
+        assert_eq!(activity.messages, 0);
+        assert!(activity.first_message.is_none());
+        assert!(activity.busiest_days.is_empty());
+        assert_eq!(activity.peak_hour(), None);
+        assert_eq!(activity.replies.median_seconds, None);
+    }
+
+    #[test]
+    fn can_time_replies() {
+        let mut replies = ReplyTimer::default();
+        let start = 1_700_000_000;
+
+        assert_eq!(replies.reply(Some(1), Person::Contact(1), start), None);
+        // A second message from the same sender is not a reply
+        assert_eq!(replies.reply(Some(1), Person::Contact(1), start + 30), None);
+        assert_eq!(replies.reply(Some(1), Person::Me, start + 90), Some(60));
+        // In a group chat, a message from any other participant is a reply
+        assert_eq!(
+            replies.reply(Some(1), Person::Contact(2), start + 100),
+            Some(10)
+        );
+        // Messages in other chats are timed separately
+        assert_eq!(replies.reply(Some(2), Person::Me, start + 110), None);
+        // Replies sent more than a day later start a new conversation
+        assert_eq!(
+            replies.reply(Some(1), Person::Me, start + 101 + REPLY_WINDOW),
+            None
+        );
+    }
+
+    #[test]
+    fn can_count_words() {
+        assert_eq!(count_words("Hello, world! How's it going?"), 5);
+        assert_eq!(count_words("\u{FFFC} look at this - 👀"), 3);
+        assert_eq!(count_words(""), 0);
+    }
+
+    #[test]
+    fn can_get_median() {
+        assert_eq!(median(&[]), None);
+        assert_eq!(median(&[30, 10, 20]), Some(20.0));
+        assert_eq!(median(&[40, 10, 20, 30]), Some(25.0));
+    }
+
+    #[test]
+    fn can_get_peak() {
+        assert_eq!(peak(&[0, 3, 1, 3]), Some(1));
+        assert_eq!(peak(&[0, 0]), None);
+    }
+
+    #[test]
+    fn can_format_seconds() {
+        assert_eq!(format_seconds(42.4), "42s");
+        assert_eq!(format_seconds(750.0), "12m");
+        assert_eq!(format_seconds(11_100.0), "3h 5m");
+    }
+
+    #[test]
+    fn can_truncate() {
+        assert_eq!(truncate("Jane Doe", 32), "Jane Doe");
+        assert_eq!(truncate("Book Club Friends", 9), "Book Clu…");
+    }
+
+    #[test]
+    fn can_render_table() {
+        let rendered = render_table(
+            &["Name", "Messages"],
+            &[
+                vec!["Jane".to_string(), "1200".to_string()],
+                vec!["Book Club".to_string(), "7".to_string()],
+            ],
+        );
+        assert_eq!(
+            rendered,
+            "Name       Messages\n---------  --------\nJane           1200\nBook Club         7\n"
+        );
+    }
+}
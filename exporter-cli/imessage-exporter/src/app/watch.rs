@@ -0,0 +1,253 @@
+/*!
+ Follows a live database so new messages can be appended to an export as they arrive.
+
+ The Messages app writes new rows to the database's write-ahead log before they are checkpointed into
+ `chat.db`, so the watcher polls the length and modification time of both files. When either
+ changes, every message with a `ROWID` above the watcher's cursor is appended to the export.
+
+ Attachments are downloaded after their message row is written, so messages whose attachment
+ files are not on disk yet are held back until they are, or until [`ATTACHMENT_TIMEOUT`] passes.
+*/
+
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    fs::metadata,
+    path::{Path, PathBuf},
+    thread::sleep,
+    time::{Duration, Instant, SystemTime},
+};
+
+use imessage_database::tables::{attachment::Attachment, messages::Message, table::MESSAGE};
+use rusqlite::Connection;
+
+use crate::app::{error::RuntimeError, runtime::Config};
+
+/// Time between checks for changes to the database
+pub const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long to hold back a message whose attachments have not finished downloading
+pub const ATTACHMENT_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// The length and modification time of a file, or `None` if it does not exist
+type FileStamp = Option<(u64, SystemTime)>;
+
+/// Tracks changes to the database and the newest message appended to the export
+pub struct DatabaseWatcher {
+    /// The database and its write-ahead log
+    paths: [PathBuf; 2],
+    /// The stamps observed at the last check
+    stamps: [FileStamp; 2],
+    /// Every message at or below this `ROWID` has been handled
+    pub cursor: i32,
+    /// Messages held back while their attachments download, with the time they were first seen
+    pending: HashMap<i32, Instant>,
+    /// The message that last caused the caches to be rebuilt
+    refreshed_for: Option<i32>,
+}
+
+impl DatabaseWatcher {
+    /// Start watching the database at `db_path`, treating every message that exists now as handled
+    pub fn new(db_path: &Path, db: &Connection) -> Result<Self, RuntimeError> {
+        let mut wal_path = OsString::from(db_path);
+        wal_path.push("-wal");
+        let paths = [db_path.to_path_buf(), PathBuf::from(wal_path)];
+        let stamps = [Self::stamp(&paths[0]), Self::stamp(&paths[1])];
+
+        Ok(Self {
+            paths,
+            stamps,
+            cursor: newest_rowid(db)?,
+            pending: HashMap::new(),
+            refreshed_for: None,
+        })
+    }
+
+    /// Get the current stamp for `path`
+    fn stamp(path: &Path) -> FileStamp {
+        let meta = metadata(path).ok()?;
+        Some((meta.len(), meta.modified().ok()?))
+    }
+
+    /// Block until the database or its write-ahead log changes
+    ///
+    /// Returns after a single poll while messages are held back, so they are written as soon
+    /// as their attachments arrive or their timeout passes.
+    pub fn wait_for_change(&mut self) {
+        loop {
+            sleep(POLL_INTERVAL);
+            let stamps = [Self::stamp(&self.paths[0]), Self::stamp(&self.paths[1])];
+            if stamps != self.stamps {
+                self.stamps = stamps;
+                return;
+            }
+            if !self.pending.is_empty() {
+                return;
+            }
+        }
+    }
+
+    /// Determine if the caches must be rebuilt before `message` can be rendered
+    ///
+    /// Each message triggers at most one rebuild, so a row that references a chat or handle
+    /// missing from the database itself is written the same way a full export writes it.
+    pub fn needs_refresh(&mut self, message: &Message, config: &Config) -> bool {
+        if config.is_cached(message) || self.refreshed_for == Some(message.rowid) {
+            return false;
+        }
+        self.refreshed_for = Some(message.rowid);
+        true
+    }
+
+    /// Determine if `message` should be held back because its attachments are still downloading
+    pub fn should_defer(&mut self, message: &Message, config: &Config) -> bool {
+        if attachments_ready(message, config) {
+            self.pending.remove(&message.rowid);
+            return false;
+        }
+
+        let first_seen = *self
+            .pending
+            .entry(message.rowid)
+            .or_insert_with(Instant::now);
+        if first_seen.elapsed() < ATTACHMENT_TIMEOUT {
+            return true;
+        }
+
+        eprintln!(
+            "Attachments for message {} did not finish downloading; writing it without them.",
+            message.guid
+        );
+        self.pending.remove(&message.rowid);
+        false
+    }
+}
+
+/// Get the highest message `ROWID` in the database
+fn newest_rowid(db: &Connection) -> Result<i32, RuntimeError> {
+    Ok(db.query_row(
+        &format!("SELECT IFNULL(MAX(ROWID), 0) FROM {MESSAGE}"),
+        [],
+        |row| row.get(0),
+    )?)
+}
+
+/// Determine if every attachment on `message` exists on disk
+///
+/// Attachments that cannot be read from the table are treated as ready, since waiting
+/// would not change the result.
+fn attachments_ready(message: &Message, config: &Config) -> bool {
+    if !message.has_attachments() {
+        return true;
+    }
+
+    let Ok(attachments) = Attachment::from_message(config.data_source.db(), message) else {
+        return true;
+    };
+    attachments.iter().all(|attachment| {
+        attachment
+            .resolved_attachment_path(
+                &config.options.platform,
+                &config.options.db_path,
+                config.options.attachment_root.as_deref(),
+            )
+            .is_some_and(|path| Path::new(&path).exists())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs::write, time::Instant};
+
+    use crate::{
+        Config, Options,
+        app::{
+            export_type::ExportType,
+            test_dir::unique_test_dir,
+            watch::{ATTACHMENT_TIMEOUT, DatabaseWatcher, POLL_INTERVAL},
+        },
+    };
+
+    fn fake_watcher(config: &Config) -> DatabaseWatcher {
+        DatabaseWatcher::new(&config.options.db_path, config.data_source.db()).unwrap()
+    }
+
+    #[test]
+    fn can_start_at_newest_message() {
+        let config = Config::fake_app(Options::fake_options(ExportType::Txt));
+        assert_eq!(fake_watcher(&config).cursor, 548_216);
+    }
+
+    #[test]
+    fn can_refresh_once_per_message() {
+        let config = Config::fake_app(Options::fake_options(ExportType::Txt));
+        let mut watcher = fake_watcher(&config);
+
+        // Messages outside of any chat need nothing from the caches
+        let mut message = Config::fake_message();
+        assert!(!watcher.needs_refresh(&message, &config));
+
+        message.rowid = 1;
+        message.chat_id = Some(5);
+        assert!(watcher.needs_refresh(&message, &config));
+        // The same message never triggers a second rebuild
+        assert!(!watcher.needs_refresh(&message, &config));
+
+        message.rowid = 2;
+        assert!(watcher.needs_refresh(&message, &config));
+    }
+
+    #[test]
+    fn can_defer_until_attachments_exist() {
+        let config = Config::fake_app(Options::fake_options(ExportType::Txt));
+        let mut watcher = fake_watcher(&config);
+
+        // The sticker attached to this message is not on disk
+        let mut message = Config::fake_message();
+        message.rowid = 452_567;
+        message.num_attachments = 1;
+        assert!(watcher.should_defer(&message, &config));
+        assert!(watcher.pending.contains_key(&message.rowid));
+
+        // Once the timeout passes, the message is written without its attachments
+        watcher.pending.insert(
+            message.rowid,
+            Instant::now().checked_sub(ATTACHMENT_TIMEOUT).unwrap(),
+        );
+        assert!(!watcher.should_defer(&message, &config));
+        assert!(watcher.pending.is_empty());
+
+        message.num_attachments = 0;
+        assert!(!watcher.should_defer(&message, &config));
+    }
+
+    #[test]
+    fn can_wait_for_change() {
+        let dir = unique_test_dir("watch_change");
+        let db_path = dir.join("chat.db");
+        write(&db_path, b"").unwrap();
+        let wal_path = dir.join("chat.db-wal");
+
+        let mut watcher = DatabaseWatcher {
+            paths: [db_path.clone(), wal_path.clone()],
+            stamps: [DatabaseWatcher::stamp(&db_path), None],
+            cursor: 0,
+            pending: HashMap::new(),
+            refreshed_for: None,
+        };
+
+        // A new write-ahead log is a change
+        write(&wal_path, b"wal").unwrap();
+        let started = Instant::now();
+        watcher.wait_for_change();
+        assert!(started.elapsed() < POLL_INTERVAL * 2);
+        assert_eq!(watcher.stamps[1].map(|(len, _)| len), Some(3));
+
+        // Held back messages are checked again after a single poll, even without a change
+        watcher.pending.insert(1, Instant::now());
+        let started = Instant::now();
+        watcher.wait_for_change();
+        assert!(started.elapsed() < POLL_INTERVAL * 2);
+        assert!(started.elapsed() >= POLL_INTERVAL);
+    }
+}
@@ -5,7 +5,7 @@ use imessage_database::{
         app::AppMessage,
         app_store::AppStoreMessage,
         collaboration::CollaborationMessage,
-        digital_touch::DigitalTouch,
+        digital_touch::DigitalTouchMessage,
         edited::EditedMessage,
         handwriting::HandwrittenMessage,
         music::MusicMessage,
@@ -52,6 +52,14 @@ pub(crate) enum AttachmentRender {
     /// Attachment manager couldn't process the file, but its filename is
     /// known. Caller renders via [`PartBodyBuilder::body_attachment_error`].
     NamedFile(String),
+    /// Attachment was excluded by the export's attachment rules. Caller
+    /// renders via [`PartBodyBuilder::body_attachment_skipped`].
+    Skipped {
+        /// The name the attachment was sent with
+        filename: String,
+        /// The attachment's size, formatted for display
+        file_size: String,
+    },
 }
 
 // MARK: Message
@@ -119,7 +127,7 @@ pub(crate) trait BalloonFormatter {
     /// Format a handwritten note message
     fn format_handwriting(&self, msg: &Message, balloon: &HandwrittenMessage) -> String;
     /// Format a digital touch message
-    fn format_digital_touch(&self, msg: &Message, balloon: &DigitalTouch) -> String;
+    fn format_digital_touch(&self, msg: &Message, balloon: &DigitalTouchMessage) -> String;
     /// Format an Apple Pay message
     fn format_apple_pay(&self, balloon: &AppMessage) -> String;
     /// Format a Fitness message
@@ -170,6 +178,8 @@ pub(crate) trait PartBodyBuilder {
     fn body_attachment_error(&self, error: &str) -> Self::Body;
     /// Attachment with missing filename metadata
     fn body_attachment_missing(&self) -> Self::Body;
+    /// Attachment excluded by the export's attachment rules
+    fn body_attachment_skipped(&self, filename: &str, file_size: &str) -> Self::Body;
     /// Sticker content, generally by reference to an external file
     fn body_sticker(&self, content: String) -> Self::Body;
     /// App message content
@@ -0,0 +1,396 @@
+/*!
+ Exports only the media in a database, as a tree of folders that can be browsed without the exporter.
+
+ Images, videos, and audio are copied to `<Person>/<YYYY>/<MM>/`, where `<Person>` is the name the
+ other exporters give the chat's file. Each file is named after when it was sent, who sent it, and its
+ original name. An `index.html` page is written at the root, for each person, and for each month.
+*/
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs::write,
+    path::{Path, PathBuf},
+};
+
+use imessage_database::tables::{
+    attachment::{Attachment, MediaType},
+    messages::Message,
+};
+
+use crate::{
+    app::{
+        compatibility::naming::original_stem,
+        error::RuntimeError,
+        incremental::IncrementalState,
+        manifest::{MANIFEST_FILENAME, write_manifest},
+        progress::ExportProgress,
+        runtime::Config,
+        sanitizers::sanitize_filename,
+    },
+    exporters::shared::{
+        driver::{for_each_message, progress_bar, write_shared_outputs},
+        render::render_template,
+    },
+};
+
+mod view_model;
+
+use view_model::{AlbumVM, AlbumsVM, GalleryItem, MediaKind, MonthVM};
+
+/// Name of the page written into the gallery root and each of its directories
+const INDEX: &str = "index.html";
+const STYLE: &str = include_str!("resources/gallery.css");
+
+/// The files copied for a single month of a person's gallery
+#[derive(Debug, Default)]
+struct Month {
+    /// The month as shown in page titles, i.e. `May 2023`
+    title: String,
+    items: Vec<GalleryItem>,
+}
+
+pub struct Gallery<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Every file copied, keyed by person directory, then by `YYYY/MM`
+    albums: BTreeMap<String, BTreeMap<String, Month>>,
+    /// Lowercased names already given to files in each directory
+    used_names: HashMap<PathBuf, HashSet<String>>,
+}
+
+impl<'a> Gallery<'a> {
+    pub fn new(config: &'a Config) -> Self {
+        Gallery {
+            config,
+            albums: BTreeMap::new(),
+            used_names: HashMap::new(),
+        }
+    }
+
+    /// Copy every media attachment selected by the query filters, then write the gallery pages
+    /// and the export's manifest
+    pub fn run(&mut self) -> Result<(), RuntimeError> {
+        let config = self.config;
+
+        eprintln!(
+            "Exporting media to {}...",
+            config.options.export_path.display()
+        );
+        let pb = progress_bar(config);
+        for_each_message(config, &pb, false, |msg| {
+            if msg.has_attachments() {
+                self.copy_media(&msg, &pb)?;
+            }
+            Ok(())
+        })?;
+        config.options.attachment_manager.conversions.finish(&pb);
+        self.link_failed_conversions();
+
+        eprintln!("Writing gallery pages...");
+        self.write_pages()?;
+
+        // Shared locations and links are written before the manifest so it lists their files
+        write_shared_outputs(config)?;
+
+        // The gallery does not write conversation files, so the manifest lists no chats
+        eprintln!("Writing {MANIFEST_FILENAME}...");
+        write_manifest(
+            config,
+            &IncrementalState::new(&config.options),
+            &mut None,
+            "",
+        )
+    }
+
+    /// Copy the image, video, and audio attachments of a message into its person's month directory
+    fn copy_media(&mut self, message: &Message, pb: &ExportProgress) -> Result<(), RuntimeError> {
+        let config = self.config;
+        let Ok(date) = message.date(config.offset) else {
+            return Ok(());
+        };
+
+        let person = config.chat_filename(message);
+        let month_key = date.format("%Y/%m").to_string();
+        let month_dir = config
+            .options
+            .export_path
+            .join(&person)
+            .join(date.format("%Y").to_string())
+            .join(date.format("%m").to_string());
+        let sender = config.who(
+            message.handle_id,
+            message.is_from_me,
+            &message.destination_caller_id,
+        );
+        let prefix = format!("{} - {sender}", date.format("%Y-%m-%d %H%M%S"));
+
+        for mut attachment in Attachment::from_message(config.data_source.db(), message)? {
+            if !config.options.attachment_manager.policy.allows(&attachment) {
+                continue;
+            }
+            let kind = match attachment.mime_type() {
+                _ if attachment.is_sticker => continue,
+                MediaType::Image(_) => MediaKind::Image,
+                MediaType::Video(_) => MediaKind::Video,
+                MediaType::Audio(_) => MediaKind::Audio,
+                _ => continue,
+            };
+
+            let stem = self.unique_name(
+                &month_dir,
+                &format!("{prefix} - {}", original_stem(&attachment)),
+            );
+            let name = match attachment.extension() {
+                Some(ext) => format!("{stem}.{ext}"),
+                None => stem,
+            };
+
+            if let Err(why) = config.options.attachment_manager.copy_attachment(
+                message,
+                &mut attachment,
+                config,
+                true,
+                |_| month_dir.join(&name),
+            ) {
+                pb.println(why);
+                continue;
+            }
+            let Some(copied) = &attachment.copied_path else {
+                continue;
+            };
+
+            let href = relative_href(&month_dir, copied);
+            let preview = config
+                .options
+                .attachment_manager
+                .handle_thumbnail(&attachment, config)
+                .map_or_else(|| href.clone(), |path| relative_href(&month_dir, &path));
+
+            let month = self
+                .albums
+                .entry(person.clone())
+                .or_default()
+                .entry(month_key.clone())
+                .or_default();
+            if month.title.is_empty() {
+                month.title = date.format("%B %Y").to_string();
+            }
+            month.items.push(GalleryItem {
+                kind,
+                href,
+                preview,
+                date: date.format("%b %d, %Y %l:%M %p").to_string(),
+                sender: sender.to_string(),
+                copied: copied.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Point items whose conversion failed at the original file copied in its place
+    fn link_failed_conversions(&mut self) {
+        let conversions = &self.config.options.attachment_manager.conversions;
+        for month in self.albums.values_mut().flat_map(BTreeMap::values_mut) {
+            for item in &mut month.items {
+                let Some(fallback) = conversions.fallback(&item.copied) else {
+                    continue;
+                };
+                let Some(month_dir) = item.copied.parent() else {
+                    continue;
+                };
+                let href = relative_href(month_dir, &fallback);
+                if item.preview == item.href {
+                    item.preview.clone_from(&href);
+                }
+                item.href = href;
+                item.copied = fallback;
+            }
+        }
+    }
+
+    /// Make `stem` unique among the files in `dir`, ignoring case
+    ///
+    /// Attachments are read in the same order on every run, so each one keeps its name when the
+    /// gallery is exported again into the same directory.
+    fn unique_name(&mut self, dir: &Path, stem: &str) -> String {
+        let stem = sanitize_filename(stem);
+        let used = self.used_names.entry(dir.to_path_buf()).or_default();
+        let mut name = stem.clone();
+        let mut copy = 2;
+        while !used.insert(name.to_lowercase()) {
+            name = format!("{stem} ({copy})");
+            copy += 1;
+        }
+        name
+    }
+
+    /// Write the root page, a page for each person, and a page for each month
+    fn write_pages(&self) -> Result<(), RuntimeError> {
+        let export_path = &self.config.options.export_path;
+
+        let mut people = Vec::with_capacity(self.albums.len());
+        for (person, months) in &self.albums {
+            let person_dir = export_path.join(person);
+
+            let mut albums = Vec::with_capacity(months.len());
+            for (key, month) in months {
+                let month_dir = person_dir.join(key);
+                write(
+                    month_dir.join(INDEX),
+                    render_template(&MonthVM {
+                        title: &format!("{person} - {}", month.title),
+                        style: STYLE,
+                        parent: "../../index.html",
+                        items: &month.items,
+                    }),
+                )?;
+                albums.push(AlbumVM {
+                    name: month.title.clone(),
+                    href: encode_href(&format!("{key}/{INDEX}")),
+                    cover: cover(&month.items).map(|preview| encode_href(key) + "/" + preview),
+                    count: month.items.len(),
+                });
+            }
+
+            write(
+                person_dir.join(INDEX),
+                render_template(&AlbumsVM {
+                    title: person,
+                    style: STYLE,
+                    parent: Some("../index.html"),
+                    albums,
+                }),
+            )?;
+
+            let person_href = encode_href(person);
+            people.push(AlbumVM {
+                name: person.clone(),
+                href: format!("{person_href}/{INDEX}"),
+                cover: months.iter().find_map(|(key, month)| {
+                    cover(&month.items)
+                        .map(|preview| format!("{person_href}/{}/{preview}", encode_href(key)))
+                }),
+                count: months.values().map(|month| month.items.len()).sum(),
+            });
+        }
+
+        write(
+            export_path.join(INDEX),
+            render_template(&AlbumsVM {
+                title: "Gallery",
+                style: STYLE,
+                parent: None,
+                albums: people,
+            }),
+        )?;
+        Ok(())
+    }
+}
+
+/// The preview of the first image in a month, used as its cover
+fn cover(items: &[GalleryItem]) -> Option<&str> {
+    items
+        .iter()
+        .find(|item| item.kind == MediaKind::Image)
+        .map(|item| item.preview.as_str())
+}
+
+/// Build a link to `path` from a page in `dir`
+fn relative_href(dir: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(dir).unwrap_or(path);
+    let joined = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    encode_href(&joined)
+}
+
+/// Percent-encode a relative path for use in `href` and `src` attributes, keeping `/` separators
+fn encode_href(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                encoded.push(byte as char);
+            }
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{
+        Config, Options,
+        app::{export_type::ExportType, manifest::Manifest, test_dir::unique_test_dir},
+        exporters::gallery::{Gallery, encode_href, relative_href},
+    };
+
+    #[test]
+    fn can_encode_href() {
+        assert_eq!(encode_href("Jane Doe/2023/05"), "Jane%20Doe/2023/05");
+        assert_eq!(encode_href("a#b?c%d"), "a%23b%3Fc%25d");
+        assert_eq!(encode_href("café"), "caf%C3%A9");
+    }
+
+    #[test]
+    fn can_get_relative_href() {
+        assert_eq!(
+            relative_href(
+                Path::new("/export/Jane/2023/05"),
+                Path::new("/export/Jane/2023/05/thumbnails/a b.jpg")
+            ),
+            "thumbnails/a%20b.jpg"
+        );
+    }
+
+    #[test]
+    fn can_make_unique_names() {
+        let mut options = Options::fake_options(ExportType::Gallery);
+        options.export_path = unique_test_dir("gallery_names");
+        let config = Config::fake_app(options);
+        let mut gallery = Gallery::new(&config);
+
+        let dir = config.options.export_path.join("Jane/2023/05");
+        assert_eq!(gallery.unique_name(&dir, "IMG_1"), "IMG_1");
+        assert_eq!(gallery.unique_name(&dir, "img_1"), "img_1 (2)");
+        assert_eq!(gallery.unique_name(&dir, "IMG_1"), "IMG_1 (3)");
+        assert_eq!(gallery.unique_name(&dir, "a:b"), "a_b");
+
+        let other = config.options.export_path.join("Jane/2023/06");
+        assert_eq!(gallery.unique_name(&other, "IMG_1"), "IMG_1");
+    }
+
+    #[test]
+    fn can_write_empty_gallery() {
+        let mut options = Options::fake_options(ExportType::Gallery);
+        options.export_path = unique_test_dir("gallery_empty");
+        let config = Config::fake_app(options);
+        let gallery = Gallery::new(&config);
+
+        gallery.write_pages().unwrap();
+        let index = std::fs::read_to_string(config.options.export_path.join("index.html")).unwrap();
+        assert!(index.contains("<h1>Gallery</h1>"));
+    }
+
+    #[test]
+    fn can_write_gallery_manifest() {
+        let mut options = Options::fake_options(ExportType::Gallery);
+        options.export_path = unique_test_dir("gallery_manifest");
+        let config = Config::fake_app(options);
+
+        Gallery::new(&config).run().unwrap();
+        let manifest = Manifest::load(&config.options.export_path).unwrap();
+        assert!(manifest.chats.is_empty());
+        assert!(
+            manifest
+                .files
+                .iter()
+                .any(|entry| entry.path == "index.html")
+        );
+    }
+}
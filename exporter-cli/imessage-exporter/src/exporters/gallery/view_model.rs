@@ -0,0 +1,51 @@
+use std::path::PathBuf;
+
+use askama::Template;
+
+/// The kind of media a gallery item holds, which determines how it is embedded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum MediaKind {
+    Image,
+    Video,
+    Audio,
+}
+
+/// A file in a month of the gallery
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct GalleryItem {
+    pub kind: MediaKind,
+    /// Link to the file, relative to the month directory
+    pub href: String,
+    /// Image shown in the grid, relative to the month directory; a thumbnail when one exists
+    pub preview: String,
+    pub date: String,
+    pub sender: String,
+    /// Where the file was copied, which a failed conversion replaces with the original file
+    pub copied: PathBuf,
+}
+
+/// A link to a person or a month, with an optional cover image
+pub(super) struct AlbumVM {
+    pub name: String,
+    pub href: String,
+    pub cover: Option<String>,
+    pub count: usize,
+}
+
+#[derive(Template)]
+#[template(path = "gallery/albums.html")]
+pub(super) struct AlbumsVM<'a> {
+    pub title: &'a str,
+    pub style: &'static str,
+    pub parent: Option<&'static str>,
+    pub albums: Vec<AlbumVM>,
+}
+
+#[derive(Template)]
+#[template(path = "gallery/month.html")]
+pub(super) struct MonthVM<'a> {
+    pub title: &'a str,
+    pub style: &'static str,
+    pub parent: &'static str,
+    pub items: &'a [GalleryItem],
+}
@@ -1,7 +1,7 @@
 use imessage_database::{
     message_types::{
         app::AppMessage, app_store::AppStoreMessage, collaboration::CollaborationMessage,
-        digital_touch::DigitalTouch, handwriting::HandwrittenMessage, music::MusicMessage,
+        digital_touch::DigitalTouchMessage, handwriting::HandwrittenMessage, music::MusicMessage,
         placemark::PlacemarkMessage, polls::Poll, url::URLMessage,
     },
     tables::{
@@ -86,12 +86,13 @@ impl BalloonFormatter for HTML<'_> {
     }
 
     fn format_handwriting(&self, _: &Message, balloon: &HandwrittenMessage) -> String {
-        balloon.render_svg()
+        balloon.render_animated_svg()
     }
 
-    fn format_digital_touch(&self, _: &Message, balloon: &DigitalTouch) -> String {
+    fn format_digital_touch(&self, _: &Message, balloon: &DigitalTouchMessage) -> String {
         render_template(&DigitalTouchVM {
-            debug: format!("{balloon:?}"),
+            kind: format!("{:?}", balloon.kind()),
+            svg: balloon.render_svg(),
         })
     }
 
@@ -167,9 +168,9 @@ impl BalloonFormatter for HTML<'_> {
                 let rendered =
                     match self.format_attachment(attachment, msg, &AttachmentMeta::default()) {
                         AttachmentRender::Embedded(html) => html,
-                        AttachmentRender::MissingFilename | AttachmentRender::NamedFile(_) => {
-                            String::new()
-                        }
+                        AttachmentRender::MissingFilename
+                        | AttachmentRender::NamedFile(_)
+                        | AttachmentRender::Skipped { .. } => String::new(),
                     };
                 Html::trust(rendered)
             })
@@ -16,10 +16,11 @@ use crate::{
         },
         shared::{
             announcement::{AnnouncementBody, resolve_announcement},
-            attachment::prepare_attachment,
+            attachment::{contact_cards, prepare_attachment},
             balloon::dispatch_app_balloon,
-            driver::{ExportState, MessageWriter},
+            driver::{ExportState, MessageWriter, truncate_footer},
             edited::{EditDiff, normalize_edited},
+            live_photo::{LivePhotos, split_pair},
             message::MessageContext,
             part::dispatch_part_body,
             render::{render_template, render_template_into},
@@ -54,9 +55,9 @@ use view_model::{
 };
 
 // MARK: HTML
-const HEADER: &str = "<html>\n<head>\n<meta charset=\"UTF-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">";
-const FOOTER: &str = "</body></html>";
-const STYLE: &str = include_str!("resources/style.css");
+pub(crate) const HEADER: &str = "<html>\n<head>\n<meta charset=\"UTF-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">";
+pub(crate) const FOOTER: &str = "</body></html>";
+pub(crate) const STYLE: &str = include_str!("resources/style.css");
 
 #[derive(Debug, Clone)]
 /// [`EventType`] is used to track the start and end of HTML text attributes
@@ -110,6 +111,10 @@ impl<'a> MessageWriter<'a> for HTML<'a> {
         Ok(())
     }
 
+    fn trim_file_footer(file: &mut File) -> Result<(), RuntimeError> {
+        truncate_footer(file, FOOTER)
+    }
+
     fn footer_notice() -> Option<&'static str> {
         Some("Writing HTML footers...")
     }
@@ -130,7 +135,17 @@ impl<'a> MessageFormatter<'a> for HTML<'a> {
         let embed_path = self.config.message_attachment_path(attachment);
 
         let variant = match attachment.mime_type() {
-            MediaType::Image(_) => AttachmentVariant::Image,
+            MediaType::Image(_) => match self
+                .config
+                .options
+                .attachment_manager
+                .handle_thumbnail(attachment, self.config)
+            {
+                Some(thumbnail) => AttachmentVariant::Thumbnail {
+                    thumbnail_path: self.config.relative_path(&thumbnail),
+                },
+                None => AttachmentVariant::Image,
+            },
             // Video duplicates the source tag intentionally; see
             // https://github.com/ReagentX/imessage-exporter/issues/73
             MediaType::Video(media_type) => AttachmentVariant::Video { media_type },
@@ -145,9 +160,16 @@ impl<'a> MessageFormatter<'a> for HTML<'a> {
                 let Some(filename) = attachment.filename() else {
                     return AttachmentRender::MissingFilename;
                 };
-                AttachmentVariant::Download {
-                    filename,
-                    file_size: attachment.file_size(),
+                match contact_cards(self.config, attachment) {
+                    Some(cards) => AttachmentVariant::Contact {
+                        cards,
+                        filename,
+                        file_size: attachment.file_size(),
+                    },
+                    None => AttachmentVariant::Download {
+                        filename,
+                        file_size: attachment.file_size(),
+                    },
                 }
             }
             MediaType::Unknown => {
@@ -184,7 +206,10 @@ impl<'a> MessageFormatter<'a> for HTML<'a> {
             match self.format_attachment(sticker, message, &AttachmentMeta::default()) {
                 AttachmentRender::Embedded(html) => html,
                 AttachmentRender::MissingFilename => return String::new(),
-                AttachmentRender::NamedFile(name) => return sanitize_html(&name).into_owned(),
+                AttachmentRender::NamedFile(name)
+                | AttachmentRender::Skipped { filename: name, .. } => {
+                    return sanitize_html(&name).into_owned();
+                }
             };
 
         if let Some(kind) = sticker.get_sticker_decoration(
@@ -346,30 +371,64 @@ impl<'a> MessageFormatter<'a> for HTML<'a> {
         let is_reply = matches!(context, RenderContext::Reply);
         let mut ctx = MessageContext::resolve(message, self.config.data_source.db())?;
         let mut attachment_index: usize = 0;
+        // Found before any attachment is copied, since conversion changes their types
+        let live_photos = LivePhotos::find(&message.components, &ctx.attachments);
 
         let mut parts = Vec::with_capacity(message.components.len());
         for (idx, message_part) in message.components.iter().enumerate() {
-            let body = dispatch_part_body(
+            // The motion half of a Live Photo is rendered with its still image
+            let is_motion = matches!(message_part, BubbleComponent::Attachment(_))
+                && live_photos.is_motion(attachment_index);
+            let live_photo = match message_part {
+                BubbleComponent::Attachment(_) => live_photos
+                    .motion_for(attachment_index)
+                    .and_then(|motion| split_pair(&mut ctx.attachments, attachment_index, motion)),
+                _ => None,
+            };
+
+            let body = if is_motion {
+                attachment_index += 1;
+                PartBody::Empty
+            } else if let Some((still, motion)) = live_photo {
+                attachment_index += 1;
+                match self.format_live_photo(still, motion, message) {
+                    AttachmentRender::Embedded(content) => self.body_attachment(content),
+                    AttachmentRender::MissingFilename => self.body_attachment_missing(),
+                    AttachmentRender::NamedFile(name) => self.body_attachment_error(&name),
+                    AttachmentRender::Skipped {
+                        filename,
+                        file_size,
+                    } => self.body_attachment_skipped(&filename, &file_size),
+                }
+            } else {
+                dispatch_part_body(
+                    self,
+                    message,
+                    idx,
+                    message_part,
+                    &mut ctx.attachments,
+                    &mut attachment_index,
+                )
+            };
+
+            let tapbacks = build_tapbacks(self, message, idx, Html::trust)?
+                .map(|tapbacks| TapbacksVM { tapbacks });
+            let replies = build_replies(
                 self,
-                message,
-                idx,
-                message_part,
-                &mut ctx.attachments,
-                &mut attachment_index,
-            );
+                ctx.replies_map.get_mut(&idx),
+                Self::BUFFER_CAPACITY,
+                Html::trust,
+            )?
+            .map(|replies| RepliesVM { replies });
+            if is_motion && tapbacks.is_none() && replies.is_none() {
+                continue;
+            }
 
             parts.push(MessagePartVM {
                 body,
                 expressive: ctx.expressive,
-                tapbacks: build_tapbacks(self, message, idx, Html::trust)?
-                    .map(|tapbacks| TapbacksVM { tapbacks }),
-                replies: build_replies(
-                    self,
-                    ctx.replies_map.get_mut(&idx),
-                    Self::BUFFER_CAPACITY,
-                    Html::trust,
-                )?
-                .map(|replies| RepliesVM { replies }),
+                tapbacks,
+                replies,
             });
         }
 
@@ -456,6 +515,13 @@ impl PartBodyBuilder for HTML<'_> {
         PartBody::AttachmentMissing
     }
 
+    fn body_attachment_skipped(&self, filename: &str, file_size: &str) -> Self::Body {
+        PartBody::AttachmentSkipped {
+            filename: Html::trust(sanitize_html(filename).into_owned()),
+            file_size: file_size.to_string(),
+        }
+    }
+
     fn body_sticker(&self, content: String) -> Self::Body {
         PartBody::Sticker {
             html: Html::trust(content),
@@ -497,6 +563,49 @@ impl PartBodyBuilder for HTML<'_> {
 
 // MARK: Impl
 impl HTML<'_> {
+    /// Render a Live Photo as its still image, which plays the motion on hover or tap
+    ///
+    /// If the motion cannot be copied, the still is rendered by itself.
+    fn format_live_photo(
+        &self,
+        still: &mut Attachment,
+        motion: &mut Attachment,
+        message: &Message,
+    ) -> AttachmentRender {
+        if let Err(render) = prepare_attachment(self.config, &self.state, still, message) {
+            return render;
+        }
+
+        let embed_path = self.config.message_attachment_path(still);
+        let thumbnail_path = self
+            .config
+            .options
+            .attachment_manager
+            .handle_thumbnail(still, self.config)
+            .map(|thumbnail| self.config.relative_path(&thumbnail));
+
+        let variant = match prepare_attachment(self.config, &self.state, motion, message) {
+            Ok(()) => match motion.mime_type() {
+                MediaType::Video(media_type) => AttachmentVariant::LivePhoto {
+                    thumbnail_path,
+                    motion_path: self.config.message_attachment_path(motion),
+                    media_type,
+                },
+                _ => AttachmentVariant::Image,
+            },
+            Err(_) => match thumbnail_path {
+                Some(thumbnail_path) => AttachmentVariant::Thumbnail { thumbnail_path },
+                None => AttachmentVariant::Image,
+            },
+        };
+
+        AttachmentRender::Embedded(render_template(&AttachmentVM {
+            lazy: !self.config.options.no_lazy,
+            embed_path,
+            variant,
+        }))
+    }
+
     fn get_time(&self, message: &Message) -> (String, String) {
         message_time(self.config, message)
     }
@@ -546,12 +655,19 @@ mod tests {
     use crate::{
         Config, HTML, Options,
         app::{
-            compatibility::attachment_manager::AttachmentManagerMode, contacts::Name,
+            compatibility::{
+                attachment_manager::AttachmentManagerMode,
+                converters::thumbnail::{THUMBNAIL_SIZE, ThumbnailFormat},
+            },
+            contacts::Name,
             export_type::ExportType,
+            test_dir::unique_test_dir,
         },
         exporters::formatter::{AttachmentRender, MessageFormatter, RenderContext},
     };
 
+    use image::RgbImage;
+
     use imessage_database::{
         message_types::text_effects::TextEffect,
         tables::{
@@ -569,6 +685,43 @@ mod tests {
         assert_eq!(exporter.state.files.len(), 0);
     }
 
+    #[test]
+    fn can_trim_footer() {
+        use std::{
+            fs::{File, read_to_string, write},
+            io::Write,
+        };
+
+        use crate::{app::test_dir::unique_test_dir, exporters::shared::driver::MessageWriter};
+
+        let path = unique_test_dir("html-trim-footer").join("chat.html");
+        write(&path, "<body>\n<p>old</p></body></html>").unwrap();
+
+        let mut file = File::options().read(true).append(true).open(&path).unwrap();
+        HTML::trim_file_footer(&mut file).unwrap();
+        file.write_all(b"<p>new</p></body></html>").unwrap();
+
+        assert_eq!(
+            read_to_string(&path).unwrap(),
+            "<body>\n<p>old</p><p>new</p></body></html>"
+        );
+    }
+
+    #[test]
+    fn can_trim_missing_footer() {
+        use std::fs::{File, read_to_string, write};
+
+        use crate::{app::test_dir::unique_test_dir, exporters::shared::driver::MessageWriter};
+
+        let path = unique_test_dir("html-trim-missing-footer").join("chat.html");
+        write(&path, "<p>old</p>").unwrap();
+
+        let mut file = File::options().read(true).append(true).open(&path).unwrap();
+        HTML::trim_file_footer(&mut file).unwrap();
+
+        assert_eq!(read_to_string(&path).unwrap(), "<p>old</p>");
+    }
+
     #[test]
     fn can_get_time_valid() {
         // Create exporter
@@ -1751,6 +1904,113 @@ mod tests {
         );
     }
 
+    #[test]
+    fn can_format_html_live_photo() {
+        // Create exporter
+        let options = Options::fake_options(ExportType::Html);
+        let config = Config::fake_app(options);
+        let exporter = HTML::new(&config).unwrap();
+
+        let message = Config::fake_message();
+
+        let mut still = Config::fake_attachment();
+        let mut motion = Config::fake_attachment();
+        motion.filename = Some("a/b/c/d.mov".to_string());
+        motion.transfer_name = Some("d.mov".to_string());
+        motion.mime_type = Some("video/quicktime".to_string());
+
+        let AttachmentRender::Embedded(actual) =
+            exporter.format_live_photo(&mut still, &mut motion, &message)
+        else {
+            panic!("Live Photo was not embedded");
+        };
+
+        assert!(actual.starts_with("<div class=\"live_photo\""));
+        assert!(actual.contains("<img src=\"a/b/c/d.jpg\" loading=\"lazy\">"));
+        assert!(actual.contains("<source src=\"a/b/c/d.mov\" type=\"quicktime\">"));
+        assert!(actual.contains("<a class=\"live_badge\" href=\"a/b/c/d.jpg\">LIVE</a>"));
+    }
+
+    #[test]
+    fn can_format_html_live_photo_motion_skipped() {
+        // Create exporter
+        let mut options = Options::fake_options(ExportType::Html);
+        options.attachment_manager.policy.images_only = true;
+        let config = Config::fake_app(options);
+        let exporter = HTML::new(&config).unwrap();
+
+        let message = Config::fake_message();
+
+        let mut still = Config::fake_attachment();
+        let mut motion = Config::fake_attachment();
+        motion.filename = Some("a/b/c/d.mov".to_string());
+        motion.mime_type = Some("video/quicktime".to_string());
+
+        let actual = exporter.format_live_photo(&mut still, &mut motion, &message);
+
+        assert_eq!(
+            actual,
+            AttachmentRender::Embedded("<img src=\"a/b/c/d.jpg\" loading=\"lazy\">".to_string())
+        );
+    }
+
+    #[test]
+    fn can_format_html_attachment_skipped() {
+        // Create exporter
+        let mut options = Options::fake_options(ExportType::Html);
+        options.attachment_manager.policy.max_size = Some(10);
+        let config = Config::fake_app(options);
+        let exporter = HTML::new(&config).unwrap();
+
+        let message = Config::fake_message();
+
+        let mut attachment = Config::fake_attachment();
+
+        let actual =
+            exporter.format_attachment(&mut attachment, &message, &AttachmentMeta::default());
+
+        assert_eq!(
+            actual,
+            AttachmentRender::Skipped {
+                filename: "d.jpg".to_string(),
+                file_size: "100.00 B".to_string()
+            }
+        );
+        assert!(attachment.copied_path.is_none());
+    }
+
+    #[test]
+    fn can_format_html_attachment_thumbnail() {
+        let source = unique_test_dir("html_thumbnail").join("large.png");
+        RgbImage::new(THUMBNAIL_SIZE * 2, THUMBNAIL_SIZE * 2)
+            .save(&source)
+            .unwrap();
+
+        // Create exporter
+        let mut options = Options::fake_options(ExportType::Html);
+        options.attachment_manager.mode = AttachmentManagerMode::Clone;
+        options.thumbnails = Some(ThumbnailFormat::Jpeg);
+        let config = Config::fake_app(options);
+        let exporter = HTML::new(&config).unwrap();
+
+        let message = Config::fake_message();
+
+        let mut attachment = Config::fake_attachment();
+        attachment.filename = Some(source.display().to_string());
+        attachment.transfer_name = Some("large.png".to_string());
+
+        let actual =
+            exporter.format_attachment(&mut attachment, &message, &AttachmentMeta::default());
+
+        assert_eq!(
+            actual,
+            AttachmentRender::Embedded(
+                "<a href=\"attachments/orphaned/0.png\"><img src=\"attachments/orphaned/thumbnails/0.png.jpg\" loading=\"lazy\"></a>"
+                    .to_string()
+            )
+        );
+    }
+
     #[test]
     fn can_format_html_attachment_macos_invalid_disabled() {
         // Create exporter
@@ -1913,6 +2173,64 @@ mod tests {
         );
     }
 
+    #[test]
+    fn can_format_html_attachment_contact() {
+        let source = unique_test_dir("html_contact").join("Jane Appleseed.vcf");
+        std::fs::write(
+            &source,
+            "BEGIN:VCARD\r\nVERSION:3.0\r\nFN:Jane Appleseed\r\nORG:Apple Inc.;\r\nTEL;type=CELL;type=VOICE;type=pref:+1 555 0100\r\nEMAIL;type=INTERNET:jane@example.com\r\nEND:VCARD\r\n",
+        )
+        .unwrap();
+
+        // Create exporter
+        let options = Options::fake_options(ExportType::Html);
+        let config = Config::fake_app(options);
+        let exporter = HTML::new(&config).unwrap();
+
+        let message = Config::fake_message();
+
+        let mut attachment = Config::fake_attachment();
+        attachment.mime_type = Some("text/vcard".to_string());
+        attachment.filename = Some(source.display().to_string());
+        attachment.transfer_name = Some("Jane Appleseed.vcf".to_string());
+
+        let actual =
+            exporter.format_attachment(&mut attachment, &message, &AttachmentMeta::default());
+
+        assert_eq!(
+            actual,
+            AttachmentRender::Embedded(format!(
+                "<div class=\"contact_card\">\n    <div class=\"contact_details\">\n        <span class=\"contact_name\">Jane Appleseed</span>\n        <span class=\"contact_organization\">Apple Inc.</span>\n        <span class=\"contact_field\"><span class=\"contact_label\">mobile</span> <a href=\"tel:+1 555 0100\">+1 555 0100</a></span>\n        <span class=\"contact_field\"><a href=\"mailto:jane@example.com\">jane@example.com</a></span>\n    </div>\n</div>\n<a href=\"{}\">Click to download Jane Appleseed.vcf (100.00 B)</a>",
+                source.display()
+            ))
+        );
+    }
+
+    #[test]
+    fn can_format_html_attachment_contact_unreadable() {
+        // Create exporter
+        let options = Options::fake_options(ExportType::Html);
+        let config = Config::fake_app(options);
+        let exporter = HTML::new(&config).unwrap();
+
+        let message = Config::fake_message();
+
+        let mut attachment = Config::fake_attachment();
+        attachment.mime_type = Some("text/x-vcard".to_string());
+        attachment.filename = Some("contact.vcf".to_string());
+        attachment.transfer_name = Some("contact.vcf".to_string());
+
+        let actual =
+            exporter.format_attachment(&mut attachment, &message, &AttachmentMeta::default());
+
+        assert_eq!(
+            actual,
+            AttachmentRender::Embedded(
+                "<a href=\"contact.vcf\">Click to download contact.vcf (100.00 B)</a>".to_string()
+            )
+        );
+    }
+
     #[test]
     fn can_format_html_attachment_application_download() {
         // Create exporter
@@ -2206,7 +2524,7 @@ mod balloon_format_tests {
         app::AppMessage,
         app_store::AppStoreMessage,
         collaboration::CollaborationMessage,
-        digital_touch::{DigitalTouch, from_payload as digital_touch_from_payload},
+        digital_touch::DigitalTouchMessage,
         handwriting::HandwrittenMessage,
         music::MusicMessage,
         placemark::{Placemark, PlacemarkMessage},
@@ -2742,11 +3060,25 @@ mod balloon_format_tests {
         let config = Config::fake_app(options);
         let exporter = HTML::new(&config).unwrap();
 
+        let payload_path = current_dir()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("imessage-database/test_data/digital_touch_message/kiss.bin");
+        let mut payload = vec![];
+        File::open(payload_path)
+            .unwrap()
+            .read_to_end(&mut payload)
+            .unwrap();
+        let touch = DigitalTouchMessage::from_payload(&payload).unwrap();
+
         let msg = Config::fake_message();
-        let actual = exporter.format_digital_touch(&msg, &DigitalTouch::Kiss);
-        let expected = "<div class=\"app_header\">\n    <div class=\"name\">Digital Touch Message</div>\n</div>\n<div class=\"app_footer\">\n    <div class=\"caption\">Kiss</div>\n</div>";
+        let actual = exporter.format_digital_touch(&msg, &touch);
+        let expected_header = "<div class=\"app_header\">\n    <div class=\"name\">Digital Touch Message</div>\n</div>\n<div class=\"digital_touch\">\n<svg ";
+        let expected_footer = "</svg>\n</div>\n<div class=\"app_footer\">\n    <div class=\"caption\">Kiss</div>\n</div>";
 
-        assert_eq!(actual, expected);
+        assert!(actual.starts_with(expected_header));
+        assert!(actual.ends_with(expected_footer));
     }
 
     #[test]
@@ -2765,13 +3097,13 @@ mod balloon_format_tests {
             .unwrap()
             .read_to_end(&mut payload)
             .unwrap();
-        let touch = digital_touch_from_payload(&payload).unwrap();
+        let touch = DigitalTouchMessage::from_payload(&payload).unwrap();
 
         let msg = Config::fake_message();
         let actual = exporter.format_digital_touch(&msg, &touch);
-        let expected = "<div class=\"app_header\">\n    <div class=\"name\">Digital Touch Message</div>\n</div>\n<div class=\"app_footer\">\n    <div class=\"caption\">Sketch</div>\n</div>";
 
-        assert_eq!(actual, expected);
+        assert!(actual.contains(&touch.render_svg()));
+        assert!(actual.contains("<div class=\"caption\">Sketch</div>"));
     }
 
     #[test]
@@ -2792,12 +3124,12 @@ mod balloon_format_tests {
             .unwrap();
         let balloon = HandwrittenMessage::from_payload(&payload).unwrap();
 
-        let mut expected = String::new();
         let expected_path = current_dir()
             .unwrap()
             .parent()
             .unwrap()
-            .join("imessage-database/test_data/handwritten_message/handwriting.svg");
+            .join("imessage-database/test_data/handwritten_message/handwriting_animated.svg");
+        let mut expected = String::new();
         File::open(expected_path)
             .unwrap()
             .read_to_string(&mut expected)
@@ -1,7 +1,10 @@
 use askama::Template;
 
 use imessage_database::{
-    message_types::{expressives::Expressive, sticker::StickerDecoration, variants::Announcement},
+    message_types::{
+        expressives::Expressive, sticker::StickerDecoration, variants::Announcement,
+        vcard::ContactCard,
+    },
     tables::messages::models::{GroupAction, Service},
 };
 
@@ -16,7 +19,9 @@ use crate::exporters::{
 #[derive(Template)]
 #[template(path = "balloons/digital_touch.html")]
 pub(super) struct DigitalTouchVM {
-    pub debug: String,
+    pub kind: String,
+    /// Rendered `svg` graphic, embedded as-is
+    pub svg: String,
 }
 
 #[derive(Template)]
@@ -196,9 +201,20 @@ pub(super) struct AttachmentVM<'a> {
 
 pub(super) enum AttachmentVariant<'a> {
     Image,
+    /// A downscaled preview that links to the full-size image at `embed_path`.
+    Thumbnail {
+        thumbnail_path: String,
+    },
     Video {
         media_type: &'a str,
     },
+    /// A still image at `embed_path` that plays its motion on hover or tap.
+    LivePhoto {
+        /// Downscaled preview of the still, if thumbnails are enabled
+        thumbnail_path: Option<String>,
+        motion_path: String,
+        media_type: &'a str,
+    },
     Audio {
         media_type: &'a str,
     },
@@ -211,6 +227,12 @@ pub(super) enum AttachmentVariant<'a> {
         filename: &'a str,
         file_size: String,
     },
+    /// A shared `.vcf` file, shown as its contacts with a link to the file.
+    Contact {
+        cards: Vec<ContactCard>,
+        filename: &'a str,
+        file_size: String,
+    },
     UnknownFolder {
         filename: &'a str,
         file_size: String,
@@ -337,6 +359,10 @@ pub(crate) enum PartBody {
         error: Html,
     },
     AttachmentMissing,
+    AttachmentSkipped {
+        filename: Html,
+        file_size: String,
+    },
     Sticker {
         html: Html,
     },
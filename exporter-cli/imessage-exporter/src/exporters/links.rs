@@ -0,0 +1,691 @@
+/*!
+ Exports every link shared in a database as a bookmarks file and a structured listing.
+
+ Links come from rich link preview balloons, the hyperlink ranges in formatted message text, and
+ web addresses typed into plain text. Each distinct URL is listed once with its preview title,
+ summary, and site name, along with every time it was shared, the sender, and the chat it was
+ shared in.
+
+ Two files are written to the export root:
+ - `links.html`, a Netscape bookmarks file that browsers and read-later services can import, with a
+   folder for each chat
+ - `links.json` or `links.csv`, with one entry for each distinct URL and a count of how many
+   times it was shared
+*/
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{Display, Write},
+    fs::write,
+    io::Error as IoError,
+};
+
+use imessage_database::{
+    message_types::{
+        text_effects::TextEffect,
+        url::URLMessage,
+        variants::{CustomBalloon, URLOverride, Variant},
+    },
+    tables::messages::{Message, models::BubbleComponent},
+    util::plist::parse_ns_keyed_archiver,
+};
+use serde::Serialize;
+
+use crate::{
+    app::{error::RuntimeError, runtime::Config},
+    exporters::{
+        locations::escape_xml,
+        shared::driver::{for_each_message, progress_bar},
+    },
+};
+
+/// Name of the files, without their extensions, that hold every link in the export
+pub const LINKS_FILE: &str = "links";
+
+/// File formats the link listing can be exported as
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LinkFormat {
+    /// A JSON array with the details and every share of each link
+    Json,
+    /// A spreadsheet with one row for each link
+    Csv,
+}
+
+impl LinkFormat {
+    /// Create an instance of the enum given user input
+    pub fn from_cli(format: &str) -> Option<Self> {
+        match format.to_lowercase().as_str() {
+            "json" => Some(Self::Json),
+            "csv" => Some(Self::Csv),
+            _ => None,
+        }
+    }
+
+    /// The file extension used for files of this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            LinkFormat::Json => "json",
+            LinkFormat::Csv => "csv",
+        }
+    }
+
+    /// Render `links` as a complete file of this format
+    fn render(&self, links: &[SharedLink]) -> Result<String, RuntimeError> {
+        match self {
+            LinkFormat::Json => render_json(links),
+            LinkFormat::Csv => Ok(render_csv(links)),
+        }
+    }
+}
+
+impl Display for LinkFormat {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LinkFormat::Json => write!(fmt, "json"),
+            LinkFormat::Csv => write!(fmt, "csv"),
+        }
+    }
+}
+
+/// A link found in a message, with any preview data the message cached for it
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+struct FoundLink {
+    url: String,
+    title: Option<String>,
+    summary: Option<String>,
+    site_name: Option<String>,
+}
+
+impl FoundLink {
+    fn new(url: &str) -> Self {
+        FoundLink {
+            url: url.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+/// A time a link was shared
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct LinkShare {
+    /// The name of the chat's file
+    chat: String,
+    sender: String,
+    /// When the message was sent, in RFC 3339 format with the local offset
+    time: String,
+    /// When the message was sent, in seconds since the Unix epoch
+    #[serde(skip)]
+    timestamp: i64,
+}
+
+/// A distinct URL and every time it was shared
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SharedLink {
+    url: String,
+    /// The webpage's title, from the first preview that included one
+    title: Option<String>,
+    /// The webpage's description, from the first preview that included one
+    summary: Option<String>,
+    /// The website's name, from the first preview that included one
+    site_name: Option<String>,
+    /// Every time the link was shared, in the order the messages were sent
+    shares: Vec<LinkShare>,
+}
+
+impl SharedLink {
+    /// The name to show for the link in a bookmarks file
+    fn title(&self) -> &str {
+        self.title.as_deref().unwrap_or(&self.url)
+    }
+
+    /// Fill in any preview data this link does not have yet
+    fn merge(&mut self, found: FoundLink) {
+        self.title = self.title.take().or(found.title);
+        self.summary = self.summary.take().or(found.summary);
+        self.site_name = self.site_name.take().or(found.site_name);
+    }
+}
+
+pub struct Links<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Every distinct link found, in the order they were first shared
+    links: Vec<SharedLink>,
+    /// Index into `links` for each URL
+    index: HashMap<String, usize>,
+}
+
+impl<'a> Links<'a> {
+    pub fn new(config: &'a Config) -> Self {
+        Links {
+            config,
+            links: vec![],
+            index: HashMap::new(),
+        }
+    }
+
+    /// Find the links in every message selected by the query filters, then write them in `format`
+    pub fn run(&mut self, format: LinkFormat) -> Result<(), RuntimeError> {
+        eprintln!("Exporting shared links...");
+        let config = self.config;
+        let pb = progress_bar(config);
+        // Links are read from the message body, so it is always decoded
+        for_each_message(config, &pb, true, |msg| {
+            let found = self.find_links(&msg);
+            if !found.is_empty() {
+                self.add_links(&msg, found);
+            }
+            Ok(())
+        })?;
+
+        self.write_files(format)
+    }
+
+    /// Find every distinct web link in a message
+    fn find_links(&self, message: &Message) -> Vec<FoundLink> {
+        let mut found: Vec<FoundLink> = vec![];
+        let mut add = |link: FoundLink| {
+            if is_web_url(&link.url) && !found.iter().any(|existing| existing.url == link.url) {
+                found.push(link);
+            }
+        };
+
+        // Rich link previews come first so their metadata is kept
+        if let Some(preview) = self.link_preview(message) {
+            add(preview);
+        }
+
+        for component in &message.components {
+            if let BubbleComponent::Text(attributes) = component {
+                for attribute in attributes {
+                    for effect in &attribute.effects {
+                        if let TextEffect::Link(url) = effect {
+                            add(FoundLink::new(url));
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(text) = &message.text {
+            for url in find_urls(text) {
+                add(FoundLink::new(url));
+            }
+        }
+
+        found
+    }
+
+    /// Read the URL and cached preview data from a rich link balloon
+    fn link_preview(&self, message: &Message) -> Option<FoundLink> {
+        if !matches!(message.variant(), Variant::App(CustomBalloon::URL)) {
+            return None;
+        }
+        let payload = message.payload_data(self.config.data_source.db())?;
+        let parsed = parse_ns_keyed_archiver(&payload).ok()?;
+        let URLOverride::Normal(balloon) = URLMessage::get_url_message_override(&parsed).ok()?
+        else {
+            return None;
+        };
+        Some(FoundLink {
+            url: balloon.get_url()?.to_string(),
+            title: balloon.title.map(String::from),
+            summary: balloon.summary.map(String::from),
+            site_name: balloon.site_name.map(String::from),
+        })
+    }
+
+    /// Record a share of each link found in a message
+    fn add_links(&mut self, message: &Message, found: Vec<FoundLink>) {
+        let config = self.config;
+        let Ok(date) = message.date(config.offset) else {
+            return;
+        };
+        let share = LinkShare {
+            chat: config.chat_filename(message),
+            sender: config
+                .who(
+                    message.handle_id,
+                    message.is_from_me(),
+                    &message.destination_caller_id,
+                )
+                .to_string(),
+            time: date.to_rfc3339(),
+            timestamp: date.timestamp(),
+        };
+
+        for link in found {
+            match self.index.get(&link.url) {
+                Some(&position) => {
+                    let existing = &mut self.links[position];
+                    existing.shares.push(share.clone());
+                    existing.merge(link);
+                }
+                None => {
+                    self.index.insert(link.url.clone(), self.links.len());
+                    self.links.push(SharedLink {
+                        url: link.url,
+                        title: link.title,
+                        summary: link.summary,
+                        site_name: link.site_name,
+                        shares: vec![share.clone()],
+                    });
+                }
+            }
+        }
+    }
+
+    /// Write the bookmarks file, then the listing in `format`
+    fn write_files(&self, format: LinkFormat) -> Result<(), RuntimeError> {
+        let export_path = &self.config.options.export_path;
+
+        write(
+            export_path.join(format!("{LINKS_FILE}.html")),
+            render_bookmarks(&self.links),
+        )?;
+        write(
+            export_path.join(format!("{LINKS_FILE}.{}", format.extension())),
+            format.render(&self.links)?,
+        )?;
+
+        eprintln!(
+            "Exported {} shared links from {} messages",
+            self.links.len(),
+            self.links
+                .iter()
+                .map(|link| link.shares.len())
+                .sum::<usize>()
+        );
+        Ok(())
+    }
+}
+
+/// Determine if a link points to a webpage, as opposed to `tel:`, `mailto:`, and other schemes
+fn is_web_url(url: &str) -> bool {
+    let lower = url.get(..8).unwrap_or(url).to_ascii_lowercase();
+    (lower.starts_with("https://") && url.len() > 8)
+        || (lower.starts_with("http://") && url.len() > 7)
+}
+
+/// Find the web addresses typed into a message's text
+fn find_urls(text: &str) -> Vec<&str> {
+    let mut urls = vec![];
+    let mut rest = text;
+    let mut offset = 0;
+
+    while let Some(position) = find_scheme(rest) {
+        let start = offset + position;
+        // Skip schemes that are part of a longer word, i.e. `xhttp://`
+        let in_word = text[..start]
+            .chars()
+            .next_back()
+            .is_some_and(|ch| ch.is_alphanumeric());
+
+        let candidate = &text[start..];
+        let end = candidate
+            .find(|ch: char| {
+                ch.is_whitespace() || ch.is_control() || matches!(ch, '<' | '>' | '"' | '\u{FFFC}')
+            })
+            .unwrap_or(candidate.len());
+        let url = trim_url(&candidate[..end]);
+
+        if !in_word && is_web_url(url) {
+            urls.push(url);
+        }
+
+        offset = start + end;
+        rest = &text[offset..];
+    }
+    urls
+}
+
+/// Find the byte offset of the next `http://` or `https://` in `text`, ignoring case
+fn find_scheme(text: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+    (0..bytes.len()).find(|&idx| {
+        let tail = &bytes[idx..];
+        tail.len() >= 7
+            && (tail[..7].eq_ignore_ascii_case(b"http://")
+                || (tail.len() >= 8 && tail[..8].eq_ignore_ascii_case(b"https://")))
+    })
+}
+
+/// Remove the punctuation that ends the sentence around a URL instead of belonging to it
+fn trim_url(mut url: &str) -> &str {
+    loop {
+        let Some(last) = url.chars().next_back() else {
+            return url;
+        };
+        let trim = match last {
+            '.' | ',' | ';' | ':' | '!' | '?' | '\'' | '*' => true,
+            // Keep closing brackets that are part of the URL, i.e. Wikipedia article names
+            ')' => url.matches('(').count() < url.matches(')').count(),
+            ']' => url.matches('[').count() < url.matches(']').count(),
+            _ => false,
+        };
+        if !trim {
+            return url;
+        }
+        url = &url[..url.len() - last.len_utf8()];
+    }
+}
+
+// MARK: Bookmarks
+/// Render a [Netscape bookmarks file](https://learn.microsoft.com/en-us/previous-versions/windows/internet-explorer/ie-developer/platform-apis/aa753582(v=vs.85))
+/// with a folder for each chat
+fn render_bookmarks(links: &[SharedLink]) -> String {
+    // Each link is filed under every chat it was shared in, dated by its first share there
+    let mut folders: BTreeMap<&str, Vec<(&SharedLink, i64)>> = BTreeMap::new();
+    for link in links {
+        for share in &link.shares {
+            let folder = folders.entry(share.chat.as_str()).or_default();
+            if !folder.iter().any(|(filed, _)| filed.url == link.url) {
+                folder.push((link, share.timestamp));
+            }
+        }
+    }
+
+    let mut html = String::from(
+        "<!DOCTYPE NETSCAPE-Bookmark-file-1>\n<META HTTP-EQUIV=\"Content-Type\" CONTENT=\"text/html; charset=UTF-8\">\n<TITLE>Shared Links</TITLE>\n<H1>Shared Links</H1>\n<DL><p>\n",
+    );
+    for (chat, links) in &folders {
+        let _ = writeln!(html, "    <DT><H3>{}</H3>", escape_xml(chat));
+        html.push_str("    <DL><p>\n");
+        for (link, timestamp) in links {
+            let _ = writeln!(
+                html,
+                "        <DT><A HREF=\"{}\" ADD_DATE=\"{timestamp}\">{}</A>",
+                escape_xml(&link.url),
+                escape_xml(link.title())
+            );
+            if let Some(summary) = &link.summary {
+                let _ = writeln!(html, "        <DD>{}", escape_xml(summary));
+            }
+        }
+        html.push_str("    </DL><p>\n");
+    }
+    html.push_str("</DL><p>\n");
+    html
+}
+
+// MARK: JSON
+#[derive(Serialize)]
+struct LinkEntry<'a> {
+    url: &'a str,
+    title: Option<&'a str>,
+    summary: Option<&'a str>,
+    site_name: Option<&'a str>,
+    count: usize,
+    first_shared: Option<&'a str>,
+    last_shared: Option<&'a str>,
+    shares: &'a [LinkShare],
+}
+
+fn render_json(links: &[SharedLink]) -> Result<String, RuntimeError> {
+    let entries: Vec<LinkEntry> = links
+        .iter()
+        .map(|link| LinkEntry {
+            url: &link.url,
+            title: link.title.as_deref(),
+            summary: link.summary.as_deref(),
+            site_name: link.site_name.as_deref(),
+            count: link.shares.len(),
+            first_shared: link.shares.first().map(|share| share.time.as_str()),
+            last_shared: link.shares.last().map(|share| share.time.as_str()),
+            shares: &link.shares,
+        })
+        .collect();
+    serde_json::to_string_pretty(&entries)
+        .map_err(|why| RuntimeError::DiskError(IoError::other(why)))
+}
+
+// MARK: CSV
+fn render_csv(links: &[SharedLink]) -> String {
+    let mut csv =
+        String::from("url,title,summary,site_name,count,first_shared,last_shared,chats,senders\n");
+    for link in links {
+        let mut chats: Vec<&str> = vec![];
+        let mut senders: Vec<&str> = vec![];
+        for share in &link.shares {
+            if !chats.contains(&share.chat.as_str()) {
+                chats.push(&share.chat);
+            }
+            if !senders.contains(&share.sender.as_str()) {
+                senders.push(&share.sender);
+            }
+        }
+
+        let row = [
+            link.url.as_str(),
+            link.title.as_deref().unwrap_or_default(),
+            link.summary.as_deref().unwrap_or_default(),
+            link.site_name.as_deref().unwrap_or_default(),
+            &link.shares.len().to_string(),
+            link.shares.first().map_or("", |share| share.time.as_str()),
+            link.shares.last().map_or("", |share| share.time.as_str()),
+            &chats.join("; "),
+            &senders.join("; "),
+        ]
+        .map(escape_csv);
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Quote a CSV field if it contains a delimiter, quote, or line break
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Config, Options,
+        app::{export_type::ExportType, test_dir::unique_test_dir},
+        exporters::links::{
+            FoundLink, LinkFormat, LinkShare, Links, SharedLink, escape_csv, find_urls, is_web_url,
+            render_bookmarks,
+        },
+    };
+
+    fn share(chat: &str, sender: &str, time: &str, timestamp: i64) -> LinkShare {
+        LinkShare {
+            chat: chat.to_string(),
+            sender: sender.to_string(),
+            time: time.to_string(),
+            timestamp,
+        }
+    }
+
+    fn article() -> SharedLink {
+        SharedLink {
+            url: "https://example.com/article?a=1&b=2".to_string(),
+            title: Some("An \"Article\"".to_string()),
+            summary: Some("About things, mostly".to_string()),
+            site_name: Some("Example".to_string()),
+            shares: vec![
+                share("Jane", "Me", "2023-10-14T13:54:29-07:00", 1697316869),
+                share("Book Club", "Jane", "2023-10-15T09:00:00-07:00", 1697385600),
+                share("Jane", "Jane", "2023-10-16T09:00:00-07:00", 1697472000),
+            ],
+        }
+    }
+
+    fn bare() -> SharedLink {
+        SharedLink {
+            url: "http://example.org".to_string(),
+            title: None,
+            summary: None,
+            site_name: None,
+            shares: vec![share(
+                "Jane",
+                "Jane",
+                "2023-10-17T09:00:00-07:00",
+                1697558400,
+            )],
+        }
+    }
+
+    #[test]
+    fn can_parse_format_any_case() {
+        assert_eq!(LinkFormat::from_cli("JSON"), Some(LinkFormat::Json));
+        assert_eq!(LinkFormat::from_cli("csv"), Some(LinkFormat::Csv));
+        assert_eq!(LinkFormat::from_cli("html"), None);
+    }
+
+    #[test]
+    fn can_find_urls() {
+        assert_eq!(
+            find_urls("Read https://example.com/a, then http://example.org/b."),
+            vec!["https://example.com/a", "http://example.org/b"]
+        );
+    }
+
+    #[test]
+    fn can_find_urls_in_parentheses() {
+        assert_eq!(
+            find_urls("(see https://en.wikipedia.org/wiki/Rust_(programming_language))"),
+            vec!["https://en.wikipedia.org/wiki/Rust_(programming_language)"]
+        );
+    }
+
+    #[test]
+    fn can_find_urls_any_case() {
+        assert_eq!(
+            find_urls("HTTPS://Example.com/Page"),
+            vec!["HTTPS://Example.com/Page"]
+        );
+    }
+
+    #[test]
+    fn cant_find_urls_without_host() {
+        assert!(find_urls("https:// and http://").is_empty());
+    }
+
+    #[test]
+    fn cant_find_urls_inside_words() {
+        assert!(find_urls("xhttps://example.com").is_empty());
+    }
+
+    #[test]
+    fn can_find_urls_next_to_attachments() {
+        assert_eq!(
+            find_urls("\u{FFFC}https://example.com\u{FFFC}"),
+            vec!["https://example.com"]
+        );
+    }
+
+    #[test]
+    fn can_check_web_url() {
+        assert!(is_web_url("https://example.com"));
+        assert!(is_web_url("HTTP://example.com"));
+        assert!(!is_web_url("mailto:jane@example.com"));
+        assert!(!is_web_url("tel:+15558675309"));
+        assert!(!is_web_url("https://"));
+    }
+
+    #[test]
+    fn can_merge_preview_data() {
+        let mut link = bare();
+        link.merge(FoundLink {
+            url: link.url.clone(),
+            title: Some("Example".to_string()),
+            summary: None,
+            site_name: Some("example.org".to_string()),
+        });
+        assert_eq!(link.title(), "Example");
+        assert_eq!(link.site_name.as_deref(), Some("example.org"));
+
+        link.merge(FoundLink {
+            url: link.url.clone(),
+            title: Some("Other".to_string()),
+            summary: None,
+            site_name: None,
+        });
+        assert_eq!(link.title(), "Example");
+    }
+
+    #[test]
+    fn can_render_bookmarks() {
+        let rendered = render_bookmarks(&[article(), bare()]);
+
+        assert!(rendered.starts_with("<!DOCTYPE NETSCAPE-Bookmark-file-1>"));
+        assert!(rendered.contains("<DT><H3>Book Club</H3>"));
+        assert!(rendered.contains(
+            "<DT><A HREF=\"https://example.com/article?a=1&amp;b=2\" ADD_DATE=\"1697316869\">An &quot;Article&quot;</A>"
+        ));
+        assert!(rendered.contains("<DD>About things, mostly"));
+        // Links without a title show their URL
+        assert!(rendered.contains(">http://example.org</A>"));
+        // A link shared twice in one chat is only filed there once
+        assert_eq!(rendered.matches("HREF=\"https://example.com").count(), 2);
+    }
+
+    #[test]
+    fn can_render_json() {
+        let rendered = LinkFormat::Json.render(&[article(), bare()]).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&rendered).unwrap();
+
+        assert_eq!(parsed[0]["url"], "https://example.com/article?a=1&b=2");
+        assert_eq!(parsed[0]["site_name"], "Example");
+        assert_eq!(parsed[0]["count"], 3);
+        assert_eq!(parsed[0]["first_shared"], "2023-10-14T13:54:29-07:00");
+        assert_eq!(parsed[0]["last_shared"], "2023-10-16T09:00:00-07:00");
+        assert_eq!(parsed[0]["shares"][1]["chat"], "Book Club");
+        assert!(parsed[0]["shares"][1].get("timestamp").is_none());
+        assert!(parsed[1]["title"].is_null());
+    }
+
+    #[test]
+    fn can_render_csv() {
+        let rendered = LinkFormat::Csv.render(&[article(), bare()]).unwrap();
+        let mut lines = rendered.lines();
+
+        assert_eq!(
+            lines.next(),
+            Some("url,title,summary,site_name,count,first_shared,last_shared,chats,senders")
+        );
+        assert_eq!(
+            lines.next(),
+            Some(
+                "https://example.com/article?a=1&b=2,\"An \"\"Article\"\"\",\"About things, mostly\",Example,3,2023-10-14T13:54:29-07:00,2023-10-16T09:00:00-07:00,Jane; Book Club,Me; Jane"
+            )
+        );
+        assert_eq!(
+            lines.next(),
+            Some(
+                "http://example.org,,,,1,2023-10-17T09:00:00-07:00,2023-10-17T09:00:00-07:00,Jane,Jane"
+            )
+        );
+    }
+
+    #[test]
+    fn can_escape_csv() {
+        assert_eq!(escape_csv("plain"), "plain");
+        assert_eq!(escape_csv("a,b"), "\"a,b\"");
+        assert_eq!(escape_csv("line\nbreak"), "\"line\nbreak\"");
+        assert_eq!(escape_csv("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn can_write_files() {
+        let mut options = Options::fake_options(ExportType::Html);
+        options.export_path = unique_test_dir("links_write");
+        let config = Config::fake_app(options);
+        let mut links = Links::new(&config);
+        links.links = vec![article(), bare()];
+
+        links.write_files(LinkFormat::Csv).unwrap();
+
+        let export_path = &config.options.export_path;
+        let bookmarks = std::fs::read_to_string(export_path.join("links.html")).unwrap();
+        assert!(bookmarks.contains("<H1>Shared Links</H1>"));
+        let listing = std::fs::read_to_string(export_path.join("links.csv")).unwrap();
+        assert_eq!(listing.lines().count(), 3);
+    }
+}
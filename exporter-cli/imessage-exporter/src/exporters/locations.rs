@@ -0,0 +1,578 @@
+/*!
+ Exports every shared location in a database as a map file that GIS tools can open offline.
+
+ Locations come from Maps placemark balloons, Check In and Find My app messages, and the legacy
+ shared location announcements. Each one is written with its sender, time, and place name to
+ `locations/<Person>.<ext>`, where `<Person>` is the name the other exporters give the chat's file,
+ and every location in the export is written to `locations.<ext>` in the export root.
+
+ Only placemarks carry coordinates. The other locations are written without a position in GeoJSON
+ and KML files so they still show up in the timeline, and are left out of GPX files, which cannot
+ store a waypoint without one.
+*/
+
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Write},
+    fs::{create_dir_all, write},
+    io::Error as IoError,
+};
+
+use imessage_database::{
+    message_types::{
+        app::AppMessage,
+        url::URLMessage,
+        variants::{BalloonProvider, CustomBalloon, URLOverride, Variant},
+    },
+    tables::messages::{Message, models::SharedLocation},
+    util::plist::parse_ns_keyed_archiver,
+};
+use serde::Serialize;
+
+use crate::{
+    app::{error::RuntimeError, runtime::Config},
+    exporters::shared::driver::{for_each_message, progress_bar},
+};
+
+/// Name of the directory that holds the file for each chat
+pub const LOCATIONS_DIR: &str = "locations";
+
+/// Name of the file, without its extension, that holds every location in the export
+const ALL_LOCATIONS: &str = "locations";
+
+/// File formats locations can be exported as
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LocationFormat {
+    /// [GeoJSON](https://datatracker.ietf.org/doc/html/rfc7946) feature collections
+    GeoJson,
+    /// [KML](https://developers.google.com/kml/documentation/kmlreference) documents, used by Google Earth
+    Kml,
+    /// [GPX](https://www.topografix.com/gpx.asp) waypoints, used by GPS devices and mapping apps
+    Gpx,
+}
+
+impl LocationFormat {
+    /// Create an instance of the enum given user input
+    pub fn from_cli(format: &str) -> Option<Self> {
+        match format.to_lowercase().as_str() {
+            "geojson" | "json" => Some(Self::GeoJson),
+            "kml" => Some(Self::Kml),
+            "gpx" => Some(Self::Gpx),
+            _ => None,
+        }
+    }
+
+    /// The file extension used for files of this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            LocationFormat::GeoJson => "geojson",
+            LocationFormat::Kml => "kml",
+            LocationFormat::Gpx => "gpx",
+        }
+    }
+
+    /// Render `points` as a complete file of this format
+    fn render(&self, title: &str, points: &[&LocationPoint]) -> Result<String, RuntimeError> {
+        match self {
+            LocationFormat::GeoJson => render_geojson(points),
+            LocationFormat::Kml => Ok(render_kml(title, points)),
+            LocationFormat::Gpx => Ok(render_gpx(title, points)),
+        }
+    }
+}
+
+impl Display for LocationFormat {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LocationFormat::GeoJson => write!(fmt, "geojson"),
+            LocationFormat::Kml => write!(fmt, "kml"),
+            LocationFormat::Gpx => write!(fmt, "gpx"),
+        }
+    }
+}
+
+/// The kind of message a location was found in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LocationKind {
+    /// A place shared from the Maps app
+    Placemark,
+    /// A Check In app message
+    CheckIn,
+    /// A Find My app message
+    FindMy,
+    /// The sender began sharing their location
+    StartedSharing,
+    /// The sender stopped sharing their location
+    StoppedSharing,
+}
+
+impl LocationKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            LocationKind::Placemark => "Shared Place",
+            LocationKind::CheckIn => "Check In",
+            LocationKind::FindMy => "Find My",
+            LocationKind::StartedSharing => "Started Sharing Location",
+            LocationKind::StoppedSharing => "Stopped Sharing Location",
+        }
+    }
+}
+
+/// A location found in a message
+#[derive(Debug, Clone, PartialEq)]
+struct LocationPoint {
+    /// The name of the chat's file
+    chat: String,
+    sender: String,
+    /// When the message was sent, in RFC 3339 format with the local offset
+    time: String,
+    /// When the message was sent, in RFC 3339 format in UTC
+    utc_time: String,
+    kind: LocationKind,
+    /// The name of the place, or the text of the app message
+    name: Option<String>,
+    address: Option<String>,
+    /// `(latitude, longitude)`, if the message includes them
+    coordinates: Option<(f64, f64)>,
+    url: Option<String>,
+}
+
+impl LocationPoint {
+    /// The name to show for the point on a map
+    fn title(&self) -> &str {
+        self.name.as_deref().unwrap_or(self.kind.as_str())
+    }
+
+    /// A line describing who shared the point, and how
+    fn description(&self) -> String {
+        let mut description = format!("{}: {}", self.kind.as_str(), self.sender);
+        if let Some(address) = &self.address {
+            let _ = write!(description, "\n{address}");
+        }
+        description
+    }
+}
+
+pub struct Locations<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// Every location found, in the order the messages were sent
+    points: Vec<LocationPoint>,
+}
+
+impl<'a> Locations<'a> {
+    pub fn new(config: &'a Config) -> Self {
+        Locations {
+            config,
+            points: vec![],
+        }
+    }
+
+    /// Find the locations in every message selected by the query filters, then write them in `format`
+    pub fn run(&mut self, format: LocationFormat) -> Result<(), RuntimeError> {
+        eprintln!("Exporting shared locations...");
+        let config = self.config;
+        let pb = progress_bar(config);
+        for_each_message(config, &pb, false, |msg| {
+            if let Some(point) = self.find_location(&msg) {
+                self.points.push(point);
+            }
+            Ok(())
+        })?;
+
+        self.write_files(format)
+    }
+
+    /// Build a [`LocationPoint`] from a message, if it shares a location
+    fn find_location(&mut self, message: &Message) -> Option<LocationPoint> {
+        let config = self.config;
+        let (kind, name, address, coordinates, url) =
+            if let Some(shared) = message.shared_location_kind() {
+                let kind = match shared {
+                    SharedLocation::Started => LocationKind::StartedSharing,
+                    SharedLocation::Stopped => LocationKind::StoppedSharing,
+                };
+                (kind, None, None, None, None)
+            } else {
+                let Variant::App(balloon) = message.variant() else {
+                    return None;
+                };
+                if !matches!(
+                    balloon,
+                    CustomBalloon::URL | CustomBalloon::CheckIn | CustomBalloon::FindMy
+                ) {
+                    return None;
+                }
+                let payload = message.payload_data(config.data_source.db())?;
+                let parsed = parse_ns_keyed_archiver(&payload).ok()?;
+
+                match balloon {
+                    CustomBalloon::URL => {
+                        let URLOverride::SharedPlacemark(placemark) =
+                            URLMessage::get_url_message_override(&parsed).ok()?
+                        else {
+                            return None;
+                        };
+                        (
+                            LocationKind::Placemark,
+                            placemark
+                                .placemark
+                                .name
+                                .or(placemark.place_name)
+                                .map(String::from),
+                            placemark
+                                .place_name
+                                .or(placemark.placemark.address)
+                                .map(String::from),
+                            placemark.coordinates(),
+                            placemark.get_url().map(String::from),
+                        )
+                    }
+                    _ => {
+                        let app = AppMessage::from_map(&parsed).ok()?;
+                        let kind = if matches!(balloon, CustomBalloon::CheckIn) {
+                            LocationKind::CheckIn
+                        } else {
+                            LocationKind::FindMy
+                        };
+                        (
+                            kind,
+                            app.ldtext.or(app.caption).map(String::from),
+                            None,
+                            None,
+                            None,
+                        )
+                    }
+                }
+            };
+
+        let date = message.date(config.offset).ok()?;
+        Some(LocationPoint {
+            chat: config.chat_filename(message),
+            sender: config
+                .who(
+                    message.handle_id,
+                    message.is_from_me(),
+                    &message.destination_caller_id,
+                )
+                .to_string(),
+            time: date.to_rfc3339(),
+            utc_time: date.naive_utc().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            kind,
+            name,
+            address,
+            coordinates,
+            url,
+        })
+    }
+
+    /// Write a file for each chat with locations, then a file with every location
+    fn write_files(&self, format: LocationFormat) -> Result<(), RuntimeError> {
+        let export_path = &self.config.options.export_path;
+        let extension = format.extension();
+
+        let mut chats: BTreeMap<&str, Vec<&LocationPoint>> = BTreeMap::new();
+        for point in &self.points {
+            chats.entry(point.chat.as_str()).or_default().push(point);
+        }
+
+        let chat_dir = export_path.join(LOCATIONS_DIR);
+        if !chats.is_empty() {
+            create_dir_all(&chat_dir)?;
+        }
+        for (chat, points) in &chats {
+            write(
+                chat_dir.join(format!("{chat}.{extension}")),
+                format.render(chat, points)?,
+            )?;
+        }
+
+        let all: Vec<&LocationPoint> = self.points.iter().collect();
+        write(
+            export_path.join(format!("{ALL_LOCATIONS}.{extension}")),
+            format.render("Shared Locations", &all)?,
+        )?;
+
+        eprintln!(
+            "Exported {} shared locations from {} chats",
+            self.points.len(),
+            chats.len()
+        );
+        Ok(())
+    }
+}
+
+// MARK: GeoJSON
+#[derive(Serialize)]
+struct FeatureCollection<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    features: Vec<Feature<'a>>,
+}
+
+#[derive(Serialize)]
+struct Feature<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    /// `null` when the location has no coordinates
+    geometry: Option<Geometry>,
+    properties: Properties<'a>,
+}
+
+#[derive(Serialize)]
+struct Geometry {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    /// `[longitude, latitude]`, as GeoJSON requires
+    coordinates: [f64; 2],
+}
+
+#[derive(Serialize)]
+struct Properties<'a> {
+    name: &'a str,
+    kind: &'static str,
+    chat: &'a str,
+    sender: &'a str,
+    time: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    address: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<&'a str>,
+}
+
+fn render_geojson(points: &[&LocationPoint]) -> Result<String, RuntimeError> {
+    let collection = FeatureCollection {
+        kind: "FeatureCollection",
+        features: points
+            .iter()
+            .map(|point| Feature {
+                kind: "Feature",
+                geometry: point.coordinates.map(|(latitude, longitude)| Geometry {
+                    kind: "Point",
+                    coordinates: [longitude, latitude],
+                }),
+                properties: Properties {
+                    name: point.title(),
+                    kind: point.kind.as_str(),
+                    chat: &point.chat,
+                    sender: &point.sender,
+                    time: &point.time,
+                    address: point.address.as_deref(),
+                    url: point.url.as_deref(),
+                },
+            })
+            .collect(),
+    };
+    serde_json::to_string_pretty(&collection)
+        .map_err(|why| RuntimeError::DiskError(IoError::other(why)))
+}
+
+// MARK: KML
+fn render_kml(title: &str, points: &[&LocationPoint]) -> String {
+    let mut kml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<kml xmlns=\"http://www.opengis.net/kml/2.2\">\n<Document>\n",
+    );
+    let _ = writeln!(kml, "<name>{}</name>", escape_xml(title));
+    for point in points {
+        kml.push_str("<Placemark>\n");
+        let _ = writeln!(kml, "<name>{}</name>", escape_xml(point.title()));
+        let _ = writeln!(
+            kml,
+            "<description>{}</description>",
+            escape_xml(&point.description())
+        );
+        let _ = writeln!(kml, "<TimeStamp><when>{}</when></TimeStamp>", point.time);
+        kml.push_str("<ExtendedData>\n");
+        for (name, value) in [
+            ("kind", Some(point.kind.as_str())),
+            ("chat", Some(point.chat.as_str())),
+            ("sender", Some(point.sender.as_str())),
+            ("address", point.address.as_deref()),
+            ("url", point.url.as_deref()),
+        ] {
+            if let Some(value) = value {
+                let _ = writeln!(
+                    kml,
+                    "<Data name=\"{name}\"><value>{}</value></Data>",
+                    escape_xml(value)
+                );
+            }
+        }
+        kml.push_str("</ExtendedData>\n");
+        if let Some((latitude, longitude)) = point.coordinates {
+            let _ = writeln!(
+                kml,
+                "<Point><coordinates>{longitude},{latitude}</coordinates></Point>"
+            );
+        }
+        kml.push_str("</Placemark>\n");
+    }
+    kml.push_str("</Document>\n</kml>\n");
+    kml
+}
+
+// MARK: GPX
+fn render_gpx(title: &str, points: &[&LocationPoint]) -> String {
+    let mut gpx = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<gpx version=\"1.1\" creator=\"imessage-exporter\" xmlns=\"http://www.topografix.com/GPX/1/1\">\n",
+    );
+    let _ = writeln!(
+        gpx,
+        "<metadata><name>{}</name></metadata>",
+        escape_xml(title)
+    );
+    for point in points {
+        // Waypoints must have a position
+        let Some((latitude, longitude)) = point.coordinates else {
+            continue;
+        };
+        let _ = writeln!(gpx, "<wpt lat=\"{latitude}\" lon=\"{longitude}\">");
+        let _ = writeln!(gpx, "<time>{}</time>", point.utc_time);
+        let _ = writeln!(gpx, "<name>{}</name>", escape_xml(point.title()));
+        let _ = writeln!(gpx, "<desc>{}</desc>", escape_xml(&point.description()));
+        if let Some(url) = &point.url {
+            let _ = writeln!(gpx, "<link href=\"{}\"/>", escape_xml(url));
+        }
+        let _ = writeln!(gpx, "<type>{}</type>", point.kind.as_str());
+        gpx.push_str("</wpt>\n");
+    }
+    gpx.push_str("</gpx>\n");
+    gpx
+}
+
+/// Escape the characters that cannot appear in XML text or attribute values
+pub(crate) fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Config, Options,
+        app::{export_type::ExportType, test_dir::unique_test_dir},
+        exporters::locations::{
+            LocationFormat, LocationKind, LocationPoint, Locations, escape_xml,
+        },
+    };
+
+    fn placemark() -> LocationPoint {
+        LocationPoint {
+            chat: "Jane".to_string(),
+            sender: "Me".to_string(),
+            time: "2023-10-14T13:54:29-07:00".to_string(),
+            utc_time: "2023-10-14T20:54:29Z".to_string(),
+            kind: LocationKind::Placemark,
+            name: Some("Cherry Cove".to_string()),
+            address: Some("Cherry Cove, Avalon".to_string()),
+            coordinates: Some((33.450858, -118.508212)),
+            url: Some("https://maps.apple.com/?ll=33.450858,-118.508212&q=A&t=m".to_string()),
+        }
+    }
+
+    fn check_in() -> LocationPoint {
+        LocationPoint {
+            chat: "Jane".to_string(),
+            sender: "Jane".to_string(),
+            time: "2023-10-14T14:00:00-07:00".to_string(),
+            utc_time: "2023-10-14T21:00:00Z".to_string(),
+            kind: LocationKind::CheckIn,
+            name: Some("Check In: Fake Location".to_string()),
+            address: None,
+            coordinates: None,
+            url: None,
+        }
+    }
+
+    #[test]
+    fn can_parse_format_any_case() {
+        assert_eq!(
+            LocationFormat::from_cli("GeoJSON"),
+            Some(LocationFormat::GeoJson)
+        );
+        assert_eq!(LocationFormat::from_cli("kml"), Some(LocationFormat::Kml));
+        assert_eq!(LocationFormat::from_cli("GPX"), Some(LocationFormat::Gpx));
+        assert_eq!(LocationFormat::from_cli("csv"), None);
+    }
+
+    #[test]
+    fn can_render_geojson() {
+        let (placemark, check_in) = (placemark(), check_in());
+        let rendered = LocationFormat::GeoJson
+            .render("Jane", &[&placemark, &check_in])
+            .unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&rendered).unwrap();
+
+        assert_eq!(parsed["type"], "FeatureCollection");
+        assert_eq!(
+            parsed["features"][0]["geometry"]["coordinates"],
+            serde_json::json!([-118.508212, 33.450858])
+        );
+        assert_eq!(parsed["features"][0]["properties"]["name"], "Cherry Cove");
+        assert_eq!(parsed["features"][0]["properties"]["sender"], "Me");
+        assert!(parsed["features"][1]["geometry"].is_null());
+        assert!(parsed["features"][1]["properties"].get("address").is_none());
+    }
+
+    #[test]
+    fn can_render_kml() {
+        let (placemark, check_in) = (placemark(), check_in());
+        let rendered = LocationFormat::Kml
+            .render("Jane", &[&placemark, &check_in])
+            .unwrap();
+
+        assert!(
+            rendered.contains("<Point><coordinates>-118.508212,33.450858</coordinates></Point>")
+        );
+        assert!(rendered.contains("<TimeStamp><when>2023-10-14T13:54:29-07:00</when></TimeStamp>"));
+        assert!(rendered.contains("ll=33.450858,-118.508212&amp;q=A&amp;t=m"));
+        assert_eq!(rendered.matches("<Placemark>").count(), 2);
+        assert_eq!(rendered.matches("<Point>").count(), 1);
+    }
+
+    #[test]
+    fn can_render_gpx() {
+        let (placemark, check_in) = (placemark(), check_in());
+        let rendered = LocationFormat::Gpx
+            .render("Jane", &[&placemark, &check_in])
+            .unwrap();
+
+        assert!(rendered.contains("<wpt lat=\"33.450858\" lon=\"-118.508212\">"));
+        assert!(rendered.contains("<time>2023-10-14T20:54:29Z</time>"));
+        assert!(rendered.contains("<desc>Shared Place: Me\nCherry Cove, Avalon</desc>"));
+        // Points without coordinates cannot be waypoints
+        assert_eq!(rendered.matches("<wpt").count(), 1);
+    }
+
+    #[test]
+    fn can_escape_xml() {
+        assert_eq!(escape_xml("a<b>&\"c'"), "a&lt;b&gt;&amp;&quot;c&apos;");
+    }
+
+    #[test]
+    fn can_write_files() {
+        let mut options = Options::fake_options(ExportType::Html);
+        options.export_path = unique_test_dir("locations_write");
+        let config = Config::fake_app(options);
+        let mut locations = Locations::new(&config);
+        locations.points = vec![placemark(), check_in()];
+
+        locations.write_files(LocationFormat::Kml).unwrap();
+
+        let export_path = &config.options.export_path;
+        let chat = std::fs::read_to_string(export_path.join("locations/Jane.kml")).unwrap();
+        assert!(chat.contains("<name>Jane</name>"));
+        let all = std::fs::read_to_string(export_path.join("locations.kml")).unwrap();
+        assert!(all.contains("<name>Shared Locations</name>"));
+    }
+}
@@ -1,4 +1,8 @@
 pub mod formatter;
+pub mod gallery;
 pub mod html;
+pub mod links;
+pub mod locations;
+pub mod report;
 pub(crate) mod shared;
 pub mod txt;
@@ -0,0 +1,855 @@
+/*!
+ Draws charts that summarize the messages in an HTML export.
+
+ `report.html` is written to the export root next to the chat files. It uses the same stylesheet as
+ the HTML export and draws every chart as inline SVG, so it works offline and without JavaScript.
+ The report shows:
+ - The messages sent each month in the busiest chats
+ - Activity by day of the week and hour of the day
+ - How long replies take, for messages we sent and messages others sent
+ - The contacts that sent the most messages
+ - The attachments sent each month, by kind
+
+ Messages are counted the same way as `--stats`: tapbacks and announcements are skipped, and a reply
+ is a message sent within a day of a message from someone else in the same chat.
+*/
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+    fs::write,
+};
+
+use chrono::{DateTime, Datelike, Local, Timelike};
+use imessage_database::{
+    tables::{attachment::Attachment, messages::Message, table::ORPHANED},
+    util::size::format_file_size,
+};
+
+use crate::{
+    app::{
+        error::RuntimeError,
+        runtime::Config,
+        sanitizers::sanitize_html,
+        stats::{Person, REPLY_WINDOW, ReplyTimer, WEEKDAYS, attachment_kind, truncate},
+    },
+    exporters::{
+        html::{FOOTER, HEADER, STYLE},
+        shared::driver::{for_each_message, progress_bar},
+    },
+};
+
+/// Name of the file the report is written to, in the export root
+pub const REPORT_FILE: &str = "report.html";
+
+/// Number of chats drawn in the messages over time chart
+const CHART_CHATS: usize = 6;
+
+/// Number of contacts drawn in the top contacts chart
+const TOP_CONTACTS: usize = 15;
+
+/// Maximum number of characters of a name shown in a chart
+const NAME_LENGTH: usize = 24;
+
+/// The upper bound, in seconds, and label of each range of reply times
+const REPLY_RANGES: [(i64, &str); 7] = [
+    (60, "< 1m"),
+    (5 * 60, "1-5m"),
+    (15 * 60, "5-15m"),
+    (60 * 60, "15m-1h"),
+    (3 * 60 * 60, "1-3h"),
+    (12 * 60 * 60, "3-12h"),
+    (REPLY_WINDOW, "12-24h"),
+];
+
+/// Colors used for each series in a chart, in order
+const PALETTE: [&str; 6] = [
+    "#1982FC", "#65c466", "#ff9f0a", "#bf5af2", "#ff375f", "#64d2ff",
+];
+
+// Chart dimensions, in SVG user units
+const WIDTH: f64 = 720.;
+const PLOT_HEIGHT: f64 = 220.;
+const LEFT: f64 = 48.;
+const RIGHT: f64 = 16.;
+const TOP: f64 = 12.;
+const BOTTOM: f64 = 28.;
+/// Width of the names beside the top contacts chart
+const NAME_WIDTH: f64 = 180.;
+/// Height of each bar in the top contacts chart and each row in the activity heatmap
+const ROW_HEIGHT: f64 = 24.;
+/// Number of lines drawn across the value axis, not counting zero
+const GRID_LINES: u64 = 4;
+/// Maximum number of labels drawn along the time axis
+const AXIS_LABELS: usize = 12;
+
+/// A year and month, as in `(2023, 1)`
+type Month = (i32, u32);
+
+/// The messages sent in a chat
+#[derive(Debug)]
+struct ChatSeries {
+    name: String,
+    messages: u64,
+    /// Number of messages sent each month
+    months: BTreeMap<Month, u64>,
+}
+
+/// The messages a contact sent, across every chat
+#[derive(Debug)]
+struct Contact {
+    name: String,
+    messages: u64,
+}
+
+/// A named set of values drawn in one color
+#[derive(Debug, PartialEq)]
+struct Series<'a> {
+    name: &'a str,
+    values: Vec<u64>,
+}
+
+pub struct Report<'a> {
+    /// Data that is setup from the application's runtime
+    pub config: &'a Config,
+    /// The messages in each chat, keyed by deduplicated chat ID
+    chats: HashMap<Option<i32>, ChatSeries>,
+    /// The messages each person other than us sent
+    contacts: HashMap<Person, Contact>,
+    /// Number of messages sent in each hour of each day of the week, starting on Monday
+    heatmap: [[u64; 24]; 7],
+    /// Number of replies we sent that fall in each of the [`REPLY_RANGES`]
+    sent_replies: [u64; REPLY_RANGES.len()],
+    /// Number of replies others sent that fall in each of the [`REPLY_RANGES`]
+    received_replies: [u64; REPLY_RANGES.len()],
+    /// Number of attachments of each kind sent each month
+    attachments: BTreeMap<Month, BTreeMap<&'static str, u64>>,
+    /// Total size of every attachment, in bytes
+    attachment_bytes: u64,
+    /// The time each reply took
+    replies: ReplyTimer,
+}
+
+impl<'a> Report<'a> {
+    pub fn new(config: &'a Config) -> Self {
+        Report {
+            config,
+            chats: HashMap::new(),
+            contacts: HashMap::new(),
+            heatmap: [[0; 24]; 7],
+            sent_replies: [0; REPLY_RANGES.len()],
+            received_replies: [0; REPLY_RANGES.len()],
+            attachments: BTreeMap::new(),
+            attachment_bytes: 0,
+            replies: ReplyTimer::default(),
+        }
+    }
+
+    /// Count every message selected by the query filters, then write the report to the export root
+    pub fn run(&mut self) -> Result<(), RuntimeError> {
+        let config = self.config;
+        let db = config.data_source.db();
+
+        eprintln!("Writing {REPORT_FILE}...");
+        let pb = progress_bar(config);
+        for_each_message(config, &pb, false, |msg| {
+            if msg.is_tapback() || msg.is_announcement() {
+                return Ok(());
+            }
+            let Ok(date) = msg.date(config.offset) else {
+                return Ok(());
+            };
+            let attachments = if msg.has_attachments() {
+                Attachment::from_message(db, &msg)?
+            } else {
+                vec![]
+            };
+            self.add(&msg, date, &attachments);
+            Ok(())
+        })?;
+
+        write(config.options.export_path.join(REPORT_FILE), self.render())?;
+        Ok(())
+    }
+
+    /// Count a message toward its chat, its sender, and the time it was sent
+    fn add(&mut self, message: &Message, date: DateTime<Local>, attachments: &[Attachment]) {
+        let config = self.config;
+        let person = Person::of(config, message);
+        let chat_id = config.conversation(message).map(|(_, id)| *id);
+        let month = (date.year(), date.month());
+
+        if let Some(seconds) = self.replies.reply(chat_id, person, date.timestamp()) {
+            let replies = match person {
+                Person::Me => &mut self.sent_replies,
+                _ => &mut self.received_replies,
+            };
+            replies[reply_range(seconds)] += 1;
+        }
+
+        let chat = self.chats.entry(chat_id).or_insert_with(|| ChatSeries {
+            name: config
+                .chat_name(message)
+                .unwrap_or_else(|| ORPHANED.to_string()),
+            messages: 0,
+            months: BTreeMap::new(),
+        });
+        chat.messages += 1;
+        *chat.months.entry(month).or_default() += 1;
+
+        self.heatmap[date.weekday().num_days_from_monday() as usize][date.hour() as usize] += 1;
+
+        if person != Person::Me {
+            self.contacts
+                .entry(person)
+                .or_insert_with(|| Contact {
+                    name: person.name(config, message),
+                    messages: 0,
+                })
+                .messages += 1;
+        }
+
+        for attachment in attachments {
+            *self
+                .attachments
+                .entry(month)
+                .or_default()
+                .entry(attachment_kind(&attachment.mime_type()))
+                .or_default() += 1;
+            self.attachment_bytes += u64::try_from(attachment.total_bytes).unwrap_or(0);
+        }
+    }
+
+    /// Build the report page
+    fn render(&self) -> String {
+        let mut chats: Vec<&ChatSeries> = self.chats.values().collect();
+        chats.sort_by(|left, right| {
+            right
+                .messages
+                .cmp(&left.messages)
+                .then_with(|| left.name.cmp(&right.name))
+        });
+        let messages: u64 = chats.iter().map(|chat| chat.messages).sum();
+        let attachment_count: u64 = self.attachments.values().flat_map(BTreeMap::values).sum();
+
+        // Every chart over time shares the same months, from the first message to the last
+        let months = month_range(
+            chats
+                .iter()
+                .flat_map(|chat| chat.months.keys())
+                .chain(self.attachments.keys())
+                .copied(),
+        );
+        let labels: Vec<String> = months
+            .iter()
+            .map(|(year, month)| format!("{year}-{month:02}"))
+            .collect();
+
+        let mut page = String::with_capacity(16384);
+        page.push_str(HEADER);
+        let _ = write!(
+            page,
+            "\n<title>Message Report</title>\n<style>\n{STYLE}\n</style><link rel=\"stylesheet\" href=\"style.css\">\n</head>\n<body>\n<div class=\"report\">\n<h1>Message Report</h1>\n<p class=\"report_summary\">{messages} messages in {} chats, with {attachment_count} attachments totaling {}</p>\n",
+            chats.len(),
+            format_file_size(self.attachment_bytes),
+        );
+
+        // Messages over time
+        let series: Vec<Series> = chats
+            .iter()
+            .take(CHART_CHATS)
+            .map(|chat| Series {
+                name: &chat.name,
+                values: months
+                    .iter()
+                    .map(|month| chat.months.get(month).copied().unwrap_or(0))
+                    .collect(),
+            })
+            .collect();
+        render_section(
+            &mut page,
+            "Messages over time",
+            &format!(
+                "Messages sent each month in the {} busiest chats",
+                series.len()
+            ),
+            (!series.is_empty()).then(|| line_chart(&labels, &series)),
+            &series,
+        );
+
+        // Activity heatmap
+        render_section(
+            &mut page,
+            "Activity",
+            "Messages sent in each hour of each day of the week",
+            (messages > 0).then(|| heatmap(&self.heatmap)),
+            &[],
+        );
+
+        // Reply times
+        let reply_labels: Vec<String> = REPLY_RANGES
+            .iter()
+            .map(|(_, label)| (*label).to_string())
+            .collect();
+        let replies = [
+            Series {
+                name: "Sent by me",
+                values: self.sent_replies.to_vec(),
+            },
+            Series {
+                name: "Sent by others",
+                values: self.received_replies.to_vec(),
+            },
+        ];
+        let has_replies = replies
+            .iter()
+            .any(|series| series.values.iter().any(|value| *value > 0));
+        render_section(
+            &mut page,
+            "Reply times",
+            "How long replies took to send, for replies sent within a day",
+            has_replies.then(|| column_chart(&reply_labels, &replies, false)),
+            &replies,
+        );
+
+        // Top contacts
+        let mut contacts: Vec<&Contact> = self.contacts.values().collect();
+        contacts.sort_by(|left, right| {
+            right
+                .messages
+                .cmp(&left.messages)
+                .then_with(|| left.name.cmp(&right.name))
+        });
+        let bars: Vec<(&str, u64)> = contacts
+            .iter()
+            .take(TOP_CONTACTS)
+            .map(|contact| (contact.name.as_str(), contact.messages))
+            .collect();
+        render_section(
+            &mut page,
+            "Top contacts",
+            "The people who sent the most messages, across every chat",
+            (!bars.is_empty()).then(|| bar_chart(&bars)),
+            &[],
+        );
+
+        // Attachment volume
+        let mut kinds: Vec<&'static str> = self
+            .attachments
+            .values()
+            .flat_map(BTreeMap::keys)
+            .copied()
+            .collect();
+        kinds.sort_unstable();
+        kinds.dedup();
+        let series: Vec<Series> = kinds
+            .iter()
+            .map(|kind| Series {
+                name: kind,
+                values: months
+                    .iter()
+                    .map(|month| {
+                        self.attachments
+                            .get(month)
+                            .and_then(|counts| counts.get(kind))
+                            .copied()
+                            .unwrap_or(0)
+                    })
+                    .collect(),
+            })
+            .collect();
+        render_section(
+            &mut page,
+            "Attachments",
+            "Attachments sent each month, by kind",
+            (!series.is_empty()).then(|| column_chart(&labels, &series, true)),
+            &series,
+        );
+
+        page.push_str("</div>\n");
+        page.push_str(FOOTER);
+        page
+    }
+}
+
+/// The index of the range in [`REPLY_RANGES`] that a reply time falls in
+fn reply_range(seconds: i64) -> usize {
+    REPLY_RANGES
+        .iter()
+        .position(|(limit, _)| seconds < *limit)
+        .unwrap_or(REPLY_RANGES.len() - 1)
+}
+
+/// Every month from the earliest to the latest of `months`
+fn month_range(months: impl Iterator<Item = Month>) -> Vec<Month> {
+    let mut bounds: Option<(Month, Month)> = None;
+    for month in months {
+        bounds = Some(match bounds {
+            Some((first, last)) => (first.min(month), last.max(month)),
+            None => (month, month),
+        });
+    }
+
+    let mut range = vec![];
+    if let Some((mut month, last)) = bounds {
+        while month <= last {
+            range.push(month);
+            month = match month {
+                (year, 12) => (year + 1, 1),
+                (year, month) => (year, month + 1),
+            };
+        }
+    }
+    range
+}
+
+/// The smallest round number, like `20`, `50`, or `100`, that is at least `max`
+fn axis_max(max: u64) -> u64 {
+    let mut scale = 1;
+    loop {
+        for step in [1, 2, 5] {
+            if step * scale >= max {
+                return (step * scale).max(1);
+            }
+        }
+        scale *= 10;
+    }
+}
+
+/// The color of the series at `index`
+fn color(index: usize) -> &'static str {
+    PALETTE[index % PALETTE.len()]
+}
+
+// MARK: Sections
+/// Write a titled chart, its legend, or a note that there is nothing to draw
+fn render_section(
+    page: &mut String,
+    title: &str,
+    description: &str,
+    chart: Option<String>,
+    legend: &[Series],
+) {
+    let _ = write!(
+        page,
+        "<section class=\"report_section\">\n<h2>{title}</h2>\n<p class=\"report_description\">{}</p>\n",
+        sanitize_html(description)
+    );
+    match chart {
+        Some(chart) => {
+            page.push_str(&chart);
+            if legend.len() > 1 {
+                page.push_str("<ul class=\"chart_legend\">");
+                for (index, series) in legend.iter().enumerate() {
+                    let _ = write!(
+                        page,
+                        "<li><span class=\"chart_swatch\" style=\"background: {}\"></span>{}</li>",
+                        color(index),
+                        sanitize_html(series.name)
+                    );
+                }
+                page.push_str("</ul>\n");
+            }
+        }
+        None => page.push_str("<p class=\"report_empty\">Nothing to show</p>\n"),
+    }
+    page.push_str("</section>\n");
+}
+
+// MARK: Charts
+/// Open an SVG element of the chart width and `height`
+fn open_svg(height: f64) -> String {
+    format!(
+        "<svg class=\"chart\" viewBox=\"0 0 {WIDTH} {height}\" xmlns=\"http://www.w3.org/2000/svg\" role=\"img\">\n"
+    )
+}
+
+/// Draw the value axis for a plot whose top is `max`
+fn value_axis(svg: &mut String, max: u64) {
+    for line in 0..=GRID_LINES {
+        let value = max * line / GRID_LINES;
+        let y = TOP + PLOT_HEIGHT - (PLOT_HEIGHT * line as f64 / GRID_LINES as f64);
+        let _ = writeln!(
+            svg,
+            "<line class=\"chart_grid\" x1=\"{LEFT}\" y1=\"{y:.1}\" x2=\"{:.1}\" y2=\"{y:.1}\"/><text class=\"chart_value\" x=\"{:.1}\" y=\"{:.1}\">{value}</text>",
+            WIDTH - RIGHT,
+            LEFT - 6.,
+            y + 4.,
+        );
+    }
+}
+
+/// Draw the labels along the bottom of a plot, skipping some when there are too many to fit
+fn label_axis(svg: &mut String, labels: &[String], x_of: impl Fn(usize) -> f64) {
+    let step = labels.len().div_ceil(AXIS_LABELS).max(1);
+    for (index, label) in labels.iter().enumerate().step_by(step) {
+        let _ = writeln!(
+            svg,
+            "<text class=\"chart_label\" x=\"{:.1}\" y=\"{:.1}\">{}</text>",
+            x_of(index),
+            TOP + PLOT_HEIGHT + 18.,
+            sanitize_html(label)
+        );
+    }
+}
+
+/// Draw a line for each series, with a point for each label
+fn line_chart(labels: &[String], series: &[Series]) -> String {
+    let max = axis_max(
+        series
+            .iter()
+            .flat_map(|series| series.values.iter())
+            .copied()
+            .max()
+            .unwrap_or(0),
+    );
+    let plot_width = WIDTH - LEFT - RIGHT;
+    let x_of = |index: usize| match labels.len() {
+        0 | 1 => LEFT + plot_width / 2.,
+        count => LEFT + plot_width * index as f64 / (count - 1) as f64,
+    };
+    let y_of = |value: u64| TOP + PLOT_HEIGHT - PLOT_HEIGHT * value as f64 / max as f64;
+
+    let mut svg = open_svg(TOP + PLOT_HEIGHT + BOTTOM);
+    value_axis(&mut svg, max);
+    label_axis(&mut svg, labels, x_of);
+
+    for (index, series) in series.iter().enumerate() {
+        let name = sanitize_html(series.name);
+        let points: Vec<String> = series
+            .values
+            .iter()
+            .enumerate()
+            .map(|(idx, value)| format!("{:.1},{:.1}", x_of(idx), y_of(*value)))
+            .collect();
+        let _ = writeln!(
+            svg,
+            "<polyline class=\"chart_line\" stroke=\"{}\" points=\"{}\"><title>{name}</title></polyline>",
+            color(index),
+            points.join(" ")
+        );
+        for (idx, value) in series.values.iter().enumerate() {
+            let _ = writeln!(
+                svg,
+                "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"2.5\" fill=\"{}\"><title>{name}, {}: {value}</title></circle>",
+                x_of(idx),
+                y_of(*value),
+                color(index),
+                sanitize_html(labels.get(idx).map_or("", String::as_str)),
+            );
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Draw a column for each series at each label, side by side or stacked
+fn column_chart(labels: &[String], series: &[Series], stacked: bool) -> String {
+    let total = |index: usize| -> u64 {
+        let values = series.iter().filter_map(|series| series.values.get(index));
+        if stacked {
+            values.sum()
+        } else {
+            values.max().copied().unwrap_or(0)
+        }
+    };
+    let max = axis_max((0..labels.len()).map(total).max().unwrap_or(0));
+
+    let slot = (WIDTH - LEFT - RIGHT) / labels.len().max(1) as f64;
+    let column_width = if stacked {
+        slot * 0.8
+    } else {
+        slot * 0.8 / series.len().max(1) as f64
+    };
+    let x_of = |index: usize| LEFT + slot * (index as f64 + 0.5);
+
+    let mut svg = open_svg(TOP + PLOT_HEIGHT + BOTTOM);
+    value_axis(&mut svg, max);
+    label_axis(&mut svg, labels, x_of);
+
+    for (index, label) in labels.iter().enumerate() {
+        let mut x = x_of(index) - slot * 0.4;
+        let mut base = 0;
+        for (series_index, series) in series.iter().enumerate() {
+            let value = series.values.get(index).copied().unwrap_or(0);
+            if value > 0 {
+                let height = PLOT_HEIGHT * value as f64 / max as f64;
+                let y = TOP + PLOT_HEIGHT - PLOT_HEIGHT * base as f64 / max as f64 - height;
+                let _ = writeln!(
+                    svg,
+                    "<rect x=\"{x:.1}\" y=\"{y:.1}\" width=\"{column_width:.1}\" height=\"{height:.1}\" fill=\"{}\"><title>{}, {}: {value}</title></rect>",
+                    color(series_index),
+                    sanitize_html(series.name),
+                    sanitize_html(label),
+                );
+            }
+            if stacked {
+                base += value;
+            } else {
+                x += column_width;
+            }
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Draw a horizontal bar for each name, longest first
+fn bar_chart(bars: &[(&str, u64)]) -> String {
+    let max = bars
+        .iter()
+        .map(|(_, value)| *value)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    // Leave room after the longest bar for its value
+    let plot_width = WIDTH - NAME_WIDTH - RIGHT - 48.;
+
+    let mut svg = open_svg(TOP + ROW_HEIGHT * bars.len() as f64);
+    for (index, (name, value)) in bars.iter().enumerate() {
+        let y = TOP + ROW_HEIGHT * index as f64;
+        let width = plot_width * *value as f64 / max as f64;
+        let _ = writeln!(
+            svg,
+            "<text class=\"chart_name\" x=\"{:.1}\" y=\"{:.1}\">{}</text><rect x=\"{NAME_WIDTH}\" y=\"{:.1}\" width=\"{width:.1}\" height=\"{:.1}\" fill=\"{}\"><title>{}: {value}</title></rect><text class=\"chart_count\" x=\"{:.1}\" y=\"{:.1}\">{value}</text>",
+            NAME_WIDTH - 8.,
+            y + ROW_HEIGHT * 0.65,
+            sanitize_html(&truncate(name, NAME_LENGTH)),
+            y + 3.,
+            ROW_HEIGHT - 6.,
+            color(0),
+            sanitize_html(name),
+            NAME_WIDTH + width + 6.,
+            y + ROW_HEIGHT * 0.65,
+        );
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Draw a grid of days of the week and hours of the day, shaded by the number of messages
+fn heatmap(grid: &[[u64; 24]; 7]) -> String {
+    let max = grid.iter().flatten().copied().max().unwrap_or(0).max(1);
+    let cell_width = (WIDTH - LEFT - RIGHT) / 24.;
+
+    let mut svg = open_svg(TOP + ROW_HEIGHT * 7. + BOTTOM);
+    for (day, hours) in grid.iter().enumerate() {
+        let y = TOP + ROW_HEIGHT * day as f64;
+        let _ = writeln!(
+            svg,
+            "<text class=\"chart_value\" x=\"{:.1}\" y=\"{:.1}\">{}</text>",
+            LEFT - 6.,
+            y + ROW_HEIGHT * 0.65,
+            WEEKDAYS[day]
+        );
+        for (hour, count) in hours.iter().enumerate() {
+            let x = LEFT + cell_width * hour as f64;
+            let shade = match count {
+                0 => String::from("class=\"chart_empty\""),
+                count => format!(
+                    "fill=\"{}\" fill-opacity=\"{:.2}\"",
+                    color(0),
+                    0.15 + 0.85 * *count as f64 / max as f64
+                ),
+            };
+            let _ = writeln!(
+                svg,
+                "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" {shade}><title>{} {hour:02}:00: {count}</title></rect>",
+                x + 1.,
+                y + 1.,
+                cell_width - 2.,
+                ROW_HEIGHT - 2.,
+                WEEKDAYS[day],
+            );
+        }
+    }
+
+    let hours: Vec<String> = (0..24).map(|hour| format!("{hour:02}")).collect();
+    for (hour, label) in hours.iter().enumerate().step_by(3) {
+        let _ = writeln!(
+            svg,
+            "<text class=\"chart_label\" x=\"{:.1}\" y=\"{:.1}\">{label}</text>",
+            LEFT + cell_width * (hour as f64 + 0.5),
+            TOP + ROW_HEIGHT * 7. + 18.,
+        );
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::{
+        app::{export_type::ExportType, options::Options, runtime::Config},
+        exporters::report::{
+            ChatSeries, Report, Series, axis_max, bar_chart, column_chart, heatmap, line_chart,
+            month_range, reply_range,
+        },
+    };
+
+    fn labels(labels: &[&str]) -> Vec<String> {
+        labels.iter().map(|label| (*label).to_string()).collect()
+    }
+
+    #[test]
+    fn can_find_reply_range() {
+        assert_eq!(reply_range(0), 0);
+        assert_eq!(reply_range(59), 0);
+        assert_eq!(reply_range(60), 1);
+        assert_eq!(reply_range(45 * 60), 3);
+        assert_eq!(reply_range(24 * 60 * 60), 6);
+    }
+
+    #[test]
+    fn can_build_month_range() {
+        let months = month_range([(2023, 11), (2024, 2), (2023, 12)].into_iter());
+        assert_eq!(months, vec![(2023, 11), (2023, 12), (2024, 1), (2024, 2)]);
+    }
+
+    #[test]
+    fn can_build_month_range_empty() {
+        assert!(month_range(std::iter::empty()).is_empty());
+    }
+
+    #[test]
+    fn can_round_axis_max() {
+        assert_eq!(axis_max(0), 1);
+        assert_eq!(axis_max(1), 1);
+        assert_eq!(axis_max(3), 5);
+        assert_eq!(axis_max(18), 20);
+        assert_eq!(axis_max(51), 100);
+        assert_eq!(axis_max(1200), 2000);
+    }
+
+    #[test]
+    fn can_draw_line_chart() {
+        let series = [
+            Series {
+                name: "Family",
+                values: vec![4, 0, 10],
+            },
+            Series {
+                name: "Tom & Jerry",
+                values: vec![1, 2, 3],
+            },
+        ];
+        let svg = line_chart(&labels(&["2023-01", "2023-02", "2023-03"]), &series);
+
+        assert!(svg.starts_with("<svg class=\"chart\""));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<polyline").count(), 2);
+        assert_eq!(svg.matches("<circle").count(), 6);
+        assert!(svg.contains("points=\"48.0,144.0 376.0,232.0 704.0,12.0\""));
+        assert!(svg.contains("<title>Tom &amp; Jerry</title>"));
+        assert!(svg.contains("<title>Family, 2023-03: 10</title>"));
+    }
+
+    #[test]
+    fn can_draw_single_point() {
+        let series = [Series {
+            name: "Family",
+            values: vec![4],
+        }];
+        let svg = line_chart(&labels(&["2023-01"]), &series);
+        assert!(svg.contains("<circle cx=\"376.0\" cy=\"56.0\""));
+    }
+
+    #[test]
+    fn can_draw_stacked_columns() {
+        let series = [
+            Series {
+                name: "images",
+                values: vec![3, 0],
+            },
+            Series {
+                name: "videos",
+                values: vec![2, 4],
+            },
+        ];
+        let svg = column_chart(&labels(&["2023-01", "2023-02"]), &series, true);
+
+        // Empty values are not drawn
+        assert_eq!(svg.matches("<rect").count(), 3);
+        // The first column stacks to the top of the axis
+        assert!(svg.contains("y=\"100.0\" width=\"262.4\" height=\"132.0\""));
+        assert!(svg.contains("y=\"12.0\" width=\"262.4\" height=\"88.0\""));
+    }
+
+    #[test]
+    fn can_draw_grouped_columns() {
+        let series = [
+            Series {
+                name: "Sent by me",
+                values: vec![5, 1],
+            },
+            Series {
+                name: "Sent by others",
+                values: vec![2, 0],
+            },
+        ];
+        let svg = column_chart(&labels(&["< 1m", "1-5m"]), &series, false);
+
+        assert_eq!(svg.matches("<rect").count(), 3);
+        assert!(svg.contains("<rect x=\"80.8\" y=\"12.0\" width=\"131.2\" height=\"220.0\""));
+        assert!(svg.contains("<rect x=\"212.0\" y=\"144.0\" width=\"131.2\" height=\"88.0\""));
+        assert!(svg.contains(">&lt; 1m</text>"));
+    }
+
+    #[test]
+    fn can_draw_bar_chart() {
+        let svg = bar_chart(&[("Jane", 10), ("A very long name for a contact", 5)]);
+
+        assert_eq!(svg.matches("<rect").count(), 2);
+        assert!(svg.contains("width=\"476.0\""));
+        assert!(svg.contains("width=\"238.0\""));
+        assert!(svg.contains("<title>A very long name for a contact: 5</title>"));
+        assert!(!svg.contains(">A very long name for a contact</text>"));
+    }
+
+    #[test]
+    fn can_draw_heatmap() {
+        let mut grid = [[0; 24]; 7];
+        grid[0][9] = 4;
+        grid[6][23] = 1;
+        let svg = heatmap(&grid);
+
+        assert_eq!(svg.matches("<rect").count(), 7 * 24);
+        assert_eq!(svg.matches("class=\"chart_empty\"").count(), 7 * 24 - 2);
+        assert!(svg.contains("fill-opacity=\"1.00\"><title>Mon 09:00: 4</title>"));
+        assert!(svg.contains("fill-opacity=\"0.36\"><title>Sun 23:00: 1</title>"));
+        assert!(svg.contains(">Wed</text>"));
+    }
+
+    #[test]
+    fn can_render_report() {
+        let options = Options::fake_options(ExportType::Html);
+        let config = Config::fake_app(options);
+        let mut report = Report::new(&config);
+        report.chats.insert(
+            Some(1),
+            ChatSeries {
+                name: "Family".to_string(),
+                messages: 3,
+                months: BTreeMap::from([((2023, 1), 2), ((2023, 3), 1)]),
+            },
+        );
+        report.heatmap[2][18] = 3;
+        report.sent_replies[0] = 1;
+
+        let page = report.render();
+        assert!(page.contains("<p class=\"report_summary\">3 messages in 1 chats"));
+        assert!(page.contains("<h2>Messages over time</h2>"));
+        // The empty month between the first and last is still drawn
+        assert!(page.contains("<title>Family, 2023-02: 0</title>"));
+        assert_eq!(page.matches("<svg").count(), 3);
+        // Nothing to draw for contacts or attachments
+        assert_eq!(page.matches("Nothing to show").count(), 2);
+        assert!(!page.contains("<script"));
+    }
+}
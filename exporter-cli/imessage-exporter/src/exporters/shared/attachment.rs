@@ -1,6 +1,9 @@
-use imessage_database::tables::{
-    attachment::{Attachment, MediaType},
-    messages::Message,
+use imessage_database::{
+    message_types::vcard::ContactCard,
+    tables::{
+        attachment::{Attachment, MediaType},
+        messages::Message,
+    },
 };
 
 use crate::{
@@ -15,6 +18,10 @@ use crate::{
 /// [`AttachmentManager`](crate::app::compatibility::attachment_manager::AttachmentManager)
 /// to copy or convert the file.
 ///
+/// Attachments excluded by the
+/// [`AttachmentPolicy`](crate::app::compatibility::policy::AttachmentPolicy)
+/// are not copied and render as [`AttachmentRender::Skipped`].
+///
 /// Returns `Ok(())` when the attachment has a filename and
 /// `handle_attachment` succeeded; otherwise returns the
 /// [`AttachmentRender`] fallback the caller should propagate.
@@ -24,6 +31,16 @@ pub(crate) fn prepare_attachment(
     attachment: &mut Attachment,
     message: &Message,
 ) -> Result<(), AttachmentRender> {
+    if !config.options.attachment_manager.policy.allows(attachment) {
+        let Some(filename) = attachment.filename() else {
+            return Err(AttachmentRender::MissingFilename);
+        };
+        return Err(AttachmentRender::Skipped {
+            filename: filename.to_string(),
+            file_size: attachment.file_size(),
+        });
+    }
+
     // Determine which conversions actually invoke ffmpeg and freeze the bar.
     // Both video transcoding (any video in Full mode) and animated-sticker
     // conversion (HEICS in Basic/Full) spawn ffmpeg, so both should surface
@@ -71,3 +88,19 @@ pub(crate) fn prepare_attachment(
     }
     Ok(())
 }
+
+/// Read the contacts from a shared contact attachment.
+///
+/// Returns `None` if the attachment is not a vCard, cannot be read, or holds
+/// no contacts, so callers can fall back to rendering it as a plain file.
+pub(crate) fn contact_cards(config: &Config, attachment: &Attachment) -> Option<Vec<ContactCard>> {
+    attachment
+        .get_contact_cards(
+            &config.options.platform,
+            &config.options.db_path,
+            config.options.attachment_root.as_deref(),
+        )
+        .ok()
+        .flatten()
+        .filter(|cards| !cards.is_empty())
+}
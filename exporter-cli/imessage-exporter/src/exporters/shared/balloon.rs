@@ -2,7 +2,7 @@ use imessage_database::{
     error::{message::MessageError, plist::PlistParseError},
     message_types::{
         app::{AppMessage, CheckInKind},
-        digital_touch,
+        digital_touch::DigitalTouchMessage,
         handwriting::HandwrittenMessage,
         url::URLMessage,
         variants::{BalloonProvider, CustomBalloon, URLOverride, Variant},
@@ -51,7 +51,7 @@ pub fn dispatch_app_balloon<F: BalloonFormatter>(
     if message.is_digital_touch()
         && let Some(payload) = message.raw_payload_data(config.data_source.db())
     {
-        return match digital_touch::from_payload(&payload) {
+        return match DigitalTouchMessage::from_payload(&payload) {
             Some(bubble) => Ok(formatter.format_digital_touch(message, &bubble)),
             None => Err(MessageError::PlistParseError(
                 PlistParseError::DigitalTouchError,
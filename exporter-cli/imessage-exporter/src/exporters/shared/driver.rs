@@ -1,10 +1,12 @@
 use std::{
     collections::{
-        HashMap,
+        BTreeMap, HashMap, HashSet, VecDeque,
         hash_map::Entry::{Occupied, Vacant},
     },
     fs::File,
-    io::{BufWriter, IsTerminal, Write, stderr},
+    io::{BufWriter, IsTerminal, Read, Seek, SeekFrom, Write, stderr},
+    iter::once,
+    path::PathBuf,
 };
 
 use imessage_database::tables::{
@@ -14,10 +16,28 @@ use imessage_database::tables::{
 use rusqlite::Connection;
 
 use crate::{
-    app::{error::RuntimeError, progress::ExportProgress, runtime::Config},
-    exporters::formatter::{MessageFormatter, RenderContext},
+    app::{
+        error::RuntimeError,
+        incremental::{IncrementalState, append_journal, clear_journal},
+        manifest::{MANIFEST_FILENAME, ManifestCache, write_manifest},
+        progress::ExportProgress,
+        runtime::Config,
+        watch::DatabaseWatcher,
+    },
+    exporters::{
+        formatter::{MessageFormatter, RenderContext},
+        links::Links,
+        locations::Locations,
+        report::Report,
+    },
 };
 
+/// Number of streamed rows between saved checkpoints
+const CHECKPOINT_INTERVAL: u64 = 5_000;
+
+/// Number of messages held back for each conversion job before the export waits for the oldest one
+const PENDING_PER_JOB: usize = 8;
+
 /// Shared per-export mutable state held by every concrete `MessageWriter`.
 /// Holds the file cache (one [`BufWriter`] per chatroom), the writer for
 /// messages that don't belong to a chat, and the progress bar. The owning
@@ -28,30 +48,145 @@ pub struct ExportState {
     pub files: HashMap<String, BufWriter<File>>,
     /// Destination for messages that don't have a conversation route.
     pub orphaned: BufWriter<File>,
+    /// File name of the orphaned file, relative to the export path.
+    pub orphaned_name: String,
     /// Drives the on-screen progress indicator.
     pub pb: ExportProgress,
+    /// Whether the orphaned file was written by a previous run.
+    pub orphaned_exists: bool,
+    /// The newest message written per chat, loaded from a previous run when
+    /// `--incremental` or `--resume` is enabled, saved at every checkpoint
+    /// and after the export finishes.
+    pub incremental: IncrementalState,
+    /// The source database and file hashes recorded in the manifest, kept
+    /// so manifests written while watching only hash what changed.
+    pub manifest: Option<ManifestCache>,
+    /// Rendered messages held back until the attachment conversions they
+    /// reference finish, in the order they are written.
+    pub pending: VecDeque<PendingMessage>,
+}
+
+/// A rendered message that references attachments still being converted.
+pub struct PendingMessage {
+    msg: Message,
+    /// The rendered message, or `None` if nothing is written for it.
+    rendered: Option<String>,
+    /// The converted paths the rendered message references.
+    conversions: Vec<PathBuf>,
 }
 
 impl ExportState {
     /// Open the orphaned file (creating it if missing) under
     /// `config.options.export_path` with the supplied extension, then build
-    /// the empty file cache and progress bar.
+    /// the empty file cache, progress bar, and incremental export state.
+    /// When resuming, every file is first rolled back to the last checkpoint.
     pub fn new(config: &Config, extension: &str) -> Result<Self, RuntimeError> {
-        let mut orphaned = config.options.export_path.clone();
+        let export_path = &config.options.export_path;
+        let incremental = if config.options.incremental || config.options.resume {
+            IncrementalState::load(&config.options)?
+        } else {
+            clear_journal(export_path)?;
+            IncrementalState::new(&config.options)
+        };
+        if config.options.resume {
+            incremental.restore_files(export_path)?;
+        }
+
+        let mut orphaned = export_path.clone();
         orphaned.push(ORPHANED);
         orphaned.set_extension(extension);
-        let file = File::options().append(true).create(true).open(&orphaned)?;
-        // `--no-progress` forces off; otherwise show only when stderr is a TTY
-        // so headless invocations (CI, redirects to logfiles) stay clean.
-        let pb_enabled = config.options.show_progress && stderr().is_terminal();
+        let orphaned_name = orphaned
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let orphaned_exists = orphaned.exists();
+        append_journal(
+            export_path,
+            &orphaned_name,
+            orphaned.metadata().map(|meta| meta.len()).unwrap_or(0),
+        )?;
+        let file = File::options()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&orphaned)?;
         Ok(Self {
             files: HashMap::new(),
             orphaned: BufWriter::new(file),
-            pb: ExportProgress::new(pb_enabled),
+            orphaned_name,
+            pb: progress_bar(config),
+            orphaned_exists,
+            incremental,
+            manifest: None,
+            pending: VecDeque::new(),
         })
     }
 }
 
+/// Build the progress bar for an export.
+/// `--no-progress` forces it off; otherwise it is shown only when stderr is a
+/// TTY so headless invocations (CI, redirects to logfiles) stay clean.
+pub fn progress_bar(config: &Config) -> ExportProgress {
+    ExportProgress::new(config.options.show_progress && stderr().is_terminal())
+}
+
+/// Stream every message selected by the query filters once, passing each to
+/// `visit`. Used by the exports that read messages without writing a
+/// conversation file for them, like the gallery and the shared links.
+///
+/// Duplicate ROWIDs are dropped (see [issue #135]) and progress is shown on
+/// `pb`. When `decode_body` is set, every message's body is decoded with
+/// [`apply_body`]; otherwise it is only decoded to check query predicates
+/// that cannot be expressed in SQL.
+///
+/// [issue #135]: https://github.com/ReagentX/imessage-exporter/issues/135
+pub fn for_each_message<F>(
+    config: &Config,
+    pb: &ExportProgress,
+    decode_body: bool,
+    mut visit: F,
+) -> Result<(), RuntimeError>
+where
+    F: FnMut(Message) -> Result<(), RuntimeError>,
+{
+    let db = config.data_source.db();
+    let context = &config.options.query_context;
+    pb.start(Message::get_count(db, context)?);
+
+    // Query predicates that cannot be expressed in SQL are checked against each decoded message
+    let post_filter = context
+        .expression
+        .as_ref()
+        .filter(|expression| !expression.is_sql_complete());
+
+    let mut statement = Message::stream_rows(db, context)?;
+    let mut current_message_row = -1;
+    let mut current_message: u64 = 0;
+    for message in Message::rows(&mut statement, [])? {
+        let mut msg = message?;
+        current_message += 1;
+        if current_message.is_multiple_of(99) {
+            pb.set_position(current_message);
+        }
+
+        if msg.rowid == current_message_row {
+            continue;
+        }
+        current_message_row = msg.rowid;
+
+        if decode_body || post_filter.is_some() {
+            apply_body(&mut msg, db);
+        }
+        if post_filter.is_some_and(|expression| !expression.matches(&msg, db)) {
+            continue;
+        }
+
+        visit(msg)?;
+    }
+    pb.finish();
+    Ok(())
+}
+
 /// Decode the message's body via [`Message::parse_body`] and apply it.
 /// `parse_body` failures are non-fatal: they leave the message's
 /// `components` empty, which downstream formatters already treat as
@@ -92,11 +227,35 @@ pub trait MessageWriter<'a>: MessageFormatter<'a> {
     /// orphaned file after iteration ends. Return `Ok(())` to emit nothing.
     fn write_file_footer(file: &mut BufWriter<File>) -> Result<(), RuntimeError>;
 
+    /// Remove the footer a previous run wrote to an existing file, so new
+    /// messages can be appended before it. Called instead of
+    /// [`MessageWriter::write_file_header`] when a file already exists on disk.
+    /// Return `Ok(())` if the format has no footer.
+    fn trim_file_footer(file: &mut File) -> Result<(), RuntimeError>;
+
     /// Optional notice printed once before per-file footers are written.
     /// Return `None` to suppress the notice.
     fn footer_notice() -> Option<&'static str>;
 }
 
+/// Truncate `footer` from the end of `file` if the file ends with it.
+pub fn truncate_footer(file: &mut File, footer: &str) -> Result<(), RuntimeError> {
+    let footer = footer.as_bytes();
+    let footer_len = footer.len() as u64;
+    let file_len = file.metadata()?.len();
+    if file_len < footer_len {
+        return Ok(());
+    }
+
+    let mut tail = vec![0; footer.len()];
+    file.seek(SeekFrom::Start(file_len - footer_len))?;
+    file.read_exact(&mut tail)?;
+    if tail == footer {
+        file.set_len(file_len - footer_len)?;
+    }
+    Ok(())
+}
+
 /// Resolve the `BufWriter` for `message`, creating the chat file (and writing
 /// its header) on first sight. Messages without a conversation route to the
 /// shared orphaned writer.
@@ -118,9 +277,22 @@ where
                     let mut path = config.options.export_path.clone();
                     path.push(entry.key());
                     // If the file already exists, don't write the headers again.
-                    // This can happen if multiple chats use the same group name.
+                    // This can happen if multiple chats use the same group name,
+                    // or when appending to a previous export.
                     let file_exists = path.exists();
-                    let file = File::options().append(true).create(true).open(&path)?;
+                    append_journal(
+                        &config.options.export_path,
+                        entry.key(),
+                        path.metadata().map(|meta| meta.len()).unwrap_or(0),
+                    )?;
+                    let mut file = File::options()
+                        .read(true)
+                        .append(true)
+                        .create(true)
+                        .open(&path)?;
+                    if file_exists {
+                        W::trim_file_footer(&mut file)?;
+                    }
                     let mut buf = BufWriter::new(file);
                     if !file_exists {
                         W::write_file_header(&mut buf)?;
@@ -133,6 +305,45 @@ where
     }
 }
 
+/// Flush every open file and save the position reached in the message stream
+/// along with the length of each file, so `--resume` can continue from here.
+/// Attachments still converting in the background are finished first, since
+/// rows before the checkpoint are never revisited.
+fn save_checkpoint<'a, W>(
+    writer: &mut W,
+    position: u64,
+    last_rowid: i32,
+) -> Result<(), RuntimeError>
+where
+    W: MessageWriter<'a>,
+{
+    writer
+        .config()
+        .options
+        .attachment_manager
+        .conversions
+        .wait_all();
+
+    let export_path = &writer.config().options.export_path;
+    let state = writer.state_mut();
+
+    let mut files = BTreeMap::new();
+    for (name, file) in &mut state.files {
+        file.flush()?;
+        files.insert(name.clone(), file.get_ref().metadata()?.len());
+    }
+    state.orphaned.flush()?;
+    files.insert(
+        state.orphaned_name.clone(),
+        state.orphaned.get_ref().metadata()?.len(),
+    );
+
+    state
+        .incremental
+        .set_checkpoint(position, last_rowid, files);
+    state.incremental.save(export_path)
+}
+
 /// Stream every message in the database, dispatching announcements and
 /// regular messages to `writer.format_announcement` /
 /// `writer.format_message_into`. Tapbacks, poll votes and poll updates are
@@ -145,6 +356,10 @@ where
 /// after the progress bar only when one or more messages were skipped.
 /// Row-deserialization errors and I/O errors remain fatal.
 ///
+/// Every [`CHECKPOINT_INTERVAL`] rows, progress is saved to the export's state
+/// file. When resuming, rows up to the saved position are skipped. Once the
+/// export finishes, its manifest is written.
+///
 /// [issue #135]: https://github.com/ReagentX/imessage-exporter/issues/135
 pub fn run_export<'a, W>(writer: &mut W) -> Result<(), RuntimeError>
 where
@@ -156,7 +371,12 @@ where
         W::LABEL,
     );
 
-    W::write_file_header(&mut writer.state_mut().orphaned)?;
+    let state = writer.state_mut();
+    if state.orphaned_exists {
+        W::trim_file_footer(state.orphaned.get_mut())?;
+    } else {
+        W::write_file_header(&mut state.orphaned)?;
+    }
 
     let mut current_message_row = -1;
     let mut current_message = 0;
@@ -176,49 +396,91 @@ where
     // output buffer. Capacity grows naturally to fit the largest message
     // and `clear()` retains it.
     let mut msg_buf = String::with_capacity(W::BUFFER_CAPACITY);
+
+    // Rows consumed from the stream, including rows skipped when resuming
+    let mut position: u64 = 0;
+    let resume_position = writer.state().incremental.resume_position();
+
+    // Save an initial checkpoint so a run interrupted before its first interval can still be resumed
+    if resume_position.is_none() {
+        save_checkpoint(writer, 0, -1)?;
+    }
+
+    // Query predicates that cannot be expressed in SQL are checked against each decoded message
+    let post_filter = writer
+        .config()
+        .options
+        .query_context
+        .expression
+        .as_ref()
+        .is_some_and(|expression| !expression.is_sql_complete());
+
     for message in Message::rows(&mut statement, [])? {
         let mut msg = message?;
+        position += 1;
 
-        // Early escape if we try and render the same message GUID twice
-        // See https://github.com/ReagentX/imessage-exporter/issues/135
-        if msg.rowid == current_message_row {
+        // Skip rows an interrupted run already wrote before its last checkpoint
+        if let Some((resume_rows, resume_rowid)) = resume_position
+            && position <= resume_rows
+        {
+            if position == resume_rows && msg.rowid != resume_rowid {
+                return Err(RuntimeError::InvalidOptions(String::from(
+                    "The database changed since the export was interrupted, so it cannot be resumed!",
+                )));
+            }
             current_message += 1;
+            if position == resume_rows {
+                current_message_row = msg.rowid;
+                writer.state().pb.set_position(current_message);
+            }
             continue;
         }
+
+        // Early escape if we try and render the same message GUID twice
+        // See https://github.com/ReagentX/imessage-exporter/issues/135
+        let duplicate = msg.rowid == current_message_row;
         current_message_row = msg.rowid;
 
-        apply_body(&mut msg, writer.config().data_source.db());
-
-        if msg.is_announcement() {
-            msg_buf.clear();
-            writer.format_announcement(&msg, &mut msg_buf);
-            let file = get_or_create_file_for(writer, &msg)?;
-            file.write_all(msg_buf.as_bytes())?;
-        }
-        // Message tapbacks and poll votes are rendered in context, so no need to render them separately
-        else if !msg.is_tapback() && !msg.is_poll_vote() && !msg.is_poll_update() {
-            msg_buf.clear();
-            match writer.format_message_into(&msg, RenderContext::TopLevel, &mut msg_buf) {
-                Ok(()) => {
-                    let file = get_or_create_file_for(writer, &msg)?;
-                    file.write_all(msg_buf.as_bytes())?;
-                }
-                Err(why) => {
-                    failures += 1;
-                    eprintln!(
-                        "Skipping message (rowid={}, guid={}): {}",
-                        msg.rowid, msg.guid, why
-                    );
-                }
+        // Skip messages a previous incremental run already wrote
+        if !duplicate && !writer.state().incremental.is_exported(&msg) {
+            apply_body(&mut msg, writer.config().data_source.db());
+
+            let matches = !post_filter
+                || writer
+                    .config()
+                    .options
+                    .query_context
+                    .expression
+                    .as_ref()
+                    .is_none_or(|expression| {
+                        expression.matches(&msg, writer.config().data_source.db())
+                    });
+            if matches {
+                write_message(writer, msg, &mut msg_buf, &mut failures)?;
             }
         }
+
         current_message += 1;
         if current_message % 99 == 0 {
             writer.state().pb.set_position(current_message);
         }
+        // Checked for skipped rows too, so a checkpoint is never missed when its row is skipped
+        if position.is_multiple_of(CHECKPOINT_INTERVAL) {
+            write_pending(writer, &mut msg_buf, &mut failures, true)?;
+            save_checkpoint(writer, position, current_message_row)?;
+        }
     }
+    write_pending(writer, &mut msg_buf, &mut failures, true)?;
     writer.state().pb.finish();
 
+    // Attachments still converting in the background have to be written before the export is complete
+    writer
+        .config()
+        .options
+        .attachment_manager
+        .conversions
+        .finish(&writer.state().pb);
+
     if failures > 0 {
         eprintln!("{failures} messages skipped due to formatting errors.");
     }
@@ -226,15 +488,431 @@ where
     if let Some(notice) = W::footer_notice() {
         eprintln!("{notice}");
     }
+    write_footers(writer)?;
+
+    // Shared locations, links, and the report are written before the manifest so it lists their files
+    let config = writer.config();
+    write_shared_outputs(config)?;
+
+    // Record the newest message written per chat so later runs can append to this export
     let state = writer.state_mut();
-    for file in state.files.values_mut() {
+    state.incremental.finish();
+    state.incremental.save(&config.options.export_path)?;
+    clear_journal(&config.options.export_path)?;
+
+    // Describe the finished export so it can be verified later
+    eprintln!("Writing {MANIFEST_FILENAME}...");
+    write_manifest(
+        config,
+        &state.incremental,
+        &mut state.manifest,
+        &state.orphaned_name,
+    )?;
+
+    Ok(())
+}
+
+/// Write the files built from every message selected by the query filters,
+/// like shared locations, links, and the report, so they include the messages just written.
+pub fn write_shared_outputs(config: &Config) -> Result<(), RuntimeError> {
+    if let Some(format) = config.options.locations {
+        Locations::new(config).run(format)?;
+    }
+    if let Some(format) = config.options.links {
+        Links::new(config).run(format)?;
+    }
+    if config.options.report {
+        Report::new(config).run()?;
+    }
+    Ok(())
+}
+
+/// Render `msg` and write it to its chat file, then record it as written.
+///
+/// A message that references attachments still being converted is held back,
+/// along with every message after it, until those conversions finish. See
+/// [`write_pending`].
+fn write_message<'a, W>(
+    writer: &mut W,
+    msg: Message,
+    msg_buf: &mut String,
+    failures: &mut u64,
+) -> Result<(), RuntimeError>
+where
+    W: MessageWriter<'a>,
+{
+    let conversions = &writer.config().options.attachment_manager.conversions;
+    conversions.take_referenced();
+    let rendered = render_message(writer, &msg, msg_buf, failures);
+    let referenced = conversions.take_referenced();
+
+    let state = writer.state_mut();
+    if referenced.is_empty() && state.pending.is_empty() {
+        if rendered {
+            get_or_create_file_for(writer, &msg)?.write_all(msg_buf.as_bytes())?;
+        }
+        writer.state_mut().incremental.record(&msg);
+    } else {
+        state.pending.push_back(PendingMessage {
+            rendered: rendered.then(|| msg_buf.clone()),
+            conversions: referenced,
+            msg,
+        });
+    }
+    write_pending(writer, msg_buf, failures, false)
+}
+
+/// Write the held back messages whose conversions have finished, in order.
+/// With `wait` set, or when too many messages are held back, this waits for
+/// the conversions instead of stopping at the first unfinished one.
+///
+/// If a conversion failed, the original file was copied with its original
+/// extension, so the message is rendered again to reference that copy.
+fn write_pending<'a, W>(
+    writer: &mut W,
+    msg_buf: &mut String,
+    failures: &mut u64,
+    wait: bool,
+) -> Result<(), RuntimeError>
+where
+    W: MessageWriter<'a>,
+{
+    let conversions = &writer.config().options.attachment_manager.conversions;
+    let limit = conversions.jobs() * PENDING_PER_JOB;
+    loop {
+        let state = writer.state_mut();
+        let Some(front) = state.pending.front() else {
+            return Ok(());
+        };
+        let ready = wait
+            || state.pending.len() > limit
+            || !front
+                .conversions
+                .iter()
+                .any(|path| conversions.is_pending(path));
+        if !ready {
+            return Ok(());
+        }
+        let Some(pending) = state.pending.pop_front() else {
+            return Ok(());
+        };
+
+        pending
+            .conversions
+            .iter()
+            .for_each(|path| conversions.wait_for(path));
+        let rendered = if pending
+            .conversions
+            .iter()
+            .any(|path| conversions.fallback(path).is_some())
+        {
+            let rendered = render_message(writer, &pending.msg, msg_buf, failures);
+            conversions.take_referenced();
+            rendered.then_some(msg_buf.as_str())
+        } else {
+            pending.rendered.as_deref()
+        };
+        if let Some(rendered) = rendered {
+            get_or_create_file_for(writer, &pending.msg)?.write_all(rendered.as_bytes())?;
+        }
+        writer.state_mut().incremental.record(&pending.msg);
+    }
+}
+
+/// Render `msg` into `msg_buf`, returning `false` if nothing is written for it.
+/// Tapbacks, poll votes and poll updates are rendered in context by their
+/// parent messages, so they're skipped. Formatting errors are logged and
+/// tallied in `failures`.
+fn render_message<'a, W>(
+    writer: &mut W,
+    msg: &Message,
+    msg_buf: &mut String,
+    failures: &mut u64,
+) -> bool
+where
+    W: MessageWriter<'a>,
+{
+    msg_buf.clear();
+    if msg.is_announcement() {
+        writer.format_announcement(msg, msg_buf);
+        true
+    }
+    // Message tapbacks and poll votes are rendered in context, so no need to render them separately
+    else if !msg.is_tapback() && !msg.is_poll_vote() && !msg.is_poll_update() {
+        match writer.format_message_into(msg, RenderContext::TopLevel, msg_buf) {
+            Ok(()) => true,
+            Err(why) => {
+                *failures += 1;
+                eprintln!(
+                    "Skipping message (rowid={}, guid={}): {}",
+                    msg.rowid, msg.guid, why
+                );
+                false
+            }
+        }
+    } else {
+        false
+    }
+}
+
+/// Write the footer to every open file and flush it.
+fn write_footers<'a, W>(writer: &mut W) -> Result<(), RuntimeError>
+where
+    W: MessageWriter<'a>,
+{
+    let state = writer.state_mut();
+    for file in state.files.values_mut().chain(once(&mut state.orphaned)) {
         W::write_file_footer(file)?;
         // Surface flush errors (disk full, quota, unmount, NFS hiccup) here
         // rather than letting `BufWriter::Drop` discard them silently.
         file.flush()?;
     }
-    W::write_file_footer(&mut state.orphaned)?;
-    state.orphaned.flush()?;
+    Ok(())
+}
 
+/// Remove the footer from every open file so new messages can be appended.
+fn trim_footers<'a, W>(writer: &mut W) -> Result<(), RuntimeError>
+where
+    W: MessageWriter<'a>,
+{
+    let state = writer.state_mut();
+    for file in state.files.values_mut().chain(once(&mut state.orphaned)) {
+        file.flush()?;
+        W::trim_file_footer(file.get_mut())?;
+    }
     Ok(())
 }
+
+/// Follow the database after [`run_export`] finishes, appending new messages to
+/// the open files as they arrive. Every batch of new messages is closed with
+/// the per-file footers, so the export stays readable between updates, and a
+/// checkpoint is saved before each batch so an interrupted batch can be rolled
+/// back with `--resume`.
+///
+/// Returns when a new message belongs to a chat or participant that is missing
+/// from the caches in [`Config`], so the caller can rebuild them and continue
+/// watching with the same [`ExportState`].
+pub fn watch_export<'a, W>(
+    writer: &mut W,
+    watcher: &mut DatabaseWatcher,
+) -> Result<(), RuntimeError>
+where
+    W: MessageWriter<'a>,
+{
+    eprintln!(
+        "Watching {} for new messages...",
+        writer.config().options.get_db_path().display()
+    );
+    loop {
+        if !append_new_messages(writer, watcher)? {
+            return Ok(());
+        }
+        watcher.wait_for_change();
+    }
+}
+
+/// Append every message newer than the watcher's cursor. Returns `false` if a
+/// message could not be rendered with the current caches.
+fn append_new_messages<'a, W>(
+    writer: &mut W,
+    watcher: &mut DatabaseWatcher,
+) -> Result<bool, RuntimeError>
+where
+    W: MessageWriter<'a>,
+{
+    let config = writer.config();
+    let mut context = config.options.query_context.clone();
+    context.set_after_rowid(watcher.cursor);
+    if Message::get_count(config.data_source.db(), &context)? == 0 {
+        return Ok(true);
+    }
+
+    save_checkpoint(writer, 0, -1)?;
+    trim_footers(writer)?;
+
+    let post_filter = context
+        .expression
+        .as_ref()
+        .is_some_and(|expression| !expression.is_sql_complete());
+    let mut statement = Message::stream_rows(config.data_source.db(), &context)?;
+    let mut msg_buf = String::with_capacity(W::BUFFER_CAPACITY);
+
+    // Chats with a message waiting for its attachments; later messages in them wait too
+    let mut held_chats = HashSet::new();
+    // The lowest `ROWID` that has to be read again on the next pass
+    let mut floor: Option<i32> = None;
+    let mut newest = watcher.cursor;
+    let mut current_message_row = -1;
+    let mut written: u64 = 0;
+    let mut failures: u64 = 0;
+    let mut stale = false;
+
+    for message in Message::rows(&mut statement, [])? {
+        let mut msg = message?;
+        if msg.rowid == current_message_row {
+            continue;
+        }
+        current_message_row = msg.rowid;
+
+        if watcher.needs_refresh(&msg, config) {
+            stale = true;
+            break;
+        }
+        let chat_id = msg.chat_id.or(msg.deleted_from);
+        if held_chats.contains(&chat_id) || watcher.should_defer(&msg, config) {
+            held_chats.insert(chat_id);
+            floor = Some(floor.map_or(msg.rowid, |floor| floor.min(msg.rowid)));
+            continue;
+        }
+        newest = newest.max(msg.rowid);
+
+        if writer.state().incremental.is_exported(&msg) {
+            continue;
+        }
+
+        apply_body(&mut msg, config.data_source.db());
+        if post_filter
+            && let Some(expression) = &context.expression
+            && !expression.matches(&msg, config.data_source.db())
+        {
+            continue;
+        }
+
+        write_message(writer, msg, &mut msg_buf, &mut failures)?;
+        written += 1;
+    }
+
+    write_pending(writer, &mut msg_buf, &mut failures, true)?;
+    write_footers(writer)?;
+    let state = writer.state_mut();
+    config
+        .options
+        .attachment_manager
+        .conversions
+        .finish(&state.pb);
+    state.incremental.finish();
+    state.incremental.save(&config.options.export_path)?;
+    clear_journal(&config.options.export_path)?;
+    if written > 0 {
+        write_shared_outputs(config)?;
+        write_manifest(
+            config,
+            &state.incremental,
+            &mut state.manifest,
+            &state.orphaned_name,
+        )?;
+    }
+
+    // Messages that were already written are skipped when they are read again
+    if !stale {
+        watcher.cursor = floor.map_or(newest, |floor| newest.min(floor - 1));
+    }
+    if written > 0 {
+        eprintln!("Appended {written} new messages.");
+    }
+    if failures > 0 {
+        eprintln!("{failures} messages skipped due to formatting errors.");
+    }
+    Ok(!stale)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{copy, read_to_string, write},
+        path::Path,
+    };
+
+    use imessage_database::tables::chat::Chat;
+    use rusqlite::Connection;
+
+    use crate::{
+        Config, Options, TXT,
+        app::{
+            compatibility::attachment_manager::AttachmentManagerMode, export_type::ExportType,
+            test_dir::unique_test_dir, watch::DatabaseWatcher,
+        },
+        exporters::shared::driver::append_new_messages,
+    };
+
+    /// Add a message we sent to `chat_id`
+    fn insert_message(db: &Connection, rowid: i32, chat_id: i32, text: &str) {
+        db.execute(
+            "INSERT INTO message (ROWID, guid, text, is_from_me, date, service) VALUES (?1, ?2, ?3, 1, 0, 'iMessage')",
+            (rowid, format!("guid-{rowid}"), text),
+        )
+        .unwrap();
+        db.execute(
+            "INSERT INTO chat_message_join (chat_id, message_id) VALUES (?1, ?2)",
+            (chat_id, rowid),
+        )
+        .unwrap();
+    }
+
+    fn fake_chat(rowid: i32, name: &str) -> Chat {
+        Chat {
+            rowid,
+            chat_identifier: name.to_string(),
+            service_name: Some("iMessage".to_string()),
+            display_name: Some(name.to_string()),
+        }
+    }
+
+    #[test]
+    fn can_append_held_chats_on_next_pass() {
+        let dir = unique_test_dir("driver_append_held");
+        let db_path = dir.join("chat.db");
+        let mut options = Options::fake_options(ExportType::Txt);
+        copy(&options.db_path, &db_path).unwrap();
+        options.db_path = db_path.clone();
+        options.attachment_manager.mode = AttachmentManagerMode::Disabled;
+
+        let mut config = Config::fake_app(options);
+        for chat in [fake_chat(1, "Held"), fake_chat(2, "Other")] {
+            config.real_chatrooms.insert(chat.rowid, chat.rowid);
+            config.chatrooms.insert(chat.rowid, chat);
+        }
+        let mut watcher = DatabaseWatcher::new(&db_path, config.data_source.db()).unwrap();
+        let start = watcher.cursor;
+
+        // The first message in the held chat is waiting for its attachment to download
+        let attachment = dir.join("photo.png");
+        let db = Connection::open(&db_path).unwrap();
+        for (rowid, name) in [(1, "Held"), (2, "Other")] {
+            db.execute(
+                "INSERT INTO chat (ROWID, guid, chat_identifier, display_name) VALUES (?1, ?2, ?2, ?2)",
+                (rowid, name),
+            )
+            .unwrap();
+        }
+        insert_message(&db, start + 1, 1, "first held");
+        insert_message(&db, start + 2, 2, "other");
+        insert_message(&db, start + 3, 1, "second held");
+        db.execute(
+            "INSERT INTO attachment (ROWID, guid, original_guid, filename, mime_type) VALUES (1000, 'a', 'a', ?1, 'image/png')",
+            [attachment.to_string_lossy()],
+        )
+        .unwrap();
+        db.execute(
+            "INSERT INTO message_attachment_join (message_id, attachment_id) VALUES (?1, 1000)",
+            [start + 1],
+        )
+        .unwrap();
+
+        let mut writer = TXT::new(&config).unwrap();
+        assert!(append_new_messages(&mut writer, &mut watcher).unwrap());
+        // Only the other chat was written, so the held chat is read again
+        assert_eq!(watcher.cursor, start);
+
+        write(&attachment, b"").unwrap();
+        assert!(append_new_messages(&mut writer, &mut watcher).unwrap());
+        assert_eq!(watcher.cursor, start + 3);
+        drop(writer);
+
+        let read = |name: &str| read_to_string(Path::new(&config.options.export_path).join(name));
+        let held = read("Held - 1.txt").unwrap();
+        assert!(held.find("first held").unwrap() < held.find("second held").unwrap());
+        // The other chat was not written again when its row was read a second time
+        assert_eq!(read("Other - 2.txt").unwrap().matches("other").count(), 1);
+    }
+}
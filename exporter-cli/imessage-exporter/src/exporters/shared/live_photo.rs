@@ -0,0 +1,208 @@
+/*!
+ Finds [Live Photos](https://support.apple.com/en-us/104966), which are sent as a still image and a
+ separate `.mov` attachment holding the motion.
+*/
+
+use std::path::Path;
+
+use imessage_database::tables::{
+    attachment::{Attachment, MediaType},
+    messages::models::{AttachmentMeta, BubbleComponent},
+};
+
+/// The Live Photos in a message, as pairs of indexes into the message's attachments
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct LivePhotos {
+    /// `(still, motion)` index pairs
+    pairs: Vec<(usize, usize)>,
+}
+
+impl LivePhotos {
+    /// Pair each still image with the `.mov` attachment sent with it
+    ///
+    /// Components and attachments are in the same order, so each attachment's
+    /// [`AttachmentMeta`] supplies the original filename when the attachment row does not.
+    /// A motion attachment matches a still with the same name, ignoring extension and case.
+    /// A hidden motion attachment sent right after a still also matches it.
+    pub(crate) fn find(components: &[BubbleComponent], attachments: &[Attachment]) -> Self {
+        let metadata: Vec<&AttachmentMeta> = components
+            .iter()
+            .filter_map(|component| match component {
+                BubbleComponent::Attachment(meta) => Some(meta),
+                _ => None,
+            })
+            .collect();
+        let stem = |idx: usize| {
+            metadata
+                .get(idx)
+                .and_then(|meta| meta.name.as_deref())
+                .or_else(|| attachments[idx].filename())
+                .and_then(|name| Path::new(name).file_stem())
+                .and_then(|stem| stem.to_str())
+                .map(str::to_lowercase)
+        };
+
+        let mut pairs = vec![];
+        let mut paired = vec![false; attachments.len()];
+        for (still, attachment) in attachments.iter().enumerate() {
+            if paired[still] || !is_still(attachment) {
+                continue;
+            }
+            let still_stem = stem(still);
+            let motion = (0..attachments.len()).find(|&motion| {
+                !paired[motion]
+                    && is_motion(&attachments[motion])
+                    && ((still_stem.is_some() && stem(motion) == still_stem)
+                        || (motion == still + 1 && attachments[motion].hide_attachment != 0))
+            });
+            if let Some(motion) = motion {
+                paired[still] = true;
+                paired[motion] = true;
+                pairs.push((still, motion));
+            }
+        }
+
+        Self { pairs }
+    }
+
+    /// The index of the motion attachment paired with the still image at `still`
+    pub(crate) fn motion_for(&self, still: usize) -> Option<usize> {
+        self.pairs
+            .iter()
+            .find_map(|(s, motion)| (*s == still).then_some(*motion))
+    }
+
+    /// Determine if the attachment at `idx` is the motion half of a Live Photo
+    pub(crate) fn is_motion(&self, idx: usize) -> bool {
+        self.pairs.iter().any(|(_, motion)| *motion == idx)
+    }
+}
+
+/// Determine if an attachment can be the still half of a Live Photo
+fn is_still(attachment: &Attachment) -> bool {
+    !attachment.is_sticker && matches!(attachment.mime_type(), MediaType::Image(_))
+}
+
+/// Determine if an attachment can be the motion half of a Live Photo
+fn is_motion(attachment: &Attachment) -> bool {
+    !attachment.is_sticker
+        && match attachment.mime_type() {
+            MediaType::Video(subtype) => {
+                subtype.eq_ignore_ascii_case("quicktime")
+                    || attachment
+                        .extension()
+                        .is_some_and(|ext| ext.eq_ignore_ascii_case("mov"))
+            }
+            _ => false,
+        }
+}
+
+/// Borrow the still and motion attachments of a Live Photo at the same time
+pub(crate) fn split_pair(
+    attachments: &mut [Attachment],
+    still: usize,
+    motion: usize,
+) -> Option<(&mut Attachment, &mut Attachment)> {
+    if still == motion || still.max(motion) >= attachments.len() {
+        return None;
+    }
+    let (low, high) = attachments.split_at_mut(still.max(motion));
+    let (first, second) = (&mut low[still.min(motion)], &mut high[0]);
+    Some(if still < motion {
+        (first, second)
+    } else {
+        (second, first)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use imessage_database::tables::{
+        attachment::Attachment,
+        messages::models::{AttachmentMeta, BubbleComponent},
+    };
+
+    use crate::{
+        Config,
+        exporters::shared::live_photo::{LivePhotos, split_pair},
+    };
+
+    fn motion(name: &str) -> Attachment {
+        let mut attachment = Config::fake_attachment();
+        attachment.filename = Some(format!("a/b/c/{name}"));
+        attachment.transfer_name = Some(name.to_string());
+        attachment.mime_type = Some("video/quicktime".to_string());
+        attachment
+    }
+
+    fn components(count: usize) -> Vec<BubbleComponent> {
+        (0..count)
+            .map(|_| BubbleComponent::Attachment(AttachmentMeta::default()))
+            .collect()
+    }
+
+    #[test]
+    fn can_pair_by_name() {
+        let mut still = Config::fake_attachment();
+        still.transfer_name = Some("IMG_0001.HEIC".to_string());
+        let attachments = vec![still, motion("img_0001.mov")];
+
+        let live_photos = LivePhotos::find(&components(2), &attachments);
+        assert_eq!(live_photos.motion_for(0), Some(1));
+        assert!(live_photos.is_motion(1));
+        assert!(!live_photos.is_motion(0));
+    }
+
+    #[test]
+    fn can_pair_by_metadata_name() {
+        let mut still = Config::fake_attachment();
+        still.transfer_name = None;
+        let attachments = vec![motion("IMG_0002.MOV"), still];
+        let components = vec![
+            BubbleComponent::Attachment(AttachmentMeta::default()),
+            BubbleComponent::Attachment(AttachmentMeta {
+                name: Some("IMG_0002.JPG".to_string()),
+                ..Default::default()
+            }),
+        ];
+
+        let live_photos = LivePhotos::find(&components, &attachments);
+        assert_eq!(live_photos.motion_for(1), Some(0));
+    }
+
+    #[test]
+    fn can_pair_hidden_motion() {
+        let mut hidden = motion("something_else.mov");
+        hidden.hide_attachment = 1;
+        let attachments = vec![Config::fake_attachment(), hidden];
+
+        let live_photos = LivePhotos::find(&components(2), &attachments);
+        assert_eq!(live_photos.motion_for(0), Some(1));
+    }
+
+    #[test]
+    fn cant_pair_unrelated() {
+        let mut video = motion("d.mp4");
+        video.mime_type = Some("video/mp4".to_string());
+        let attachments = vec![Config::fake_attachment(), motion("other.mov"), video];
+
+        let live_photos = LivePhotos::find(&components(3), &attachments);
+        assert_eq!(live_photos, LivePhotos::default());
+    }
+
+    #[test]
+    fn can_split_pair() {
+        let mut attachments = vec![Config::fake_attachment(), motion("d.mov")];
+
+        let (still, motion) = split_pair(&mut attachments, 0, 1).unwrap();
+        assert_eq!(still.transfer_name.as_deref(), Some("d.jpg"));
+        assert_eq!(motion.transfer_name.as_deref(), Some("d.mov"));
+
+        let (still, motion) = split_pair(&mut attachments, 1, 0).unwrap();
+        assert_eq!(still.transfer_name.as_deref(), Some("d.mov"));
+        assert_eq!(motion.transfer_name.as_deref(), Some("d.jpg"));
+
+        assert!(split_pair(&mut attachments, 0, 0).is_none());
+        assert!(split_pair(&mut attachments, 0, 2).is_none());
+    }
+}
@@ -3,6 +3,7 @@ pub(crate) mod attachment;
 pub(crate) mod balloon;
 pub(crate) mod driver;
 pub(crate) mod edited;
+pub(crate) mod live_photo;
 pub(crate) mod message;
 pub(crate) mod part;
 pub(crate) mod render;
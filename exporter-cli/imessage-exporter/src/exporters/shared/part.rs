@@ -74,6 +74,10 @@ where
                     AttachmentRender::Embedded(content) => formatter.body_attachment(content),
                     AttachmentRender::MissingFilename => formatter.body_attachment_missing(),
                     AttachmentRender::NamedFile(name) => formatter.body_attachment_error(&name),
+                    AttachmentRender::Skipped {
+                        filename,
+                        file_size,
+                    } => formatter.body_attachment_skipped(&filename, &file_size),
                 }
             };
             *attachment_index += 1;
@@ -3,7 +3,7 @@ use askama::Template;
 use imessage_database::{
     message_types::{
         app::AppMessage, app_store::AppStoreMessage, collaboration::CollaborationMessage,
-        digital_touch::DigitalTouch, handwriting::HandwrittenMessage, music::MusicMessage,
+        digital_touch::DigitalTouchMessage, handwriting::HandwrittenMessage, music::MusicMessage,
         placemark::PlacemarkMessage, polls::Poll, url::URLMessage,
     },
     tables::{attachment::Attachment, messages::Message},
@@ -109,9 +109,19 @@ impl BalloonFormatter for TXT<'_> {
         }
     }
 
-    fn format_digital_touch(&self, _: &Message, balloon: &DigitalTouch) -> String {
+    fn format_digital_touch(&self, msg: &Message, balloon: &DigitalTouchMessage) -> String {
+        let path = match self.config.options.attachment_manager.mode {
+            AttachmentManagerMode::Disabled => None,
+            _ => self
+                .config
+                .options
+                .attachment_manager
+                .handle_digital_touch(msg, balloon, self.config)
+                .map(|filepath| self.config.relative_path(&filepath)),
+        };
         render_balloon(&DigitalTouchVM {
-            debug: format!("{balloon:?}"),
+            kind: format!("{:?}", balloon.kind()),
+            path,
         })
     }
 
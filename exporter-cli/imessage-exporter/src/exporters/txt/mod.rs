@@ -6,7 +6,7 @@ use crate::{
         formatter::{AttachmentRender, MessageFormatter, PartBodyBuilder, RenderContext},
         shared::{
             announcement::{AnnouncementBody, resolve_announcement},
-            attachment::prepare_attachment,
+            attachment::{contact_cards, prepare_attachment},
             balloon::dispatch_app_balloon,
             driver::{ExportState, MessageWriter},
             edited::{EditDiff, normalize_edited},
@@ -36,8 +36,8 @@ mod balloons;
 mod view_model;
 
 use view_model::{
-    AnnouncementVM, AttachmentVM, EditedRow, EditedVM, MessagePartVM, MessageVM, PartBody,
-    RepliesVM, StickerVM, TapbackVM, TapbacksVM,
+    AnnouncementVM, AttachmentVM, ContactVM, EditedRow, EditedVM, MessagePartVM, MessageVM,
+    PartBody, RepliesVM, StickerVM, TapbackVM, TapbacksVM,
 };
 
 /// Indentation prepended to every line of a reply rendered inside its
@@ -85,6 +85,10 @@ impl<'a> MessageWriter<'a> for TXT<'a> {
         Ok(())
     }
 
+    fn trim_file_footer(_file: &mut File) -> Result<(), RuntimeError> {
+        Ok(())
+    }
+
     fn footer_notice() -> Option<&'static str> {
         None
     }
@@ -102,8 +106,13 @@ impl<'a> MessageFormatter<'a> for TXT<'a> {
             return render;
         }
 
+        let embed_path = self.config.message_attachment_path(attachment);
+        if let Some(cards) = contact_cards(self.config, attachment) {
+            return AttachmentRender::Embedded(render_template(&ContactVM { cards, embed_path }));
+        }
+
         AttachmentRender::Embedded(render_template(&AttachmentVM {
-            embed_path: self.config.message_attachment_path(attachment),
+            embed_path,
             transcription: metadata.transcription.as_deref(),
         }))
     }
@@ -118,7 +127,8 @@ impl<'a> MessageFormatter<'a> for TXT<'a> {
             match self.format_attachment(sticker, message, &AttachmentMeta::default()) {
                 AttachmentRender::Embedded(p) => (p, true),
                 AttachmentRender::MissingFilename => (String::new(), false),
-                AttachmentRender::NamedFile(name) => (name, false),
+                AttachmentRender::NamedFile(name)
+                | AttachmentRender::Skipped { filename: name, .. } => (name, false),
             };
 
         let decoration = if has_source {
@@ -343,6 +353,12 @@ impl PartBodyBuilder for TXT<'_> {
         }
     }
 
+    fn body_attachment_skipped(&self, filename: &str, file_size: &str) -> Self::Body {
+        PartBody::Line {
+            text: format!("Attachment not exported: {filename} ({file_size})"),
+        }
+    }
+
     fn body_sticker(&self, content: String) -> Self::Body {
         PartBody::Line { text: content }
     }
@@ -1363,6 +1379,65 @@ mod tests {
         );
     }
 
+    #[test]
+    fn can_format_txt_attachment_contact() {
+        use crate::app::test_dir::unique_test_dir;
+
+        let source = unique_test_dir("txt_contact").join("Jane Appleseed.vcf");
+        std::fs::write(
+            &source,
+            "BEGIN:VCARD\r\nVERSION:3.0\r\nFN:Jane Appleseed\r\nORG:Apple Inc.;\r\nTEL;type=CELL;type=VOICE;type=pref:+1 555 0100\r\nEMAIL;type=INTERNET:jane@example.com\r\nEND:VCARD\r\n",
+        )
+        .unwrap();
+
+        // Create exporter
+        let options = Options::fake_options(ExportType::Txt);
+        let config = Config::fake_app(options);
+        let exporter = TXT::new(&config).unwrap();
+
+        let message = Config::fake_message();
+
+        let mut attachment = Config::fake_attachment();
+        attachment.mime_type = Some("text/vcard".to_string());
+        attachment.filename = Some(source.display().to_string());
+        attachment.transfer_name = Some("Jane Appleseed.vcf".to_string());
+
+        let actual =
+            exporter.format_attachment(&mut attachment, &message, &AttachmentMeta::default());
+
+        assert_eq!(
+            actual,
+            AttachmentRender::Embedded(format!(
+                "Contact: Jane Appleseed\nOrganization: Apple Inc.\nPhone (mobile): +1 555 0100\nEmail: jane@example.com\n{}",
+                source.display()
+            ))
+        );
+    }
+
+    #[test]
+    fn can_format_txt_attachment_skipped() {
+        // Create exporter
+        let mut options = Options::fake_options(ExportType::Txt);
+        options.attachment_manager.policy.skip_types = vec!["jpg".to_string()];
+        let config = Config::fake_app(options);
+        let exporter = TXT::new(&config).unwrap();
+
+        let message = Config::fake_message();
+
+        let mut attachment = Config::fake_attachment();
+
+        let actual =
+            exporter.format_attachment(&mut attachment, &message, &AttachmentMeta::default());
+
+        assert_eq!(
+            actual,
+            AttachmentRender::Skipped {
+                filename: "d.jpg".to_string(),
+                file_size: "100.00 B".to_string()
+            }
+        );
+    }
+
     #[test]
     fn can_format_txt_attachment_macos_invalid_disabled() {
         // Create exporter
@@ -2034,14 +2109,17 @@ mod balloon_format_tests {
     use std::{collections::HashMap, env::current_dir, fs::File, io::Read};
 
     use crate::{
-        Config, Options, TXT, app::export_type::ExportType::Txt,
+        Config, Options, TXT,
+        app::{
+            compatibility::attachment_manager::AttachmentManagerMode, export_type::ExportType::Txt,
+        },
         exporters::formatter::BalloonFormatter,
     };
     use imessage_database::message_types::{
         app::AppMessage,
         app_store::AppStoreMessage,
         collaboration::CollaborationMessage,
-        digital_touch::DigitalTouch,
+        digital_touch::DigitalTouchMessage,
         handwriting::HandwrittenMessage,
         music::MusicMessage,
         placemark::{Placemark, PlacemarkMessage},
@@ -2589,8 +2667,20 @@ mod balloon_format_tests {
         let config = Config::fake_app(options);
         let exporter = TXT::new(&config).unwrap();
 
+        let payload_path = current_dir()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("imessage-database/test_data/digital_touch_message/kiss.bin");
+        let mut payload = vec![];
+        File::open(payload_path)
+            .unwrap()
+            .read_to_end(&mut payload)
+            .unwrap();
+        let touch = DigitalTouchMessage::from_payload(&payload).unwrap();
+
         let msg = Config::fake_message();
-        let actual = exporter.format_digital_touch(&msg, &DigitalTouch::Kiss);
+        let actual = exporter.format_digital_touch(&msg, &touch);
         let expected = "Digital Touch Message: Kiss";
 
         assert_eq!(actual, expected);
@@ -2631,6 +2721,36 @@ mod balloon_format_tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn can_format_txt_handwriting_clone_mode() {
+        let mut options = Options::fake_options(Txt);
+        options.attachment_manager.mode = AttachmentManagerMode::Clone;
+        let config = Config::fake_app(options);
+        let exporter = TXT::new(&config).unwrap();
+
+        let payload_path = current_dir()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("imessage-database/test_data/handwritten_message/handwriting.bin");
+        let mut payload = vec![];
+        File::open(payload_path)
+            .unwrap()
+            .read_to_end(&mut payload)
+            .unwrap();
+        let balloon = HandwrittenMessage::from_payload(&payload).unwrap();
+
+        let msg = Config::fake_message();
+        let actual = exporter.format_handwriting(&msg, &balloon);
+
+        assert_eq!(
+            actual,
+            "attachments/orphaned/e8fae151-5b83-4efa-98c6-b207381f004c.png"
+        );
+        let png = std::fs::read(config.options.export_path.join(&actual)).unwrap();
+        assert_eq!(png[1..4], *b"PNG");
+    }
+
     #[test]
     fn can_format_txt_check_in_estimated_end_time() {
         let options = Options::fake_options(Txt);
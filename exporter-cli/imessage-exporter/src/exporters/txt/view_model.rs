@@ -1,7 +1,7 @@
 use askama::Template;
 
 use imessage_database::{
-    message_types::{expressives::Expressive, variants::Announcement},
+    message_types::{expressives::Expressive, variants::Announcement, vcard::ContactCard},
     tables::messages::models::GroupAction,
 };
 
@@ -44,7 +44,9 @@ pub(super) struct FindMyVM<'a> {
 #[derive(Template)]
 #[template(path = "balloons/digital_touch.txt")]
 pub(super) struct DigitalTouchVM {
-    pub debug: String,
+    pub kind: String,
+    /// Path to the rendered `svg` graphic, if one was written
+    pub path: Option<String>,
 }
 
 #[derive(Template)]
@@ -146,6 +148,14 @@ pub(super) struct AttachmentVM<'a> {
     pub transcription: Option<&'a str>,
 }
 
+/// A shared `.vcf` file, listed as its contacts followed by the file path.
+#[derive(Template)]
+#[template(path = "attachments/contact.txt")]
+pub(super) struct ContactVM {
+    pub cards: Vec<ContactCard>,
+    pub embed_path: String,
+}
+
 #[derive(Template)]
 #[template(path = "attachments/sticker.txt")]
 pub(super) struct StickerVM<'a> {
@@ -8,6 +8,8 @@ pub use exporters::{html::HTML, txt::TXT};
 use std::process::ExitCode;
 
 use app::{
+    diff::compare_databases,
+    manifest::verify_export,
     options::{Options, from_command_line},
     runtime::Config,
 };
@@ -20,10 +22,30 @@ fn main() -> ExitCode {
 
     // Create app state and start
     match options {
+        // Verifying an export only reads the export directory, so the database is never opened
+        Ok(Options {
+            verify: Some(path), ..
+        }) => {
+            if let Err(why) = verify_export(&path) {
+                eprintln!("Unable to verify: {why}");
+                return ExitCode::FAILURE;
+            }
+            ExitCode::SUCCESS
+        }
+        // Comparing databases builds its own app state for each database
+        Ok(options) if options.compare.is_some() => {
+            if let Err(why) = compare_databases(options, &args) {
+                eprintln!("Unable to compare: {why}");
+                return ExitCode::FAILURE;
+            }
+            ExitCode::SUCCESS
+        }
         Ok(options) => match Config::new(options) {
             Ok(mut app) => {
                 // Resolve the filtered contacts, if provided
                 app.resolve_filtered_handles();
+                // Resolve the names used in the query expression, if provided
+                app.resolve_query_expression();
 
                 if let Err(why) = app.start() {
                     eprintln!("Unable to export: {why}");
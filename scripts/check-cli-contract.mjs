@@ -41,6 +41,10 @@ const requiredChecks = [
   },
   {
     file: 'exporter-cli/imessage-exporter/src/app/compatibility/attachment_manager.rs',
+    labels: [['attachment policy gate', 'self.policy.allows']],
+  },
+  {
+    file: 'exporter-cli/imessage-exporter/src/app/compatibility/policy.rs',
     labels: [['images only attachment gate', 'images_only']],
   },
   {